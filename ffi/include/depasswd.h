@@ -0,0 +1,69 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#ifndef DEPASSWD_H
+#define DEPASSWD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DEPASSWD_ERR_NULL_POINTER -1
+#define DEPASSWD_ERR_INVALID_UTF8 -2
+#define DEPASSWD_ERR_INVALID_INPUT -3
+#define DEPASSWD_ERR_BUFFER_TOO_SMALL -4
+#define DEPASSWD_ERR_DERIVATION -5
+#define DEPASSWD_ERR_PANIC -6
+
+/* Strings are NUL-terminated UTF-8, borrowed for the duration of the call. */
+typedef struct depasswd_params {
+    const char *user_id;
+    const char *master_password;
+    const char *service_id;
+    uint64_t generation;
+    /* Character set names ("small", "custom:...", ...), or NULL for every preset. */
+    const char *const *char_sets;
+    size_t char_sets_len;
+    uint16_t password_length;
+    /* 0 selects the default scheme. */
+    uint32_t scheme;
+} depasswd_params;
+
+/*
+ * Writes the password of `params` into `out` as a NUL-terminated string and
+ * returns its length in bytes, or a negative DEPASSWD_ERR_* code. On failure
+ * the first `cap` bytes of `out` are zeroed. `out` stays owned by the
+ * caller, who should wipe it with depasswd_wipe() once it is no longer needed.
+ */
+int32_t depasswd_derive(const depasswd_params *params, char *out, size_t cap);
+
+/* Static description of a DEPASSWD_ERR_* code. */
+const char *depasswd_strerror(int32_t code);
+
+/* Zeroes `len` bytes of `buf` without being optimized away. */
+void depasswd_wipe(char *buf, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DEPASSWD_H */
@@ -0,0 +1,248 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The C ABI of depasswd, so that GTK/Qt frontends and other languages
+//! link against the canonical implementation instead of porting it. The
+//! declarations are in `include/depasswd.h`.
+//!
+//! Every string is borrowed for the duration of the call and every buffer
+//! is owned by the caller: nothing allocated here crosses the boundary.
+//! The password is written into the caller's buffer and all of its copies
+//! on the Rust side are zeroized before returning.
+
+use std::{
+    ffi::{CStr, c_char},
+    panic::{self, AssertUnwindSafe},
+    slice,
+    str::FromStr,
+};
+
+use depasswd::{
+    DeriveParams, DerivePassRunner,
+    derived_pass::DerivedPass,
+    scheme::SchemeVersion,
+    user_input::{
+        CHAR_SET_NAMES, CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID,
+    },
+};
+use zeroize::Zeroize;
+
+pub const DEPASSWD_ERR_NULL_POINTER: i32 = -1;
+pub const DEPASSWD_ERR_INVALID_UTF8: i32 = -2;
+pub const DEPASSWD_ERR_INVALID_INPUT: i32 = -3;
+pub const DEPASSWD_ERR_BUFFER_TOO_SMALL: i32 = -4;
+pub const DEPASSWD_ERR_DERIVATION: i32 = -5;
+pub const DEPASSWD_ERR_PANIC: i32 = -6;
+
+/// The inputs of one derivation. Strings are NUL-terminated UTF-8.
+#[repr(C)]
+pub struct DepasswdParams {
+    pub user_id: *const c_char,
+    pub master_password: *const c_char,
+    pub service_id: *const c_char,
+    pub generation: u64,
+    /// Character set names as accepted by `CharSet::from_names`, or NULL
+    /// for every preset.
+    pub char_sets: *const *const c_char,
+    pub char_sets_len: usize,
+    pub password_length: u16,
+    /// 0 selects the default scheme.
+    pub scheme: u32,
+}
+
+/// Derives the password of `params` into `out` as a NUL-terminated string
+/// and returns its length in bytes, or one of the negative `DEPASSWD_ERR_*`
+/// codes. On failure the first `cap` bytes of `out` are zeroed.
+///
+/// # Safety
+///
+/// `params` must point to a valid `DepasswdParams` whose strings are valid
+/// NUL-terminated strings and whose `char_sets` holds `char_sets_len` of
+/// them. `out` must be valid for writes of `cap` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn depasswd_derive(
+    params: *const DepasswdParams,
+    out: *mut c_char,
+    cap: usize,
+) -> i32 {
+    if params.is_null() || out.is_null() {
+        return DEPASSWD_ERR_NULL_POINTER;
+    }
+    let out = unsafe { slice::from_raw_parts_mut(out.cast::<u8>(), cap) };
+    let derived_pass = panic::catch_unwind(AssertUnwindSafe(|| unsafe { derive(&*params) }))
+        .unwrap_or(Err(DEPASSWD_ERR_PANIC));
+    match derived_pass {
+        Ok(derived_pass) if derived_pass.expose_secret().len() < out.len() => {
+            let password = derived_pass.expose_secret().as_bytes();
+            out[..password.len()].copy_from_slice(password);
+            out[password.len()] = 0;
+            password.len() as i32
+        }
+        Ok(_) => {
+            out.zeroize();
+            DEPASSWD_ERR_BUFFER_TOO_SMALL
+        }
+        Err(code) => {
+            out.zeroize();
+            code
+        }
+    }
+}
+
+/// Returns a static, NUL-terminated description of a `DEPASSWD_ERR_*` code.
+#[unsafe(no_mangle)]
+pub extern "C" fn depasswd_strerror(code: i32) -> *const c_char {
+    match code {
+        DEPASSWD_ERR_NULL_POINTER => c"A required pointer is NULL",
+        DEPASSWD_ERR_INVALID_UTF8 => c"A string is not valid UTF-8",
+        DEPASSWD_ERR_INVALID_INPUT => c"Invalid derivation parameters",
+        DEPASSWD_ERR_BUFFER_TOO_SMALL => c"The output buffer is too small",
+        DEPASSWD_ERR_DERIVATION => c"The derivation failed",
+        DEPASSWD_ERR_PANIC => c"Internal error",
+        code if code >= 0 => c"Success",
+        _ => c"Unknown error",
+    }
+    .as_ptr()
+}
+
+/// Zeroes `len` bytes of `buf` in a way the compiler does not optimize
+/// away, for callers without `explicit_bzero`.
+///
+/// # Safety
+///
+/// `buf` must be NULL or valid for writes of `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn depasswd_wipe(buf: *mut c_char, len: usize) {
+    if !buf.is_null() {
+        unsafe { slice::from_raw_parts_mut(buf.cast::<u8>(), len) }.zeroize();
+    }
+}
+
+unsafe fn derive(params: &DepasswdParams) -> Result<DerivedPass, i32> {
+    let invalid = |_| DEPASSWD_ERR_INVALID_INPUT;
+    let char_set = if params.char_sets.is_null() {
+        CharSet::from_names(&CHAR_SET_NAMES)
+    } else {
+        let names = unsafe { slice::from_raw_parts(params.char_sets, params.char_sets_len) }
+            .iter()
+            .map(|name| unsafe { c_str(*name) })
+            .collect::<Result<Vec<&str>, i32>>()?;
+        CharSet::from_names(&names)
+    }
+    .map_err(invalid)?;
+    let scheme = match params.scheme {
+        0 => SchemeVersion::default(),
+        scheme => SchemeVersion::try_from(scheme).map_err(invalid)?,
+    };
+    let params = DeriveParams {
+        scheme,
+        ..DeriveParams::new(
+            UserID::from_str(unsafe { c_str(params.user_id) }?).map_err(invalid)?,
+            MasterPasswordPlain::from_str(unsafe { c_str(params.master_password) }?)
+                .map_err(invalid)?,
+            ServiceID::from_str(unsafe { c_str(params.service_id) }?).map_err(invalid)?,
+            usize::try_from(params.generation)
+                .map_err(|_| DEPASSWD_ERR_INVALID_INPUT)
+                .and_then(|generation| Generation::try_from(generation).map_err(invalid))?,
+            char_set,
+            PasswordLength::try_from(params.password_length).map_err(invalid)?,
+        )
+    };
+    DerivePassRunner::run(params).map_err(|e| {
+        if e.is_invalid_request() {
+            DEPASSWD_ERR_INVALID_INPUT
+        } else {
+            DEPASSWD_ERR_DERIVATION
+        }
+    })
+}
+
+unsafe fn c_str<'a>(ptr: *const c_char) -> Result<&'a str, i32> {
+    if ptr.is_null() {
+        return Err(DEPASSWD_ERR_NULL_POINTER);
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| DEPASSWD_ERR_INVALID_UTF8)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    fn params(user_id: &CStr) -> DepasswdParams {
+        DepasswdParams {
+            user_id: user_id.as_ptr(),
+            master_password: c"]lE~WExZ468ty{I5mtg[".as_ptr(),
+            service_id: c"Example Service Name".as_ptr(),
+            generation: 1,
+            char_sets: ptr::null(),
+            char_sets_len: 0,
+            password_length: 20,
+            scheme: 0,
+        }
+    }
+
+    #[test]
+    fn can_derive_into_buffer() {
+        let params = params(c"Example Eleonora");
+        let mut out = [0x55 as c_char; 21];
+
+        let len = unsafe { depasswd_derive(&params, out.as_mut_ptr(), out.len()) };
+        assert_eq!(len, 20);
+        assert_eq!(
+            unsafe { CStr::from_ptr(out.as_ptr()) },
+            c"1@MWtAAqZ0p>;;y@zZ6d"
+        );
+
+        let len = unsafe { depasswd_derive(&params, out.as_mut_ptr(), 20) };
+        assert_eq!(len, DEPASSWD_ERR_BUFFER_TOO_SMALL);
+        assert!(out[..20].iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn can_reject_invalid_params() {
+        let mut out = [0 as c_char; 64];
+        let derive = |params: &DepasswdParams, out: &mut [c_char]| unsafe {
+            depasswd_derive(params, out.as_mut_ptr(), out.len())
+        };
+
+        assert_eq!(
+            unsafe { depasswd_derive(ptr::null(), out.as_mut_ptr(), out.len()) },
+            DEPASSWD_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            derive(&params(c"short"), &mut out),
+            DEPASSWD_ERR_INVALID_INPUT
+        );
+        assert_eq!(
+            derive(&params(c"Example \xff"), &mut out),
+            DEPASSWD_ERR_INVALID_UTF8
+        );
+        let names = [c"small".as_ptr(), c"greek".as_ptr()];
+        let params = DepasswdParams {
+            char_sets: names.as_ptr(),
+            char_sets_len: names.len(),
+            ..params(c"Example Eleonora")
+        };
+        assert_eq!(derive(&params, &mut out), DEPASSWD_ERR_INVALID_INPUT);
+    }
+}
@@ -0,0 +1,29 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#![no_main]
+
+use depasswd::utils::Utils;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|hex: &str| {
+    if let Some(bytes) = Utils::hex_to_bytes(hex) {
+        assert_eq!(bytes.len() * 2, hex.len());
+    }
+});
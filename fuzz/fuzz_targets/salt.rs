@@ -0,0 +1,55 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#![no_main]
+
+use base64::prelude::*;
+use depasswd::{
+    master_secret::MasterSecret,
+    service_secret::ServiceSecret,
+    user_input::{Generation, PasswordLength, ServiceID, UserID},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (UserID, ServiceID, Generation, PasswordLength)| {
+    let (user_id, service_id, generation, password_length) = input;
+    assert_eq!(
+        BASE64_STANDARD_NO_PAD
+            .decode(MasterSecret::salt(&user_id))
+            .unwrap(),
+        format!("{}{}", user_id.len(), user_id).into_bytes()
+    );
+    assert_eq!(
+        BASE64_STANDARD_NO_PAD
+            .decode(ServiceSecret::salt(
+                &service_id,
+                &generation,
+                &password_length
+            ))
+            .unwrap(),
+        format!(
+            "{}{}{}{}",
+            service_id.len(),
+            service_id,
+            password_length,
+            generation
+        )
+        .into_bytes()
+    );
+});
@@ -0,0 +1,107 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! age (age-encryption.org) X25519 identities derived per service, so files
+//! encrypted to the recipient can always be decrypted by re-deriving the
+//! identity.
+
+use anyhow::Result;
+use crypto_box::SecretKey;
+use zeroize::Zeroizing;
+
+use crate::{
+    DerivePassError, derivation_path::Purpose, keys::KeyPair, scheme::SchemeVersion,
+    service_secret::ServiceSecret, user_input::UserInputError, utils::Utils,
+};
+
+pub struct AgeIdentity {
+    secret_key: SecretKey,
+}
+
+impl AgeIdentity {
+    /// The X25519 secret key is the first 32 bytes of the expansion of the
+    /// service secret under the label of the age purpose.
+    pub fn new(service_secret: &ServiceSecret, scheme: SchemeVersion) -> Result<Self> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for age identities",
+            )
+            .into());
+        }
+        let expanded = Zeroizing::new(service_secret.expand(&Purpose::age().label(), 32)?);
+        let bytes: Zeroizing<[u8; 32]> = Zeroizing::new(
+            expanded[..]
+                .try_into()
+                .map_err(|_| DerivePassError::Secret)?,
+        );
+        Ok(Self {
+            secret_key: SecretKey::from_bytes(*bytes),
+        })
+    }
+    /// The key pair, to serialize it with `KeyPair::private_key`.
+    pub fn key_pair(&self, comment: &str) -> KeyPair {
+        KeyPair::x25519(&Zeroizing::new(self.secret_key.to_bytes()), comment)
+    }
+    /// The `AGE-SECRET-KEY-1…` line of an age identity file.
+    pub fn identity(&self) -> Zeroizing<String> {
+        Zeroizing::new(
+            Utils::bech32(
+                "age-secret-key-",
+                &Zeroizing::new(self.secret_key.to_bytes())[..],
+            )
+            .to_uppercase(),
+        )
+    }
+    /// The `age1…` recipient to encrypt to.
+    pub fn recipient(&self) -> String {
+        Utils::bech32("age", self.secret_key.public_key().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn can_encode_age_identity() {
+        let identity = AgeIdentity {
+            secret_key: SecretKey::from_bytes(std::array::from_fn(|i| i as u8)),
+        };
+        assert_eq!(
+            *identity.identity(),
+            "AGE-SECRET-KEY-1QQQSYQCYQ5RQWZQFPG9SCRGWPUGPZYSNZS23V9CCRYDPK8QARC0SWRYDWG"
+        );
+        assert_eq!(
+            identity.recipient(),
+            "age13aqvttdk3ujkyjh9kg2w5an6dmy5mq5a84a4uxk3hfhnugfc9p0sy5p2wh"
+        );
+
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        assert!(
+            AgeIdentity::new(&service_secret, SchemeVersion::V2)
+                .unwrap()
+                .recipient()
+                .starts_with("age1")
+        );
+        assert!(AgeIdentity::new(&service_secret, SchemeVersion::V1).is_err());
+    }
+}
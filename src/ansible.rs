@@ -0,0 +1,107 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Ansible Vault 1.1 encryption, as done by `ansible-vault encrypt_string`.
+
+use aes::{
+    Aes256,
+    cipher::{KeyIvInit, StreamCipher},
+};
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+use crate::utils::Utils;
+
+const HEADER: &str = "$ANSIBLE_VAULT;1.1;AES256";
+const PBKDF2_ROUNDS: u32 = 10000;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+pub struct AnsibleVault {}
+
+impl AnsibleVault {
+    /// Encrypts `plaintext` with a random salt into the vault envelope.
+    pub fn encrypt(plaintext: &str, vault_password: &str) -> Result<String> {
+        let mut salt = [0u8; 32];
+        OsRng.fill_bytes(&mut salt);
+        Self::encrypt_with_salt(plaintext, vault_password, &salt)
+    }
+    pub fn encrypt_with_salt(plaintext: &str, vault_password: &str, salt: &[u8]) -> Result<String> {
+        let mut keys = [0u8; 80];
+        pbkdf2::pbkdf2_hmac::<Sha256>(vault_password.as_bytes(), salt, PBKDF2_ROUNDS, &mut keys);
+        let (cipher_key, rest) = keys.split_at(32);
+        let (hmac_key, iv) = rest.split_at(32);
+
+        // PKCS#7 padding to the AES block size, even though CTR does not need it.
+        let padding = 16 - plaintext.len() % 16;
+        let mut ciphertext = plaintext.as_bytes().to_vec();
+        ciphertext.extend(std::iter::repeat_n(padding as u8, padding));
+        Aes256Ctr::new(cipher_key.into(), iv.into()).apply_keystream(&mut ciphertext);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(hmac_key)?;
+        mac.update(&ciphertext);
+        let body = format!(
+            "{}\n{}\n{}",
+            Utils::bytes_to_hex(salt),
+            Utils::bytes_to_hex(&mac.finalize().into_bytes()),
+            Utils::bytes_to_hex(&ciphertext)
+        );
+        let body = Utils::bytes_to_hex(body.as_bytes());
+        let lines: Vec<&str> = body
+            .as_bytes()
+            .chunks(80)
+            .map(|c| std::str::from_utf8(c).unwrap_or_default())
+            .collect();
+        Ok(format!("{}\n{}", HEADER, lines.join("\n")))
+    }
+    /// Renders a YAML variable holding the encrypted value, like the output
+    /// of `ansible-vault encrypt_string --name`.
+    pub fn variable(name: &str, plaintext: &str, vault_password: &str) -> Result<String> {
+        Ok(format!(
+            "{}: !vault |\n{}",
+            name,
+            Self::encrypt(plaintext, vault_password)?
+                .lines()
+                .map(|l| format!("          {}", l))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_encrypt_ansible_vault() {
+        let encrypted =
+            AnsibleVault::encrypt_with_salt("abc", "vault password", &[0u8; 32]).unwrap();
+        let mut lines = encrypted.lines();
+
+        assert_eq!(lines.next(), Some(HEADER));
+        let body: String = lines.collect();
+        let body = String::from_utf8(Utils::hex_to_bytes(&body).unwrap()).unwrap();
+        let parts: Vec<&str> = body.split('\n').collect();
+        assert_eq!(parts[0], "0".repeat(64));
+        assert_eq!(parts[2].len(), 32);
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Opt-in record of what an unlocked session was asked to derive. Events
+//! never contain secrets: the service identifier is blinded with an HMAC
+//! keyed by the master secret, so only its owner can tell services apart.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The interface the request came through, e.g. `cli` or `jsonrpc`.
+    pub client: String,
+    /// `derive` for passwords, `derive-seed` for key material.
+    pub action: String,
+    /// Blinded service identifier, see `Session::blind`.
+    pub service: String,
+    pub scheme: u32,
+}
+
+impl AuditEvent {
+    pub fn new(client: &str, action: &str, service: String, scheme: u32) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            client: client.to_owned(),
+            action: action.to_owned(),
+            service,
+            scheme,
+        }
+    }
+}
+
+/// Receives an event before the derivation result is returned. A failing
+/// sink fails the derivation, so nothing is derived unrecorded.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, event: &AuditEvent) -> Result<()>;
+}
+
+/// Appends one JSON object per line to a file only the owner can read.
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            lock: Mutex::new(()),
+        }
+    }
+    pub fn read(path: &Path) -> Result<Vec<AuditEvent>> {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Can not read audit log: {}", path.display()))?
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| Ok(serde_json::from_str(l)?))
+            .collect()
+    }
+}
+
+impl AuditSink for AuditLog {
+    fn record(&self, event: &AuditEvent) -> Result<()> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Audit log is unavailable"))?;
+        let mut options = OpenOptions::new();
+        options.append(true).create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("Can not open audit log: {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(event)?)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        session::Session,
+        user_input::{CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID},
+    };
+
+    #[test]
+    fn can_record_derivations() {
+        let path = std::env::temp_dir().join("depasswd-audit-test.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap()
+        .with_audit(Arc::new(AuditLog::new(&path)), "test");
+        let service_id = ServiceID::from_str("Example Service Name").unwrap();
+
+        let derived_pass = session
+            .derive(
+                &service_id,
+                &Generation::from_str("1").unwrap(),
+                &CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap(),
+                &PasswordLength::from_str("20").unwrap(),
+            )
+            .unwrap();
+        session
+            .derive_seed(b"ssh-ed25519\0github.com\x001")
+            .unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(!log.contains("Example Service Name"));
+        assert!(!log.contains(derived_pass.expose_secret()));
+        let events = AuditLog::read(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].client, "test");
+        assert_eq!(events[0].action, "derive");
+        assert_eq!(
+            events[0].service,
+            session.blind("Example Service Name").unwrap()
+        );
+        assert_eq!(events[1].action, "derive-seed");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    derived_pass::DerivedPass,
+    session::Session,
+    user_input::{CHAR_SET_NAMES, CharSet, Generation, PasswordLength, ServiceID},
+};
+
+/// The non-secret inputs of one derivation, as used by batch files and the
+/// JSON-RPC interface.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationRequest {
+    /// Name of the derived value in structured outputs, defaults to the service id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub service_id: String,
+    #[serde(default = "DerivationRequest::default_generation")]
+    pub generation: usize,
+    #[serde(default = "DerivationRequest::default_char_sets")]
+    pub char_sets: Vec<String>,
+    pub password_length: u16,
+}
+
+impl DerivationRequest {
+    fn default_generation() -> usize {
+        1
+    }
+    fn default_char_sets() -> Vec<String> {
+        CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect()
+    }
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(&self.service_id)
+    }
+    /// Validates the request and derives its password. Validation failures
+    /// are returned as `UserInputError`.
+    pub fn derive(&self, session: &Session) -> Result<DerivedPass> {
+        let service_id = ServiceID::from_str(&self.service_id)?;
+        let generation = Generation::from_str(&self.generation.to_string())?;
+        let char_set = CharSet::from_names(&self.char_sets)?;
+        let password_length = PasswordLength::from_str(&self.password_length.to_string())?;
+        Ok(session.derive(&service_id, &generation, &char_set, &password_length)?)
+    }
+}
+
+/// A TOML file with one `[[derivation]]` table per `DerivationRequest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    #[serde(default, rename = "derivation")]
+    pub derivations: Vec<DerivationRequest>,
+}
+
+impl Batch {
+    pub fn load(path: &Path) -> Result<Self> {
+        toml::from_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("Can not read batch file: {}", path.display()))?,
+        )
+        .with_context(|| format!("Invalid batch file: {}", path.display()))
+    }
+    /// Derives every request, returning `(key, password)` pairs in file order.
+    pub fn derive_all(&self, session: &Session) -> Result<Vec<(String, DerivedPass)>> {
+        self.derivations
+            .iter()
+            .map(|d| {
+                d.derive(session)
+                    .with_context(|| format!("Can not derive {}", d.key()))
+                    .map(|p| (d.key().to_owned(), p))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_batch_file() {
+        let batch: Batch = toml::from_str(
+            r#"
+            [[derivation]]
+            key = "DB_PASSWORD"
+            service_id = "postgres"
+            generation = 3
+            password_length = 32
+            char_sets = ["small", "numbers"]
+
+            [[derivation]]
+            service_id = "redis"
+            password_length = 20
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(batch.derivations.len(), 2);
+        assert_eq!(batch.derivations[0].key(), "DB_PASSWORD");
+        assert_eq!(batch.derivations[0].generation, 3);
+        assert_eq!(batch.derivations[1].key(), "redis");
+        assert_eq!(batch.derivations[1].generation, 1);
+        assert_eq!(batch.derivations[1].char_sets, CHAR_SET_NAMES);
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! BIP39 mnemonics of derived entropy, for wallets and backup seeds that
+//! can be recovered from the master password and a service label.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    derivation_path::Purpose, scheme::SchemeVersion, service_secret::ServiceSecret,
+    user_input::UserInputError,
+};
+
+/// The English wordlist of BIP39, 2048 words in their specified order.
+const ENGLISH: &str = include_str!("bip39_english.txt");
+
+pub struct Mnemonic {
+    entropy: Zeroizing<Vec<u8>>,
+}
+
+impl Mnemonic {
+    /// The word counts BIP39 defines, 128 to 256 bits of entropy.
+    pub const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+    /// Takes the entropy of `words` words from the expansion of the service
+    /// secret under the label of the bip39 purpose.
+    pub fn new(
+        service_secret: &ServiceSecret,
+        words: usize,
+        scheme: SchemeVersion,
+    ) -> Result<Self> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for mnemonics",
+            )
+            .into());
+        }
+        if !Self::WORD_COUNTS.contains(&words) {
+            return Err(UserInputError::new(format!(
+                "A BIP39 mnemonic has 12, 15, 18, 21 or 24 words, not {}",
+                words
+            ))
+            .into());
+        }
+        Ok(Self::from_entropy(Zeroizing::new(
+            service_secret.expand(&Purpose::bip39().label(), words * 4 / 3)?,
+        )))
+    }
+    fn from_entropy(entropy: Zeroizing<Vec<u8>>) -> Self {
+        Self { entropy }
+    }
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+    /// The words: the entropy followed by the first `bits / 32` bits of its
+    /// SHA-256 hash as checksum, split into 11-bit indices of the wordlist.
+    pub fn phrase(&self) -> Zeroizing<String> {
+        let wordlist: Vec<&str> = ENGLISH.lines().collect();
+        let checksum = Sha256::digest(&*self.entropy);
+        let mut bits = Zeroizing::new(Vec::with_capacity(self.entropy.len() * 8 + 8));
+        for byte in self.entropy.iter().chain(std::iter::once(&checksum[0])) {
+            bits.extend((0..8).rev().map(|i| byte >> i & 1));
+        }
+        bits.truncate(self.entropy.len() * 8 + self.entropy.len() / 4);
+        Zeroizing::new(
+            bits.chunks(11)
+                .map(|chunk| {
+                    wordlist[chunk
+                        .iter()
+                        .fold(0usize, |index, bit| index << 1 | usize::from(*bit))]
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::utils::Utils;
+
+    #[test]
+    fn can_encode_mnemonic() {
+        assert_eq!(ENGLISH.lines().count(), 2048);
+        // Test vectors of the BIP39 reference implementation.
+        let test_cases = [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            ),
+            (
+                "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            ),
+            (
+                "80808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+            ),
+        ];
+        for (entropy, phrase) in test_cases {
+            let mnemonic =
+                Mnemonic::from_entropy(Zeroizing::new(Utils::hex_to_bytes(entropy).unwrap()));
+            assert_eq!(*mnemonic.phrase(), phrase);
+        }
+
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        for words in Mnemonic::WORD_COUNTS {
+            let mnemonic = Mnemonic::new(&service_secret, words, SchemeVersion::V2).unwrap();
+            assert_eq!(mnemonic.entropy().len() * 8, words * 32 / 3);
+            assert_eq!(mnemonic.phrase().split(' ').count(), words);
+        }
+        assert!(Mnemonic::new(&service_secret, 13, SchemeVersion::V2).is_err());
+        assert!(Mnemonic::new(&service_secret, 12, SchemeVersion::V1).is_err());
+    }
+}
@@ -0,0 +1,732 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The keepassxc-browser protocol, so the unmodified KeePassXC browser
+//! extensions can fill derived passwords.
+//!
+//! `BrowserServer` plays the part of the KeePassXC application: it listens on
+//! a Unix socket and answers the (NaCl box encrypted) JSON messages of the
+//! extension. `BrowserProxy` is the native messaging host the browser starts;
+//! it relays length-prefixed messages between stdin/stdout and the socket,
+//! like `keepassxc-proxy` does.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use base64::prelude::*;
+use crypto_box::{
+    PublicKey, SalsaBox, SecretKey,
+    aead::{Aead, OsRng},
+};
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use crate::{
+    batch::DerivationRequest,
+    catalog::{Catalog, CatalogEntry},
+    session::Session,
+    utils::Utils,
+};
+
+/// The extension enables features by the reported application version.
+const KEEPASSXC_VERSION: &str = "2.7.0";
+
+const ERROR_DATABASE_NOT_OPENED: u8 = 1;
+const ERROR_CLIENT_PUBLIC_KEY_NOT_RECEIVED: u8 = 3;
+const ERROR_CANNOT_DECRYPT_MESSAGE: u8 = 4;
+const ERROR_ASSOCIATION_FAILED: u8 = 8;
+const ERROR_INCORRECT_ACTION: u8 = 12;
+const ERROR_EMPTY_MESSAGE_RECEIVED: u8 = 13;
+const ERROR_NO_URL_PROVIDED: u8 = 14;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Association {
+    id: String,
+    id_key: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Associations {
+    /// Random, so the database hash the extension sees does not depend on
+    /// the master secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    database_id: Option<String>,
+    #[serde(default, rename = "association")]
+    associations: Vec<Association>,
+}
+
+impl Associations {
+    fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid association file: {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Can not read association file: {}", path.display()))
+            }
+        }
+    }
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Can not write association file: {}", path.display()))
+    }
+    /// The key is compared in constant time, the id is public.
+    fn contains(&self, id: &str, id_key: &str) -> bool {
+        self.associations
+            .iter()
+            .any(|a| a.id == id && a.id_key.as_bytes().ct_eq(id_key.as_bytes()).into())
+    }
+}
+
+#[derive(Debug)]
+struct BrowserError {
+    code: u8,
+    message: &'static str,
+}
+
+impl BrowserError {
+    fn new(code: u8, message: &'static str) -> Self {
+        Self { code, message }
+    }
+}
+
+pub struct BrowserServer {
+    session: Arc<Mutex<Session>>,
+    char_sets: Vec<String>,
+    password_length: u16,
+    catalog_path: PathBuf,
+    associations_path: PathBuf,
+    clients: Mutex<HashMap<String, SalsaBox>>,
+    /// The `database_id` of the association file, once read or created.
+    database_id: Mutex<Option<String>>,
+    /// Until when one new association is accepted, see `allow_associate`.
+    associate_until: Mutex<Option<Instant>>,
+}
+
+impl BrowserServer {
+    pub fn new(
+        session: Arc<Mutex<Session>>,
+        char_sets: &[String],
+        password_length: u16,
+        catalog_path: PathBuf,
+        associations_path: PathBuf,
+    ) -> Self {
+        Self {
+            session,
+            char_sets: char_sets.to_vec(),
+            password_length,
+            catalog_path,
+            associations_path,
+            clients: Mutex::new(HashMap::new()),
+            database_id: Mutex::new(None),
+            associate_until: Mutex::new(None),
+        }
+    }
+    /// Accepts one new association during `window`. Without it every
+    /// client that can reach the socket could associate itself and read
+    /// the passwords of any page, so the user opens it explicitly, like
+    /// the confirmation dialog of KeePassXC.
+    pub fn allow_associate(self, window: Duration) -> Self {
+        *self
+            .associate_until
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + window);
+        self
+    }
+    pub fn default_associations_path() -> Result<PathBuf> {
+        Ok(Utils::config_dir()?.join("browser-associations.toml"))
+    }
+    /// The socket in `Utils::runtime_dir`.
+    pub fn default_socket_path() -> Result<PathBuf> {
+        Ok(Utils::runtime_dir("--socket")?.join("depasswd.BrowserServer"))
+    }
+    /// Accepts connections on the Unix socket, one thread per connection.
+    #[cfg(unix)]
+    pub fn serve(&self, socket: &Path) -> Result<()> {
+        let listener = Utils::bind_private(socket)?;
+        std::thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        eprintln!("Can not accept a browser connection: {}", e);
+                        continue;
+                    }
+                };
+                scope.spawn(move || -> Result<()> {
+                    let reader = stream.try_clone()?;
+                    for message in
+                        serde_json::Deserializer::from_reader(reader).into_iter::<Value>()
+                    {
+                        let response = self.handle(&message?.to_string());
+                        stream.write_all(response.as_bytes())?;
+                        stream.flush()?;
+                    }
+                    Ok(())
+                });
+            }
+            Ok(())
+        })
+    }
+    /// Answers one message of the extension.
+    pub fn handle(&self, message: &str) -> String {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(_) => {
+                return Self::error(
+                    "",
+                    BrowserError::new(ERROR_EMPTY_MESSAGE_RECEIVED, "Empty message received"),
+                )
+                .to_string();
+            }
+        };
+        let action = request["action"].as_str().unwrap_or_default().to_owned();
+        match self.dispatch(&action, &request) {
+            Ok(response) => response,
+            Err(e) => Self::error(&action, e),
+        }
+        .to_string()
+    }
+    fn dispatch(&self, action: &str, request: &Value) -> Result<Value, BrowserError> {
+        let client_id = request["clientID"].as_str().unwrap_or_default();
+        let nonce = Self::decode_nonce(&request["nonce"])?;
+        if action == "change-public-keys" {
+            let client_key = Self::decode_key(&request["publicKey"]).ok_or(BrowserError::new(
+                ERROR_CLIENT_PUBLIC_KEY_NOT_RECEIVED,
+                "Client public key not received",
+            ))?;
+            let server_key = SecretKey::generate(&mut OsRng);
+            let public_key = BASE64_STANDARD.encode(server_key.public_key().as_bytes());
+            self.clients.lock().map_err(|_| Self::not_opened())?.insert(
+                client_id.to_owned(),
+                SalsaBox::new(&client_key, &server_key),
+            );
+            return Ok(json!({
+                "action": action,
+                "version": KEEPASSXC_VERSION,
+                "publicKey": public_key,
+                "nonce": BASE64_STANDARD.encode(Self::increment(nonce)),
+                "success": "true",
+            }));
+        }
+        let clients = self.clients.lock().map_err(|_| Self::not_opened())?;
+        let crypto_box = clients.get(client_id).ok_or(BrowserError::new(
+            ERROR_CLIENT_PUBLIC_KEY_NOT_RECEIVED,
+            "Client public key not received",
+        ))?;
+        let decrypt_error =
+            || BrowserError::new(ERROR_CANNOT_DECRYPT_MESSAGE, "Cannot decrypt message");
+        let plaintext = request["message"]
+            .as_str()
+            .and_then(|m| BASE64_STANDARD.decode(m).ok())
+            .and_then(|m| crypto_box.decrypt(&nonce.into(), m.as_slice()).ok())
+            .ok_or_else(decrypt_error)?;
+        let inner: Value = serde_json::from_slice(&plaintext).map_err(|_| decrypt_error())?;
+        let response_nonce = Self::increment(nonce);
+        let mut response = self.dispatch_inner(action, &inner)?;
+        response["action"] = json!(action);
+        response["version"] = json!(KEEPASSXC_VERSION);
+        response["success"] = json!("true");
+        response["nonce"] = json!(BASE64_STANDARD.encode(response_nonce));
+        let message = crypto_box
+            .encrypt(&response_nonce.into(), response.to_string().as_bytes())
+            .map_err(|_| decrypt_error())?;
+        Ok(json!({
+            "action": action,
+            "message": BASE64_STANDARD.encode(message),
+            "nonce": BASE64_STANDARD.encode(response_nonce),
+        }))
+    }
+    fn dispatch_inner(&self, action: &str, inner: &Value) -> Result<Value, BrowserError> {
+        match action {
+            "get-databasehash" => Ok(json!({"hash": self.database_hash()?})),
+            "associate" => {
+                let id_key = inner["idKey"].as_str().ok_or(BrowserError::new(
+                    ERROR_ASSOCIATION_FAILED,
+                    "Association failed",
+                ))?;
+                let mut associate_until = self
+                    .associate_until
+                    .lock()
+                    .map_err(|_| Self::not_opened())?;
+                // Writes the database id first, so saving the association
+                // below keeps it.
+                let hash = self.database_hash()?;
+                if !associate_until.is_some_and(|until| Instant::now() < until) {
+                    eprintln!(
+                        "Rejected a new browser association, restart browser-server with --allow-associate to accept one"
+                    );
+                    return Err(BrowserError::new(
+                        ERROR_ASSOCIATION_FAILED,
+                        "Association failed",
+                    ));
+                }
+                let mut associations =
+                    Associations::load(&self.associations_path).map_err(|_| Self::not_opened())?;
+                let id = format!("depasswd-{}", associations.associations.len() + 1);
+                associations.associations.push(Association {
+                    id: id.clone(),
+                    id_key: id_key.to_owned(),
+                });
+                associations.save(&self.associations_path).map_err(|_| {
+                    BrowserError::new(ERROR_ASSOCIATION_FAILED, "Association failed")
+                })?;
+                *associate_until = None;
+                eprintln!("Associated a new browser client as {}", id);
+                Ok(json!({"hash": hash, "id": id}))
+            }
+            "test-associate" => {
+                let (id, key) = (
+                    inner["id"].as_str().unwrap_or_default(),
+                    inner["key"].as_str().unwrap_or_default(),
+                );
+                if self.is_associated(id, key)? {
+                    Ok(json!({"hash": self.database_hash()?, "id": id}))
+                } else {
+                    Err(BrowserError::new(
+                        ERROR_ASSOCIATION_FAILED,
+                        "Association failed",
+                    ))
+                }
+            }
+            "get-logins" => {
+                let associated = inner["keys"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|k| {
+                        self.is_associated(
+                            k["id"].as_str().unwrap_or_default(),
+                            k["key"].as_str().unwrap_or_default(),
+                        )
+                    })
+                    .collect::<Result<Vec<bool>, BrowserError>>()?;
+                if !associated.contains(&true) {
+                    return Err(BrowserError::new(
+                        ERROR_ASSOCIATION_FAILED,
+                        "Association failed",
+                    ));
+                }
+                let url = inner["url"]
+                    .as_str()
+                    .ok_or(BrowserError::new(ERROR_NO_URL_PROVIDED, "No URL provided"))?;
+                let entries = self.logins(url)?;
+                Ok(json!({
+                    "count": entries.len(),
+                    "entries": entries,
+                    "hash": self.database_hash()?,
+                }))
+            }
+            _ => Err(BrowserError::new(
+                ERROR_INCORRECT_ACTION,
+                "Action not supported",
+            )),
+        }
+    }
+    /// Derives a login for every catalog entry of the page's service, with
+    /// the settings of the entry, or one without user name when the catalog
+    /// has none.
+    fn logins(&self, url: &str) -> Result<Vec<Value>, BrowserError> {
+        let service_id = Catalog::canonical_service_id(url)
+            .ok_or(BrowserError::new(ERROR_NO_URL_PROVIDED, "No URL provided"))?;
+        let catalog = Catalog::load(&self.catalog_path).map_err(|_| Self::not_opened())?;
+        let mut entries: Vec<CatalogEntry> = catalog
+            .entries()
+            .iter()
+            .filter(|e| {
+                e.service_id == service_id
+                    || e.url
+                        .as_deref()
+                        .and_then(Catalog::canonical_service_id)
+                        .as_deref()
+                        == Some(&service_id)
+            })
+            .cloned()
+            .collect();
+        if entries.is_empty() {
+            entries.push(CatalogEntry::new(service_id));
+        }
+        entries
+            .into_iter()
+            .map(|entry| {
+                let login = entry.username.clone().unwrap_or_default();
+                let password = self.derive(&entry)?;
+                let uuid = Utils::bytes_to_hex(
+                    &Sha256::digest(format!("{}\n{}", entry.service_id, login).as_bytes())[..16],
+                );
+                Ok(json!({
+                    "login": login,
+                    "name": entry.service_id,
+                    "password": password.as_str(),
+                    "uuid": uuid,
+                    "group": "depasswd",
+                    "totp": "",
+                    "expired": "false",
+                    "stringFields": [],
+                }))
+            })
+            .collect()
+    }
+    /// Derives with the settings of `entry`, the server defaults filling
+    /// the unset ones.
+    fn derive(&self, entry: &CatalogEntry) -> Result<Zeroizing<String>, BrowserError> {
+        let request = DerivationRequest {
+            key: None,
+            service_id: entry.service_id.clone(),
+            generation: entry.generation.unwrap_or(1),
+            char_sets: entry
+                .char_sets
+                .clone()
+                .unwrap_or_else(|| self.char_sets.clone()),
+            password_length: entry.password_length.unwrap_or(self.password_length),
+        };
+        let session = self.session.lock().map_err(|_| Self::not_opened())?;
+        request
+            .derive(&session)
+            .map(|p| Zeroizing::new(p.expose_secret().to_owned()))
+            .map_err(|_| Self::not_opened())
+    }
+    /// Identifies the "database" by a random id kept in the association
+    /// file, created on first use. Unassociated clients can read it, so it
+    /// must not be derived from the master secret.
+    fn database_hash(&self) -> Result<String, BrowserError> {
+        let mut database_id = self.database_id.lock().map_err(|_| Self::not_opened())?;
+        if let Some(id) = database_id.as_ref() {
+            return Ok(id.clone());
+        }
+        let mut associations =
+            Associations::load(&self.associations_path).map_err(|_| Self::not_opened())?;
+        let id = match &associations.database_id {
+            Some(id) => id.clone(),
+            None => {
+                let mut bytes = [0u8; 32];
+                OsRng.fill_bytes(&mut bytes);
+                let id = Utils::bytes_to_hex(&bytes);
+                associations.database_id = Some(id.clone());
+                associations
+                    .save(&self.associations_path)
+                    .map_err(|_| Self::not_opened())?;
+                id
+            }
+        };
+        *database_id = Some(id.clone());
+        Ok(id)
+    }
+    fn is_associated(&self, id: &str, id_key: &str) -> Result<bool, BrowserError> {
+        Ok(Associations::load(&self.associations_path)
+            .map_err(|_| Self::not_opened())?
+            .contains(id, id_key))
+    }
+    fn not_opened() -> BrowserError {
+        BrowserError::new(ERROR_DATABASE_NOT_OPENED, "Database not opened")
+    }
+    fn decode_key(key: &Value) -> Option<PublicKey> {
+        let key: [u8; 32] = BASE64_STANDARD
+            .decode(key.as_str()?)
+            .ok()?
+            .try_into()
+            .ok()?;
+        Some(PublicKey::from(key))
+    }
+    fn decode_nonce(nonce: &Value) -> Result<[u8; 24], BrowserError> {
+        nonce
+            .as_str()
+            .and_then(|n| BASE64_STANDARD.decode(n).ok())
+            .and_then(|n| n.try_into().ok())
+            .ok_or(BrowserError::new(
+                ERROR_CANNOT_DECRYPT_MESSAGE,
+                "Cannot decrypt message",
+            ))
+    }
+    /// Little-endian increment, as `sodium_increment` does.
+    fn increment(mut nonce: [u8; 24]) -> [u8; 24] {
+        for byte in nonce.iter_mut() {
+            let (sum, overflow) = byte.overflowing_add(1);
+            *byte = sum;
+            if !overflow {
+                break;
+            }
+        }
+        nonce
+    }
+    fn error(action: &str, error: BrowserError) -> Value {
+        json!({
+            "action": action,
+            "errorCode": error.code.to_string(),
+            "error": error.message,
+        })
+    }
+}
+
+pub struct BrowserProxy {}
+
+impl BrowserProxy {
+    /// Relays native messages (32-bit native-endian length + JSON) from
+    /// stdin to the socket, and the server's JSON answers back to stdout.
+    #[cfg(unix)]
+    pub fn relay(socket: &Path) -> Result<()> {
+        use std::os::unix::net::UnixStream;
+
+        let mut stream = UnixStream::connect(socket).with_context(|| {
+            format!(
+                "Can not connect to {}, is `depasswd browser-server` running?",
+                socket.display()
+            )
+        })?;
+        let responses = stream.try_clone()?;
+        std::thread::spawn(move || -> Result<()> {
+            let mut stdout = std::io::stdout().lock();
+            for response in serde_json::Deserializer::from_reader(responses).into_iter::<Value>() {
+                let response = response?.to_string();
+                stdout.write_all(&(response.len() as u32).to_ne_bytes())?;
+                stdout.write_all(response.as_bytes())?;
+                stdout.flush()?;
+            }
+            Ok(())
+        });
+        let mut stdin = std::io::stdin().lock();
+        loop {
+            let mut length = [0u8; 4];
+            match stdin.read_exact(&mut length) {
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                result => result?,
+            }
+            let mut message = vec![0u8; u32::from_ne_bytes(length) as usize];
+            stdin.read_exact(&mut message)?;
+            stream.write_all(&message)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        user_input::{CHAR_SET_NAMES, MasterPasswordPlain, UserID},
+    };
+
+    struct Client {
+        secret_key: SecretKey,
+        crypto_box: Option<SalsaBox>,
+    }
+
+    impl Client {
+        fn call(&mut self, server: &BrowserServer, action: &str, inner: Value) -> Value {
+            let nonce = [7u8; 24];
+            let request = match &self.crypto_box {
+                None => json!({
+                    "action": action,
+                    "publicKey": BASE64_STANDARD.encode(self.secret_key.public_key().as_bytes()),
+                    "nonce": BASE64_STANDARD.encode(nonce),
+                    "clientID": "test-client",
+                }),
+                Some(crypto_box) => json!({
+                    "action": action,
+                    "message": BASE64_STANDARD.encode(
+                        crypto_box.encrypt(&nonce.into(), inner.to_string().as_bytes()).unwrap()
+                    ),
+                    "nonce": BASE64_STANDARD.encode(nonce),
+                    "clientID": "test-client",
+                }),
+            };
+            let response: Value =
+                serde_json::from_str(&server.handle(&request.to_string())).unwrap();
+            assert_eq!(
+                response["nonce"],
+                BASE64_STANDARD.encode(BrowserServer::increment(nonce)),
+                "{}",
+                response
+            );
+            match &self.crypto_box {
+                None => {
+                    let server_key = BrowserServer::decode_key(&response["publicKey"]).unwrap();
+                    self.crypto_box = Some(SalsaBox::new(&server_key, &self.secret_key));
+                    response
+                }
+                Some(crypto_box) => {
+                    let message = BASE64_STANDARD
+                        .decode(response["message"].as_str().unwrap())
+                        .unwrap();
+                    let nonce = BrowserServer::decode_nonce(&response["nonce"]).unwrap();
+                    serde_json::from_slice(
+                        &crypto_box
+                            .decrypt(&nonce.into(), message.as_slice())
+                            .unwrap(),
+                    )
+                    .unwrap()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn can_increment_nonce() {
+        let mut nonce = [0u8; 24];
+        nonce[0] = 255;
+        let incremented = BrowserServer::increment(nonce);
+        assert_eq!(incremented[0], 0);
+        assert_eq!(incremented[1], 1);
+    }
+
+    #[test]
+    fn can_check_association() {
+        let associations = Associations {
+            associations: vec![Association {
+                id: "firefox".to_owned(),
+                id_key: "a2V5".to_owned(),
+            }],
+            ..Associations::default()
+        };
+        assert!(associations.contains("firefox", "a2V5"));
+        assert!(!associations.contains("firefox", "a2V6"));
+        assert!(!associations.contains("firefox", "a2V"));
+        assert!(!associations.contains("firefox", ""));
+        assert!(!associations.contains("chrome", "a2V5"));
+    }
+
+    #[test]
+    fn can_associate_and_get_logins() {
+        let dir = std::env::temp_dir().join("depasswd-browser-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut catalog = Catalog::default();
+        catalog.add(CatalogEntry {
+            service_id: "example.com".to_owned(),
+            username: Some("eleonora".to_owned()),
+            ..CatalogEntry::default()
+        });
+        catalog.add(CatalogEntry {
+            service_id: "Example Shop".to_owned(),
+            username: Some("eleonora@example.com".to_owned()),
+            url: Some("https://shop.example.com/".to_owned()),
+            generation: Some(2),
+            password_length: Some(16),
+            char_sets: Some(vec!["small".to_owned(), "numbers".to_owned()]),
+        });
+        catalog.save(&dir.join("catalog.toml")).unwrap();
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let char_sets: Vec<String> = CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect();
+        let server = BrowserServer::new(
+            Arc::new(Mutex::new(session)),
+            &char_sets,
+            20,
+            dir.join("catalog.toml"),
+            dir.join("associations.toml"),
+        )
+        .allow_associate(Duration::from_secs(60));
+        let mut client = Client {
+            secret_key: SecretKey::generate(&mut OsRng),
+            crypto_box: None,
+        };
+
+        assert_eq!(
+            client.call(&server, "change-public-keys", Value::Null)["success"],
+            "true"
+        );
+        let id_key = BASE64_STANDARD.encode([1u8; 32]);
+        let associated = client.call(
+            &server,
+            "associate",
+            json!({"action": "associate", "idKey": id_key}),
+        );
+        let id = associated["id"].as_str().unwrap();
+        // The window accepts a single association.
+        let crypto_box = client.crypto_box.as_ref().unwrap();
+        let inner = json!({"action": "associate", "idKey": BASE64_STANDARD.encode([2u8; 32])});
+        let request = json!({
+            "action": "associate",
+            "message": BASE64_STANDARD.encode(
+                crypto_box.encrypt(&[7u8; 24].into(), inner.to_string().as_bytes()).unwrap()
+            ),
+            "nonce": BASE64_STANDARD.encode([7u8; 24]),
+            "clientID": "test-client",
+        });
+        let refused: Value = serde_json::from_str(&server.handle(&request.to_string())).unwrap();
+        assert_eq!(refused["errorCode"], ERROR_ASSOCIATION_FAILED.to_string());
+        let tested = client.call(&server, "test-associate", json!({"id": id, "key": id_key}));
+        assert_eq!(tested["success"], "true");
+        // The hash is the random id of the association file.
+        assert_eq!(tested["hash"], associated["hash"]);
+        assert_eq!(
+            Associations::load(&dir.join("associations.toml"))
+                .unwrap()
+                .database_id
+                .as_deref(),
+            associated["hash"].as_str()
+        );
+        let logins = client.call(
+            &server,
+            "get-logins",
+            json!({"url": "https://www.example.com/login", "keys": [{"id": id, "key": id_key}]}),
+        );
+        assert_eq!(logins["count"], 1);
+        assert_eq!(logins["entries"][0]["login"], "eleonora");
+        assert_eq!(logins["entries"][0]["password"].as_str().unwrap().len(), 20);
+        let logins = client.call(
+            &server,
+            "get-logins",
+            json!({"url": "https://shop.example.com/cart", "keys": [{"id": id, "key": id_key}]}),
+        );
+        assert_eq!(logins["entries"][0]["name"], "Example Shop");
+        let session = server.session.lock().unwrap();
+        let expected = DerivationRequest {
+            key: None,
+            service_id: "Example Shop".to_owned(),
+            generation: 2,
+            char_sets: vec!["small".to_owned(), "numbers".to_owned()],
+            password_length: 16,
+        }
+        .derive(&session)
+        .unwrap();
+        drop(session);
+        assert_eq!(logins["entries"][0]["password"], expected.expose_secret());
+
+        let rejected: Value = serde_json::from_str(&server.handle(
+            &json!({"action": "get-logins", "message": "AAAA", "nonce": BASE64_STANDARD.encode([0u8; 24]), "clientID": "unknown"}).to_string(),
+        ))
+        .unwrap();
+        assert_eq!(
+            rejected["errorCode"],
+            ERROR_CLIENT_PUBLIC_KEY_NOT_RECEIVED.to_string()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The catalog is an optional plain TOML file listing the services a user
+//! derives passwords for. It never contains secrets, only the non-secret
+//! inputs needed to find and re-derive an account.
+
+use std::{fs, io::ErrorKind, path::Path, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+use crate::utils::Utils;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub service_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Settings of the last interactive derivation, offered as defaults.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_length: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_sets: Option<Vec<String>>,
+}
+
+impl CatalogEntry {
+    pub fn new(service_id: impl Into<String>) -> Self {
+        Self {
+            service_id: service_id.into(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Catalog {
+    #[serde(default, rename = "entry")]
+    entries: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Utils::config_dir()?.join("catalog.toml"))
+    }
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid catalog file: {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Can not read catalog file: {}", path.display()))
+            }
+        }
+    }
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Can not write catalog file: {}", path.display()))
+    }
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+    pub fn get(&self, service_id: &str) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|e| e.service_id == service_id)
+    }
+    pub fn get_mut(&mut self, service_id: &str) -> Option<&mut CatalogEntry> {
+        self.entries.iter_mut().find(|e| e.service_id == service_id)
+    }
+    /// Adds the entry unless one with the same service identifier exists.
+    /// Returns whether the entry was added.
+    pub fn add(&mut self, entry: CatalogEntry) -> bool {
+        if self.get(&entry.service_id).is_some() {
+            false
+        } else {
+            self.entries.push(entry);
+            true
+        }
+    }
+    /// Turns a URL into the service identifier proposed for it: the lowercase
+    /// host name without the `www.`/`m.` prefixes and without port. Local
+    /// addresses and non-web URLs yield `None`.
+    pub fn canonical_service_id(url: &str) -> Option<String> {
+        let url = Url::parse(url.trim()).ok()?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return None;
+        }
+        match url.host()? {
+            Host::Domain(domain) => {
+                let domain = domain.trim_end_matches('.').to_lowercase();
+                let domain = ["www.", "m.", "mobile."]
+                    .iter()
+                    .find_map(|prefix| domain.strip_prefix(prefix))
+                    .unwrap_or(&domain)
+                    .to_owned();
+                if domain.contains('.') && domain != "localhost" {
+                    Some(domain)
+                } else {
+                    None
+                }
+            }
+            Host::Ipv4(_) | Host::Ipv6(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_canonicalize_service_id() {
+        let test_cases = [
+            ("https://www.GitHub.com/login", Some("github.com")),
+            ("http://m.facebook.com:8080/", Some("facebook.com")),
+            (
+                "https://accounts.google.com./signin",
+                Some("accounts.google.com"),
+            ),
+            ("https://localhost:3000", None),
+            ("http://192.168.0.1/admin", None),
+            ("ftp://files.example.com", None),
+            ("javascript:void(0)", None),
+            ("not an url", None),
+        ];
+
+        for (url, expected) in test_cases {
+            assert_eq!(
+                Catalog::canonical_service_id(url).as_deref(),
+                expected,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn can_round_trip_catalog() {
+        let mut catalog = Catalog::default();
+        assert!(catalog.add(CatalogEntry {
+            service_id: "github.com".to_owned(),
+            username: Some("octocat".to_owned()),
+            url: Some("https://github.com".to_owned()),
+            ..CatalogEntry::default()
+        }));
+        assert!(!catalog.add(CatalogEntry::new("github.com")));
+        assert!(catalog.add(CatalogEntry {
+            generation: Some(2),
+            password_length: Some(20),
+            char_sets: Some(vec!["small".to_owned(), "numbers".to_owned()]),
+            ..CatalogEntry::new("example.com")
+        }));
+
+        let path = std::env::temp_dir().join("depasswd-catalog-test.toml");
+        catalog.save(&path).unwrap();
+        assert_eq!(Catalog::load(&path).unwrap(), catalog);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Catalog::load(&path).unwrap(), Catalog::default());
+    }
+}
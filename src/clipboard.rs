@@ -0,0 +1,184 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Copies through the platform's clipboard command line tools, so no display
+//! server libraries have to be linked.
+
+use std::{
+    env,
+    ffi::OsStr,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::doctor::Doctor;
+
+pub const DEFAULT_CLEAR_AFTER: u64 = 45;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipboardTool {
+    pub name: &'static str,
+    /// Environment variable that must be set for the tool to reach a display.
+    pub display: Option<&'static str>,
+    copy_args: &'static [&'static str],
+    paste: Option<(&'static str, &'static [&'static str])>,
+}
+
+impl ClipboardTool {
+    /// The tools of this platform, in order of preference.
+    pub fn candidates() -> &'static [ClipboardTool] {
+        if cfg!(target_os = "macos") {
+            &[ClipboardTool {
+                name: "pbcopy",
+                display: None,
+                copy_args: &[],
+                paste: Some(("pbpaste", &[])),
+            }]
+        } else if cfg!(windows) {
+            &[ClipboardTool {
+                name: "clip.exe",
+                display: None,
+                copy_args: &[],
+                paste: None,
+            }]
+        } else {
+            &[
+                ClipboardTool {
+                    name: "wl-copy",
+                    display: Some("WAYLAND_DISPLAY"),
+                    copy_args: &[],
+                    paste: Some(("wl-paste", &["--no-newline"])),
+                },
+                ClipboardTool {
+                    name: "xclip",
+                    display: Some("DISPLAY"),
+                    copy_args: &["-selection", "clipboard"],
+                    paste: Some(("xclip", &["-selection", "clipboard", "-o"])),
+                },
+                ClipboardTool {
+                    name: "xsel",
+                    display: Some("DISPLAY"),
+                    copy_args: &["--clipboard", "--input"],
+                    paste: Some(("xsel", &["--clipboard", "--output"])),
+                },
+            ]
+        }
+    }
+    pub fn is_usable(&self, path: &OsStr) -> bool {
+        Doctor::find_in_path(self.name, path).is_some()
+            && self.display.is_none_or(|d| env::var_os(d).is_some())
+    }
+}
+
+pub struct Clipboard {
+    tool: ClipboardTool,
+}
+
+impl Clipboard {
+    pub fn detect() -> Result<Self> {
+        let path = env::var_os("PATH").unwrap_or_default();
+        ClipboardTool::candidates()
+            .iter()
+            .find(|tool| tool.is_usable(&path))
+            .map(|tool| Self { tool: *tool })
+            .ok_or(anyhow!(
+                "No usable clipboard tool found, run `depasswd doctor` for details"
+            ))
+    }
+    pub fn tool(&self) -> &ClipboardTool {
+        &self.tool
+    }
+    pub fn copy(&self, text: &str) -> Result<()> {
+        let mut child = Command::new(self.tool.name)
+            .args(self.tool.copy_args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if !child.wait()?.success() {
+            bail!("{} failed", self.tool.name);
+        }
+        Ok(())
+    }
+    /// The current clipboard content, `None` if the tool can not read it.
+    pub fn paste(&self) -> Result<Option<String>> {
+        let Some((command, args)) = self.tool.paste else {
+            return Ok(None);
+        };
+        let output = Command::new(command)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()?;
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+    /// Waits, then empties the clipboard unless something else was copied
+    /// in the meantime.
+    pub fn clear_after(&self, text: &str, timeout: Duration) -> Result<()> {
+        thread::sleep(timeout);
+        match self.paste()? {
+            Some(content) if content != text => Ok(()),
+            _ => self.copy(""),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const FAKE_CLIPBOARD: &str = "/tmp/depasswd-clipboard-test";
+
+    #[test]
+    fn can_clear_own_content_only() {
+        let clipboard = Clipboard {
+            tool: ClipboardTool {
+                name: "sh",
+                display: None,
+                copy_args: &["-c", "cat > /tmp/depasswd-clipboard-test"],
+                paste: Some(("cat", &[FAKE_CLIPBOARD])),
+            },
+        };
+        clipboard.copy("1@MWtAAqZ0p>;;y@zZ6d").unwrap();
+        assert_eq!(
+            clipboard.paste().unwrap().as_deref(),
+            Some("1@MWtAAqZ0p>;;y@zZ6d")
+        );
+        clipboard
+            .clear_after("1@MWtAAqZ0p>;;y@zZ6d", Duration::ZERO)
+            .unwrap();
+        assert_eq!(clipboard.paste().unwrap().as_deref(), Some(""));
+
+        clipboard.copy("copied by the user").unwrap();
+        clipboard
+            .clear_after("1@MWtAAqZ0p>;;y@zZ6d", Duration::ZERO)
+            .unwrap();
+        assert_eq!(
+            clipboard.paste().unwrap().as_deref(),
+            Some("copied by the user")
+        );
+        std::fs::remove_file(FAKE_CLIPBOARD).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Optional `config.toml` with defaults for the interactive prompts and the
+//! command line. Flags override it, and prompts only use it as their default
+//! answer, so it never silently changes a derivation.
+
+use std::{fs, io::ErrorKind, path::Path, path::PathBuf, str::FromStr};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    kdf::KdfParams,
+    pepper::PepperSource,
+    scheme::SchemeVersion,
+    ssh_agent::SshKeySpec,
+    user_input::{CharSet, PasswordLength, UserID},
+    utils::Utils,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_sets: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_length: Option<u16>,
+    /// Derivation scheme of new sessions, see `SchemeVersion`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<SchemeVersion>,
+    /// Ask the master password with pinentry instead of in the terminal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinentry: Option<bool>,
+    /// Seconds until `--copy` clears the clipboard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clear_clipboard_after: Option<u64>,
+    /// Keep salted hashes of the service identifiers derived for, to warn
+    /// about new ones, see `ServiceHistory`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<bool>,
+    /// Seconds an unlocked master secret is cached in the keychain of the
+    /// operating system, unset disables the cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keychain_cache: Option<u64>,
+    /// Ask for Touch ID (macOS) or Windows Hello (Windows) before a cached
+    /// master secret is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keychain_biometry: Option<bool>,
+    /// Seal the cached master secret to the TPM2, bound to these PCRs (e.g.
+    /// "7" or "0+7"), so the cache can survive reboots (Linux).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keychain_tpm2_pcrs: Option<String>,
+    /// Stretch the master password together with this file, see `Keyfile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyfile: Option<PathBuf>,
+    /// Mix this static pepper into the master secret, see `PepperSource`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pepper: Option<PepperSource>,
+    /// Mix the content of this OpenPGP encrypted file, decrypted by gpg
+    /// (e.g. with a key on a smartcard), into the master secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pepper_gpg_file: Option<PathBuf>,
+    /// Mix the HMAC-SHA1 challenge-response of this YubiKey slot (1 or 2)
+    /// into the master secret, see `YubiKey`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yubikey_slot: Option<u8>,
+    /// Path of the bare git repository `depasswd recipe sync` keeps the
+    /// recipe vault in, see `RecipeSync`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe_sync_remote: Option<String>,
+    /// KDF of the master secret, see `KdfParams`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<KdfParams>,
+    #[serde(default, skip_serializing_if = "Argon2Config::is_empty")]
+    pub argon2: Argon2Config,
+    /// Keys `depasswd agent` offers, as `SERVICE[:GENERATION]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ssh_keys: Vec<String>,
+}
+
+/// Parameters of the argon2id based output formats.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Argon2Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallelism: Option<u32>,
+}
+
+impl Argon2Config {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Utils::config_dir()?.join("config.toml"))
+    }
+    /// Loads and validates the file, a missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Self> {
+        let config: Self = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid config file: {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Can not read config file: {}", path.display()));
+            }
+        };
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file: {}", path.display()))?;
+        Ok(config)
+    }
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Can not write config file: {}", path.display()))
+    }
+    fn validate(&self) -> Result<()> {
+        self.user_id()?;
+        self.char_set()?;
+        self.password_length()?;
+        self.ssh_keys()?;
+        if let Some(kdf_params) = &self.kdf {
+            kdf_params.kdf()?;
+        }
+        Ok(())
+    }
+    pub fn user_id(&self) -> Result<Option<UserID>> {
+        Ok(self.user_id.as_deref().map(UserID::from_str).transpose()?)
+    }
+    pub fn char_set(&self) -> Result<Option<CharSet>> {
+        Ok(self
+            .char_sets
+            .as_deref()
+            .map(CharSet::from_names)
+            .transpose()?)
+    }
+    pub fn password_length(&self) -> Result<Option<PasswordLength>> {
+        Ok(self
+            .password_length
+            .map(|l| PasswordLength::from_str(&l.to_string()))
+            .transpose()?)
+    }
+    pub fn ssh_keys(&self) -> Result<Vec<SshKeySpec>> {
+        Ok(self
+            .ssh_keys
+            .iter()
+            .map(|k| SshKeySpec::from_str(k))
+            .collect::<std::result::Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdf::KdfAlgorithm;
+
+    #[test]
+    fn can_load_config() {
+        let path = std::env::temp_dir().join("depasswd-config-test.toml");
+        fs::write(
+            &path,
+            r#"
+user_id = "Example Eleonora"
+char_sets = ["small", "numbers"]
+password_length = 20
+ssh_keys = ["github.com", "gitlab.com:2"]
+
+[kdf]
+m_cost = 65536
+t_cost = 3
+p_cost = 1
+
+[argon2]
+memory = 65536
+"#,
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.user_id.as_deref(), Some("Example Eleonora"));
+        assert_eq!(config.password_length().unwrap().unwrap().as_u16(), 20);
+        assert_eq!(
+            config.ssh_keys().unwrap()[1],
+            SshKeySpec {
+                service_id: "gitlab.com".to_owned(),
+                generation: 2
+            }
+        );
+        assert_eq!(config.argon2.memory, Some(65536));
+        assert_eq!(config.argon2.iterations, None);
+        assert_eq!(
+            config.kdf,
+            Some(KdfParams::new(KdfAlgorithm::Argon2id, 65536, 3, 1).unwrap())
+        );
+
+        fs::write(&path, "password_length = 257\n").unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(&path, "char_sets = [\"emoji\"]\n").unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(&path, "ssh_keys = [\"github.com:0\"]\n").unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(&path, "[kdf]\nm_cost = 8\nt_cost = 1\np_cost = 4\n").unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(
+            &path,
+            "pepper = { command = [\"pass\", \"show\", \"pepper\"] }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().pepper,
+            Some(PepperSource::Command(vec![
+                "pass".to_owned(),
+                "show".to_owned(),
+                "pepper".to_owned()
+            ]))
+        );
+        fs::write(&path, "pepper = { url = \"https://example.org\" }\n").unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(&path, "master_password = \"hunter2\"\n").unwrap();
+        assert!(Config::load(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+    }
+}
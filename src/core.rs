@@ -0,0 +1,620 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The derivation pipeline, master secret → service secret → password,
+//! written against `core` and `alloc` only. It builds with
+//! `default-features = false`, which leaves out everything that needs
+//! `std`, so the algorithm can run on embedded devices such as hardware
+//! password tokens. The rest of the crate wraps it with validated inputs,
+//! other password hashes, purposes and policies.
+//!
+//! The returned secrets are plain buffers: callers are expected to wipe
+//! them, e.g. with `zeroize`, once they are done.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt;
+
+use argon2::{Algorithm, Argon2, Params, Version, password_hash::SaltString};
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use zeroize::Zeroize;
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// Domain separation labels of the stages of the pipeline from scheme v2
+/// on. The labels of the other purposes are in `scheme`.
+pub const MASTER_LABEL: &[u8] = b"depasswd/v2/master";
+pub const SERVICE_LABEL: &[u8] = b"depasswd/v2/service";
+pub const PASSWORD_LABEL: &[u8] = b"depasswd/v2/password";
+pub const PEPPER_LABEL: &[u8] = b"depasswd/v2/pepper";
+/// Labels the key of the recipe vault, derived from the master secret.
+pub const RECIPE_VAULT_LABEL: &[u8] = b"depasswd/v2/recipe-vault";
+/// Labels the master password and keyfile digest stretched together.
+pub const KEYFILE_LABEL: &[u8] = b"depasswd/v2/keyfile";
+/// Labels the user id hashed into the challenge of a YubiKey pepper.
+pub const YUBIKEY_CHALLENGE_LABEL: &[u8] = b"depasswd/v2/yubikey-challenge";
+
+/// The Argon2id costs every scheme used before they became configurable:
+/// memory in KiB, iterations and lanes.
+pub const ARGON2_M_COST: u32 = 32 * 1024;
+pub const ARGON2_T_COST: u32 = 4;
+pub const ARGON2_P_COST: u32 = 4;
+
+pub const MASTER_SECRET_LENGTH: usize = 32;
+
+/// Bytes of expansion scheme v2 reads before giving up on finding a
+/// candidate that satisfies every rule.
+const MAX_STREAM_LENGTH: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "std",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u32", into = "u32")
+)]
+pub enum SchemeVersion {
+    /// The original algorithm: Argon2id master secret, HMAC-SHA512 service
+    /// secret, one byte modulo the alphabet length per character.
+    #[default]
+    V1,
+    /// Length-prefixed, labelled salts that no two inputs share, and
+    /// characters picked by rejection sampling, so every character of the
+    /// alphabet is equally likely.
+    V2,
+    /// Same as v2, but the service secret is a BLAKE3 hash keyed by the
+    /// master secret: much faster than HMAC-SHA512 on embedded and WASM
+    /// targets, and of any length without a separate expansion.
+    V3,
+}
+
+impl SchemeVersion {
+    pub const ALL: [SchemeVersion; 3] = [SchemeVersion::V1, SchemeVersion::V2, SchemeVersion::V3];
+    pub const LATEST: SchemeVersion = SchemeVersion::V3;
+
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            SchemeVersion::V1 => 1,
+            SchemeVersion::V2 => 2,
+            SchemeVersion::V3 => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The user id does not fit into the salt of scheme v1.
+    InvalidSalt(argon2::password_hash::Error),
+    Kdf(argon2::Error),
+    Secret,
+    Char,
+    /// Scheme v1 reads one byte of the service secret per character.
+    TooLong {
+        max: usize,
+    },
+    TooManyClasses {
+        length: usize,
+        classes: usize,
+    },
+    /// No password of `MAX_STREAM_LENGTH` bytes of expansion was accepted.
+    Exhausted,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSalt(e) => write!(f, "{}", e),
+            Error::Kdf(e) => write!(f, "Argon2 failed: {}", e),
+            Error::Secret => write!(f, "Secret error"),
+            Error::Char => write!(f, "Character error"),
+            Error::TooLong { max } => write!(
+                f,
+                "Scheme v1 derives at most {} characters, use scheme v2 for longer passwords",
+                max
+            ),
+            Error::TooManyClasses { length, classes } => write!(
+                f,
+                "A password of {} characters can not contain all of the {} selected character sets",
+                length, classes
+            ),
+            Error::Exhausted => write!(
+                f,
+                "No password of this length and character set satisfies the policy"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The Argon2id costs of the master password hash: memory in KiB,
+/// iterations and lanes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Costs {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Argon2Costs {
+    pub const DEFAULT: Argon2Costs = Argon2Costs {
+        m_cost: ARGON2_M_COST,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+    };
+
+    /// The `KdfParams::id` of Argon2id with these costs, which salts
+    /// non-default costs, see `master_salt_with_kdf`.
+    pub fn id(&self) -> String {
+        format!(
+            "argon2id-m{}-t{}-p{}",
+            self.m_cost, self.t_cost, self.p_cost
+        )
+    }
+}
+
+/// Derives the password of a service in one go, with the default Argon2id
+/// costs. `classes` are the character sets the password is drawn from:
+/// scheme v1 indexes their concatenation, later schemes draw from their
+/// sorted union and put at least one character of each into the password.
+pub fn derive(
+    user_id: &str,
+    master_password: &[u8],
+    service_id: &str,
+    generation: u64,
+    classes: &[&str],
+    password_length: u16,
+    scheme: SchemeVersion,
+) -> Result<String, Error> {
+    let mut master_secret = [0u8; MASTER_SECRET_LENGTH];
+    let password = self::master_secret(user_id, master_password, scheme, &mut master_secret)
+        .and_then(|()| {
+            self::password(
+                &master_secret,
+                service_id,
+                generation,
+                classes,
+                password_length,
+                scheme,
+            )
+        });
+    master_secret.zeroize();
+    password
+}
+
+/// The service secret and password steps of `derive`, from a master secret
+/// stretched with any costs.
+pub fn password(
+    master_secret: &[u8; MASTER_SECRET_LENGTH],
+    service_id: &str,
+    generation: u64,
+    classes: &[&str],
+    password_length: u16,
+    scheme: SchemeVersion,
+) -> Result<String, Error> {
+    let mut service_secret = self::service_secret(
+        master_secret,
+        service_id,
+        generation,
+        password_length,
+        scheme,
+    );
+    let password = match (&service_secret, scheme) {
+        (Ok(service_secret), SchemeVersion::V1) => password_v1(
+            service_secret,
+            &classes.concat(),
+            usize::from(password_length),
+        ),
+        (Ok(service_secret), SchemeVersion::V2 | SchemeVersion::V3) => password_v2(
+            service_secret,
+            scheme,
+            PASSWORD_LABEL,
+            &canonical_alphabet(classes),
+            classes,
+            usize::from(password_length),
+            |_| true,
+        ),
+        (Err(e), _) => Err(*e),
+    };
+    if let Ok(service_secret) = &mut service_secret {
+        service_secret.zeroize();
+    }
+    password
+}
+
+/// Concatenates `fields`, each preceded by its length as a big-endian
+/// `u32`, so different field lists never encode to the same bytes.
+pub fn length_prefixed(fields: &[&[u8]]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(fields.iter().map(|f| 4 + f.len()).sum());
+    for field in fields {
+        encoded.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(field);
+    }
+    encoded
+}
+
+/// The KDF input of a master password used with a keyfile: `KEYFILE_LABEL`,
+/// the password and the SHA-256 digest of the keyfile, length-prefixed.
+/// Without a keyfile the password is the input as it is.
+pub fn keyfile_password(master_password: &[u8], keyfile_digest: &[u8]) -> Vec<u8> {
+    length_prefixed(&[KEYFILE_LABEL, master_password, keyfile_digest])
+}
+
+/// The base64 text scheme v1 salts the master password hash with.
+pub fn master_salt_v1(user_id: &str) -> String {
+    BASE64_STANDARD_NO_PAD.encode(format!("{}{}", user_id.len(), user_id))
+}
+
+/// The salt of the master password hash. From scheme v2 on it is
+/// `MASTER_LABEL` and the user id, length-prefixed, so it can not be
+/// confused with any other salt.
+pub fn master_salt(user_id: &str, scheme: SchemeVersion) -> Result<Vec<u8>, Error> {
+    match scheme {
+        SchemeVersion::V1 => {
+            let mut bytes = [0u8; 64];
+            let salt = SaltString::from_b64(&master_salt_v1(user_id))
+                .and_then(|salt| salt.as_salt().decode_b64(&mut bytes).map(<[u8]>::to_vec))
+                .map_err(Error::InvalidSalt);
+            bytes.zeroize();
+            salt
+        }
+        SchemeVersion::V2 | SchemeVersion::V3 => {
+            Ok(length_prefixed(&[MASTER_LABEL, user_id.as_bytes()]))
+        }
+    }
+}
+
+/// The salt of the master password hash stretched with other than the
+/// default KDF parameters. From scheme v2 on `kdf_id`, the
+/// `KdfParams::id` naming the algorithm and its costs, follows the
+/// `master_salt` fields length-prefixed, so every parameter set hashes
+/// with its own salt. Scheme v1 keeps its salt.
+pub fn master_salt_with_kdf(
+    user_id: &str,
+    scheme: SchemeVersion,
+    kdf_id: &str,
+) -> Result<Vec<u8>, Error> {
+    match scheme {
+        SchemeVersion::V1 => master_salt(user_id, scheme),
+        SchemeVersion::V2 | SchemeVersion::V3 => Ok(length_prefixed(&[
+            MASTER_LABEL,
+            user_id.as_bytes(),
+            kdf_id.as_bytes(),
+        ])),
+    }
+}
+
+/// Stretches the master password with Argon2id and the default costs.
+pub fn master_secret(
+    user_id: &str,
+    master_password: &[u8],
+    scheme: SchemeVersion,
+    output: &mut [u8; MASTER_SECRET_LENGTH],
+) -> Result<(), Error> {
+    master_secret_with_costs(
+        user_id,
+        master_password,
+        scheme,
+        Argon2Costs::DEFAULT,
+        output,
+    )
+}
+
+/// Stretches the master password with Argon2id and `costs`, salted like
+/// `MasterSecret` salts the same `KdfParams`.
+pub fn master_secret_with_costs(
+    user_id: &str,
+    master_password: &[u8],
+    scheme: SchemeVersion,
+    costs: Argon2Costs,
+    output: &mut [u8; MASTER_SECRET_LENGTH],
+) -> Result<(), Error> {
+    let params = Params::new(costs.m_cost, costs.t_cost, costs.p_cost, None).map_err(Error::Kdf)?;
+    let mut salt = if costs == Argon2Costs::DEFAULT {
+        master_salt(user_id, scheme)?
+    } else {
+        master_salt_with_kdf(user_id, scheme, &costs.id())?
+    };
+    let result = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(master_password, &salt, output)
+        .map_err(Error::Kdf);
+    salt.zeroize();
+    result
+}
+
+/// Mixes a pepper, a secret kept apart from the master password, into the
+/// stretched master secret: HMAC-SHA512 keyed with the pepper over
+/// `PEPPER_LABEL` and the master secret, truncated to its length.
+pub fn pepper_master_secret(
+    master_secret: &mut [u8; MASTER_SECRET_LENGTH],
+    pepper: &[u8],
+) -> Result<(), Error> {
+    let mut hmac_sha512 = HmacSha512::new_from_slice(pepper).map_err(|_| Error::Secret)?;
+    hmac_sha512.update(&length_prefixed(&[PEPPER_LABEL, master_secret]));
+    let mut peppered = hmac_sha512.finalize().into_bytes();
+    master_secret.copy_from_slice(&peppered[..MASTER_SECRET_LENGTH]);
+    peppered.zeroize();
+    Ok(())
+}
+
+/// The base64 text scheme v1 salts the service secret with. It
+/// concatenates decimal text, where e.g. length 1 with generation 11
+/// equals length 11 with generation 1.
+pub fn service_salt_v1(service_id: &str, generation: u64, password_length: u16) -> String {
+    BASE64_STANDARD_NO_PAD.encode(format!(
+        "{}{}{}{}",
+        service_id.len(),
+        service_id,
+        password_length,
+        generation
+    ))
+}
+
+/// The salt from scheme v2: `SERVICE_LABEL` and every field
+/// length-prefixed, the numbers as big-endian integers, so no two inputs
+/// share a salt.
+pub fn canonical_service_salt(service_id: &str, generation: u64, password_length: u16) -> Vec<u8> {
+    length_prefixed(&[
+        SERVICE_LABEL,
+        service_id.as_bytes(),
+        &password_length.to_be_bytes(),
+        &generation.to_be_bytes(),
+    ])
+}
+
+/// The 64 byte secret of one service: HMAC-SHA512 keyed by the hex of the
+/// master secret up to scheme v2, the BLAKE3 output stream keyed by the
+/// master secret from v3.
+pub fn service_secret(
+    master_secret: &[u8],
+    service_id: &str,
+    generation: u64,
+    password_length: u16,
+    scheme: SchemeVersion,
+) -> Result<Vec<u8>, Error> {
+    let mut salt = match scheme {
+        SchemeVersion::V1 => service_salt_v1(service_id, generation, password_length).into_bytes(),
+        SchemeVersion::V2 | SchemeVersion::V3 => {
+            canonical_service_salt(service_id, generation, password_length)
+        }
+    };
+    let service_secret = match scheme {
+        SchemeVersion::V1 | SchemeVersion::V2 => {
+            let mut key = bytes_to_hex(master_secret);
+            let hmac_sha512 = HmacSha512::new_from_slice(key.as_bytes());
+            key.zeroize();
+            let mut hmac_sha512 = hmac_sha512.map_err(|_| Error::Secret)?;
+            hmac_sha512.update(&salt);
+            Ok(hmac_sha512.finalize().into_bytes().to_vec())
+        }
+        SchemeVersion::V3 => <&[u8; 32]>::try_from(master_secret)
+            .map_err(|_| Error::Secret)
+            .map(|key| {
+                let mut service_secret = vec![0u8; 64];
+                blake3::Hasher::new_keyed(key)
+                    .update(&salt)
+                    .finalize_xof()
+                    .fill(&mut service_secret);
+                service_secret
+            }),
+    };
+    salt.zeroize();
+    service_secret
+}
+
+/// Expansion keyed by the service secret, for output longer than the 64
+/// bytes of the secret itself. A longer expansion starts with the
+/// shorter.
+///
+/// Up to scheme v2 it is counter-mode HMAC-SHA512: every block is
+/// `HMAC(service_secret, info || counter)` with a big-endian `u32`
+/// counter starting at 1. From v3 it is the BLAKE3 output stream of
+/// `info`, keyed by the first 32 bytes of the service secret.
+pub fn expand(
+    service_secret: &[u8],
+    scheme: SchemeVersion,
+    info: &[u8],
+    length: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::with_capacity(length);
+    match scheme {
+        SchemeVersion::V1 | SchemeVersion::V2 => {
+            let mut counter: u32 = 0;
+            while output.len() < length {
+                counter = counter.checked_add(1).ok_or(Error::Secret)?;
+                let mut hmac_sha512 =
+                    HmacSha512::new_from_slice(service_secret).map_err(|_| Error::Secret)?;
+                hmac_sha512.update(info);
+                hmac_sha512.update(&counter.to_be_bytes());
+                output.extend_from_slice(&hmac_sha512.finalize().into_bytes());
+            }
+            output.truncate(length);
+        }
+        SchemeVersion::V3 => {
+            let key: &[u8; 32] = service_secret
+                .get(..32)
+                .and_then(|key| key.try_into().ok())
+                .ok_or(Error::Secret)?;
+            output.resize(length, 0);
+            blake3::Hasher::new_keyed(key)
+                .update(info)
+                .finalize_xof()
+                .fill(&mut output);
+        }
+    }
+    Ok(output)
+}
+
+/// The alphabet of scheme v2: the characters of `classes` sorted by code
+/// point without duplicates, so neither the order of the classes nor
+/// overlaps between them matter.
+pub fn canonical_alphabet(classes: &[&str]) -> Vec<char> {
+    let mut alphabet: Vec<char> = classes.iter().flat_map(|class| class.chars()).collect();
+    alphabet.sort_unstable();
+    alphabet.dedup();
+    alphabet
+}
+
+/// Scheme v1: character `i` is `char_set[service_secret[i] % len]`, where
+/// the length is counted in bytes, so only ASCII sets are uniform.
+pub fn password_v1(service_secret: &[u8], char_set: &str, length: usize) -> Result<String, Error> {
+    if service_secret.len() < length {
+        return Err(Error::TooLong {
+            max: service_secret.len(),
+        });
+    }
+    if char_set.is_empty() {
+        return Err(Error::Char);
+    }
+    service_secret[..length]
+        .iter()
+        .map(|byte| {
+            char_set
+                .chars()
+                .nth(usize::from(*byte) % char_set.len())
+                .ok_or(Error::Char)
+        })
+        .collect()
+}
+
+/// Scheme v2 and later: characters come from the expansion of the service
+/// secret under `label`, each byte mapped by `uniform_pick` onto
+/// `alphabet`. Candidates missing a character of any of `classes` or
+/// rejected by `accept` are skipped, so the first compliant one is chosen
+/// uniformly among all compliant passwords.
+pub fn password_v2<S: AsRef<str>>(
+    service_secret: &[u8],
+    scheme: SchemeVersion,
+    label: &[u8],
+    alphabet: &[char],
+    classes: &[S],
+    length: usize,
+    accept: impl Fn(&str) -> bool,
+) -> Result<String, Error> {
+    if alphabet.is_empty() || alphabet.len() > 256 {
+        return Err(Error::Char);
+    }
+    if classes.len() > length {
+        return Err(Error::TooManyClasses {
+            length,
+            classes: classes.len(),
+        });
+    }
+    let mut password = String::with_capacity(length);
+    let mut count = 0;
+    let mut stream: Vec<u8> = vec![];
+    let mut consumed = 0;
+    loop {
+        if consumed == stream.len() {
+            if stream.len() >= MAX_STREAM_LENGTH {
+                stream.zeroize();
+                password.zeroize();
+                return Err(Error::Exhausted);
+            }
+            // The expansion is prefix-stable, so growing it only appends.
+            let stream_length = (stream.len() * 2).max(service_secret.len());
+            stream.zeroize();
+            stream = expand(service_secret, scheme, label, stream_length)?;
+        }
+        if let Some(c) = uniform_pick(alphabet, stream[consumed]) {
+            password.push(c);
+            count += 1;
+        }
+        consumed += 1;
+        if count == length {
+            if classes
+                .iter()
+                .all(|class| password.chars().any(|c| class.as_ref().contains(c)))
+                && accept(&password)
+            {
+                break;
+            }
+            password.zeroize();
+            count = 0;
+        }
+    }
+    stream.zeroize();
+    Ok(password)
+}
+
+/// Maps `byte` onto `alphabet` by rejection sampling: bytes past the
+/// largest multiple of the alphabet length yield `None`.
+pub fn uniform_pick<T: Copy>(alphabet: &[T], byte: u8) -> Option<T> {
+    let limit = 256 - 256 % alphabet.len();
+    let byte = usize::from(byte);
+    (byte < limit).then(|| alphabet[byte % alphabet.len()])
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    bytes
+        .iter()
+        .flat_map(|b| [DIGITS[usize::from(b >> 4)], DIGITS[usize::from(b & 0xf)]])
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_derive_without_std() {
+        let classes = [
+            crate::SMALL_LETTERS,
+            crate::CAPITAL_LETTERS,
+            crate::NUMBERS,
+            crate::SPECIAL_CHARS,
+        ];
+        let derive_with = |password_length, scheme| {
+            derive(
+                "Example Eleonora",
+                br##"]lE~WExZ468ty{I5mtg["##,
+                "Example Service Name",
+                1,
+                &classes,
+                password_length,
+                scheme,
+            )
+        };
+        assert_eq!(
+            derive_with(20, SchemeVersion::V1).unwrap(),
+            "1@MWtAAqZ0p>;;y@zZ6d"
+        );
+        assert_eq!(
+            derive_with(20, SchemeVersion::V2).unwrap(),
+            r##"'TrDo+]E\VNL:n5jrd&}"##
+        );
+        assert_eq!(
+            derive_with(20, SchemeVersion::V3).unwrap(),
+            r##"i2z(cqLFmcFX{TBjS4+,"##
+        );
+        assert_eq!(
+            derive_with(65, SchemeVersion::V1),
+            Err(Error::TooLong { max: 64 })
+        );
+        assert_eq!(
+            derive_with(3, SchemeVersion::V3),
+            Err(Error::TooManyClasses {
+                length: 3,
+                classes: 4
+            })
+        );
+    }
+}
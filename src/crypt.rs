@@ -0,0 +1,203 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{PasswordHasher, SaltString},
+};
+use hmac::{Hmac, Mac};
+use pwhash::{
+    bcrypt::{BcryptSetup, BcryptVariant},
+    sha512_crypt,
+};
+use rand_core::OsRng;
+use sha2::Sha256;
+
+use crate::yescrypt::{ITOA64, Yescrypt};
+
+pub const DEFAULT_BCRYPT_COST: u32 = 12;
+
+/// Hash algorithm of the `shadow` output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadowAlgorithm {
+    /// `$6$`, understood by every glibc based system and installer.
+    #[default]
+    Sha512,
+    /// `$y$`, the default of current Debian, Fedora and Arch.
+    Yescrypt,
+}
+
+impl ShadowAlgorithm {
+    pub const ALL: [ShadowAlgorithm; 2] = [ShadowAlgorithm::Sha512, ShadowAlgorithm::Yescrypt];
+}
+
+impl FromStr for ShadowAlgorithm {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.to_string() == s)
+            .ok_or(anyhow!(
+                "Unknown shadow algorithm: {} (expected one of: {})",
+                s,
+                Self::ALL.map(|a| a.to_string()).join(", ")
+            ))
+    }
+}
+
+impl Display for ShadowAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ShadowAlgorithm::Sha512 => "sha512",
+                ShadowAlgorithm::Yescrypt => "yescrypt",
+            }
+        )
+    }
+}
+
+/// One-way hashes of a derived password, for systems that only need to verify
+/// it (web server basic auth, `/etc/shadow`).
+pub struct Crypt {}
+
+impl Crypt {
+    /// `$2y$` bcrypt hash, the variant Apache's `htpasswd -B` produces.
+    pub fn bcrypt(password: &str, cost: u32) -> Result<String> {
+        Ok(pwhash::bcrypt::hash_with(
+            BcryptSetup {
+                salt: None,
+                cost: Some(cost),
+                variant: Some(BcryptVariant::V2y),
+            },
+            password,
+        )?)
+    }
+    /// Argon2id PHC string. `Params::default()` is the OWASP recommended cost.
+    pub fn argon2id(password: &str, params: Params) -> Result<String> {
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))?
+            .to_string())
+    }
+    /// `$6$` SHA-512 crypt hash with a random salt, as used in `/etc/shadow`.
+    pub fn sha512_crypt(password: &str) -> Result<String> {
+        Ok(sha512_crypt::hash(password)?)
+    }
+    /// `/etc/shadow` hash with a salt derived from `salt_key` and the
+    /// password, so that deriving again prints the very same line. The salt
+    /// is a keyed hash: it does not help guessing the password.
+    pub fn shadow(password: &str, algorithm: ShadowAlgorithm, salt_key: &[u8]) -> Result<String> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(salt_key)?;
+        mac.update(b"depasswd shadow salt\0");
+        mac.update(password.as_bytes());
+        let salt = &mac.finalize().into_bytes()[..16];
+        Ok(match algorithm {
+            ShadowAlgorithm::Sha512 => {
+                let salt: String = salt
+                    .iter()
+                    .map(|b| ITOA64[(b & 0x3f) as usize] as char)
+                    .collect();
+                sha512_crypt::hash_with(format!("$6${}", salt).as_str(), password)?
+            }
+            ShadowAlgorithm::Yescrypt => Yescrypt::hash(password.as_bytes(), salt),
+        })
+    }
+    pub fn htpasswd_line(username: &str, hash: &str) -> String {
+        format!("{}:{}", username, hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_hash_with_bcrypt() {
+        let hash = Crypt::bcrypt("1@MWtAAqZ0p>;;y@zZ6d", 4).unwrap();
+        assert!(hash.starts_with("$2y$04$"));
+        assert!(pwhash::bcrypt::verify("1@MWtAAqZ0p>;;y@zZ6d", &hash));
+        assert!(!pwhash::bcrypt::verify("1@MWtAAqZ0p>;;y@zZ6e", &hash));
+    }
+
+    #[test]
+    fn can_hash_with_argon2id() {
+        use argon2::{PasswordHash, PasswordVerifier};
+
+        let hash = Crypt::argon2id("1@MWtAAqZ0p>;;y@zZ6d", Params::default()).unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+        let hash = Crypt::argon2id(
+            "1@MWtAAqZ0p>;;y@zZ6d",
+            Params::new(8192, 3, 1, None).unwrap(),
+        )
+        .unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=8192,t=3,p=1$"));
+        assert!(
+            Argon2::default()
+                .verify_password(b"1@MWtAAqZ0p>;;y@zZ6d", &PasswordHash::new(&hash).unwrap())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn can_hash_with_sha512_crypt() {
+        let hash = Crypt::sha512_crypt("1@MWtAAqZ0p>;;y@zZ6d").unwrap();
+        assert!(hash.starts_with("$6$"));
+        assert!(sha512_crypt::verify("1@MWtAAqZ0p>;;y@zZ6d", &hash));
+    }
+
+    #[test]
+    fn can_hash_for_shadow() {
+        let sha512 =
+            Crypt::shadow("1@MWtAAqZ0p>;;y@zZ6d", ShadowAlgorithm::Sha512, b"key").unwrap();
+        assert!(sha512.starts_with("$6$"));
+        assert_eq!(sha512.split('$').nth(2).unwrap().len(), 16);
+        assert!(sha512_crypt::verify("1@MWtAAqZ0p>;;y@zZ6d", &sha512));
+        assert_eq!(
+            Crypt::shadow("1@MWtAAqZ0p>;;y@zZ6d", ShadowAlgorithm::Sha512, b"key").unwrap(),
+            sha512
+        );
+        assert_ne!(
+            Crypt::shadow(
+                "1@MWtAAqZ0p>;;y@zZ6d",
+                ShadowAlgorithm::Sha512,
+                b"other key"
+            )
+            .unwrap(),
+            sha512
+        );
+        assert_eq!(
+            pwhash::sha512_crypt::hash_with("$6$abcdefghijklmnop", "1@MWtAAqZ0p>;;y@zZ6d").unwrap(),
+            "$6$abcdefghijklmnop$Xin/ChzFQrTAEglm50WIqe4Uq2j3gQewquWvImVMcAaWC6D6sb0Azze1BqOC5Z.pQHJsrVyXTKaTwePCpZbnI/"
+        );
+
+        let yescrypt =
+            Crypt::shadow("1@MWtAAqZ0p>;;y@zZ6d", ShadowAlgorithm::Yescrypt, b"key").unwrap();
+        assert!(yescrypt.starts_with("$y$j9T$"));
+        assert_eq!(yescrypt.split('$').nth(3).unwrap().len(), 22);
+    }
+
+    #[test]
+    fn can_format_htpasswd_line() {
+        assert_eq!(Crypt::htpasswd_line("admin", "$2y$04$x"), "admin:$2y$04$x");
+    }
+}
@@ -0,0 +1,799 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The part of D-Bus depasswd needs, without a native library: the wire
+//! format, SASL `EXTERNAL` authentication over Unix sockets, and sending
+//! and receiving messages. Enough to call the bus, own a name and answer
+//! method calls.
+
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+};
+
+use anyhow::{Result, anyhow, bail};
+use percent_encoding::percent_decode_str;
+use zeroize::{Zeroize, Zeroizing};
+
+pub const BUS_NAME: &str = "org.freedesktop.DBus";
+pub const BUS_PATH: &str = "/org/freedesktop/DBus";
+
+pub const NO_REPLY_EXPECTED: u8 = 0x1;
+
+/// The limit of the specification.
+const MAX_MESSAGE_LENGTH: usize = 128 * 1024 * 1024;
+const MAX_ARRAY_LENGTH: usize = 64 * 1024 * 1024;
+/// Containers nested deeper are refused instead of overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+/// A D-Bus value of the types depasswd uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    I32(i32),
+    U32(u32),
+    U64(u64),
+    Str(String),
+    ObjectPath(String),
+    Signature(String),
+    /// The element signature and the elements, so that empty arrays are typed.
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+    Variant(Box<Value>),
+}
+
+impl Value {
+    pub fn str(value: impl Into<String>) -> Self {
+        Value::Str(value.into())
+    }
+    pub fn path(value: impl Into<String>) -> Self {
+        Value::ObjectPath(value.into())
+    }
+    pub fn variant(value: Value) -> Self {
+        Value::Variant(Box::new(value))
+    }
+    /// An array of object paths.
+    pub fn paths(values: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Value::Array(
+            "o".to_owned(),
+            values.into_iter().map(|v| Value::path(v)).collect(),
+        )
+    }
+    pub fn bytes(values: &[u8]) -> Self {
+        Value::Array(
+            "y".to_owned(),
+            values.iter().copied().map(Value::Byte).collect(),
+        )
+    }
+    /// A dictionary (`a{..}`) of `entries`, keys of type `key_signature`
+    /// and values of type `value_signature`.
+    pub fn dict(
+        key_signature: &str,
+        value_signature: &str,
+        entries: impl IntoIterator<Item = (Value, Value)>,
+    ) -> Self {
+        Value::Array(
+            format!("{{{}{}}}", key_signature, value_signature),
+            entries
+                .into_iter()
+                .map(|(k, v)| Value::DictEntry(Box::new(k), Box::new(v)))
+                .collect(),
+        )
+    }
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => "y".to_owned(),
+            Value::Bool(_) => "b".to_owned(),
+            Value::I32(_) => "i".to_owned(),
+            Value::U32(_) => "u".to_owned(),
+            Value::U64(_) => "t".to_owned(),
+            Value::Str(_) => "s".to_owned(),
+            Value::ObjectPath(_) => "o".to_owned(),
+            Value::Signature(_) => "g".to_owned(),
+            Value::Array(element, _) => format!("a{}", element),
+            Value::Struct(fields) => format!(
+                "({})",
+                fields.iter().map(Value::signature).collect::<String>()
+            ),
+            Value::DictEntry(k, v) => format!("{{{}{}}}", k.signature(), v.signature()),
+            Value::Variant(_) => "v".to_owned(),
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) | Value::ObjectPath(s) | Value::Signature(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::U32(v) => Some(*v),
+            _ => None,
+        }
+    }
+    /// The bytes of an `ay` array.
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        self.as_array()?
+            .iter()
+            .map(|b| match b {
+                Value::Byte(b) => Some(*b),
+                _ => None,
+            })
+            .collect()
+    }
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(_, items) => Some(items),
+            _ => None,
+        }
+    }
+    /// The entries of an `a{ss}` dictionary.
+    pub fn as_string_dict(&self) -> Option<Vec<(&str, &str)>> {
+        self.as_array()?
+            .iter()
+            .map(|entry| match entry {
+                Value::DictEntry(k, v) => Some((k.as_str()?, v.as_str()?)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Zeroize for Value {
+    fn zeroize(&mut self) {
+        match self {
+            Value::Byte(v) => v.zeroize(),
+            Value::Bool(v) => v.zeroize(),
+            Value::I32(v) => v.zeroize(),
+            Value::U32(v) => v.zeroize(),
+            Value::U64(v) => v.zeroize(),
+            Value::Str(v) | Value::ObjectPath(v) | Value::Signature(v) => v.zeroize(),
+            Value::Array(_, items) | Value::Struct(items) => items.zeroize(),
+            Value::DictEntry(k, v) => {
+                k.zeroize();
+                v.zeroize();
+            }
+            Value::Variant(v) => v.zeroize(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    MethodCall = 1,
+    MethodReturn = 2,
+    Error = 3,
+    Signal = 4,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = anyhow::Error;
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(MessageType::MethodCall),
+            2 => Ok(MessageType::MethodReturn),
+            3 => Ok(MessageType::Error),
+            4 => Ok(MessageType::Signal),
+            _ => bail!("Unknown D-Bus message type {}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub message_type: MessageType,
+    pub flags: u8,
+    /// Assigned by `Connection::send`.
+    pub serial: u32,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub reply_serial: Option<u32>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    pub body: Vec<Value>,
+}
+
+impl Message {
+    fn new(message_type: MessageType) -> Self {
+        Self {
+            message_type,
+            flags: 0,
+            serial: 0,
+            path: None,
+            interface: None,
+            member: None,
+            error_name: None,
+            reply_serial: None,
+            destination: None,
+            sender: None,
+            body: vec![],
+        }
+    }
+    pub fn method_call(
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        body: Vec<Value>,
+    ) -> Self {
+        Self {
+            path: Some(path.to_owned()),
+            interface: Some(interface.to_owned()),
+            member: Some(member.to_owned()),
+            destination: Some(destination.to_owned()),
+            body,
+            ..Self::new(MessageType::MethodCall)
+        }
+    }
+    pub fn method_return(call: &Message, body: Vec<Value>) -> Self {
+        Self {
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            body,
+            ..Self::new(MessageType::MethodReturn)
+        }
+    }
+    pub fn error(call: &Message, name: &str, text: &str) -> Self {
+        Self {
+            message_type: MessageType::Error,
+            error_name: Some(name.to_owned()),
+            ..Self::method_return(call, vec![Value::str(text)])
+        }
+    }
+    pub fn expects_reply(&self) -> bool {
+        self.message_type == MessageType::MethodCall && self.flags & NO_REPLY_EXPECTED == 0
+    }
+    /// The signature of the body.
+    pub fn signature(&self) -> String {
+        self.body.iter().map(Value::signature).collect()
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        let field = |code, value| Value::Struct(vec![Value::Byte(code), Value::variant(value)]);
+        let mut fields = vec![];
+        fields.extend(self.path.clone().map(|v| field(1, Value::ObjectPath(v))));
+        fields.extend(self.interface.clone().map(|v| field(2, Value::Str(v))));
+        fields.extend(self.member.clone().map(|v| field(3, Value::Str(v))));
+        fields.extend(self.error_name.clone().map(|v| field(4, Value::Str(v))));
+        fields.extend(self.reply_serial.map(|v| field(5, Value::U32(v))));
+        fields.extend(self.destination.clone().map(|v| field(6, Value::Str(v))));
+        fields.extend(self.sender.clone().map(|v| field(7, Value::Str(v))));
+        if !self.body.is_empty() {
+            fields.push(field(8, Value::Signature(self.signature())));
+        }
+
+        let mut writer = Writer::default();
+        writer
+            .buffer
+            .extend([b'l', self.message_type as u8, self.flags, 1]);
+        writer.put_u32(0);
+        writer.put_u32(self.serial);
+        writer.put(&Value::Array("(yv)".to_owned(), fields));
+        writer.align(8);
+        let body_start = writer.buffer.len();
+        for value in &self.body {
+            writer.put(value);
+        }
+        let body_length = (writer.buffer.len() - body_start) as u32;
+        writer.buffer[4..8].copy_from_slice(&body_length.to_le_bytes());
+        writer.buffer
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader {
+            buffer: bytes,
+            position: 0,
+            big_endian: match bytes.first() {
+                Some(b'l') => false,
+                Some(b'B') => true,
+                _ => bail!("Invalid D-Bus message"),
+            },
+        };
+        let header = reader.take(4)?;
+        if header[3] != 1 {
+            bail!("Unsupported D-Bus protocol version {}", header[3]);
+        }
+        let mut message = Self::new(MessageType::try_from(header[1])?);
+        message.flags = header[2];
+        let body_length = reader.u32()? as usize;
+        message.serial = reader.u32()?;
+        let mut signature = String::new();
+        for field in reader.get("a(yv)", 0)?.as_array().unwrap_or_default() {
+            let (code, value) = match field {
+                Value::Struct(fields) => match fields.as_slice() {
+                    [Value::Byte(code), Value::Variant(value)] => (*code, value.as_ref()),
+                    _ => bail!("Invalid D-Bus header field"),
+                },
+                _ => bail!("Invalid D-Bus header field"),
+            };
+            let string = || value.as_str().map(str::to_owned);
+            match code {
+                1 => message.path = string(),
+                2 => message.interface = string(),
+                3 => message.member = string(),
+                4 => message.error_name = string(),
+                5 => message.reply_serial = value.as_u32(),
+                6 => message.destination = string(),
+                7 => message.sender = string(),
+                8 => signature = string().unwrap_or_default(),
+                _ => {}
+            }
+        }
+        reader.align(8)?;
+        if reader.buffer.len() - reader.position != body_length {
+            bail!("Invalid D-Bus message length");
+        }
+        let mut rest = signature.as_str();
+        while !rest.is_empty() {
+            let (single, tail) = split_type(rest)?;
+            message.body.push(reader.get(single, 0)?);
+            rest = tail;
+        }
+        if reader.position != reader.buffer.len() {
+            bail!("The D-Bus message body does not match its signature");
+        }
+        Ok(message)
+    }
+}
+
+/// The alignment of the first type of `signature`.
+fn alignment(signature: &str) -> usize {
+    match signature.as_bytes().first() {
+        Some(b'b' | b'i' | b'u' | b's' | b'o' | b'a') => 4,
+        Some(b't' | b'(' | b'{') => 8,
+        _ => 1,
+    }
+}
+
+/// Splits the first complete type off `signature`.
+fn split_type(signature: &str) -> Result<(&str, &str)> {
+    let length = match signature.as_bytes().first() {
+        Some(b'y' | b'b' | b'i' | b'u' | b't' | b's' | b'o' | b'g' | b'v') => 1,
+        Some(b'a') => 1 + split_type(&signature[1..])?.0.len(),
+        Some(open @ (b'(' | b'{')) => {
+            let close = if *open == b'(' { b')' } else { b'}' };
+            let mut depth = 0;
+            signature
+                .bytes()
+                .position(|c| {
+                    if c == *open {
+                        depth += 1;
+                    } else if c == close {
+                        depth -= 1;
+                    }
+                    depth == 0
+                })
+                .ok_or_else(|| anyhow!("Invalid D-Bus signature: {}", signature))?
+                + 1
+        }
+        _ => bail!("Unsupported D-Bus signature: {}", signature),
+    };
+    Ok(signature.split_at(length))
+}
+
+#[derive(Default)]
+struct Writer {
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self, alignment: usize) {
+        self.buffer
+            .resize(self.buffer.len().next_multiple_of(alignment), 0);
+    }
+    fn put_u32(&mut self, value: u32) {
+        self.align(4);
+        self.buffer.extend(value.to_le_bytes());
+    }
+    fn put(&mut self, value: &Value) {
+        match value {
+            Value::Byte(v) => self.buffer.push(*v),
+            Value::Bool(v) => self.put_u32(*v as u32),
+            Value::I32(v) => self.put_u32(*v as u32),
+            Value::U32(v) => self.put_u32(*v),
+            Value::U64(v) => {
+                self.align(8);
+                self.buffer.extend(v.to_le_bytes());
+            }
+            Value::Str(v) | Value::ObjectPath(v) => {
+                self.put_u32(v.len() as u32);
+                self.buffer.extend(v.as_bytes());
+                self.buffer.push(0);
+            }
+            Value::Signature(v) => {
+                self.buffer.push(v.len() as u8);
+                self.buffer.extend(v.as_bytes());
+                self.buffer.push(0);
+            }
+            Value::Array(element, items) => {
+                self.put_u32(0);
+                let length_at = self.buffer.len() - 4;
+                self.align(alignment(element));
+                let start = self.buffer.len();
+                for item in items {
+                    self.put(item);
+                }
+                let length = (self.buffer.len() - start) as u32;
+                self.buffer[length_at..length_at + 4].copy_from_slice(&length.to_le_bytes());
+            }
+            Value::Struct(fields) => {
+                self.align(8);
+                for field in fields {
+                    self.put(field);
+                }
+            }
+            Value::DictEntry(k, v) => {
+                self.align(8);
+                self.put(k);
+                self.put(v);
+            }
+            Value::Variant(v) => {
+                self.put(&Value::Signature(v.signature()));
+                self.put(v);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn align(&mut self, alignment: usize) -> Result<()> {
+        let position = self.position.next_multiple_of(alignment);
+        if position > self.buffer.len() {
+            bail!("Truncated D-Bus message");
+        }
+        self.position = position;
+        Ok(())
+    }
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .buffer
+            .get(self.position..self.position + length)
+            .ok_or_else(|| anyhow!("Truncated D-Bus message"))?;
+        self.position += length;
+        Ok(bytes)
+    }
+    fn u32(&mut self) -> Result<u32> {
+        self.align(4)?;
+        let bytes = self.take(4)?.try_into()?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+    fn string(&mut self, length: usize) -> Result<String> {
+        let string = String::from_utf8(self.take(length)?.to_vec())?;
+        if self.take(1)? != [0] {
+            bail!("Unterminated D-Bus string");
+        }
+        Ok(string)
+    }
+    /// Reads one value of the complete type `signature`.
+    fn get(&mut self, signature: &str, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("D-Bus value is nested too deeply");
+        }
+        Ok(match signature.as_bytes()[0] {
+            b'y' => Value::Byte(self.take(1)?[0]),
+            b'b' => match self.u32()? {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                _ => bail!("Invalid D-Bus boolean"),
+            },
+            b'i' => Value::I32(self.u32()? as i32),
+            b'u' => Value::U32(self.u32()?),
+            b't' => {
+                self.align(8)?;
+                let bytes = self.take(8)?.try_into()?;
+                Value::U64(if self.big_endian {
+                    u64::from_be_bytes(bytes)
+                } else {
+                    u64::from_le_bytes(bytes)
+                })
+            }
+            b's' => {
+                let length = self.u32()? as usize;
+                Value::Str(self.string(length)?)
+            }
+            b'o' => {
+                let length = self.u32()? as usize;
+                Value::ObjectPath(self.string(length)?)
+            }
+            b'g' => {
+                let length = self.take(1)?[0] as usize;
+                Value::Signature(self.string(length)?)
+            }
+            b'a' => {
+                let length = self.u32()? as usize;
+                if length > MAX_ARRAY_LENGTH {
+                    bail!("D-Bus array is too long");
+                }
+                let element = &signature[1..];
+                self.align(alignment(element))?;
+                let end = self.position + length;
+                let mut items = vec![];
+                while self.position < end {
+                    items.push(self.get(element, depth + 1)?);
+                }
+                if self.position != end {
+                    bail!("Invalid D-Bus array length");
+                }
+                Value::Array(element.to_owned(), items)
+            }
+            b'(' => {
+                self.align(8)?;
+                let mut rest = &signature[1..signature.len() - 1];
+                let mut fields = vec![];
+                while !rest.is_empty() {
+                    let (field, tail) = split_type(rest)?;
+                    fields.push(self.get(field, depth + 1)?);
+                    rest = tail;
+                }
+                Value::Struct(fields)
+            }
+            b'{' => {
+                self.align(8)?;
+                let (key, value) = split_type(&signature[1..signature.len() - 1])?;
+                Value::DictEntry(
+                    Box::new(self.get(key, depth + 1)?),
+                    Box::new(self.get(value, depth + 1)?),
+                )
+            }
+            b'v' => {
+                let length = self.take(1)?[0] as usize;
+                let inner = self.string(length)?;
+                match split_type(&inner)? {
+                    (single, "") => Value::variant(self.get(single, depth + 1)?),
+                    _ => bail!("A D-Bus variant holds exactly one type"),
+                }
+            }
+            _ => bail!("Unsupported D-Bus signature: {}", signature),
+        })
+    }
+}
+
+/// A connection to a message bus.
+pub struct Connection {
+    stream: UnixStream,
+    serial: u32,
+    unique_name: String,
+}
+
+impl Connection {
+    /// Connects to the bus of the desktop session.
+    pub fn session() -> Result<Self> {
+        let address = env::var("DBUS_SESSION_BUS_ADDRESS")
+            .map_err(|_| anyhow!("DBUS_SESSION_BUS_ADDRESS is not set, no session bus found"))?;
+        Self::connect(&address)
+    }
+    /// Connects to the first reachable Unix socket of a bus address list.
+    pub fn connect(address: &str) -> Result<Self> {
+        let stream = address
+            .split(';')
+            .find_map(|address| Self::open(address).ok())
+            .ok_or_else(|| anyhow!("Can not connect to the D-Bus address {}", address))?;
+        let mut connection = Self {
+            stream,
+            serial: 0,
+            unique_name: String::new(),
+        };
+        connection.authenticate()?;
+        let reply = connection.call(Message::method_call(
+            BUS_NAME,
+            BUS_PATH,
+            BUS_NAME,
+            "Hello",
+            vec![],
+        ))?;
+        connection.unique_name = reply
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Invalid reply to Hello"))?
+            .to_owned();
+        Ok(connection)
+    }
+    fn open(address: &str) -> Result<UnixStream> {
+        let options = address
+            .strip_prefix("unix:")
+            .ok_or_else(|| anyhow!("Unsupported D-Bus transport: {}", address))?;
+        for option in options.split(',') {
+            let Some((key, value)) = option.split_once('=') else {
+                continue;
+            };
+            let value = percent_decode_str(value).decode_utf8()?;
+            match key {
+                "path" => return Ok(UnixStream::connect(&*value)?),
+                #[cfg(target_os = "linux")]
+                "abstract" => {
+                    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+                    return Ok(UnixStream::connect_addr(&SocketAddr::from_abstract_name(
+                        value.as_bytes(),
+                    )?)?);
+                }
+                _ => {}
+            }
+        }
+        bail!("Unsupported D-Bus address: {}", address)
+    }
+    /// SASL `EXTERNAL` with the identity the bus reads from the socket.
+    fn authenticate(&mut self) -> Result<()> {
+        self.stream.write_all(b"\0AUTH EXTERNAL\r\n")?;
+        let mut line = self.read_line()?;
+        if line == "DATA" {
+            self.stream.write_all(b"DATA\r\n")?;
+            line = self.read_line()?;
+        }
+        if !line.starts_with("OK ") {
+            bail!("D-Bus authentication failed: {}", line);
+        }
+        self.stream.write_all(b"BEGIN\r\n")?;
+        Ok(())
+    }
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = vec![];
+        let mut byte = [0u8];
+        while !line.ends_with(b"\r\n") {
+            if line.len() > 1024 {
+                bail!("D-Bus authentication line is too long");
+            }
+            self.stream.read_exact(&mut byte)?;
+            line.push(byte[0]);
+        }
+        line.truncate(line.len() - 2);
+        Ok(String::from_utf8(line)?)
+    }
+    /// The name the bus assigned to this connection.
+    pub fn unique_name(&self) -> &str {
+        &self.unique_name
+    }
+    /// Sends `message` with the next serial and returns the serial.
+    pub fn send(&mut self, message: &mut Message) -> Result<u32> {
+        self.serial += 1;
+        message.serial = self.serial;
+        self.stream.write_all(&Zeroizing::new(message.encode()))?;
+        Ok(self.serial)
+    }
+    pub fn receive(&mut self) -> Result<Message> {
+        let mut fixed = [0u8; 16];
+        self.stream.read_exact(&mut fixed)?;
+        let u32_at = |at: usize| {
+            let bytes = fixed[at..at + 4].try_into().unwrap_or_default();
+            if fixed[0] == b'B' {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+        let length = (16 + u32_at(12) as usize).next_multiple_of(8) + u32_at(4) as usize;
+        if length > MAX_MESSAGE_LENGTH {
+            bail!("D-Bus message is too long");
+        }
+        let mut bytes = Zeroizing::new(fixed.to_vec());
+        bytes.resize(length, 0);
+        self.stream.read_exact(&mut bytes[16..])?;
+        Message::decode(&bytes)
+    }
+    /// Calls a method and waits for its reply. Messages received in the
+    /// meantime are dropped.
+    pub fn call(&mut self, mut message: Message) -> Result<Vec<Value>> {
+        let serial = self.send(&mut message)?;
+        loop {
+            let reply = self.receive()?;
+            if reply.reply_serial != Some(serial) {
+                continue;
+            }
+            if reply.message_type == MessageType::Error {
+                bail!(
+                    "{}: {}",
+                    reply.error_name.as_deref().unwrap_or_default(),
+                    reply
+                        .body
+                        .first()
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                );
+            }
+            return Ok(reply.body);
+        }
+    }
+    /// Requests `name` without queueing, returns whether it is now owned by
+    /// this connection.
+    pub fn request_name(&mut self, name: &str) -> Result<bool> {
+        const DO_NOT_QUEUE: u32 = 0x4;
+        const PRIMARY_OWNER: u32 = 1;
+        let reply = self.call(Message::method_call(
+            BUS_NAME,
+            BUS_PATH,
+            BUS_NAME,
+            "RequestName",
+            vec![Value::str(name), Value::U32(DO_NOT_QUEUE)],
+        ))?;
+        Ok(reply.first().and_then(Value::as_u32) == Some(PRIMARY_OWNER))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_round_trip_message() {
+        let mut call = Message::method_call(
+            "org.freedesktop.secrets",
+            "/org/freedesktop/secrets",
+            "org.freedesktop.DBus.Properties",
+            "GetAll",
+            vec![
+                Value::str("org.freedesktop.Secret.Service"),
+                Value::Byte(7),
+                Value::U64(u64::MAX),
+                Value::dict(
+                    "s",
+                    "v",
+                    [
+                        (Value::str("a"), Value::variant(Value::Bool(true))),
+                        (Value::str("b"), Value::variant(Value::paths(["/x", "/y"]))),
+                    ],
+                ),
+                Value::Struct(vec![Value::I32(-1), Value::bytes(&[])]),
+            ],
+        );
+        call.serial = 42;
+        call.sender = Some(":1.7".to_owned());
+        assert_eq!(call.signature(), "syta{sv}(iay)");
+        assert_eq!(Message::decode(&call.encode()).unwrap(), call);
+
+        let reply = Message::error(&call, "org.freedesktop.DBus.Error.Failed", "no");
+        let decoded = Message::decode(&reply.encode()).unwrap();
+        assert_eq!(decoded.message_type, MessageType::Error);
+        assert_eq!(decoded.reply_serial, Some(42));
+        assert_eq!(decoded.destination.as_deref(), Some(":1.7"));
+    }
+
+    #[test]
+    fn can_encode_wire_format() {
+        let mut call = Message::method_call(BUS_NAME, BUS_PATH, BUS_NAME, "Hello", vec![]);
+        call.serial = 1;
+        let bytes = call.encode();
+        assert_eq!(
+            &bytes[..12],
+            b"l\x01\x00\x01\x00\x00\x00\x00\x01\x00\x00\x00"
+        );
+        assert_eq!(bytes.len() % 8, 0);
+
+        let mut message = Message::method_return(&call, vec![Value::str("ab"), Value::U32(3)]);
+        message.serial = 2;
+        let bytes = message.encode();
+        let body = &bytes[bytes.len() - 12..];
+        assert_eq!(body, b"\x02\x00\x00\x00ab\x00\x00\x03\x00\x00\x00");
+
+        assert!(Message::decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(split_type("a{sv").is_err());
+        assert_eq!(split_type("a{sv}as").unwrap(), ("a{sv}", "as"));
+    }
+}
@@ -0,0 +1,231 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Paths name every secret derived from a master secret, SLIP-21 style:
+//! `m / user / service / generation / purpose`. Each level narrows the one
+//! before it, so a service can have several independent secrets (a login
+//! password, a PIN, a recovery answer) without inventing fake service ids.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::user_input::{Generation, ServiceID, UserID, UserInputError};
+
+/// What a secret of a service is for. Every purpose derives its characters
+/// from the service secret under its own label: `depasswd/v2/<purpose>` for
+/// the built-in ones and `depasswd/v2/purpose/<purpose>` for those parsed
+/// from user input, so a user purpose named `totp` or `key` can not read the
+/// stream of a built-in secret.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Purpose {
+    name: String,
+    builtin: bool,
+}
+
+impl Purpose {
+    fn builtin(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            builtin: true,
+        }
+    }
+    /// The purpose of plain derivations, whose label is `PASSWORD_LABEL`.
+    pub fn password() -> Self {
+        Self::builtin("password")
+    }
+    pub fn is_password(&self) -> bool {
+        self.name == "password"
+    }
+    /// The purpose of age identities, see `AgeIdentity`.
+    pub fn age() -> Self {
+        Self::builtin("age")
+    }
+    /// The purpose of WireGuard keys, see `WireguardKeys`.
+    pub fn wireguard() -> Self {
+        Self::builtin("wireguard")
+    }
+    /// The purpose of BIP39 mnemonics, see `Mnemonic`.
+    pub fn bip39() -> Self {
+        Self::builtin("bip39")
+    }
+    /// The purpose of raw key material, see `KeyMaterial`.
+    pub fn key() -> Self {
+        Self::builtin("key")
+    }
+    /// The purpose of seeds for other deterministic generators, see
+    /// `KeyMaterial::with_purpose`.
+    pub fn rng_seed() -> Self {
+        Self::builtin("rng-seed")
+    }
+    /// The purpose of usernames, see `DerivedPass::username`.
+    pub fn username() -> Self {
+        Self::builtin("username")
+    }
+    /// The purpose of Wi-Fi pre-shared keys, see `Wifi::psk`.
+    pub fn wifi() -> Self {
+        Self::builtin("wifi")
+    }
+    pub fn label(&self) -> Vec<u8> {
+        if self.builtin {
+            format!("depasswd/v2/{}", self.name).into_bytes()
+        } else {
+            format!("depasswd/v2/purpose/{}", self.name).into_bytes()
+        }
+    }
+}
+
+impl Default for Purpose {
+    fn default() -> Self {
+        Self::password()
+    }
+}
+
+/// Only `password` parses to a built-in purpose, every other name is a user
+/// purpose.
+impl FromStr for Purpose {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s == "password" {
+            Ok(Self::password())
+        } else if !s.is_empty()
+            && s.len() <= 32
+            && s.bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        {
+            Ok(Self {
+                name: s.to_owned(),
+                builtin: false,
+            })
+        } else {
+            Err(UserInputError::new(
+                "Purpose must be 1 to 32 lowercase letters, digits or dashes",
+            ))
+        }
+    }
+}
+
+impl Display for Purpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DerivationPath {
+    pub user_id: UserID,
+    pub service_id: ServiceID,
+    pub generation: Generation,
+    pub purpose: Purpose,
+}
+
+impl DerivationPath {
+    pub fn new(
+        user_id: &UserID,
+        service_id: &ServiceID,
+        generation: &Generation,
+        purpose: &Purpose,
+    ) -> Self {
+        Self {
+            user_id: user_id.clone(),
+            service_id: service_id.clone(),
+            generation: generation.clone(),
+            purpose: purpose.clone(),
+        }
+    }
+    fn escape(segment: &str) -> String {
+        segment.replace('%', "%25").replace('/', "%2F")
+    }
+    fn unescape(segment: &str) -> String {
+        segment.replace("%2F", "/").replace("%25", "%")
+    }
+}
+
+/// Formats as `m/<user id>/<service id>/<generation>/<purpose>`, with `%`
+/// and `/` in the ids escaped as `%25` and `%2F`.
+impl Display for DerivationPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "m/{}/{}/{}/{}",
+            Self::escape(&self.user_id.to_string()),
+            Self::escape(&self.service_id.to_string()),
+            self.generation,
+            self.purpose
+        )
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let segments: Vec<&str> = s.split('/').collect();
+        let ["m", user_id, service_id, generation, purpose] = segments.as_slice() else {
+            return Err(UserInputError::new(format!(
+                "Invalid derivation path: {} (expected m/<user id>/<service id>/<generation>/<purpose>)",
+                s
+            )));
+        };
+        Ok(Self {
+            user_id: UserID::from_str(&Self::unescape(user_id))?,
+            service_id: ServiceID::from_str(&Self::unescape(service_id))?,
+            generation: Generation::from_str(generation)?,
+            purpose: Purpose::from_str(purpose)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scheme::PASSWORD_LABEL;
+
+    use super::*;
+
+    #[test]
+    fn can_round_trip_derivation_path() {
+        let path = DerivationPath::new(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &ServiceID::from_str("https://example.com/100%").unwrap(),
+            &Generation::from_str("2").unwrap(),
+            &Purpose::from_str("pin").unwrap(),
+        );
+        let text = path.to_string();
+        assert_eq!(
+            text,
+            "m/Example Eleonora/https:%2F%2Fexample.com%2F100%25/2/pin"
+        );
+        let parsed = DerivationPath::from_str(&text).unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.service_id.to_string(), "https://example.com/100%");
+
+        assert!(DerivationPath::from_str("m/Example Eleonora/example.com/2").is_err());
+        assert!(DerivationPath::from_str("x/Example Eleonora/example.com/2/pin").is_err());
+        assert!(Purpose::from_str("PIN").is_err());
+        assert!(Purpose::from_str("").is_err());
+        assert_eq!(Purpose::default().label(), PASSWORD_LABEL);
+        assert_eq!(Purpose::from_str("password").unwrap(), Purpose::password());
+        assert_eq!(
+            Purpose::from_str("pin").unwrap().label(),
+            b"depasswd/v2/purpose/pin"
+        );
+        assert_ne!(Purpose::from_str("key").unwrap(), Purpose::key());
+        assert_ne!(
+            Purpose::from_str("key").unwrap().label(),
+            Purpose::key().label()
+        );
+    }
+}
@@ -17,16 +17,76 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::fmt::Display;
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
+};
 
 use anyhow::Result;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{
-    DerivePassError,
+    DerivePassError, NUMBERS, SMALL_LETTERS,
+    derivation_path::Purpose,
+    policy::PasswordPolicy,
+    scheme::{
+        ANSWER_LABEL, PASSPHRASE_LABEL, PRONOUNCEABLE_LABEL, RECOVERY_CODE_LABEL, SchemeVersion,
+        TEMPLATE_LABEL,
+    },
     service_secret::ServiceSecret,
-    user_input::{CharSet, PasswordLength},
+    template::Template,
+    user_input::{CharSet, PasswordLength, UserInputError},
+    utils::Utils,
+    wordlist::Wordlist,
 };
 
+/// Letters of pronounceable passwords, without `c`, `q`, `w`, `x` and `y`,
+/// whose sound is easily confused or spelled differently across languages.
+pub const CONSONANTS: &str = "bdfghjklmnprstvz";
+pub const VOWELS: &str = "aeiou";
+
+/// Lowercase letters and digits without `0`, `1`, `i`, `l` and `o`, which
+/// are easily misread on a printed recovery code.
+pub const RECOVERY_CODE_CHARS: &str = "23456789abcdefghjkmnpqrstuvwxyz";
+
+/// How usernames look: `Alphanumeric` is a lowercase letter followed by
+/// lowercase letters and digits, `Pronounceable` alternates `CONSONANTS`
+/// and `VOWELS` like pronounceable passwords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UsernameStyle {
+    #[default]
+    Alphanumeric,
+    Pronounceable,
+}
+
+impl UsernameStyle {
+    pub const ALL: [UsernameStyle; 2] = [UsernameStyle::Alphanumeric, UsernameStyle::Pronounceable];
+}
+
+impl FromStr for UsernameStyle {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|style| style.to_string() == s)
+            .ok_or(UserInputError::new(format!(
+                "Unknown username style: {} (expected one of: {})",
+                s,
+                Self::ALL.map(|style| style.to_string()).join(", ")
+            )))
+    }
+}
+
+impl Display for UsernameStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsernameStyle::Alphanumeric => write!(f, "alphanumeric"),
+            UsernameStyle::Pronounceable => write!(f, "pronounceable"),
+        }
+    }
+}
+
 pub struct DerivedPass {
     derived_pass: String,
 }
@@ -36,47 +96,507 @@ impl DerivedPass {
         service_secret: &ServiceSecret,
         char_set: &CharSet,
         password_length: &PasswordLength,
+        scheme: SchemeVersion,
+    ) -> Result<DerivedPass> {
+        Self::with_purpose(
+            service_secret,
+            char_set,
+            password_length,
+            scheme,
+            &Purpose::password(),
+        )
+    }
+    /// Derives the secret of the service meant for `purpose`. Scheme v1
+    /// only knows the password purpose.
+    pub fn with_purpose(
+        service_secret: &ServiceSecret,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        scheme: SchemeVersion,
+        purpose: &Purpose,
+    ) -> Result<DerivedPass> {
+        Self::with_policy(
+            service_secret,
+            char_set,
+            password_length,
+            scheme,
+            purpose,
+            None,
+        )
+    }
+    /// Derives a secret that satisfies `policy`. Scheme v2 skips candidates
+    /// breaking it, while scheme v1 can only fail if its password does.
+    pub fn with_policy(
+        service_secret: &ServiceSecret,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        scheme: SchemeVersion,
+        purpose: &Purpose,
+        policy: Option<&PasswordPolicy>,
+    ) -> Result<DerivedPass> {
+        match scheme {
+            SchemeVersion::V1 if purpose.is_password() => {
+                let derived_pass = Self::new_v1(service_secret, char_set, password_length)?;
+                if let Some(policy) = policy {
+                    policy.shape(char_set, password_length)?;
+                    policy
+                        .validate(&derived_pass.derived_pass)
+                        .map_err(|e| match e {
+                            DerivePassError::PolicyViolation(violation) => {
+                                DerivePassError::PolicyViolation(format!(
+                                    "{}, use scheme v2 to follow the policy",
+                                    violation
+                                ))
+                            }
+                            e => e,
+                        })?;
+                }
+                Ok(derived_pass)
+            }
+            SchemeVersion::V1 => Err(UserInputError::new(format!(
+                "Scheme v1 only derives passwords, use scheme v2 for the {} purpose",
+                purpose
+            ))
+            .into()),
+            SchemeVersion::V2 | SchemeVersion::V3 => match policy {
+                Some(policy) => Self::new_v2(
+                    service_secret,
+                    &policy.shape(char_set, password_length)?,
+                    password_length,
+                    &purpose.label(),
+                    |password| policy.violations(password).is_empty(),
+                ),
+                None => Self::new_v2(
+                    service_secret,
+                    char_set,
+                    password_length,
+                    &purpose.label(),
+                    |_| true,
+                ),
+            },
+        }
+    }
+    fn new_v1(
+        service_secret: &ServiceSecret,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+    ) -> Result<DerivedPass> {
+        Ok(DerivedPass {
+            derived_pass: crate::core::password_v1(
+                service_secret.as_bytes(),
+                &char_set.to_string(),
+                password_length.as_usize(),
+            )
+            .map_err(DerivePassError::from)?,
+        })
+    }
+    /// See `core::password_v2`, with the canonical alphabet and the classes
+    /// of `char_set`.
+    fn new_v2(
+        service_secret: &ServiceSecret,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        label: &[u8],
+        accept: impl Fn(&str) -> bool,
+    ) -> Result<DerivedPass> {
+        Ok(DerivedPass {
+            derived_pass: crate::core::password_v2(
+                service_secret.as_bytes(),
+                service_secret.scheme(),
+                label,
+                &char_set.canonical(),
+                char_set.classes(),
+                password_length.as_usize(),
+                accept,
+            )
+            .map_err(DerivePassError::from)?,
+        })
+    }
+    /// Alternates consonants and vowels, starting with a consonant, so the
+    /// password reads as syllables that are easy to relay over the phone.
+    pub fn pronounceable(
+        service_secret: &ServiceSecret,
+        password_length: &PasswordLength,
+        scheme: SchemeVersion,
+    ) -> Result<DerivedPass> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives character passwords, use scheme v2 for pronounceable ones",
+            )
+            .into());
+        }
+        let consonants: Vec<char> = CONSONANTS.chars().collect();
+        let vowels: Vec<char> = VOWELS.chars().collect();
+        let slots: Vec<&[char]> = (0..password_length.as_usize())
+            .map(|i| {
+                if i % 2 == 0 {
+                    &consonants[..]
+                } else {
+                    &vowels[..]
+                }
+            })
+            .collect();
+        Ok(DerivedPass {
+            derived_pass: Zeroizing::new(Self::fill_slots(
+                service_secret,
+                PRONOUNCEABLE_LABEL,
+                &slots,
+            )?)
+            .iter()
+            .collect(),
+        })
+    }
+    /// Derives a username of `length` characters under the label of the
+    /// username purpose and the style, so every style is independent.
+    pub fn username(
+        service_secret: &ServiceSecret,
+        length: &PasswordLength,
+        style: UsernameStyle,
+        scheme: SchemeVersion,
+    ) -> Result<DerivedPass> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for usernames",
+            )
+            .into());
+        }
+        let (first, even, odd): (Vec<char>, Vec<char>, Vec<char>) = match style {
+            UsernameStyle::Alphanumeric => {
+                let letters: Vec<char> = SMALL_LETTERS.chars().collect();
+                let alphanumeric: Vec<char> =
+                    SMALL_LETTERS.chars().chain(NUMBERS.chars()).collect();
+                (letters, alphanumeric.clone(), alphanumeric)
+            }
+            UsernameStyle::Pronounceable => (
+                CONSONANTS.chars().collect(),
+                CONSONANTS.chars().collect(),
+                VOWELS.chars().collect(),
+            ),
+        };
+        let slots: Vec<&[char]> = (0..length.as_usize())
+            .map(|i| match i {
+                0 => &first[..],
+                i if i % 2 == 0 => &even[..],
+                _ => &odd[..],
+            })
+            .collect();
+        let label =
+            Utils::length_prefixed(&[&Purpose::username().label(), style.to_string().as_bytes()]);
+        Ok(DerivedPass {
+            derived_pass: Zeroizing::new(Self::fill_slots(service_secret, &label, &slots)?)
+                .iter()
+                .collect(),
+        })
+    }
+    /// Derives `count` recovery codes of `length` characters. Code `i`
+    /// (counted from 1) is filled from the expansion of the service secret
+    /// under `RECOVERY_CODE_LABEL` and `i`, so the codes are independent and
+    /// asking for more codes keeps the first ones.
+    pub fn recovery_codes(
+        service_secret: &ServiceSecret,
+        count: u32,
+        length: &PasswordLength,
+        scheme: SchemeVersion,
+    ) -> Result<Vec<DerivedPass>> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for recovery codes",
+            )
+            .into());
+        }
+        let alphabet: Vec<char> = RECOVERY_CODE_CHARS.chars().collect();
+        let slots = vec![&alphabet[..]; length.as_usize()];
+        (1..=count)
+            .map(|index| {
+                let label = Utils::length_prefixed(&[RECOVERY_CODE_LABEL, &index.to_be_bytes()]);
+                Ok(DerivedPass {
+                    derived_pass: Zeroizing::new(Self::fill_slots(service_secret, &label, &slots)?)
+                        .iter()
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+    /// Picks one pattern of `template` from the expansion of the service
+    /// secret under `TEMPLATE_LABEL`, then fills its slots from the
+    /// expansion under the label and the pattern.
+    pub fn from_template(
+        service_secret: &ServiceSecret,
+        template: &Template,
+        scheme: SchemeVersion,
     ) -> Result<DerivedPass> {
-        if service_secret.len() < password_length.as_usize() {
-            return Err(DerivePassError::Char.into());
-        }
-        let mut derived_pass: String = "".to_owned();
-        for i in 0..password_length.as_u8() {
-            let hash_byte: usize = service_secret
-                .as_bytes()
-                .get(usize::from(i))
-                .ok_or(DerivePassError::Char)?
-                .to_owned()
-                .into();
-            derived_pass.push(Self::get_password_char(char_set, hash_byte)?);
-        }
-        Ok(DerivedPass { derived_pass })
-    }
-    fn get_password_char(char_pool: &CharSet, secret_byte: usize) -> Result<char> {
-        let char_pool_string = char_pool.to_string();
-        Ok(char_pool
-            .to_string()
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives character passwords, use scheme v2 for templates",
+            )
+            .into());
+        }
+        let patterns: Vec<&str> = template.patterns().iter().map(String::as_str).collect();
+        let pattern = Self::fill_slots(service_secret, TEMPLATE_LABEL, &[&patterns[..]])?[0];
+        let alphabets = pattern
             .chars()
-            .nth(secret_byte % char_pool_string.len())
-            .ok_or(DerivePassError::Char)?)
+            .map(|class| {
+                Template::class(class)
+                    .map(|chars| chars.chars().collect::<Vec<char>>())
+                    .ok_or(DerivePassError::Char)
+            })
+            .collect::<Result<Vec<Vec<char>>, DerivePassError>>()?;
+        let slots: Vec<&[char]> = alphabets.iter().map(Vec::as_slice).collect();
+        let label = Utils::length_prefixed(&[TEMPLATE_LABEL, pattern.as_bytes()]);
+        Ok(DerivedPass {
+            derived_pass: Zeroizing::new(Self::fill_slots(service_secret, &label, &slots)?)
+                .iter()
+                .collect(),
+        })
+    }
+    /// The bits of entropy of a pronounceable password of `length` characters.
+    pub fn pronounceable_entropy_bits(length: usize) -> f64 {
+        let consonants = length.div_ceil(2) as f64 * (CONSONANTS.len() as f64).log2();
+        let vowels = (length / 2) as f64 * (VOWELS.len() as f64).log2();
+        ((consonants + vowels) * 10.0).round() / 10.0
+    }
+    /// Fills every slot with a character of its alphabet, picked from the
+    /// expansion of the service secret under `label` by `uniform_pick`.
+    fn fill_slots<T: Copy>(
+        service_secret: &ServiceSecret,
+        label: &[u8],
+        slots: &[&[T]],
+    ) -> Result<Vec<T>> {
+        let mut filled = Vec::with_capacity(slots.len());
+        let mut stream: Vec<u8> = vec![];
+        let mut consumed = 0;
+        for alphabet in slots {
+            if alphabet.is_empty() || alphabet.len() > 256 {
+                return Err(DerivePassError::Char.into());
+            }
+            loop {
+                if consumed == stream.len() {
+                    // The expansion is prefix-stable, so growing it only appends.
+                    let length = (stream.len() * 2).max(service_secret.len());
+                    stream.zeroize();
+                    stream = service_secret.expand(label, length)?;
+                }
+                consumed += 1;
+                if let Some(c) = crate::core::uniform_pick(alphabet, stream[consumed - 1]) {
+                    filled.push(c);
+                    break;
+                }
+            }
+        }
+        stream.zeroize();
+        Ok(filled)
+    }
+    /// Picks `words` words of `wordlist` from the expansion of the service
+    /// secret under `PASSPHRASE_LABEL` and the wordlist fingerprint. Every
+    /// big-endian 32-bit chunk at or above the largest multiple of the list
+    /// length is skipped, the others index the list modulo its length.
+    pub fn passphrase(
+        service_secret: &ServiceSecret,
+        wordlist: &Wordlist,
+        words: &PasswordLength,
+        separator: &str,
+        scheme: SchemeVersion,
+    ) -> Result<DerivedPass> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for passphrases",
+            )
+            .into());
+        }
+        let label = Utils::length_prefixed(&[PASSPHRASE_LABEL, wordlist.fingerprint()]);
+        Ok(DerivedPass {
+            derived_pass: Self::pick_words(service_secret, &label, wordlist, words.as_usize())?
+                .join(separator),
+        })
+    }
+    /// Derives a fake answer of `words` lowercase words to a security
+    /// question, under the label of the wordlist and the normalized question,
+    /// so every question of a service gets its own answer.
+    pub fn answer(
+        service_secret: &ServiceSecret,
+        wordlist: &Wordlist,
+        question: &str,
+        words: &PasswordLength,
+        scheme: SchemeVersion,
+    ) -> Result<DerivedPass> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for answers",
+            )
+            .into());
+        }
+        let question = Self::normalize_question(question);
+        if question.is_empty() {
+            return Err(UserInputError::new("The question can not be empty").into());
+        }
+        let label =
+            Utils::length_prefixed(&[ANSWER_LABEL, wordlist.fingerprint(), question.as_bytes()]);
+        Ok(DerivedPass {
+            derived_pass: Zeroizing::new(
+                Self::pick_words(service_secret, &label, wordlist, words.as_usize())?.join(" "),
+            )
+            .to_lowercase(),
+        })
+    }
+    /// Lowercases the question, drops apostrophes and turns every other run
+    /// of non-alphanumeric characters into a single space, so retyping it
+    /// with different case or punctuation gives the same answer.
+    pub fn normalize_question(question: &str) -> String {
+        question
+            .to_lowercase()
+            .replace(['\'', '\u{2019}'], "")
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+    /// Picks `count` words of `wordlist` from the expansion of the service
+    /// secret under `label`, see `passphrase`.
+    fn pick_words<'a>(
+        service_secret: &ServiceSecret,
+        label: &[u8],
+        wordlist: &'a Wordlist,
+        count: usize,
+    ) -> Result<Vec<&'a str>> {
+        let list_length = wordlist.words().len() as u64;
+        let limit = (1u64 << 32) - (1u64 << 32) % list_length;
+        let mut picked: Vec<&str> = Vec::with_capacity(count);
+        let mut stream: Vec<u8> = vec![];
+        let mut consumed = 0;
+        while picked.len() < count {
+            if consumed == stream.len() {
+                // The expansion is prefix-stable, so growing it only appends.
+                let length = (stream.len() * 2).max(4 * count);
+                stream.zeroize();
+                stream = service_secret.expand(label, length)?;
+            }
+            let chunk: [u8; 4] = stream[consumed..consumed + 4]
+                .try_into()
+                .map_err(|_| DerivePassError::Char)?;
+            let value = u64::from(u32::from_be_bytes(chunk));
+            if value < limit {
+                picked.push(&wordlist.words()[(value % list_length) as usize]);
+            }
+            consumed += 4;
+        }
+        stream.zeroize();
+        Ok(picked)
+    }
+    /// The password itself. Copies of it belong in a `Zeroizing`.
+    pub fn expose_secret(&self) -> &str {
+        &self.derived_pass
+    }
+    /// Compares in constant time, so a timing side channel does not reveal
+    /// how many leading characters of the candidate were right.
+    pub fn matches(&self, candidate: &str) -> bool {
+        self.derived_pass
+            .as_bytes()
+            .ct_eq(candidate.as_bytes())
+            .into()
+    }
+}
+
+/// Redacted, the secret is only available through `expose_secret`.
+impl Debug for DerivedPass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DerivedPass(****)")
     }
 }
 
 impl Display for DerivedPass {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.derived_pass)
+        Debug::fmt(self, f)
+    }
+}
+
+impl Zeroize for DerivedPass {
+    fn zeroize(&mut self) {
+        self.derived_pass.zeroize();
     }
 }
 
+impl Drop for DerivedPass {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for DerivedPass {}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::{
+        DeriveParams, DerivePassRunner,
+        master_secret::MasterSecret,
+        user_input::{Generation, MasterPasswordPlain, ServiceID, UserID},
+    };
 
-    use std::str::FromStr;
+    #[test]
+    fn can_match_candidate() {
+        let test_secret: Vec<u8> = Vec::from_iter(0..64);
+        let derived_pass = DerivedPass::new(
+            &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
+            &CharSet::try_from([0].as_slice()).unwrap(),
+            &PasswordLength::from_str("5").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        assert!(derived_pass.matches("abcde"));
+        assert!(!derived_pass.matches("abcdf"));
+        assert!(!derived_pass.matches("abcd"));
+        assert!(!derived_pass.matches(""));
+    }
+    #[test]
+    fn can_redact_secrets() {
+        let test_secret: Vec<u8> = Vec::from_iter(0..64);
+        let derived_pass = DerivedPass::new(
+            &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
+            &CharSet::try_from([0].as_slice()).unwrap(),
+            &PasswordLength::from_str("5").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        assert_eq!(derived_pass.expose_secret(), "abcde");
+        assert_eq!(format!("{:?}", derived_pass), "DerivedPass(****)");
+        assert_eq!(derived_pass.to_string(), "DerivedPass(****)");
 
-    use crate::utils::Utils;
+        let master_password_plain = MasterPasswordPlain::from_str("hunter2hunter2").unwrap();
+        assert_eq!(master_password_plain.expose_secret(), "hunter2hunter2");
+        assert_eq!(
+            format!("{:?} {}", master_password_plain, master_password_plain),
+            "MasterPasswordPlain(****) MasterPasswordPlain(****)"
+        );
 
-    use super::*;
+        let master_secret = MasterSecret::from_str(&"ab".repeat(32)).unwrap();
+        assert_eq!(
+            format!("{:?} {}", master_secret, master_secret),
+            "MasterSecret(****) MasterSecret(****)"
+        );
+    }
+    #[test]
+    fn can_zeroize_secrets() {
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<MasterPasswordPlain>();
+        assert_zeroize_on_drop::<MasterSecret>();
+        assert_zeroize_on_drop::<ServiceSecret>();
+        assert_zeroize_on_drop::<DerivedPass>();
 
+        let test_secret: Vec<u8> = Vec::from_iter(0..64);
+        let mut service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap();
+        let mut derived_pass = DerivedPass::new(
+            &service_secret,
+            &CharSet::try_from([0].as_slice()).unwrap(),
+            &PasswordLength::from_str("5").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        derived_pass.zeroize();
+        service_secret.zeroize();
+        assert_eq!(derived_pass.expose_secret(), "");
+        assert!(service_secret.is_empty());
+    }
     #[test]
     fn can_small_letter_pool() {
         let expected_result = "abcdefghijklmnopqrstuvwxyza";
@@ -85,10 +605,11 @@ mod tests {
             DerivedPass::new(
                 &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
                 &CharSet::try_from([0].as_slice()).unwrap(),
-                &PasswordLength::from_str("27").unwrap()
+                &PasswordLength::from_str("27").unwrap(),
+                SchemeVersion::V1,
             )
             .unwrap()
-            .to_string(),
+            .expose_secret(),
             expected_result
         );
     }
@@ -100,10 +621,11 @@ mod tests {
             DerivedPass::new(
                 &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
                 &CharSet::try_from([1].as_slice()).unwrap(),
-                &PasswordLength::from_str("27").unwrap()
+                &PasswordLength::from_str("27").unwrap(),
+                SchemeVersion::V1,
             )
             .unwrap()
-            .to_string(),
+            .expose_secret(),
             expected_result
         );
     }
@@ -116,10 +638,11 @@ mod tests {
             DerivedPass::new(
                 &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
                 &CharSet::try_from([2].as_slice()).unwrap(),
-                &PasswordLength::from_str("11").unwrap()
+                &PasswordLength::from_str("11").unwrap(),
+                SchemeVersion::V1,
             )
             .unwrap()
-            .to_string(),
+            .expose_secret(),
             expected_result
         );
     }
@@ -131,11 +654,446 @@ mod tests {
             DerivedPass::new(
                 &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
                 &CharSet::try_from([3].as_slice()).unwrap(),
-                &PasswordLength::from_str("33").unwrap()
+                &PasswordLength::from_str("33").unwrap(),
+                SchemeVersion::V1,
             )
             .unwrap()
-            .to_string(),
+            .expose_secret(),
             expected_result
         );
     }
+    #[test]
+    fn can_select_chars_uniformly() {
+        // 256 is not a multiple of 10, so bytes 250..=255 would favour the
+        // digits 0 to 5 under v1. v2 skips them.
+        let digits: Vec<char> = crate::NUMBERS.chars().collect();
+        let mut counts = [0; 10];
+        for byte in 0..=255 {
+            if let Some(c) = crate::core::uniform_pick(&digits, byte) {
+                counts[c.to_digit(10).unwrap() as usize] += 1;
+            }
+        }
+        assert_eq!(counts, [25; 10]);
+
+        let test_secret = [255u8; 64];
+        let derived_pass = DerivedPass::new(
+            &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
+            &CharSet::try_from([0].as_slice()).unwrap(),
+            &PasswordLength::from_str("20").unwrap(),
+            SchemeVersion::V2,
+        )
+        .unwrap()
+        .expose_secret()
+        .to_owned();
+        assert_eq!(derived_pass.len(), 20);
+        assert!(derived_pass.chars().all(|c| c.is_ascii_lowercase()));
+    }
+
+    #[test]
+    fn can_derive_v2_password() {
+        let test_secret: Vec<u8> = Vec::from_iter(0..64);
+        for scheme in [SchemeVersion::V2, SchemeVersion::V3] {
+            assert_eq!(
+                DerivedPass::new(
+                    &ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap(),
+                    &CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap(),
+                    &PasswordLength::from_str("20").unwrap(),
+                    scheme,
+                )
+                .unwrap()
+                .expose_secret(),
+                "`3Iz?$AR1hzTkMZ1,T6H"
+            );
+        }
+    }
+
+    #[test]
+    fn can_ignore_char_set_order() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let password_length = PasswordLength::from_str("20").unwrap();
+        let derive = |pools: &[usize], scheme| {
+            DerivedPass::new(
+                &service_secret,
+                &CharSet::try_from(pools).unwrap(),
+                &password_length,
+                scheme,
+            )
+            .unwrap()
+            .expose_secret()
+            .to_owned()
+        };
+        assert_eq!(
+            derive(&[2, 0], SchemeVersion::V2),
+            derive(&[0, 2], SchemeVersion::V2)
+        );
+        assert_eq!(
+            derive(&[0, 0], SchemeVersion::V2),
+            derive(&[0], SchemeVersion::V2)
+        );
+        assert_ne!(
+            derive(&[2, 0], SchemeVersion::V1),
+            derive(&[0, 2], SchemeVersion::V1)
+        );
+    }
+
+    #[test]
+    fn can_derive_pronounceable_password() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let derive = |length: &str, scheme| {
+            DerivedPass::pronounceable(
+                &service_secret,
+                &PasswordLength::from_str(length).unwrap(),
+                scheme,
+            )
+        };
+        let password = derive("11", SchemeVersion::V3)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_eq!(password.len(), 11);
+        for (i, c) in password.chars().enumerate() {
+            let alphabet = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+            assert!(alphabet.contains(c), "{}", password);
+        }
+        assert!(
+            derive("40", SchemeVersion::V3)
+                .unwrap()
+                .expose_secret()
+                .starts_with(&password)
+        );
+        assert!(derive("11", SchemeVersion::V1).is_err());
+        assert_eq!(DerivedPass::pronounceable_entropy_bits(2), 6.3);
+    }
+
+    #[test]
+    fn can_derive_username() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let derive = |style, scheme| {
+            DerivedPass::username(
+                &service_secret,
+                &PasswordLength::from_str("10").unwrap(),
+                style,
+                scheme,
+            )
+        };
+        let alphanumeric = derive(UsernameStyle::Alphanumeric, SchemeVersion::V2)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_eq!(alphanumeric.len(), 10);
+        assert!(alphanumeric.starts_with(|c: char| c.is_ascii_lowercase()));
+        assert!(
+            alphanumeric
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        );
+        let pronounceable = derive(UsernameStyle::Pronounceable, SchemeVersion::V2)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        for (i, c) in pronounceable.chars().enumerate() {
+            let alphabet = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+            assert!(alphabet.contains(c), "{}", pronounceable);
+        }
+        assert!(derive(UsernameStyle::Alphanumeric, SchemeVersion::V1).is_err());
+        assert_eq!(
+            UsernameStyle::from_str("pronounceable").unwrap(),
+            UsernameStyle::Pronounceable
+        );
+        assert!(UsernameStyle::from_str("emoji").is_err());
+    }
+
+    #[test]
+    fn can_derive_from_template() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let derive = |template: &str, scheme| {
+            DerivedPass::from_template(
+                &service_secret,
+                &Template::from_str(template).unwrap(),
+                scheme,
+            )
+        };
+        let password = derive("CvcvnoCvcv x", SchemeVersion::V2)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_eq!(password.chars().count(), 12);
+        for (class, c) in "CvcvnoCvcv x".chars().zip(password.chars()) {
+            assert!(Template::class(class).unwrap().contains(c), "{}", password);
+        }
+        let long = derive("long", SchemeVersion::V3)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert!(
+            Template::from_str("long")
+                .unwrap()
+                .patterns()
+                .iter()
+                .any(|p| {
+                    p.len() == long.len()
+                        && p.chars()
+                            .zip(long.chars())
+                            .all(|(class, c)| Template::class(class).unwrap().contains(c))
+                })
+        );
+        assert!(derive("long", SchemeVersion::V1).is_err());
+    }
+
+    #[test]
+    fn can_derive_recovery_codes() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let length = PasswordLength::from_str("8").unwrap();
+        let codes: Vec<String> =
+            DerivedPass::recovery_codes(&service_secret, 10, &length, SchemeVersion::V2)
+                .unwrap()
+                .iter()
+                .map(|code| code.expose_secret().to_owned())
+                .collect();
+        assert_eq!(codes.len(), 10);
+        for code in &codes {
+            assert_eq!(code.len(), 8);
+            assert!(code.chars().all(|c| RECOVERY_CODE_CHARS.contains(c)));
+        }
+        let mut unique = codes.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 10);
+        let first: Vec<String> =
+            DerivedPass::recovery_codes(&service_secret, 3, &length, SchemeVersion::V2)
+                .unwrap()
+                .iter()
+                .map(|code| code.expose_secret().to_owned())
+                .collect();
+        assert_eq!(first, codes[..3]);
+        assert!(
+            DerivedPass::recovery_codes(&service_secret, 10, &length, SchemeVersion::V1).is_err()
+        );
+    }
+
+    #[test]
+    fn can_derive_answer() {
+        let wordlist =
+            Wordlist::parse(&(0..2000).map(|i| format!("W{}\n", i)).collect::<String>()).unwrap();
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let words = PasswordLength::from_str("3").unwrap();
+        let answer = |question: &str, scheme| {
+            DerivedPass::answer(&service_secret, &wordlist, question, &words, scheme)
+        };
+        let pet = answer("What was the name of your first pet?", SchemeVersion::V2)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_eq!(pet.split(' ').count(), 3);
+        assert_eq!(pet, pet.to_lowercase());
+        assert_eq!(
+            answer("  what was the NAME of your first pet", SchemeVersion::V2)
+                .unwrap()
+                .expose_secret(),
+            pet
+        );
+        assert_ne!(
+            answer("What was your mother's maiden name?", SchemeVersion::V2)
+                .unwrap()
+                .expose_secret(),
+            pet
+        );
+        assert!(answer("?!", SchemeVersion::V2).is_err());
+        assert!(answer("What was the name of your first pet?", SchemeVersion::V1).is_err());
+        assert_eq!(
+            DerivedPass::normalize_question("Your mother\u{2019}s maiden-name?"),
+            "your mothers maiden name"
+        );
+    }
+
+    #[test]
+    fn can_derive_passphrase() {
+        let wordlist =
+            Wordlist::parse(&(0..2000).map(|i| format!("w{}\n", i)).collect::<String>()).unwrap();
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let words = PasswordLength::from_str("6").unwrap();
+        let passphrase =
+            DerivedPass::passphrase(&service_secret, &wordlist, &words, " ", SchemeVersion::V2)
+                .unwrap()
+                .expose_secret()
+                .to_owned();
+        assert_eq!(passphrase.split(' ').count(), 6);
+        assert!(
+            passphrase
+                .split(' ')
+                .all(|word| wordlist.words().iter().any(|w| w == word))
+        );
+
+        let other = Wordlist::parse(
+            &(0..2000)
+                .map(|i| format!("w{}\n", i))
+                .collect::<String>()
+                .replace("w0\n", "x0\n"),
+        )
+        .unwrap();
+        assert_ne!(
+            DerivedPass::passphrase(&service_secret, &other, &words, " ", SchemeVersion::V2)
+                .unwrap()
+                .expose_secret(),
+            passphrase
+        );
+        assert!(
+            DerivedPass::passphrase(&service_secret, &wordlist, &words, " ", SchemeVersion::V1)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_shape_with_policy() {
+        let policy = PasswordPolicy {
+            required_classes: vec!["numbers".to_owned(), "special".to_owned()],
+            forbidden_chars: "\";\\".to_owned(),
+            no_leading_symbol: true,
+            no_trailing_symbol: true,
+            max_repeat_run: Some(1),
+            ..PasswordPolicy::default()
+        };
+        let char_set = CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap();
+        let password_length = PasswordLength::from_str("8").unwrap();
+        for seed in 0u8..16 {
+            let service_secret =
+                ServiceSecret::from_str(&Utils::bytes_to_hex(&[seed; 64])).unwrap();
+            let derive = |scheme| {
+                DerivedPass::with_policy(
+                    &service_secret,
+                    &char_set,
+                    &password_length,
+                    scheme,
+                    &Purpose::password(),
+                    Some(&policy),
+                )
+            };
+            let derived_pass = derive(SchemeVersion::V2)
+                .unwrap()
+                .expose_secret()
+                .to_owned();
+            assert_eq!(policy.violations(&derived_pass), Vec::<String>::new());
+            // Scheme v1 can not reshape its output, only refuse it.
+            if let Ok(derived_pass) = derive(SchemeVersion::V1) {
+                assert!(policy.validate(derived_pass.expose_secret()).is_ok());
+            }
+        }
+        assert!(
+            DerivedPass::with_policy(
+                &ServiceSecret::from_str(&Utils::bytes_to_hex(&[0; 64])).unwrap(),
+                &CharSet::try_from([0].as_slice()).unwrap(),
+                &password_length,
+                SchemeVersion::V2,
+                &Purpose::password(),
+                Some(&policy),
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn can_include_every_class() {
+        let char_set = CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap();
+        let password_length = PasswordLength::from_str("4").unwrap();
+        for seed in 0u8..32 {
+            let service_secret =
+                ServiceSecret::from_str(&Utils::bytes_to_hex(&[seed; 64])).unwrap();
+            let derived_pass = DerivedPass::new(
+                &service_secret,
+                &char_set,
+                &password_length,
+                SchemeVersion::V2,
+            )
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+            for class in char_set.classes() {
+                assert!(
+                    derived_pass.chars().any(|c| class.contains(c)),
+                    "{} misses {}",
+                    derived_pass,
+                    class
+                );
+            }
+        }
+        assert!(
+            DerivedPass::new(
+                &ServiceSecret::from_str(&Utils::bytes_to_hex(&[0; 64])).unwrap(),
+                &char_set,
+                &PasswordLength::from_str("3").unwrap(),
+                SchemeVersion::V2,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn can_derive_long_password() {
+        let test_secret: Vec<u8> = Vec::from_iter(0..64);
+        let service_secret = ServiceSecret::from_str(&Utils::bytes_to_hex(&test_secret)).unwrap();
+        let char_set = CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap();
+        let long = DerivedPass::new(
+            &service_secret,
+            &char_set,
+            &PasswordLength::from_str("256").unwrap(),
+            SchemeVersion::V2,
+        )
+        .unwrap()
+        .expose_secret()
+        .to_owned();
+        let short = DerivedPass::new(
+            &service_secret,
+            &char_set,
+            &PasswordLength::from_str("100").unwrap(),
+            SchemeVersion::V2,
+        )
+        .unwrap()
+        .expose_secret()
+        .to_owned();
+        assert_eq!(long.len(), 256);
+        assert!(long.starts_with(&short));
+        assert!(
+            DerivedPass::new(
+                &service_secret,
+                &char_set,
+                &PasswordLength::from_str("65").unwrap(),
+                SchemeVersion::V1,
+            )
+            .is_err()
+        );
+        assert!(PasswordLength::from_str("257").is_err());
+    }
+
+    #[test]
+    fn can_derive_known_answers() {
+        let test_cases = [
+            (SchemeVersion::V1, "1@MWtAAqZ0p>;;y@zZ6d"),
+            (SchemeVersion::V2, r##"'TrDo+]E\VNL:n5jrd&}"##),
+            (SchemeVersion::V3, r##"i2z(cqLFmcFX{TBjS4+,"##),
+        ];
+        for (scheme, expected_result) in test_cases {
+            let params = DeriveParams {
+                scheme,
+                ..DeriveParams::new(
+                    UserID::from_str("Example Eleonora").unwrap(),
+                    MasterPasswordPlain::from_str(r##"]lE~WExZ468ty{I5mtg["##).unwrap(),
+                    ServiceID::from_str("Example Service Name").unwrap(),
+                    Generation::from_str("1").unwrap(),
+                    CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap(),
+                    PasswordLength::from_str("20").unwrap(),
+                )
+            };
+            assert_eq!(
+                DerivePassRunner::run(params).unwrap().expose_secret(),
+                expected_result
+            );
+        }
+    }
 }
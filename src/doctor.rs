@@ -0,0 +1,275 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    env,
+    ffi::OsStr,
+    fmt::Display,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::{
+    clipboard::ClipboardTool,
+    kdf::KdfParams,
+    master_secret::MasterSecret,
+    scheme::SchemeVersion,
+    user_input::{MasterPasswordPlain, UserID},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, " OK "),
+            CheckStatus::Warning => write!(f, "WARN"),
+            CheckStatus::Error => write!(f, "FAIL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl CheckResult {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warning,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+    fn error(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Error,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Doctor {}
+
+impl Doctor {
+    pub fn run() -> Vec<CheckResult> {
+        vec![
+            Self::check_tty(),
+            Self::check_clipboard(),
+            Self::check_keychain(),
+            Self::check_locale(
+                ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .iter()
+                    .find_map(|v| env::var(v).ok().filter(|v| !v.is_empty()))
+                    .as_deref(),
+            ),
+            Self::check_unicode_normalization(),
+            Self::check_argon2_timing(),
+        ]
+    }
+    fn check_tty() -> CheckResult {
+        let stdin = std::io::stdin().is_terminal();
+        let stdout = std::io::stdout().is_terminal();
+        if stdin && stdout {
+            CheckResult::ok("terminal", "stdin and stdout are attached to a TTY")
+        } else {
+            CheckResult::warning(
+                "terminal",
+                format!(
+                    "stdin is {}a TTY, stdout is {}a TTY; interactive prompts will not work",
+                    if stdin { "" } else { "not " },
+                    if stdout { "" } else { "not " }
+                ),
+                "run depasswd from an interactive terminal (use `ssh -t` over SSH)",
+            )
+        }
+    }
+    fn check_clipboard() -> CheckResult {
+        let path = env::var_os("PATH").unwrap_or_default();
+        let installed: Vec<&ClipboardTool> = ClipboardTool::candidates()
+            .iter()
+            .filter(|tool| Self::find_in_path(tool.name, &path).is_some())
+            .collect();
+        match installed.iter().find(|tool| tool.is_usable(&path)) {
+            Some(tool) => CheckResult::ok("clipboard", format!("{} is available", tool.name)),
+            None if installed.is_empty() => CheckResult::warning(
+                "clipboard",
+                "no clipboard tool found in PATH",
+                "install wl-clipboard (Wayland) or xclip (X11)",
+            ),
+            None => CheckResult::warning(
+                "clipboard",
+                "clipboard tools are installed but no graphical session was detected",
+                "run depasswd inside a desktop session or forward the display",
+            ),
+        }
+    }
+    fn check_keychain() -> CheckResult {
+        if cfg!(target_os = "macos") || cfg!(windows) {
+            CheckResult::ok("keychain", "the platform keychain is always available")
+        } else if env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() {
+            CheckResult::ok("keychain", "a D-Bus session bus is reachable")
+        } else {
+            CheckResult::warning(
+                "keychain",
+                "no D-Bus session bus found, the Secret Service keychain is unreachable",
+                "run inside a desktop session or start one with `dbus-run-session`",
+            )
+        }
+    }
+    pub fn check_locale(locale: Option<&str>) -> CheckResult {
+        match locale {
+            Some(locale)
+                if locale.to_ascii_uppercase().contains("UTF-8")
+                    || locale.to_ascii_uppercase().contains("UTF8") =>
+            {
+                CheckResult::ok("locale", format!("{} uses UTF-8", locale))
+            }
+            Some(locale) => CheckResult::warning(
+                "locale",
+                format!(
+                    "{} is not a UTF-8 locale, non-ASCII input may be encoded differently",
+                    locale
+                ),
+                "export LANG=en_US.UTF-8 (or another UTF-8 locale)",
+            ),
+            None => CheckResult::warning(
+                "locale",
+                "no locale is set, non-ASCII input may be encoded differently",
+                "export LANG=en_US.UTF-8 (or another UTF-8 locale)",
+            ),
+        }
+    }
+    fn check_unicode_normalization() -> CheckResult {
+        let salt = |user_id: &str| {
+            UserID::from_str(user_id)
+                .ok()
+                .map(|u| MasterSecret::salt(&u))
+        };
+        if salt("caf\u{e9}-doctor") == salt("cafe\u{301}-doctor") {
+            CheckResult::ok("unicode", "identifiers are normalized before derivation")
+        } else {
+            CheckResult::warning(
+                "unicode",
+                "identifiers are used byte-for-byte: a precomposed \"é\" and \"e\" + combining accent derive different passwords",
+                "prefer ASCII identifiers, or type non-ASCII ones with the same keyboard layout every time",
+            )
+        }
+    }
+    fn check_argon2_timing() -> CheckResult {
+        match Self::time_argon2() {
+            Ok(elapsed) if elapsed > Duration::from_secs(5) => CheckResult::warning(
+                "argon2",
+                format!("one derivation takes {} ms", elapsed.as_millis()),
+                "use a release build (`cargo build -r`) or a faster machine",
+            ),
+            Ok(elapsed) => CheckResult::ok(
+                "argon2",
+                format!("one derivation takes {} ms", elapsed.as_millis()),
+            ),
+            Err(e) => CheckResult::error(
+                "argon2",
+                format!("derivation failed: {}", e),
+                "make sure at least 32 MiB of memory is available to depasswd",
+            ),
+        }
+    }
+    fn time_argon2() -> Result<Duration> {
+        let start = Instant::now();
+        MasterSecret::new(
+            &UserID::from_str("depasswd-doctor")?,
+            &MasterPasswordPlain::from_str("depasswd-doctor")?,
+            SchemeVersion::LATEST,
+            &KdfParams::DEFAULT,
+        )?;
+        Ok(start.elapsed())
+    }
+    pub fn find_in_path(tool: &str, path: &OsStr) -> Option<PathBuf> {
+        env::split_paths(path)
+            .map(|dir| dir.join(tool))
+            .find(|candidate| Path::new(candidate).is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_check_locale() {
+        assert_eq!(
+            Doctor::check_locale(Some("en_US.UTF-8")).status,
+            CheckStatus::Ok
+        );
+        assert_eq!(
+            Doctor::check_locale(Some("hu_HU.utf8")).status,
+            CheckStatus::Ok
+        );
+        assert_eq!(Doctor::check_locale(Some("C")).status, CheckStatus::Warning);
+        assert_eq!(Doctor::check_locale(None).status, CheckStatus::Warning);
+    }
+
+    #[test]
+    fn can_find_in_path() {
+        let dir = env::temp_dir().join("depasswd-doctor-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("wl-copy"), "").unwrap();
+        let path = env::join_paths([Path::new("/nonexistent"), &dir]).unwrap();
+
+        assert_eq!(
+            Doctor::find_in_path("wl-copy", &path),
+            Some(dir.join("wl-copy"))
+        );
+        assert_eq!(Doctor::find_in_path("xclip", &path), None);
+    }
+}
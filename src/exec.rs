@@ -0,0 +1,197 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Runs a child process with derived secrets, either in its environment or
+//! in private files on a memory-backed filesystem.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{batch::DerivationRequest, user_input::UserInputError};
+
+/// One `VAR=service:generation` argument. The generation defaults to 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretMapping {
+    pub var: String,
+    pub service_id: String,
+    pub generation: usize,
+}
+
+impl SecretMapping {
+    pub fn request(&self, char_sets: &[String], password_length: u16) -> DerivationRequest {
+        DerivationRequest {
+            key: Some(self.var.clone()),
+            service_id: self.service_id.clone(),
+            generation: self.generation,
+            char_sets: char_sets.to_vec(),
+            password_length,
+        }
+    }
+}
+
+impl FromStr for SecretMapping {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (var, service) = s.split_once('=').ok_or(UserInputError::new(format!(
+            "Invalid mapping {}, expected VAR=service:generation",
+            s
+        )))?;
+        if var.is_empty() || var.contains('\0') {
+            return Err(UserInputError::new(format!(
+                "Invalid environment variable name: {:?}",
+                var
+            )));
+        }
+        let (service_id, generation) = match service.rsplit_once(':') {
+            Some((service_id, generation)) => match generation.parse::<usize>() {
+                Ok(generation) if generation > 0 => (service_id, generation),
+                _ => (service, 1),
+            },
+            None => (service, 1),
+        };
+        Ok(Self {
+            var: var.to_owned(),
+            service_id: service_id.to_owned(),
+            generation,
+        })
+    }
+}
+
+pub enum SecretDelivery {
+    /// Every secret is set as an environment variable of the child.
+    Environment,
+    /// Every secret is written to a file in the given directory and the child
+    /// gets its path in `<VAR>_FILE`.
+    Files(PathBuf),
+}
+
+pub struct Exec {}
+
+impl Exec {
+    /// Creates a directory only the current user can access, preferring
+    /// `XDG_RUNTIME_DIR` and `/dev/shm` so the secrets never reach a disk.
+    pub fn secret_dir() -> Result<PathBuf> {
+        let base = env::var_os("XDG_RUNTIME_DIR")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from("/dev/shm")).filter(|d| d.is_dir()))
+            .unwrap_or_else(env::temp_dir);
+        let dir = base.join(format!("depasswd-exec-{}", std::process::id()));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder
+            .create(&dir)
+            .with_context(|| format!("Can not create secret directory: {}", dir.display()))?;
+        Ok(dir)
+    }
+    /// Builds the child command from `argv` with the `(var, secret)` pairs.
+    pub fn command(
+        argv: &[String],
+        secrets: &[(String, impl AsRef<str>)],
+        delivery: &SecretDelivery,
+    ) -> Result<Command> {
+        let Some((program, args)) = argv.split_first() else {
+            bail!("No command given to exec");
+        };
+        let mut command = Command::new(program);
+        command.args(args);
+        for (var, secret) in secrets {
+            match delivery {
+                SecretDelivery::Environment => {
+                    command.env(var, secret.as_ref());
+                }
+                SecretDelivery::Files(dir) => {
+                    let path = dir.join(var);
+                    Self::write_private(&path, secret.as_ref())?;
+                    command.env(format!("{}_FILE", var), path);
+                }
+            }
+        }
+        Ok(command)
+    }
+    fn write_private(path: &Path, secret: &str) -> Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        std::io::Write::write_all(&mut options.open(path)?, secret.as_bytes())
+            .with_context(|| format!("Can not write secret file: {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_secret_mapping() {
+        assert_eq!(
+            SecretMapping::from_str("DB_PASSWORD=postgres:3").unwrap(),
+            SecretMapping {
+                var: "DB_PASSWORD".to_owned(),
+                service_id: "postgres".to_owned(),
+                generation: 3,
+            }
+        );
+        assert_eq!(
+            SecretMapping::from_str("API=https://example.com")
+                .unwrap()
+                .service_id,
+            "https://example.com"
+        );
+        assert!(SecretMapping::from_str("postgres:3").is_err());
+        assert!(SecretMapping::from_str("=postgres").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn can_pass_secrets_to_child() {
+        let secrets = [("DEPASSWD_TEST".to_owned(), "abc".to_owned())];
+        let argv = |script: &str| ["sh", "-c", script].map(str::to_owned);
+
+        let status = Exec::command(
+            &argv(r#"test "$DEPASSWD_TEST" = abc"#),
+            &secrets,
+            &SecretDelivery::Environment,
+        )
+        .unwrap()
+        .status()
+        .unwrap();
+        assert!(status.success());
+
+        let dir = Exec::secret_dir().unwrap();
+        let status = Exec::command(
+            &argv(r#"test "$(cat "$DEPASSWD_TEST_FILE")" = abc"#),
+            &secrets,
+            &SecretDelivery::Files(dir.clone()),
+        )
+        .unwrap()
+        .status()
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(status.success());
+    }
+}
@@ -0,0 +1,336 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A KeePass KDBX 4 database of derived entries, as an offline backup any
+//! KeePass client opens with its own passphrase. Written with AES-256-CBC,
+//! an Argon2id key and no compression, the choices every KDBX 4 reader
+//! supports. Values are not protected in the inner XML, the whole payload
+//! is encrypted and authenticated anyway.
+
+use aes::{
+    Aes256,
+    cipher::{BlockEncrypt, KeyInit},
+};
+use anyhow::{Result, anyhow};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use quick_xml::escape::escape;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+
+const SIGNATURE: [u32; 2] = [0x9AA2_D903, 0xB54B_FB67];
+const VERSION: u32 = 0x0004_0000;
+const CIPHER_AES256: [u8; 16] = [
+    0x31, 0xC1, 0xF2, 0xE6, 0xBF, 0x71, 0x43, 0x50, 0xBE, 0x58, 0x05, 0x21, 0x6A, 0xFC, 0x5A, 0xFF,
+];
+const KDF_ARGON2ID: [u8; 16] = [
+    0x9E, 0x29, 0x8B, 0x19, 0x56, 0xDB, 0x47, 0x73, 0xB2, 0x3D, 0xFC, 0x3E, 0xC6, 0xF0, 0xA1, 0xE6,
+];
+/// Inner random stream of KDBX 4.
+const CHACHA20_STREAM: u32 = 3;
+const BLOCK_SIZE: usize = 1 << 20;
+
+pub struct KdbxEntry {
+    pub title: String,
+    pub username: Option<String>,
+    pub password: Zeroizing<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+}
+
+pub struct KdbxWriter {
+    /// Argon2id memory in KiB.
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// The random values of a database, fixed by tests.
+struct Seeds {
+    master_seed: [u8; 32],
+    iv: [u8; 16],
+    kdf_salt: [u8; 32],
+    stream_key: [u8; 64],
+}
+
+impl KdbxWriter {
+    /// 64 MiB, as KeePassXC creates databases.
+    pub const DEFAULT: KdbxWriter = KdbxWriter {
+        memory: 64 * 1024,
+        iterations: 10,
+        parallelism: 2,
+    };
+    /// The database file of `entries` in a group named `name`, opened by
+    /// `passphrase`.
+    pub fn write(&self, name: &str, entries: &[KdbxEntry], passphrase: &str) -> Result<Vec<u8>> {
+        let mut seeds = Seeds {
+            master_seed: [0; 32],
+            iv: [0; 16],
+            kdf_salt: [0; 32],
+            stream_key: [0; 64],
+        };
+        OsRng.fill_bytes(&mut seeds.master_seed);
+        OsRng.fill_bytes(&mut seeds.iv);
+        OsRng.fill_bytes(&mut seeds.kdf_salt);
+        OsRng.fill_bytes(&mut seeds.stream_key);
+        self.write_with_seeds(name, entries, passphrase, &seeds)
+    }
+    fn write_with_seeds(
+        &self,
+        name: &str,
+        entries: &[KdbxEntry],
+        passphrase: &str,
+        seeds: &Seeds,
+    ) -> Result<Vec<u8>> {
+        let composite = Sha256::digest(Sha256::digest(passphrase.as_bytes()));
+        let mut transformed = Zeroizing::new([0u8; 32]);
+        Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(self.memory, self.iterations, self.parallelism, Some(32))
+                .map_err(|e| anyhow!("Invalid Argon2 parameters: {}", e))?,
+        )
+        .hash_password_into(&composite, &seeds.kdf_salt, transformed.as_mut())
+        .map_err(|e| anyhow!("Argon2 failed: {}", e))?;
+        let cipher_key = Zeroizing::new(<[u8; 32]>::from(Sha256::digest(
+            [seeds.master_seed.as_slice(), transformed.as_slice()].concat(),
+        )));
+        let hmac_key = Zeroizing::new(Sha512::digest(
+            [seeds.master_seed.as_slice(), transformed.as_slice(), &[1]].concat(),
+        ));
+
+        let header = self.header(seeds);
+        let mut file = header.clone();
+        file.extend(Sha256::digest(&header));
+        file.extend(Self::hmac(&hmac_key, u64::MAX, &header)?);
+
+        let mut plaintext = Zeroizing::new(Self::inner_header(&seeds.stream_key));
+        plaintext.extend(Self::xml(name, entries).as_bytes());
+        let ciphertext = Self::aes_cbc(&cipher_key, &seeds.iv, &plaintext);
+        let mut chunks: Vec<&[u8]> = ciphertext.chunks(BLOCK_SIZE).collect();
+        chunks.push(&[]);
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let length = (chunk.len() as i32).to_le_bytes();
+            let index = index as u64;
+            file.extend(Self::hmac(
+                &hmac_key,
+                index,
+                &[&index.to_le_bytes(), length.as_slice(), chunk].concat(),
+            )?);
+            file.extend(length);
+            file.extend(chunk);
+        }
+        Ok(file)
+    }
+    fn header(&self, seeds: &Seeds) -> Vec<u8> {
+        let mut kdf = 0x0100u16.to_le_bytes().to_vec();
+        Self::variant(&mut kdf, 0x42, "$UUID", &KDF_ARGON2ID);
+        Self::variant(&mut kdf, 0x42, "S", &seeds.kdf_salt);
+        Self::variant(&mut kdf, 0x04, "P", &self.parallelism.to_le_bytes());
+        Self::variant(
+            &mut kdf,
+            0x05,
+            "M",
+            &(self.memory as u64 * 1024).to_le_bytes(),
+        );
+        Self::variant(&mut kdf, 0x05, "I", &(self.iterations as u64).to_le_bytes());
+        Self::variant(&mut kdf, 0x04, "V", &0x13u32.to_le_bytes());
+        kdf.push(0);
+
+        let mut header = vec![];
+        header.extend(SIGNATURE[0].to_le_bytes());
+        header.extend(SIGNATURE[1].to_le_bytes());
+        header.extend(VERSION.to_le_bytes());
+        for (id, data) in [
+            (2, CIPHER_AES256.as_slice()),
+            (3, &0u32.to_le_bytes()),
+            (4, &seeds.master_seed),
+            (7, &seeds.iv),
+            (11, &kdf),
+            (0, b"\r\n\r\n"),
+        ] {
+            Self::field(&mut header, id, data);
+        }
+        header
+    }
+    fn inner_header(stream_key: &[u8; 64]) -> Vec<u8> {
+        let mut header = vec![];
+        Self::field(&mut header, 1, &CHACHA20_STREAM.to_le_bytes());
+        Self::field(&mut header, 2, stream_key);
+        Self::field(&mut header, 0, &[]);
+        header
+    }
+    fn xml(name: &str, entries: &[KdbxEntry]) -> Zeroizing<String> {
+        let mut xml = Zeroizing::new(format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n\
+             <KeePassFile><Meta><Generator>depasswd</Generator>\
+             <DatabaseName>{0}</DatabaseName></Meta>\
+             <Root><Group><UUID>{1}</UUID><Name>{0}</Name>",
+            escape(name),
+            Self::uuid()
+        ));
+        for entry in entries {
+            xml.push_str(&format!("<Entry><UUID>{}</UUID>", Self::uuid()));
+            for (key, value) in [
+                ("Title", Some(entry.title.as_str())),
+                ("UserName", entry.username.as_deref()),
+                ("Password", Some(entry.password.as_str())),
+                ("URL", entry.url.as_deref()),
+                ("Notes", entry.notes.as_deref()),
+            ] {
+                xml.push_str(&format!(
+                    "<String><Key>{}</Key><Value>{}</Value></String>",
+                    key,
+                    escape(value.unwrap_or_default())
+                ));
+            }
+            xml.push_str("</Entry>");
+        }
+        xml.push_str("</Group></Root></KeePassFile>\n");
+        xml
+    }
+    fn uuid() -> String {
+        let mut uuid = [0u8; 16];
+        OsRng.fill_bytes(&mut uuid);
+        BASE64_STANDARD.encode(uuid)
+    }
+    fn field(header: &mut Vec<u8>, id: u8, data: &[u8]) {
+        header.push(id);
+        header.extend((data.len() as u32).to_le_bytes());
+        header.extend(data);
+    }
+    /// An entry of a KDBX `VariantDictionary`.
+    fn variant(dictionary: &mut Vec<u8>, kind: u8, key: &str, value: &[u8]) {
+        dictionary.push(kind);
+        dictionary.extend((key.len() as u32).to_le_bytes());
+        dictionary.extend(key.as_bytes());
+        dictionary.extend((value.len() as u32).to_le_bytes());
+        dictionary.extend(value);
+    }
+    /// HMAC-SHA256 of block `index`, `u64::MAX` being the header.
+    fn hmac(hmac_key: &[u8], index: u64, data: &[u8]) -> Result<[u8; 32]> {
+        let key = Zeroizing::new(Sha512::digest([&index.to_le_bytes(), hmac_key].concat()));
+        let mut hmac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&key).map_err(|_| anyhow!("Invalid HMAC key"))?;
+        hmac.update(data);
+        Ok(hmac.finalize().into_bytes().into())
+    }
+    /// AES-256-CBC with PKCS#7 padding.
+    fn aes_cbc(key: &[u8; 32], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+        let cipher = Aes256::new(key.into());
+        let padding = 16 - plaintext.len() % 16;
+        let mut data = plaintext.to_vec();
+        data.extend(std::iter::repeat_n(padding as u8, padding));
+        let mut previous = *iv;
+        for block in data.chunks_mut(16) {
+            block
+                .iter_mut()
+                .zip(previous)
+                .for_each(|(byte, chained)| *byte ^= chained);
+            cipher.encrypt_block(block.into());
+            previous.copy_from_slice(block);
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aes::cipher::BlockDecrypt;
+
+    use super::*;
+
+    #[test]
+    fn can_write_kdbx_database() {
+        let writer = KdbxWriter {
+            memory: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let seeds = Seeds {
+            master_seed: [1; 32],
+            iv: [2; 16],
+            kdf_salt: [3; 32],
+            stream_key: [4; 64],
+        };
+        let entry = KdbxEntry {
+            title: "github.com".to_owned(),
+            username: Some("octocat".to_owned()),
+            password: Zeroizing::new("<hunter2&>".to_owned()),
+            url: None,
+            notes: None,
+        };
+        let file = writer
+            .write_with_seeds("depasswd", &[entry], "passphrase", &seeds)
+            .unwrap();
+        let header_length = writer.header(&seeds).len();
+        assert_eq!(&file[..4], &SIGNATURE[0].to_le_bytes());
+        assert_eq!(
+            &file[header_length..header_length + 32],
+            Sha256::digest(&file[..header_length]).as_slice()
+        );
+
+        // Opened the way a reader does, from the passphrase.
+        let mut transformed = [0u8; 32];
+        Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(64, 1, 1, Some(32)).unwrap(),
+        )
+        .hash_password_into(
+            &Sha256::digest(Sha256::digest(b"passphrase")),
+            &seeds.kdf_salt,
+            &mut transformed,
+        )
+        .unwrap();
+        let hmac_key = Sha512::digest([[1u8; 32].as_slice(), &transformed, &[1]].concat());
+        assert_eq!(
+            &file[header_length + 32..header_length + 64],
+            KdbxWriter::hmac(&hmac_key, u64::MAX, &file[..header_length])
+                .unwrap()
+                .as_slice()
+        );
+        let blocks = &file[header_length + 64..];
+        let length = i32::from_le_bytes(blocks[32..36].try_into().unwrap()) as usize;
+        let mut data = blocks[36..36 + length].to_vec();
+        assert_eq!(blocks.len(), 36 + length + 36);
+
+        let cipher = Aes256::new(&Sha256::digest(
+            [[1u8; 32].as_slice(), &transformed].concat(),
+        ));
+        let mut previous = seeds.iv;
+        for block in data.chunks_mut(16) {
+            let encrypted: [u8; 16] = block.try_into().unwrap();
+            cipher.decrypt_block(block.into());
+            block
+                .iter_mut()
+                .zip(previous)
+                .for_each(|(byte, chained)| *byte ^= chained);
+            previous = encrypted;
+        }
+        let plaintext = String::from_utf8_lossy(&data);
+        assert!(
+            plaintext.contains(
+                "<String><Key>Password</Key><Value>&lt;hunter2&amp;&gt;</Value></String>"
+            )
+        );
+    }
+}
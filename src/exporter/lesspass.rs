@@ -0,0 +1,149 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! LessPass profiles of the recipes. LessPass derives its own passwords,
+//! so a profile only reproduces the passwords of services still using the
+//! LessPass ones, e.g. during a migration from it.
+
+use serde::Serialize;
+
+use crate::{catalog::Catalog, recipe_vault::RecipeEntry, user_input::CHAR_SET_NAMES};
+
+/// The password length range of LessPass.
+const LENGTH_RANGE: std::ops::RangeInclusive<u16> = 5..=35;
+
+/// A site profile as in the LessPass API and its JSON exports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LessPassProfile {
+    pub site: String,
+    pub login: String,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub length: u16,
+    pub counter: usize,
+    pub version: u8,
+}
+
+pub struct LessPassExporter {}
+
+impl LessPassExporter {
+    /// The profiles of `entries`, with their username or the one of their
+    /// catalog entry as login, and the service identifiers LessPass can not express:
+    /// custom, layout safe or filtered character sets and lengths outside
+    /// its range.
+    pub fn profiles(
+        entries: &[RecipeEntry],
+        catalog: &Catalog,
+    ) -> (Vec<LessPassProfile>, Vec<String>) {
+        let mut profiles = vec![];
+        let mut skipped = vec![];
+        for entry in entries {
+            match Self::profile(entry, catalog) {
+                Some(profile) => profiles.push(profile),
+                None => skipped.push(entry.service_id.clone()),
+            }
+        }
+        (profiles, skipped)
+    }
+    fn profile(entry: &RecipeEntry, catalog: &Catalog) -> Option<LessPassProfile> {
+        if !LENGTH_RANGE.contains(&entry.password_length) {
+            return None;
+        }
+        let mut profile = LessPassProfile {
+            site: entry.service_id.clone(),
+            login: entry
+                .username
+                .clone()
+                .or_else(|| {
+                    catalog
+                        .get(&entry.service_id)
+                        .and_then(|e| e.username.clone())
+                })
+                .unwrap_or_default(),
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            length: entry.password_length,
+            counter: entry.generation,
+            version: 2,
+        };
+        for name in &entry.char_sets {
+            // The presets are in the order of LessPass' options.
+            match CHAR_SET_NAMES.iter().position(|n| n == name)? {
+                0 => profile.lowercase = true,
+                1 => profile.uppercase = true,
+                2 => profile.digits = true,
+                _ => profile.symbols = true,
+            }
+        }
+        Some(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::CatalogEntry;
+
+    #[test]
+    fn can_export_lesspass_profiles() {
+        let entry = |service_id: &str, length: u16, char_sets: &[&str]| RecipeEntry {
+            service_id: service_id.to_owned(),
+            generation: 2,
+            password_length: length,
+            char_sets: char_sets.iter().map(|s| s.to_string()).collect(),
+            policy: None,
+            username: None,
+            notes: None,
+            pending_rotation: false,
+        };
+        let mut catalog = Catalog::default();
+        catalog.add(CatalogEntry {
+            username: Some("octocat".to_owned()),
+            ..CatalogEntry::new("github.com")
+        });
+
+        let (profiles, skipped) = LessPassExporter::profiles(
+            &[
+                entry("github.com", 16, &["small", "capital", "numbers"]),
+                entry("bank.example", 16, &["numbers", "custom:#!"]),
+                entry("example.com", 64, &["small"]),
+            ],
+            &catalog,
+        );
+        assert_eq!(
+            profiles,
+            vec![LessPassProfile {
+                site: "github.com".to_owned(),
+                login: "octocat".to_owned(),
+                lowercase: true,
+                uppercase: true,
+                digits: true,
+                symbols: false,
+                length: 16,
+                counter: 2,
+                version: 2,
+            }]
+        );
+        assert_eq!(skipped, vec!["bank.example", "example.com"]);
+    }
+}
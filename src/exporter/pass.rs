@@ -0,0 +1,165 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Derived passwords as entries of a pass(1) password store, encrypted by
+//! gpg to the recipients of its `.gpg-id`, for machines using pass based
+//! tools. The entries are copies: a rotation needs another export.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+use zeroize::Zeroizing;
+
+use crate::recipe_vault::RecipeEntry;
+
+pub struct PassExporter {
+    store: PathBuf,
+    recipients: Vec<String>,
+}
+
+impl PassExporter {
+    /// `$PASSWORD_STORE_DIR`, or `~/.password-store` as pass uses.
+    pub fn default_store() -> Result<PathBuf> {
+        if let Some(dir) = env::var_os("PASSWORD_STORE_DIR").filter(|d| !d.is_empty()) {
+            return Ok(PathBuf::from(dir));
+        }
+        env::var_os("HOME")
+            .filter(|d| !d.is_empty())
+            .map(|home| PathBuf::from(home).join(".password-store"))
+            .context("Can not find the home directory")
+    }
+    /// An initialized store, with the recipients of its top `.gpg-id`.
+    pub fn new(store: &Path) -> Result<Self> {
+        let gpg_id = store.join(".gpg-id");
+        let recipients: Vec<String> = fs::read_to_string(&gpg_id)
+            .with_context(|| {
+                format!(
+                    "Not a password store, run `pass init GPG-ID` first: {}",
+                    store.display()
+                )
+            })?
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_owned)
+            .collect();
+        if recipients.is_empty() {
+            bail!("No recipient in {}", gpg_id.display());
+        }
+        Ok(Self {
+            store: store.to_owned(),
+            recipients,
+        })
+    }
+    /// The name of the entry of `service_id` under `prefix`. Slashes would
+    /// nest folders and a leading dot hide the entry, both are replaced.
+    pub fn entry_name(prefix: &str, service_id: &str) -> String {
+        let name: String = service_id
+            .chars()
+            .map(|c| if matches!(c, '/' | '\\') { '_' } else { c })
+            .collect();
+        let name = match name.strip_prefix('.') {
+            Some(rest) => format!("_{}", rest),
+            None => name,
+        };
+        match prefix.trim_matches('/') {
+            "" => name,
+            prefix => format!("{}/{}", prefix, name),
+        }
+    }
+    /// The password on the first line, then the `key: value` lines pass
+    /// extensions and browser plugins read.
+    pub fn content(password: &str, entry: &RecipeEntry) -> Zeroizing<String> {
+        let mut content = Zeroizing::new(format!("{}\n", password));
+        if let Some(username) = &entry.username {
+            content.push_str(&format!("login: {}\n", username));
+        }
+        if entry.service_id.contains('.') && !entry.service_id.contains(char::is_whitespace) {
+            content.push_str(&format!("url: {}\n", entry.service_id));
+        }
+        content.push_str(&format!(
+            "generated-by: depasswd, generation {}\n",
+            entry.generation
+        ));
+        content
+    }
+    /// Encrypts `content` to the recipients into the entry `name`.
+    pub fn write(&self, name: &str, content: &str) -> Result<()> {
+        let path = self.store.join(format!("{}.gpg", name));
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut command = Command::new("gpg");
+        command.args(["--encrypt", "--quiet", "--batch", "--yes", "--output"]);
+        command.arg(&path);
+        for recipient in &self.recipients {
+            command.args(["--recipient", recipient]);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .context("Can not start gpg")?;
+        child
+            .stdin
+            .take()
+            .context("Can not write to gpg")?
+            .write_all(content.as_bytes())?;
+        if !child.wait()?.success() {
+            bail!("gpg can not encrypt {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_lay_out_pass_entries() {
+        assert_eq!(
+            PassExporter::entry_name("depasswd/", "github.com"),
+            "depasswd/github.com"
+        );
+        assert_eq!(
+            PassExporter::entry_name("", "../etc/passwd"),
+            "_._etc_passwd"
+        );
+        let entry = RecipeEntry {
+            service_id: "github.com".to_owned(),
+            generation: 2,
+            password_length: 20,
+            char_sets: vec!["small".to_owned()],
+            policy: None,
+            username: Some("octocat".to_owned()),
+            notes: None,
+            pending_rotation: false,
+        };
+        assert_eq!(
+            *PassExporter::content("hunter2", &entry),
+            "hunter2\nlogin: octocat\nurl: github.com\ngenerated-by: depasswd, generation 2\n"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Opt-in record of which service identifiers were derived for, without
+//! the identifiers themselves: each is kept as an HMAC keyed by a random
+//! salt of the file, with the time of its last use. It can only answer
+//! whether an exact identifier was used before, which catches the typos
+//! and case changes that silently derive another password.
+
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::utils::Utils;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Hex HMAC-SHA256 of the service identifier keyed by the salt.
+    pub hash: String,
+    /// Seconds since the Unix epoch.
+    pub last_used: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceHistory {
+    /// Hex random salt, generated with the file.
+    salt: String,
+    #[serde(default, rename = "entry")]
+    entries: Vec<HistoryEntry>,
+}
+
+impl ServiceHistory {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Utils::state_dir()?.join("history.toml"))
+    }
+    /// A missing file is an empty history with a fresh salt.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid history file: {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let mut salt = [0u8; 32];
+                OsRng.fill_bytes(&mut salt);
+                Ok(Self {
+                    salt: Utils::bytes_to_hex(&salt),
+                    entries: vec![],
+                })
+            }
+            Err(e) => {
+                Err(e).with_context(|| format!("Can not read history file: {}", path.display()))
+            }
+        }
+    }
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let content = toml::to_string_pretty(self)?;
+        options
+            .open(path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .with_context(|| format!("Can not write history file: {}", path.display()))
+    }
+    /// When `service_id` was last derived for, in seconds since the Unix
+    /// epoch.
+    pub fn last_used(&self, service_id: &str) -> Result<Option<u64>> {
+        let hash = self.hash(service_id)?;
+        Ok(self
+            .entries
+            .iter()
+            .find(|e| e.hash == hash)
+            .map(|e| e.last_used))
+    }
+    /// Records a use of `service_id` now.
+    pub fn touch(&mut self, service_id: &str) -> Result<()> {
+        let hash = self.hash(service_id)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        match self.entries.iter_mut().find(|e| e.hash == hash) {
+            Some(entry) => entry.last_used = now,
+            None => self.entries.push(HistoryEntry {
+                hash,
+                last_used: now,
+            }),
+        }
+        Ok(())
+    }
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+    fn hash(&self, service_id: &str) -> Result<String> {
+        let salt =
+            Utils::hex_to_bytes(&self.salt).ok_or_else(|| anyhow!("Invalid history salt"))?;
+        let mut hmac = <Hmac<Sha256> as Mac>::new_from_slice(&salt)
+            .map_err(|_| anyhow!("Invalid history salt"))?;
+        hmac.update(service_id.as_bytes());
+        Ok(Utils::bytes_to_hex(&hmac.finalize().into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_remember_hashed_services() {
+        let path =
+            std::env::temp_dir().join(format!("depasswd-history-{}.toml", std::process::id()));
+        let mut history = ServiceHistory::load(&path).unwrap();
+        assert_eq!(history.last_used("GitHub").unwrap(), None);
+        history.touch("GitHub").unwrap();
+        history.touch("GitHub").unwrap();
+        history.save(&path).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("GitHub"));
+        let history = ServiceHistory::load(&path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+        assert!(history.last_used("GitHub").unwrap().is_some());
+        assert_eq!(history.last_used("github").unwrap(), None);
+        assert_ne!(
+            ServiceHistory::load(Path::new("/nonexistent"))
+                .unwrap()
+                .salt,
+            history.salt
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
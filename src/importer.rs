@@ -0,0 +1,133 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+
+use crate::catalog::{Catalog, CatalogEntry};
+
+pub mod bitwarden;
+pub mod bookmarks;
+pub mod csv;
+pub mod keepass;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportProposal {
+    pub entry: CatalogEntry,
+    pub occurrences: usize,
+}
+
+impl ImportProposal {
+    /// Groups URLs by their canonical service identifier, most frequent first.
+    pub fn from_urls<'a>(urls: impl IntoIterator<Item = &'a str>) -> Vec<ImportProposal> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for service_id in urls.into_iter().filter_map(Catalog::canonical_service_id) {
+            *counts.entry(service_id).or_default() += 1;
+        }
+        let mut proposals: Vec<ImportProposal> = counts
+            .into_iter()
+            .map(|(service_id, occurrences)| ImportProposal {
+                entry: CatalogEntry {
+                    url: Some(format!("https://{}", service_id)),
+                    username: None,
+                    service_id,
+                    ..Default::default()
+                },
+                occurrences,
+            })
+            .collect();
+        proposals.sort_by(|a, b| {
+            b.occurrences
+                .cmp(&a.occurrences)
+                .then_with(|| a.entry.service_id.cmp(&b.entry.service_id))
+        });
+        proposals
+    }
+}
+
+/// Builds the catalog entry for an account exported from another password
+/// manager: the service identifier is derived from the URL when it has one,
+/// otherwise the entry's title is used as is.
+pub fn account_entry(
+    title: Option<&str>,
+    username: Option<&str>,
+    url: Option<&str>,
+) -> Option<CatalogEntry> {
+    fn non_empty(s: Option<&str>) -> Option<&str> {
+        s.map(str::trim).filter(|s| !s.is_empty())
+    }
+    let url = non_empty(url);
+    let service_id = url
+        .and_then(Catalog::canonical_service_id)
+        .or_else(|| non_empty(title).map(str::to_owned))?;
+    Some(CatalogEntry {
+        service_id,
+        username: non_empty(username).map(str::to_owned),
+        url: url.map(str::to_owned),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_group_urls_into_proposals() {
+        let proposals = ImportProposal::from_urls([
+            "https://github.com/kovacsdavid",
+            "https://example.org/",
+            "https://www.github.com/login",
+            "http://localhost:8080/",
+        ]);
+
+        assert_eq!(
+            proposals
+                .iter()
+                .map(|p| (p.entry.service_id.as_str(), p.occurrences))
+                .collect::<Vec<_>>(),
+            vec![("github.com", 2), ("example.org", 1)]
+        );
+        assert_eq!(
+            proposals[0].entry.url.as_deref(),
+            Some("https://github.com")
+        );
+    }
+
+    #[test]
+    fn can_build_account_entry() {
+        assert_eq!(
+            account_entry(
+                Some("GitHub"),
+                Some("octocat"),
+                Some("https://github.com/login")
+            ),
+            Some(CatalogEntry {
+                service_id: "github.com".to_owned(),
+                username: Some("octocat".to_owned()),
+                url: Some("https://github.com/login".to_owned()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            account_entry(Some(" Home router "), Some(""), None),
+            Some(CatalogEntry::new("Home router"))
+        );
+        assert_eq!(account_entry(Some(""), Some("admin"), Some(" ")), None);
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+use crate::{catalog::CatalogEntry, importer::account_entry};
+
+// Only the non-secret fields are declared, so stored passwords, notes and TOTP
+// seeds are skipped by the parser instead of being read into memory.
+#[derive(Deserialize)]
+struct BitwardenExport {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenItem {
+    name: Option<String>,
+    login: Option<BitwardenLogin>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    #[serde(default)]
+    uris: Option<Vec<BitwardenUri>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+pub struct BitwardenImporter {}
+
+impl BitwardenImporter {
+    pub fn entries(content: &str) -> Result<Vec<CatalogEntry>> {
+        let export: BitwardenExport = serde_json::from_str(content)?;
+        if export.encrypted {
+            bail!("Encrypted Bitwarden exports are not supported, export as unencrypted JSON");
+        }
+        Ok(export
+            .items
+            .iter()
+            .filter_map(|item| {
+                let login = item.login.as_ref()?;
+                let url = login.uris.iter().flatten().find_map(|u| u.uri.as_deref());
+                account_entry(item.name.as_deref(), login.username.as_deref(), url)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_bitwarden_export() {
+        let content = r#"{
+            "encrypted": false,
+            "folders": [],
+            "items": [
+                {
+                    "type": 1,
+                    "name": "GitHub",
+                    "notes": "secret note",
+                    "login": {
+                        "username": "octocat",
+                        "password": "hunter2",
+                        "totp": null,
+                        "uris": [{ "match": null, "uri": "https://github.com/login" }]
+                    }
+                },
+                { "type": 2, "name": "Secure note", "secureNote": { "type": 0 } },
+                {
+                    "type": 1,
+                    "name": "NAS",
+                    "login": { "username": "admin", "password": "hunter2", "uris": null }
+                }
+            ]
+        }"#;
+
+        assert_eq!(
+            BitwardenImporter::entries(content).unwrap(),
+            vec![
+                CatalogEntry {
+                    service_id: "github.com".to_owned(),
+                    username: Some("octocat".to_owned()),
+                    url: Some("https://github.com/login".to_owned()),
+                    ..Default::default()
+                },
+                CatalogEntry {
+                    service_id: "NAS".to_owned(),
+                    username: Some("admin".to_owned()),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_encrypted_export() {
+        assert!(BitwardenImporter::entries(r#"{"encrypted": true, "data": "..."}"#).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkFormat {
+    /// Chrome's `Bookmarks` file or a Firefox JSON backup.
+    Json,
+    /// The Netscape bookmark HTML exported by every major browser.
+    Html,
+    /// One URL per line, or a CSV history export with URL columns.
+    UrlList,
+}
+
+pub struct BookmarkImporter {}
+
+impl BookmarkImporter {
+    pub fn detect(content: &str) -> BookmarkFormat {
+        let start = content.trim_start();
+        if start.starts_with('{') {
+            BookmarkFormat::Json
+        } else if start.starts_with('<') {
+            BookmarkFormat::Html
+        } else {
+            BookmarkFormat::UrlList
+        }
+    }
+    pub fn urls(content: &[u8]) -> Result<Vec<String>> {
+        if content.starts_with(b"mozLz40\0") {
+            bail!(
+                "Compressed Firefox backups (.jsonlz4) are not supported, export the bookmarks as HTML or JSON from the Library window"
+            );
+        }
+        let content = String::from_utf8_lossy(content);
+        match Self::detect(&content) {
+            BookmarkFormat::Json => {
+                let mut urls = vec![];
+                Self::json_urls(&serde_json::from_str(&content)?, &mut urls);
+                Ok(urls)
+            }
+            BookmarkFormat::Html => Ok(Self::html_urls(&content)),
+            BookmarkFormat::UrlList => Ok(Self::list_urls(&content)),
+        }
+    }
+    fn json_urls(value: &Value, urls: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match (key.as_str(), value) {
+                        ("url" | "uri", Value::String(url)) => urls.push(url.to_owned()),
+                        _ => Self::json_urls(value, urls),
+                    }
+                }
+            }
+            Value::Array(array) => array.iter().for_each(|v| Self::json_urls(v, urls)),
+            _ => {}
+        }
+    }
+    fn html_urls(content: &str) -> Vec<String> {
+        let lowercase = content.to_ascii_lowercase();
+        lowercase
+            .match_indices("href=\"")
+            .filter_map(|(i, m)| {
+                let start = i + m.len();
+                content[start..]
+                    .find('"')
+                    .map(|end| content[start..start + end].replace("&amp;", "&"))
+            })
+            .collect()
+    }
+    fn list_urls(content: &str) -> Vec<String> {
+        content
+            .lines()
+            .flat_map(|line| line.split([',', '\t', ';']))
+            .map(|field| field.trim().trim_matches('"'))
+            .filter(|field| field.starts_with("http://") || field.starts_with("https://"))
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_chrome_bookmarks() {
+        let content = r#"{
+            "checksum": "0",
+            "roots": {
+                "bookmark_bar": {
+                    "children": [
+                        { "name": "GitHub", "type": "url", "url": "https://github.com/" },
+                        { "name": "Work", "type": "folder", "children": [
+                            { "name": "Mail", "type": "url", "url": "https://mail.example.com/inbox" }
+                        ] }
+                    ],
+                    "type": "folder"
+                }
+            },
+            "version": 1
+        }"#;
+
+        assert_eq!(
+            BookmarkImporter::urls(content.as_bytes()).unwrap(),
+            vec!["https://github.com/", "https://mail.example.com/inbox"]
+        );
+    }
+
+    #[test]
+    fn can_read_firefox_json_backup() {
+        let content = r#"{"guid":"root________","children":[{"guid":"menu________",
+            "children":[{"title":"Codeberg","uri":"https://codeberg.org/user/login"}]}]}"#;
+
+        assert_eq!(
+            BookmarkImporter::urls(content.as_bytes()).unwrap(),
+            vec!["https://codeberg.org/user/login"]
+        );
+    }
+
+    #[test]
+    fn can_read_netscape_html() {
+        let content = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><A HREF="https://www.example.com/?a=1&amp;b=2" ADD_DATE="1">Example</A>
+    <DT><a href="https://news.ycombinator.com/">HN</a>
+</DL><p>"#;
+
+        assert_eq!(
+            BookmarkImporter::urls(content.as_bytes()).unwrap(),
+            vec![
+                "https://www.example.com/?a=1&b=2",
+                "https://news.ycombinator.com/"
+            ]
+        );
+    }
+
+    #[test]
+    fn can_read_history_csv() {
+        let content = "\"order\",\"url\",\"title\"\n1,\"https://example.com/a\",\"A\"\n2,https://example.org,B\n";
+
+        assert_eq!(
+            BookmarkImporter::urls(content.as_bytes()).unwrap(),
+            vec!["https://example.com/a", "https://example.org"]
+        );
+    }
+
+    #[test]
+    fn rejects_compressed_firefox_backup() {
+        assert!(BookmarkImporter::urls(b"mozLz40\0\x10\x00").is_err());
+    }
+}
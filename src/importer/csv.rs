@@ -0,0 +1,141 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{Result, bail};
+use zeroize::Zeroize;
+
+use crate::{catalog::CatalogEntry, importer::account_entry};
+
+/// Password CSV exports of Chrome (`name,url,username,password`), Firefox
+/// (`url,username,password,...`) and Bitwarden (`...,name,...,login_uri,
+/// login_username,login_password,...`). The columns are found by their
+/// header, the passwords are wiped as soon as a row is read.
+pub struct CsvImporter {}
+
+impl CsvImporter {
+    pub fn entries(content: &str) -> Result<Vec<CatalogEntry>> {
+        let mut rows = Self::rows(content)?.into_iter();
+        let Some(mut header) = rows.next() else {
+            return Ok(vec![]);
+        };
+        header.iter_mut().for_each(|h| *h = h.trim().to_lowercase());
+        let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+        let url = column(&["url", "login_uri"]);
+        let username = column(&["username", "login_username"]);
+        let name = column(&["name"]);
+        // Bitwarden also exports notes and cards.
+        let kind = column(&["type"]);
+        if url.is_none() && name.is_none() {
+            bail!("Not a password export: no url, login_uri or name column");
+        }
+        Ok(rows
+            .filter_map(|mut row| {
+                let field = |i: Option<usize>| i.and_then(|i| row.get(i)).map(String::as_str);
+                let entry = match field(kind) {
+                    Some(kind) if kind != "login" => None,
+                    _ => account_entry(field(name), field(username), field(url)),
+                };
+                row.zeroize();
+                entry
+            })
+            .collect())
+    }
+    /// RFC 4180 records: quoted fields may hold commas, newlines and
+    /// doubled quotes.
+    fn rows(content: &str) -> Result<Vec<Vec<String>>> {
+        let mut rows = vec![];
+        let mut row = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') if field.is_empty() => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\r') if chars.peek() == Some(&'\n') => {}
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, c) => field.push(c),
+            }
+        }
+        if quoted {
+            bail!("Unterminated quoted field in the CSV export");
+        }
+        if !field.is_empty() || !row.is_empty() {
+            row.push(field);
+            rows.push(row);
+        }
+        rows.retain(|row| row.iter().any(|f| !f.is_empty()));
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_csv_exports() {
+        let chrome = "name,url,username,password,note\r\n\
+            github.com,https://github.com/login,octocat,\"hunter,2\",\r\n\
+            NAS,,admin,\"multi\nline \"\"quoted\"\"\",\r\n";
+        assert_eq!(
+            CsvImporter::entries(chrome).unwrap(),
+            vec![
+                CatalogEntry {
+                    service_id: "github.com".to_owned(),
+                    username: Some("octocat".to_owned()),
+                    url: Some("https://github.com/login".to_owned()),
+                    ..Default::default()
+                },
+                CatalogEntry {
+                    service_id: "NAS".to_owned(),
+                    username: Some("admin".to_owned()),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        let firefox = "\"url\",\"username\",\"password\",\"httpRealm\"\n\
+            \"https://www.example.org\",\"eleonora\",\"hunter2\",\n";
+        assert_eq!(
+            CsvImporter::entries(firefox).unwrap()[0].service_id,
+            "example.org"
+        );
+
+        let bitwarden = "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+            ,,note,Secure note,secret,,0,,,,\n\
+            ,1,login,GitLab,,,0,https://gitlab.com,tanuki,hunter2,\n";
+        let entries = CsvImporter::entries(bitwarden).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].service_id, "gitlab.com");
+        assert_eq!(entries[0].username.as_deref(), Some("tanuki"));
+
+        assert!(CsvImporter::entries("a,b\n\"unterminated\n").is_err());
+        assert!(CsvImporter::entries("password\nhunter2\n").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use quick_xml::{Reader, escape::resolve_xml_entity, events::Event};
+use zeroize::Zeroize;
+
+use crate::{catalog::CatalogEntry, importer::account_entry};
+
+const IMPORTED_KEYS: [&str; 3] = ["Title", "UserName", "URL"];
+
+pub struct KeePassImporter {}
+
+impl KeePassImporter {
+    /// Reads a KeePass 2 XML export. Previous versions of entries (stored under
+    /// `History`) are skipped and every field other than title, user name and
+    /// URL is wiped right after it is read.
+    pub fn entries(content: &str) -> Result<Vec<CatalogEntry>> {
+        let mut reader = Reader::from_str(content);
+        let mut path: Vec<String> = vec![];
+        let mut fields: HashMap<String, String> = HashMap::new();
+        let mut key = String::new();
+        let mut value = String::new();
+        let mut entries = vec![];
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(e) => path.push(e.name().as_ref().to_owned()),
+                Event::Text(t) => Self::buffer(&path, &mut key, &mut value, &t.xml10_content()),
+                Event::CData(t) => Self::buffer(&path, &mut key, &mut value, &t.into_inner()),
+                Event::GeneralRef(r) => {
+                    let resolved = match r.resolve_char_ref()? {
+                        Some(c) => c.to_string(),
+                        None => resolve_xml_entity(&r.xml10_content())
+                            .ok_or(anyhow!("Unknown XML entity: {}", r.xml10_content()))?
+                            .to_owned(),
+                    };
+                    Self::buffer(&path, &mut key, &mut value, &resolved);
+                }
+                Event::End(_) => {
+                    let in_history = path.iter().any(|p| p == "History");
+                    match path.pop().as_deref() {
+                        Some("String") if !in_history => {
+                            if IMPORTED_KEYS.contains(&key.as_str()) {
+                                fields.insert(key.clone(), value.clone());
+                            }
+                            key.clear();
+                            value.zeroize();
+                        }
+                        Some("Entry") if !in_history => {
+                            entries.extend(account_entry(
+                                fields.get("Title").map(String::as_str),
+                                fields.get("UserName").map(String::as_str),
+                                fields.get("URL").map(String::as_str),
+                            ));
+                            fields.clear();
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(entries)
+    }
+    fn buffer(path: &[String], key: &mut String, value: &mut String, text: &str) {
+        if path.iter().any(|p| p == "History") {
+            return;
+        }
+        match (path.last().map(String::as_str), path.iter().rev().nth(1)) {
+            (Some("Key"), Some(parent)) if parent == "String" => key.push_str(text),
+            (Some("Value"), Some(parent)) if parent == "String" => value.push_str(text),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_keepass_xml() {
+        let content = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+  <Root>
+    <Group>
+      <Name>Root</Name>
+      <Entry>
+        <String><Key>Notes</Key><Value>note</Value></String>
+        <String><Key>Password</Key><Value ProtectInMemory="True">hunter2</Value></String>
+        <String><Key>Title</Key><Value>Example &amp; Co</Value></String>
+        <String><Key>URL</Key><Value>https://www.example.com/login</Value></String>
+        <String><Key>UserName</Key><Value>jane</Value></String>
+        <History>
+          <Entry>
+            <String><Key>Title</Key><Value>Old title</Value></String>
+            <String><Key>URL</Key><Value>https://old.example.net</Value></String>
+          </Entry>
+        </History>
+      </Entry>
+      <Group>
+        <Name>Devices</Name>
+        <Entry>
+          <String><Key>Title</Key><Value>Router</Value></String>
+          <String><Key>UserName</Key><Value/></String>
+        </Entry>
+      </Group>
+    </Group>
+  </Root>
+</KeePassFile>"#;
+
+        assert_eq!(
+            KeePassImporter::entries(content).unwrap(),
+            vec![
+                CatalogEntry {
+                    service_id: "example.com".to_owned(),
+                    username: Some("jane".to_owned()),
+                    url: Some("https://www.example.com/login".to_owned()),
+                    ..Default::default()
+                },
+                CatalogEntry::new("Router"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,594 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! JSON-RPC 2.0 over newline-delimited stdin/stdout, for editors and other
+//! long-lived programs embedding depasswd as a subprocess, or over a Unix
+//! socket for the clients of `depasswd agent`.
+//!
+//! Methods:
+//! - `derive`: `{service_id, generation?, char_sets?, password_length, user_id?, master_password?}`.
+//!   Passing `user_id` and `master_password` (re)unlocks the session first,
+//!   with the KDF parameters and scheme of the server (`scheme?` may only
+//!   repeat the latter).
+//! - `batch`: `{requests: [derive params...], user_id?, master_password?}`.
+//! - `lock`: forgets the unlocked master secret.
+//! - `status`: reports whether the session is unlocked and when it expires.
+
+use std::{
+    io::{BufRead, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use zeroize::Zeroize;
+
+use crate::{
+    DerivePassError,
+    audit::AuditSink,
+    batch::DerivationRequest,
+    kdf::KdfParams,
+    keyfile::Keyfile,
+    pepper::Pepper,
+    scheme::SchemeVersion,
+    session::Session,
+    user_input::{MasterPasswordPlain, UserID, UserInputError},
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const DERIVATION_ERROR: i64 = -32000;
+const SESSION_LOCKED: i64 = -32001;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Credentials {
+    user_id: Option<String>,
+    master_password: Option<String>,
+    scheme: Option<SchemeVersion>,
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        self.master_password.zeroize();
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchParams {
+    requests: Vec<DerivationRequest>,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+pub struct JsonRpcServer {
+    session: Arc<Mutex<Option<Session>>>,
+    audit: Option<(Arc<dyn AuditSink>, String)>,
+    pepper: Option<Pepper>,
+    keyfile: Option<Keyfile>,
+    kdf_params: KdfParams,
+    scheme: Option<SchemeVersion>,
+    ttl: Option<Duration>,
+    expires_at: Mutex<Option<Instant>>,
+}
+
+impl JsonRpcServer {
+    pub fn new() -> Self {
+        Self {
+            session: Arc::new(Mutex::new(None)),
+            audit: None,
+            pepper: None,
+            keyfile: None,
+            kdf_params: KdfParams::DEFAULT,
+            scheme: None,
+            ttl: None,
+            expires_at: Mutex::new(None),
+        }
+    }
+    /// Starts with `session` unlocked, so clients do not need the master password.
+    pub fn with_session(self, session: Session) -> Self {
+        if let Ok(mut current) = self.session.lock() {
+            self.unlocked(&session);
+            *current = Some(session);
+        }
+        self
+    }
+    /// Mixes `pepper` into the sessions unlocked by the clients, it has to
+    /// match the one of `with_session`.
+    pub fn with_pepper(mut self, pepper: Pepper) -> Self {
+        self.pepper = Some(pepper);
+        self
+    }
+    /// Stretches the master passwords of the clients together with
+    /// `keyfile`, like the one of `with_session`.
+    pub fn with_keyfile(mut self, keyfile: Keyfile) -> Self {
+        self.keyfile = Some(keyfile);
+        self
+    }
+    /// Stretches the master passwords of the clients with `kdf_params`, like
+    /// the one of `with_session`.
+    pub fn with_kdf_params(mut self, kdf_params: KdfParams) -> Self {
+        self.kdf_params = kdf_params;
+        self
+    }
+    /// Unlocks the sessions of the clients with `scheme`, like the one of
+    /// `with_session`. Clients asking for another scheme are refused, without
+    /// it they choose (v1 by default).
+    pub fn with_scheme(mut self, scheme: SchemeVersion) -> Self {
+        self.scheme = Some(scheme);
+        self
+    }
+    /// Forgets the unlocked session `ttl` after it was unlocked.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        if self.session.lock().is_ok_and(|s| s.is_some()) {
+            *self.expires_at.get_mut().unwrap_or_else(|e| e.into_inner()) =
+                Some(Instant::now() + ttl);
+        }
+        self
+    }
+    /// Reports the derivations of every session unlocked by this server.
+    pub fn with_audit(mut self, sink: Arc<dyn AuditSink>, client: &str) -> Self {
+        self.audit = Some((sink, client.to_owned()));
+        self
+    }
+    /// The unlocked session, so it can be registered with the panic guard.
+    pub fn session(&self) -> &Arc<Mutex<Option<Session>>> {
+        &self.session
+    }
+    /// Locks the session if its TTL passed, returns whether it is locked.
+    pub fn expire(&self) -> bool {
+        let Ok(mut session) = self.session.lock() else {
+            return true;
+        };
+        let mut expires_at = self.expires_at.lock().unwrap_or_else(|e| e.into_inner());
+        if expires_at.is_some_and(|t| t <= Instant::now()) {
+            *expires_at = None;
+            if let Some(mut s) = session.take() {
+                s.zeroize();
+            }
+        }
+        session.is_none()
+    }
+    /// Listens on a Unix socket readable only by the current user, with one
+    /// thread per connection. Expired sessions are locked even while no
+    /// request arrives.
+    #[cfg(unix)]
+    pub fn serve_unix(&self, socket: &std::path::Path) -> Result<()> {
+        self.serve_listener(crate::utils::Utils::bind_private(socket)?)
+    }
+    /// Serves on an already listening socket, e.g. one passed by systemd.
+    #[cfg(unix)]
+    pub fn serve_listener(&self, listener: std::os::unix::net::UnixListener) -> Result<()> {
+        use std::io::BufReader;
+
+        std::thread::scope(|scope| {
+            self.spawn_reaper(scope);
+            for stream in listener.incoming() {
+                let stream = stream?;
+                scope.spawn(move || -> Result<()> {
+                    self.serve(BufReader::new(stream.try_clone()?), stream)
+                });
+            }
+            Ok(())
+        })
+    }
+    /// Listens on a named pipe accessible only by the current user, the
+    /// Windows counterpart of `serve_unix`.
+    #[cfg(windows)]
+    pub fn serve_pipe(&self, path: &str) -> Result<()> {
+        use std::io::BufReader;
+
+        use crate::platform::windows::NamedPipe;
+
+        std::thread::scope(|scope| {
+            self.spawn_reaper(scope);
+            NamedPipe::serve(path, |pipe| {
+                self.serve(BufReader::new(pipe.try_clone()?), pipe)
+            })
+        })
+    }
+    /// Locks the session once its TTL passed, even while no request arrives.
+    #[cfg(any(unix, windows))]
+    fn spawn_reaper<'scope, 'env>(&'env self, scope: &'scope std::thread::Scope<'scope, 'env>) {
+        if let Some(ttl) = self.ttl {
+            scope.spawn(move || {
+                loop {
+                    std::thread::sleep(ttl.min(Duration::from_secs(1)));
+                    self.expire();
+                }
+            });
+        }
+    }
+    pub fn serve(&self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+    /// Handles one message (a request or a batch array) and returns the
+    /// serialized response, or `None` when only notifications were sent.
+    pub fn handle(&self, message: &str) -> Option<String> {
+        self.expire();
+        let value: Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => {
+                return Some(Self::error(Value::Null, RpcError::new(PARSE_ERROR, e)).to_string());
+            }
+        };
+        match value {
+            Value::Array(requests) if requests.is_empty() => Some(
+                Self::error(Value::Null, RpcError::new(INVALID_REQUEST, "Empty batch")).to_string(),
+            ),
+            Value::Array(requests) => {
+                let responses: Vec<Value> = requests
+                    .into_iter()
+                    .filter_map(|r| self.handle_value(r))
+                    .collect();
+                (!responses.is_empty()).then(|| Value::Array(responses).to_string())
+            }
+            value => self.handle_value(value).map(|r| r.to_string()),
+        }
+    }
+    fn handle_value(&self, value: Value) -> Option<Value> {
+        let request: Request = match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => return Some(Self::error(Value::Null, RpcError::new(INVALID_REQUEST, e))),
+        };
+        if request.jsonrpc != "2.0" {
+            return Some(Self::error(
+                request.id.unwrap_or(Value::Null),
+                RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"),
+            ));
+        }
+        let result = self.dispatch(&request.method, request.params);
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+            Err(e) => Self::error(id, e),
+        })
+    }
+    fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "derive" => {
+                self.unlock_if_requested(&params)?;
+                let params: DerivationRequest = Self::params(params)?;
+                self.derive(&params)
+            }
+            "batch" => {
+                self.unlock_if_requested(&params)?;
+                let params: BatchParams = Self::params(params)?;
+                params
+                    .requests
+                    .iter()
+                    .map(|r| self.derive(r))
+                    .collect::<Result<Vec<Value>, RpcError>>()
+                    .map(Value::Array)
+            }
+            "lock" => {
+                let mut session = self.lock_session()?;
+                if let Some(mut s) = session.take() {
+                    s.zeroize();
+                }
+                *self.expires_at.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                Ok(json!({"locked": true}))
+            }
+            "status" => {
+                let session = self.lock_session()?;
+                Ok(json!({
+                    "locked": session.is_none(),
+                    "user_id": session.as_ref().map(|s| s.user_id().to_string()),
+                    "expires_in": self
+                        .expires_at
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .map(|t| t.saturating_duration_since(Instant::now()).as_secs()),
+                    "version": env!("CARGO_PKG_VERSION"),
+                }))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            )),
+        }
+    }
+    fn unlock_if_requested(&self, params: &Value) -> Result<(), RpcError> {
+        let credentials: Credentials = Self::params(params.clone())?;
+        match (&credentials.user_id, &credentials.master_password) {
+            (Some(user_id), Some(master_password)) => {
+                let user_id =
+                    UserID::from_str(user_id).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+                let master_password_plain = MasterPasswordPlain::from_str(master_password)
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+                let scheme = match (self.scheme, credentials.scheme) {
+                    (Some(scheme), Some(requested)) if scheme != requested => {
+                        return Err(RpcError::new(
+                            INVALID_PARAMS,
+                            format!(
+                                "This server derives with scheme {}, not {}",
+                                scheme, requested
+                            ),
+                        ));
+                    }
+                    (Some(scheme), _) | (None, Some(scheme)) => scheme,
+                    (None, None) => SchemeVersion::default(),
+                };
+                let mut unlocked = Session::unlock_with_keyfile(
+                    &user_id,
+                    &master_password_plain,
+                    scheme,
+                    &self.kdf_params,
+                    self.keyfile.as_ref(),
+                )
+                .map_err(|e| RpcError::new(DERIVATION_ERROR, e))?;
+                if let Some(pepper) = &self.pepper {
+                    unlocked = unlocked
+                        .with_pepper(pepper)
+                        .map_err(|e| RpcError::new(DERIVATION_ERROR, e))?;
+                }
+                if let Some((sink, client)) = &self.audit {
+                    unlocked = unlocked.with_audit(sink.clone(), client);
+                }
+                let mut session = self.lock_session()?;
+                self.unlocked(&unlocked);
+                if let Some(mut previous) = session.replace(unlocked) {
+                    previous.zeroize();
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+            _ => Err(RpcError::new(
+                INVALID_PARAMS,
+                "user_id and master_password must be given together",
+            )),
+        }
+    }
+    fn derive(&self, request: &DerivationRequest) -> Result<Value, RpcError> {
+        let session = self.lock_session()?;
+        let session = session
+            .as_ref()
+            .ok_or(RpcError::new(SESSION_LOCKED, "Session is locked"))?;
+        let derived_pass = request.derive(session).map_err(|e| {
+            if e.is::<UserInputError>()
+                || e.downcast_ref::<DerivePassError>()
+                    .is_some_and(DerivePassError::is_invalid_request)
+            {
+                RpcError::new(INVALID_PARAMS, e)
+            } else {
+                RpcError::new(DERIVATION_ERROR, e)
+            }
+        })?;
+        Ok(json!({
+            "service_id": request.service_id,
+            "generation": request.generation,
+            "password_length": request.password_length,
+            "password": derived_pass.expose_secret(),
+        }))
+    }
+    /// Called with the session lock held, before `session` is stored.
+    fn unlocked(&self, session: &Session) {
+        session.lock_in_memory();
+        *self.expires_at.lock().unwrap_or_else(|e| e.into_inner()) =
+            self.ttl.map(|ttl| Instant::now() + ttl);
+    }
+    fn lock_session(&self) -> Result<std::sync::MutexGuard<'_, Option<Session>>, RpcError> {
+        self.session
+            .lock()
+            .map_err(|_| RpcError::new(DERIVATION_ERROR, "Session state is unavailable"))
+    }
+    fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+        serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+    }
+    fn error(id: Value, error: RpcError) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "error": {"code": error.code, "message": error.message},
+            "id": id,
+        })
+    }
+}
+
+impl Default for JsonRpcServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(server: &JsonRpcServer, request: &str) -> Value {
+        serde_json::from_str(&server.handle(request).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn can_report_status_and_errors() {
+        let server = JsonRpcServer::new();
+
+        assert_eq!(
+            call(&server, r#"{"jsonrpc":"2.0","method":"status","id":1}"#)["result"]["locked"],
+            true
+        );
+        assert_eq!(
+            call(&server, r#"{"jsonrpc":"2.0","method":"nope","id":2}"#)["error"]["code"],
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(call(&server, "{not json")["error"]["code"], PARSE_ERROR);
+        assert_eq!(
+            call(
+                &server,
+                r#"{"jsonrpc":"2.0","method":"derive","params":{"service_id":"x","password_length":8},"id":3}"#
+            )["error"]["code"],
+            SESSION_LOCKED
+        );
+        assert_eq!(
+            server.handle(r#"{"jsonrpc":"2.0","method":"status"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn can_derive_batch_and_lock() {
+        let server = JsonRpcServer::new();
+        let response = call(
+            &server,
+            r#"{"jsonrpc":"2.0","method":"batch","id":1,"params":{
+                "user_id":"Example Eleonora","master_password":"]lE~WExZ468ty{I5mtg[",
+                "requests":[
+                    {"service_id":"Example Service Name","password_length":20},
+                    {"service_id":"Example Service Name","password_length":20,"generation":1,"char_sets":["numbers"]}
+                ]}}"#,
+        );
+
+        assert_eq!(response["result"][0]["password"], "1@MWtAAqZ0p>;;y@zZ6d");
+        assert!(
+            response["result"][1]["password"]
+                .as_str()
+                .unwrap()
+                .chars()
+                .all(|c| c.is_ascii_digit())
+        );
+        assert_eq!(
+            call(&server, r#"{"jsonrpc":"2.0","method":"status","id":2}"#)["result"]["user_id"],
+            "Example Eleonora"
+        );
+        call(&server, r#"{"jsonrpc":"2.0","method":"lock","id":3}"#);
+        assert_eq!(
+            call(&server, r#"{"jsonrpc":"2.0","method":"status","id":4}"#)["result"]["locked"],
+            true
+        );
+    }
+
+    #[test]
+    fn can_expire_session() {
+        let unlock = || {
+            Session::unlock(
+                &UserID::from_str("Example Eleonora").unwrap(),
+                &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+                SchemeVersion::V1,
+            )
+            .unwrap()
+        };
+        let derive = r#"{"jsonrpc":"2.0","method":"derive","params":{"service_id":"Example Service Name","password_length":20},"id":1}"#;
+
+        let server = JsonRpcServer::new()
+            .with_session(unlock())
+            .with_ttl(Duration::from_secs(3600));
+        assert_eq!(
+            call(&server, derive)["result"]["password"],
+            "1@MWtAAqZ0p>;;y@zZ6d"
+        );
+        assert!(!server.expire());
+        assert!(
+            call(&server, r#"{"jsonrpc":"2.0","method":"status","id":2}"#)["result"]["expires_in"]
+                .as_u64()
+                .unwrap()
+                > 3500
+        );
+
+        let server = JsonRpcServer::new()
+            .with_session(unlock())
+            .with_ttl(Duration::ZERO);
+        assert_eq!(call(&server, derive)["error"]["code"], SESSION_LOCKED);
+        assert!(server.expire());
+    }
+
+    #[test]
+    fn can_unlock_again_after_expiry() {
+        let kdf_params = KdfParams::from_str("argon2id-m8192-t2-p1").unwrap();
+        let session = Session::unlock_with_kdf_params(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V2,
+            &kdf_params,
+        )
+        .unwrap();
+        let server = JsonRpcServer::new()
+            .with_session(session)
+            .with_kdf_params(kdf_params)
+            .with_scheme(SchemeVersion::V2)
+            .with_ttl(Duration::ZERO);
+        let unlocked_password = {
+            let session = server.session().lock().unwrap();
+            DerivationRequest {
+                key: None,
+                service_id: "Example Service Name".to_owned(),
+                generation: 1,
+                char_sets: vec!["small".to_owned(), "numbers".to_owned()],
+                password_length: 20,
+            }
+            .derive(session.as_ref().unwrap())
+            .unwrap()
+            .expose_secret()
+            .to_owned()
+        };
+        assert!(server.expire());
+
+        let derive = |scheme: &str| {
+            call(
+                &server,
+                &format!(
+                    r#"{{"jsonrpc":"2.0","method":"derive","id":1,"params":{{
+                        "user_id":"Example Eleonora","master_password":"]lE~WExZ468ty{{I5mtg[",{}
+                        "service_id":"Example Service Name","password_length":20,"char_sets":["small","numbers"]}}}}"#,
+                    scheme
+                ),
+            )
+        };
+        assert_eq!(derive("")["result"]["password"], unlocked_password);
+        assert_eq!(
+            derive(r#""scheme":2,"#)["result"]["password"],
+            unlocked_password
+        );
+        assert_eq!(derive(r#""scheme":1,"#)["error"]["code"], INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,491 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The password hash that stretches the master password into the master
+//! secret, and its cost parameters. Other parameters derive other passwords,
+//! so they have to be kept (in a profile) just like the user id.
+
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+
+use crate::{
+    core::{ARGON2_M_COST, ARGON2_P_COST, ARGON2_T_COST},
+    user_input::UserInputError,
+};
+
+/// Calibration starts at 8 MiB and stops at 4 GiB of memory and 16 iterations.
+const CALIBRATE_MIN_MEMORY: u32 = 8 * 1024;
+const CALIBRATE_MAX_MEMORY: u32 = 4 * 1024 * 1024;
+const CALIBRATE_MAX_ITERATIONS: u32 = 16;
+/// PBKDF2 calibration starts at 10 000 and stops at about 671 million rounds.
+const CALIBRATE_MIN_ROUNDS: u32 = 10_000;
+const CALIBRATE_MAX_ROUNDS: u32 = 10_000 << 16;
+
+/// A password hash usable for the master secret.
+pub trait MasterKdf {
+    /// Fills `output` with the hash of `password` salted with `salt`.
+    fn derive(&self, password: &[u8], salt: &[u8], output: &mut [u8]) -> Result<()>;
+}
+
+pub struct Argon2idKdf {
+    params: Params,
+}
+
+impl MasterKdf for Argon2idKdf {
+    fn derive(&self, password: &[u8], salt: &[u8], output: &mut [u8]) -> Result<()> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+            .hash_password_into(password, salt, output)
+            .map_err(|e| anyhow!("Argon2 failed: {}", e))
+    }
+}
+
+pub struct ScryptKdf {
+    params: scrypt::Params,
+}
+
+impl MasterKdf for ScryptKdf {
+    fn derive(&self, password: &[u8], salt: &[u8], output: &mut [u8]) -> Result<()> {
+        scrypt::scrypt(password, salt, &self.params, output)
+            .map_err(|e| anyhow!("scrypt failed: {}", e))
+    }
+}
+
+pub struct Pbkdf2Sha512Kdf {
+    rounds: u32,
+}
+
+impl MasterKdf for Pbkdf2Sha512Kdf {
+    fn derive(&self, password: &[u8], salt: &[u8], output: &mut [u8]) -> Result<()> {
+        pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, self.rounds, output);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KdfAlgorithm {
+    #[default]
+    Argon2id,
+    /// For memory-constrained platforms such as WASM: unlike Argon2id, its
+    /// memory can be lowered without giving up all of its hardness.
+    Scrypt,
+    /// For environments that only allow FIPS approved algorithms.
+    Pbkdf2Sha512,
+}
+
+impl KdfAlgorithm {
+    pub const ALL: [KdfAlgorithm; 3] = [
+        KdfAlgorithm::Argon2id,
+        KdfAlgorithm::Scrypt,
+        KdfAlgorithm::Pbkdf2Sha512,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KdfAlgorithm::Argon2id => "argon2id",
+            KdfAlgorithm::Scrypt => "scrypt",
+            KdfAlgorithm::Pbkdf2Sha512 => "pbkdf2-sha512",
+        }
+    }
+}
+
+impl FromStr for KdfAlgorithm {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| {
+                UserInputError::new(format!(
+                    "Unknown KDF: {} (expected argon2id, scrypt or pbkdf2-sha512)",
+                    s
+                ))
+            })
+    }
+}
+
+impl Display for KdfAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The meaning of the costs depends on the algorithm:
+///
+/// - argon2id: memory in KiB, iterations and lanes;
+/// - scrypt: memory in KiB (N, a power of two, with r = 8), always 1 and p;
+/// - pbkdf2-sha512: always 0, rounds and always 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KdfParams {
+    #[serde(default)]
+    pub algorithm: KdfAlgorithm,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl KdfParams {
+    /// The parameters every scheme used before they became configurable.
+    pub const DEFAULT: KdfParams = KdfParams {
+        algorithm: KdfAlgorithm::Argon2id,
+        m_cost: ARGON2_M_COST,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+    };
+
+    pub fn new(algorithm: KdfAlgorithm, m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Self> {
+        let params = Self {
+            algorithm,
+            m_cost,
+            t_cost,
+            p_cost,
+        };
+        params.kdf()?;
+        Ok(params)
+    }
+    /// Recommended starting point for `algorithm`.
+    pub fn default_for(algorithm: KdfAlgorithm) -> Self {
+        match algorithm {
+            KdfAlgorithm::Argon2id => Self::DEFAULT,
+            KdfAlgorithm::Scrypt => Self {
+                algorithm,
+                m_cost: 32 * 1024,
+                t_cost: 1,
+                p_cost: 1,
+            },
+            KdfAlgorithm::Pbkdf2Sha512 => Self {
+                algorithm,
+                m_cost: 0,
+                t_cost: 210_000,
+                p_cost: 1,
+            },
+        }
+    }
+    /// Validates the parameters and returns the hash they describe.
+    pub fn kdf(&self) -> Result<Box<dyn MasterKdf>> {
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => Ok(Box::new(Argon2idKdf {
+                params: Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+                    .map_err(|e| anyhow!("Invalid Argon2 parameters: {}", e))?,
+            })),
+            KdfAlgorithm::Scrypt => {
+                if !self.m_cost.is_power_of_two() || self.m_cost < 2 || self.t_cost != 1 {
+                    bail!("Invalid scrypt parameters: m must be a power of two and t must be 1");
+                }
+                let log_n = u8::try_from(self.m_cost.trailing_zeros())?;
+                Ok(Box::new(ScryptKdf {
+                    params: scrypt::Params::new(log_n, 8, self.p_cost, 32)
+                        .map_err(|e| anyhow!("Invalid scrypt parameters: {}", e))?,
+                }))
+            }
+            KdfAlgorithm::Pbkdf2Sha512 => {
+                if self.m_cost != 0 || self.t_cost == 0 || self.p_cost != 1 {
+                    bail!("Invalid PBKDF2 parameters: m must be 0, t at least 1 and p 1");
+                }
+                Ok(Box::new(Pbkdf2Sha512Kdf {
+                    rounds: self.t_cost,
+                }))
+            }
+        }
+    }
+    pub fn is_default(&self) -> bool {
+        *self == Self::DEFAULT
+    }
+    /// Short form, e.g. `argon2id-m32768-t4-p4`, accepted back by `FromStr`.
+    /// It salts the master secret (`core::master_salt_with_kdf`), so the
+    /// algorithm tag and costs keep every parameter set apart; changing the
+    /// format changes the passwords.
+    pub fn id(&self) -> String {
+        format!(
+            "{}-m{}-t{}-p{}",
+            self.algorithm, self.m_cost, self.t_cost, self.p_cost
+        )
+    }
+    /// How to compute the hash without depasswd, for the recovery sheet.
+    pub fn recipe(&self) -> String {
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => format!(
+                "argon2id (version 0x13, m={} KiB, t={}, p={}, 32-byte output)",
+                self.m_cost, self.t_cost, self.p_cost
+            ),
+            KdfAlgorithm::Scrypt => format!(
+                "scrypt (N={}, r=8, p={}, 32-byte output)",
+                self.m_cost, self.p_cost
+            ),
+            KdfAlgorithm::Pbkdf2Sha512 => format!(
+                "PBKDF2-HMAC-SHA512 ({} rounds, 32-byte output)",
+                self.t_cost
+            ),
+        }
+    }
+    /// Hashes a fixed input once with these parameters and returns how long it took.
+    pub fn measure(&self) -> Result<Duration> {
+        let kdf = self.kdf()?;
+        let mut output = [0u8; 32];
+        let start = Instant::now();
+        kdf.derive(b"depasswd-calibrate", b"depasswd-calibrate", &mut output)?;
+        Ok(start.elapsed())
+    }
+    /// Finds the strongest parameters of `algorithm` with `p_cost` lanes
+    /// whose `measure`d time stays within `target`: memory is doubled first,
+    /// as it costs an attacker the most, then iterations are added (PBKDF2
+    /// only has rounds, which are doubled). Every measurement is passed to
+    /// `progress`. If even the smallest setting is too slow, that one is
+    /// returned.
+    pub fn calibrate(
+        algorithm: KdfAlgorithm,
+        target: Duration,
+        p_cost: u32,
+        mut measure: impl FnMut(&KdfParams) -> Result<Duration>,
+        mut progress: impl FnMut(&KdfParams, Duration),
+    ) -> Result<(KdfParams, Duration)> {
+        let mut measure = |params: KdfParams| -> Result<(KdfParams, Duration)> {
+            let elapsed = measure(&params)?;
+            progress(&params, elapsed);
+            Ok((params, elapsed))
+        };
+        let first = match algorithm {
+            KdfAlgorithm::Argon2id => {
+                Self::new(algorithm, CALIBRATE_MIN_MEMORY.max(8 * p_cost), 1, p_cost)?
+            }
+            KdfAlgorithm::Scrypt => Self::new(algorithm, CALIBRATE_MIN_MEMORY, 1, p_cost)?,
+            KdfAlgorithm::Pbkdf2Sha512 => Self::new(algorithm, 0, CALIBRATE_MIN_ROUNDS, 1)?,
+        };
+        let mut best = measure(first)?;
+        if best.1 > target {
+            return Ok(best);
+        }
+        let mut growing_memory = true;
+        loop {
+            let candidate = match best.0.stronger(growing_memory) {
+                Some(params) => Some(measure(params)?),
+                None => None,
+            };
+            match candidate {
+                Some(candidate) if candidate.1 <= target => best = candidate,
+                _ if growing_memory => growing_memory = false,
+                _ => return Ok(best),
+            }
+        }
+    }
+    /// The next calibration step: doubled memory (iterations reset to 1), or
+    /// one more iteration. `None` past the limits or for steps the
+    /// algorithm does not have.
+    fn stronger(&self, memory: bool) -> Option<Self> {
+        let (m_cost, t_cost) = match (self.algorithm, memory) {
+            (KdfAlgorithm::Argon2id | KdfAlgorithm::Scrypt, true)
+                if self.m_cost < CALIBRATE_MAX_MEMORY =>
+            {
+                (self.m_cost * 2, 1)
+            }
+            (KdfAlgorithm::Argon2id, false) if self.t_cost < CALIBRATE_MAX_ITERATIONS => {
+                (self.m_cost, self.t_cost + 1)
+            }
+            (KdfAlgorithm::Pbkdf2Sha512, true) if self.t_cost < CALIBRATE_MAX_ROUNDS => {
+                (0, self.t_cost * 2)
+            }
+            _ => return None,
+        };
+        Self::new(self.algorithm, m_cost, t_cost, self.p_cost).ok()
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => write!(
+                f,
+                "argon2id m={} KiB, t={}, p={}",
+                self.m_cost, self.t_cost, self.p_cost
+            ),
+            KdfAlgorithm::Scrypt => write!(f, "scrypt m={} KiB, p={}", self.m_cost, self.p_cost),
+            KdfAlgorithm::Pbkdf2Sha512 => write!(f, "pbkdf2-sha512 t={}", self.t_cost),
+        }
+    }
+}
+
+impl FromStr for KdfParams {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            UserInputError::new(format!(
+                "Invalid KDF parameters: {} (expected e.g. {})",
+                s,
+                Self::DEFAULT.id()
+            ))
+        };
+        let cost = |part: Option<&str>, prefix: char| {
+            part.and_then(|p| p.strip_prefix(prefix))
+                .and_then(|p| p.parse::<u32>().ok())
+                .ok_or_else(invalid)
+        };
+        // The algorithm name itself may contain dashes, so split from the end
+        let mut parts = s.rsplitn(4, '-');
+        let p_cost = cost(parts.next(), 'p')?;
+        let t_cost = cost(parts.next(), 't')?;
+        let m_cost = cost(parts.next(), 'm')?;
+        let algorithm = KdfAlgorithm::from_str(parts.next().ok_or_else(invalid)?)?;
+        Self::new(algorithm, m_cost, t_cost, p_cost).map_err(|e| UserInputError::new(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_validate_kdf_params() {
+        assert_eq!(
+            KdfParams::new(KdfAlgorithm::Argon2id, 32 * 1024, 4, 4).unwrap(),
+            KdfParams::DEFAULT
+        );
+        assert!(KdfParams::default().is_default());
+        assert!(
+            !KdfParams::new(KdfAlgorithm::Argon2id, 64 * 1024, 3, 1)
+                .unwrap()
+                .is_default()
+        );
+        assert!(KdfParams::new(KdfAlgorithm::Argon2id, 32 * 1024, 0, 4).is_err());
+        assert!(KdfParams::new(KdfAlgorithm::Argon2id, 8, 4, 4).is_err());
+        assert!(KdfParams::new(KdfAlgorithm::Argon2id, 32 * 1024, 4, 0).is_err());
+        for algorithm in KdfAlgorithm::ALL {
+            assert!(KdfParams::default_for(algorithm).kdf().is_ok());
+        }
+        assert!(KdfParams::new(KdfAlgorithm::Scrypt, 3000, 1, 1).is_err());
+        assert!(KdfParams::new(KdfAlgorithm::Scrypt, 4096, 2, 1).is_err());
+        assert!(KdfParams::new(KdfAlgorithm::Pbkdf2Sha512, 0, 0, 1).is_err());
+        assert!(KdfParams::new(KdfAlgorithm::Pbkdf2Sha512, 1024, 1000, 1).is_err());
+    }
+
+    #[test]
+    fn can_derive_with_every_kdf() {
+        let mut outputs = vec![];
+        for params in [
+            KdfParams::new(KdfAlgorithm::Argon2id, 8 * 1024, 1, 1).unwrap(),
+            KdfParams::new(KdfAlgorithm::Scrypt, 1024, 1, 1).unwrap(),
+            KdfParams::new(KdfAlgorithm::Pbkdf2Sha512, 0, 1000, 1).unwrap(),
+        ] {
+            let mut output = [0u8; 32];
+            params
+                .kdf()
+                .unwrap()
+                .derive(b"password", b"saltsalt", &mut output)
+                .unwrap();
+            outputs.push(output);
+        }
+        assert_ne!(outputs[0], outputs[1]);
+        assert_ne!(outputs[1], outputs[2]);
+        // RFC 7914, section 12 (first 32 bytes of the 64-byte output)
+        let mut output = [0u8; 32];
+        KdfParams::new(KdfAlgorithm::Scrypt, 1024, 1, 16)
+            .unwrap()
+            .kdf()
+            .unwrap()
+            .derive(b"password", b"NaCl", &mut output)
+            .unwrap();
+        assert_eq!(
+            crate::utils::Utils::bytes_to_hex(&output),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162"
+        );
+    }
+
+    #[test]
+    fn can_parse_kdf_params_id() {
+        assert_eq!(KdfParams::DEFAULT.id(), "argon2id-m32768-t4-p4");
+        for algorithm in KdfAlgorithm::ALL {
+            let params = KdfParams::default_for(algorithm);
+            assert_eq!(KdfParams::from_str(&params.id()).unwrap(), params);
+        }
+        assert_eq!(
+            KdfParams::from_str("pbkdf2-sha512-m0-t210000-p1").unwrap(),
+            KdfParams::default_for(KdfAlgorithm::Pbkdf2Sha512)
+        );
+        assert!(KdfParams::from_str("argon2id-m32768-t4").is_err());
+        assert!(KdfParams::from_str("argon2id-m32768-t4-p4-x").is_err());
+        assert!(KdfParams::from_str("argon2i-m32768-t4-p4").is_err());
+        assert!(KdfParams::from_str("argon2id-m8-t4-p4").is_err());
+    }
+
+    #[test]
+    fn can_calibrate_kdf_params() {
+        // Pretends every KiB of every iteration takes a microsecond.
+        let cost = |p: &KdfParams| Ok(Duration::from_micros(u64::from(p.m_cost * p.t_cost)));
+        let argon2id = KdfAlgorithm::Argon2id;
+        let mut measured = 0;
+        let (params, elapsed) =
+            KdfParams::calibrate(argon2id, Duration::from_millis(500), 4, cost, |_, _| {
+                measured += 1
+            })
+            .unwrap();
+        assert_eq!(params, KdfParams::new(argon2id, 256 * 1024, 1, 4).unwrap());
+        assert_eq!(elapsed, Duration::from_micros(256 * 1024));
+        assert_eq!(measured, 8);
+
+        let (params, _) =
+            KdfParams::calibrate(argon2id, Duration::from_millis(100), 1, cost, |_, _| {}).unwrap();
+        assert_eq!(params, KdfParams::new(argon2id, 64 * 1024, 1, 1).unwrap());
+
+        let (params, _) =
+            KdfParams::calibrate(argon2id, Duration::from_millis(1), 4, cost, |_, _| {}).unwrap();
+        assert_eq!(params, KdfParams::new(argon2id, 8 * 1024, 1, 4).unwrap());
+
+        let (params, _) = KdfParams::calibrate(
+            KdfAlgorithm::Scrypt,
+            Duration::from_millis(100),
+            1,
+            cost,
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(
+            params,
+            KdfParams::new(KdfAlgorithm::Scrypt, 64 * 1024, 1, 1).unwrap()
+        );
+
+        // A round counts as a KiB here
+        let rounds = |p: &KdfParams| Ok(Duration::from_micros(u64::from(p.t_cost)));
+        let (params, _) = KdfParams::calibrate(
+            KdfAlgorithm::Pbkdf2Sha512,
+            Duration::from_millis(100),
+            1,
+            rounds,
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(
+            params,
+            KdfParams::new(KdfAlgorithm::Pbkdf2Sha512, 0, 80_000, 1).unwrap()
+        );
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Raw key material for other software: symmetric keys, API tokens and
+//! seeds, derived per service and printed in the encoding it expects.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Result;
+use base64::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{
+    derivation_path::Purpose,
+    scheme::SchemeVersion,
+    service_secret::ServiceSecret,
+    user_input::{PasswordLength, UserInputError},
+    utils::Utils,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyEncoding {
+    #[default]
+    Hex,
+    Base64,
+    Base32,
+    Base58,
+}
+
+impl KeyEncoding {
+    pub const ALL: [KeyEncoding; 4] = [
+        KeyEncoding::Hex,
+        KeyEncoding::Base64,
+        KeyEncoding::Base32,
+        KeyEncoding::Base58,
+    ];
+}
+
+impl FromStr for KeyEncoding {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|encoding| encoding.to_string() == s)
+            .ok_or(UserInputError::new(format!(
+                "Unknown key encoding: {} (expected one of: {})",
+                s,
+                Self::ALL.map(|encoding| encoding.to_string()).join(", ")
+            )))
+    }
+}
+
+impl Display for KeyEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyEncoding::Hex => write!(f, "hex"),
+            KeyEncoding::Base64 => write!(f, "base64"),
+            KeyEncoding::Base32 => write!(f, "base32"),
+            KeyEncoding::Base58 => write!(f, "base58"),
+        }
+    }
+}
+
+pub struct KeyMaterial {
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl KeyMaterial {
+    /// Takes `length` bytes of the expansion of the service secret under the
+    /// label of the key purpose.
+    pub fn new(
+        service_secret: &ServiceSecret,
+        length: &PasswordLength,
+        scheme: SchemeVersion,
+    ) -> Result<Self> {
+        Self::with_purpose(service_secret, length, &Purpose::key(), scheme)
+    }
+    /// Takes `length` bytes of the expansion of the service secret under the
+    /// label of `purpose`, e.g. `Purpose::rng_seed` for the seeds of other
+    /// deterministic generators.
+    pub fn with_purpose(
+        service_secret: &ServiceSecret,
+        length: &PasswordLength,
+        purpose: &Purpose,
+        scheme: SchemeVersion,
+    ) -> Result<Self> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for key material",
+            )
+            .into());
+        }
+        Ok(Self {
+            key: Zeroizing::new(service_secret.expand(&purpose.label(), length.as_usize())?),
+        })
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+    pub fn encode(&self, encoding: KeyEncoding) -> Zeroizing<String> {
+        Zeroizing::new(match encoding {
+            KeyEncoding::Hex => Utils::bytes_to_hex(&self.key),
+            KeyEncoding::Base64 => BASE64_STANDARD.encode(&*self.key),
+            KeyEncoding::Base32 => Utils::bytes_to_base32(&self.key),
+            KeyEncoding::Base58 => Utils::bytes_to_base58(&self.key),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_derive_key_material() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let derive = |length: &str, scheme| {
+            KeyMaterial::new(
+                &service_secret,
+                &PasswordLength::from_str(length).unwrap(),
+                scheme,
+            )
+        };
+        let key = derive("32", SchemeVersion::V2).unwrap();
+        assert_eq!(key.as_bytes().len(), 32);
+        assert_eq!(key.encode(KeyEncoding::Hex).len(), 64);
+        assert_eq!(key.encode(KeyEncoding::Base64).len(), 44);
+        assert_eq!(
+            BASE64_STANDARD
+                .decode(key.encode(KeyEncoding::Base64).as_bytes())
+                .unwrap(),
+            key.as_bytes()
+        );
+        assert_eq!(key.encode(KeyEncoding::Base32).len(), 52);
+        assert_eq!(
+            derive("200", SchemeVersion::V3).unwrap().as_bytes().len(),
+            200
+        );
+        assert!(derive("32", SchemeVersion::V1).is_err());
+        assert_ne!(
+            KeyMaterial::with_purpose(
+                &service_secret,
+                &PasswordLength::from_str("32").unwrap(),
+                &Purpose::rng_seed(),
+                SchemeVersion::V2
+            )
+            .unwrap()
+            .as_bytes(),
+            key.as_bytes()
+        );
+        assert_eq!(
+            KeyEncoding::from_str("base58").unwrap(),
+            KeyEncoding::Base58
+        );
+        assert!(KeyEncoding::from_str("base85").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Caches unlocked master secrets in the keychain of the operating system
+//! through the `keyring` crate, so commands can skip the master password
+//! prompt for a configured lifetime: the macOS Keychain, the Windows
+//! Credential Manager, or the Secret Service of the desktop session (GNOME
+//! Keyring, KWallet) over an encrypted session elsewhere.
+//!
+//! The platform modules add what `keyring` does not: a `Protection`
+//! encrypting the secrets before they are stored, with DPAPI and optionally
+//! Windows Hello on Windows (`platform::windows`) or the TPM2 on Linux with
+//! `keychain_tpm2_pcrs` (`platform::linux`), and Touch ID on macOS
+//! (`platform::macos`), an access control of the item `keyring` can not set.
+//!
+//! A cached secret older than its lifetime is deleted instead of used.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow, bail};
+use keyring::Entry;
+use zeroize::Zeroizing;
+
+use crate::{
+    config::Config, core::MASTER_SECRET_LENGTH, kdf::KdfParams, master_secret::MasterSecret,
+    pepper::Pepper, scheme::SchemeVersion, session::Session, user_input::UserID,
+};
+
+/// The service of the keyring entries.
+pub(crate) const SERVICE: &str = "depasswd";
+/// The entry listing the accounts of the others, the keyring can not be
+/// searched.
+const INDEX_ACCOUNT: &str = "accounts";
+
+pub trait Keychain {
+    /// The cached session of `user_id` unlocked with these parameters, when
+    /// its lifetime has not passed.
+    fn load(
+        &mut self,
+        user_id: &UserID,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+        pepper: Option<&Pepper>,
+    ) -> Result<Option<Session>>;
+    /// Caches the master secret of `session` for `lifetime`, replacing the
+    /// one cached with the same parameters.
+    fn store(
+        &mut self,
+        session: &Session,
+        pepper: Option<&Pepper>,
+        lifetime: Duration,
+    ) -> Result<()>;
+    /// Deletes every cached master secret, or only those of `user_id`.
+    /// Returns how many were deleted.
+    fn forget(&mut self, user_id: Option<&UserID>) -> Result<usize>;
+}
+
+/// Encrypts the packed master secrets before `KeyringKeychain` stores them.
+pub trait Protection {
+    fn protect(&self, packed: &[u8]) -> Result<Vec<u8>>;
+    /// Can ask the user to verify their presence first.
+    fn unprotect(&self, protected: &[u8], user_id: &UserID) -> Result<Zeroizing<Vec<u8>>>;
+}
+
+/// The keychain of this platform chosen by `config`. With
+/// `keychain_biometry` reading the secrets cached from then on needs Touch
+/// ID on macOS or Windows Hello on Windows.
+pub fn open(config: &Config) -> Result<Box<dyn Keychain>> {
+    let biometry = config.keychain_biometry == Some(true);
+    #[cfg(target_os = "linux")]
+    if let Some(pcrs) = &config.keychain_tpm2_pcrs {
+        if biometry {
+            bail!("keychain_biometry can not be combined with keychain_tpm2_pcrs");
+        }
+        return Ok(Box::new(KeyringKeychain::new(Some(Box::new(
+            crate::platform::linux::Tpm2::new(pcrs)?,
+        )))));
+    }
+    #[cfg(not(target_os = "linux"))]
+    if config.keychain_tpm2_pcrs.is_some() {
+        bail!("keychain_tpm2_pcrs is only available on Linux");
+    }
+    #[cfg(target_os = "macos")]
+    if biometry {
+        return Ok(Box::new(crate::platform::macos::BiometricKeychain));
+    }
+    #[cfg(windows)]
+    {
+        Ok(Box::new(KeyringKeychain::new(Some(Box::new(
+            crate::platform::windows::Dpapi::new(biometry),
+        )))))
+    }
+    #[cfg(not(windows))]
+    {
+        if biometry && !cfg!(target_os = "macos") {
+            bail!("keychain_biometry is only available on macOS and Windows");
+        }
+        Ok(Box::new(KeyringKeychain::new(None)))
+    }
+}
+
+/// The parameters the secret was unlocked with and, last as it may contain
+/// spaces, the user id.
+pub(crate) fn account(
+    user_id: &UserID,
+    scheme: SchemeVersion,
+    kdf_params: &KdfParams,
+    pepper: Option<&Pepper>,
+) -> String {
+    format!(
+        "{} {} {} {}",
+        scheme,
+        kdf_params.id(),
+        pepper.map(Pepper::fingerprint).unwrap_or_default(),
+        user_id
+    )
+}
+
+pub(crate) fn account_user_id(account: &str) -> Option<&str> {
+    account.splitn(4, ' ').nth(3)
+}
+
+/// The expiry (big-endian Unix seconds) followed by the master secret.
+pub(crate) fn pack(session: &Session, lifetime: Duration) -> Result<Zeroizing<Vec<u8>>> {
+    let expires_at = SystemTime::now()
+        .checked_add(lifetime)
+        .ok_or(anyhow!("The keychain cache lifetime is too long"))?
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+    let mut packed = Zeroizing::new(expires_at.to_be_bytes().to_vec());
+    packed.extend_from_slice(session.master_secret().as_bytes());
+    Ok(packed)
+}
+
+/// The session of a packed master secret, unless it expired.
+pub(crate) fn unpack(
+    cached: &[u8],
+    user_id: &UserID,
+    scheme: SchemeVersion,
+    kdf_params: &KdfParams,
+) -> Result<Option<Session>> {
+    let Some(master_secret) = unexpired(cached) else {
+        return Ok(None);
+    };
+    Ok(Some(Session::from_master_secret(
+        user_id,
+        MasterSecret::from_bytes(master_secret)?,
+        scheme,
+        kdf_params,
+    )))
+}
+
+fn unexpired(cached: &[u8]) -> Option<&[u8]> {
+    let (expires_at, master_secret) = cached.split_first_chunk::<8>()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    (u64::from_be_bytes(*expires_at) > now && master_secret.len() == MASTER_SECRET_LENGTH)
+        .then_some(master_secret)
+}
+
+/// The keychain `keyring` chooses for the platform, one entry per account.
+pub struct KeyringKeychain {
+    protection: Option<Box<dyn Protection>>,
+}
+
+impl KeyringKeychain {
+    pub fn new(protection: Option<Box<dyn Protection>>) -> Self {
+        Self { protection }
+    }
+    fn read(account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        match Entry::new(SERVICE, account)?.get_secret() {
+            Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    fn delete(account: &str) -> Result<bool> {
+        match Entry::new(SERVICE, account)?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+    fn accounts() -> Result<Vec<String>> {
+        match Self::read(INDEX_ACCOUNT)? {
+            Some(index) => Ok(serde_json::from_slice(&index)?),
+            None => Ok(vec![]),
+        }
+    }
+    fn set_accounts(accounts: &[String]) -> Result<()> {
+        if accounts.is_empty() {
+            Self::delete(INDEX_ACCOUNT)?;
+        } else {
+            Entry::new(SERVICE, INDEX_ACCOUNT)?.set_secret(&serde_json::to_vec(accounts)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Keychain for KeyringKeychain {
+    fn load(
+        &mut self,
+        user_id: &UserID,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+        pepper: Option<&Pepper>,
+    ) -> Result<Option<Session>> {
+        let account = account(user_id, scheme, kdf_params, pepper);
+        let Some(cached) = Self::read(&account)? else {
+            return Ok(None);
+        };
+        let cached = match &self.protection {
+            Some(protection) => protection.unprotect(&cached, user_id)?,
+            None => cached,
+        };
+        let session = unpack(&cached, user_id, scheme, kdf_params)?;
+        if session.is_none() {
+            Self::delete(&account)?;
+        }
+        Ok(session)
+    }
+    fn store(
+        &mut self,
+        session: &Session,
+        pepper: Option<&Pepper>,
+        lifetime: Duration,
+    ) -> Result<()> {
+        let account = account(
+            session.user_id(),
+            session.scheme(),
+            session.kdf_params(),
+            pepper,
+        );
+        let packed = pack(session, lifetime)?;
+        let cached = match &self.protection {
+            Some(protection) => Zeroizing::new(protection.protect(&packed)?),
+            None => packed,
+        };
+        Entry::new(SERVICE, &account)?.set_secret(&cached)?;
+        let mut accounts = Self::accounts()?;
+        if !accounts.contains(&account) {
+            accounts.push(account);
+            Self::set_accounts(&accounts)?;
+        }
+        Ok(())
+    }
+    fn forget(&mut self, user_id: Option<&UserID>) -> Result<usize> {
+        let user_id = user_id.map(UserID::to_string);
+        let (forget, keep): (Vec<String>, Vec<String>) =
+            Self::accounts()?.into_iter().partition(|account| {
+                user_id.is_none() || account_user_id(account) == user_id.as_deref()
+            });
+        let mut forgotten = 0;
+        for account in &forget {
+            forgotten += usize::from(Self::delete(account)?);
+        }
+        Self::set_accounts(&keep)?;
+        Ok(forgotten)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn can_unpack_cached_secret() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let cached = |expires_at: u64, length: usize| {
+            let mut cached = expires_at.to_be_bytes().to_vec();
+            cached.extend(vec![7u8; length]);
+            cached
+        };
+
+        assert_eq!(
+            unexpired(&cached(now + 60, MASTER_SECRET_LENGTH)),
+            Some(&[7u8; MASTER_SECRET_LENGTH][..])
+        );
+        assert_eq!(unexpired(&cached(now - 1, MASTER_SECRET_LENGTH)), None);
+        assert_eq!(unexpired(&cached(now + 60, 16)), None);
+        assert_eq!(unexpired(&[0; 4]), None);
+    }
+
+    #[test]
+    fn can_name_accounts() {
+        let user_id = UserID::from_str("Example Eleonora").unwrap();
+        let v1 = account(&user_id, SchemeVersion::V1, &KdfParams::default(), None);
+        assert_eq!(account_user_id(&v1), Some("Example Eleonora"));
+        assert_ne!(
+            v1,
+            account(&user_id, SchemeVersion::V2, &KdfParams::default(), None)
+        );
+        assert_eq!(account_user_id(INDEX_ACCOUNT), None);
+    }
+}
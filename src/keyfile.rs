@@ -0,0 +1,110 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A keyfile is a second secret mixed into the master password before the
+//! KDF, KeePass-style: any file, e.g. on a USB stick, is hashed with
+//! SHA-256 and the digest is stretched together with the password, so
+//! both are needed to unlock. Unlike a `Pepper` it also slows down
+//! guessing the password of a stolen keyfile.
+
+use std::{fmt::Debug, fs::File, io, path::Path};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+#[derive(Clone)]
+pub struct Keyfile {
+    digest: Zeroizing<[u8; 32]>,
+}
+
+impl Keyfile {
+    /// Hashes every byte of the file, it may be of any size and type.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        File::open(path)
+            .and_then(|mut file| io::copy(&mut file, &mut hasher))
+            .with_context(|| format!("Can not read keyfile: {}", path.display()))?;
+        Ok(Self {
+            digest: Zeroizing::new(hasher.finalize().into()),
+        })
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            digest: Zeroizing::new(Sha256::digest(bytes).into()),
+        }
+    }
+    pub fn digest(&self) -> &[u8] {
+        self.digest.as_slice()
+    }
+}
+
+/// Redacted, the digest is as secret as the keyfile.
+impl Debug for Keyfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Keyfile(****)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, str::FromStr};
+
+    use super::*;
+    use crate::{
+        kdf::KdfParams,
+        scheme::SchemeVersion,
+        session::Session,
+        user_input::{CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID},
+    };
+
+    #[test]
+    fn can_mix_a_keyfile_into_the_master_password() {
+        let path = env::temp_dir().join(format!("depasswd-keyfile-{}", std::process::id()));
+        fs::write(&path, b"keyfile\n").unwrap();
+        let keyfile = Keyfile::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(keyfile.digest(), Keyfile::from_bytes(b"keyfile\n").digest());
+
+        let derive = |keyfile: Option<&Keyfile>| {
+            Session::unlock_with_keyfile(
+                &UserID::from_str("Example Eleonora").unwrap(),
+                &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+                SchemeVersion::V1,
+                &KdfParams::DEFAULT,
+                keyfile,
+            )
+            .unwrap()
+            .derive(
+                &ServiceID::from_str("Example Service Name").unwrap(),
+                &Generation::from_str("1").unwrap(),
+                &CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap(),
+                &PasswordLength::from_str("20").unwrap(),
+            )
+            .unwrap()
+            .expose_secret()
+            .to_owned()
+        };
+        assert_eq!(derive(None), "1@MWtAAqZ0p>;;y@zZ6d");
+        let with_keyfile = derive(Some(&keyfile));
+        assert_ne!(with_keyfile, derive(None));
+        assert_eq!(with_keyfile, derive(Some(&keyfile)));
+        assert_ne!(with_keyfile, derive(Some(&Keyfile::from_bytes(b"keyfile"))));
+    }
+}
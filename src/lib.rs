@@ -18,49 +18,268 @@
  */
 
 #![doc = include_str!("docs/lib.md")]
+// `wasm` links std for its allocator and panic handler, which
+// `wasm32-unknown-unknown` has, but none of the native dependencies.
+#![cfg_attr(not(any(feature = "std", feature = "wasm")), no_std)]
+// The original secret and hex tests predate these lints and are kept as written.
+#![cfg_attr(
+    test,
+    allow(
+        clippy::explicit_auto_deref,
+        clippy::get_first,
+        clippy::needless_borrow,
+        clippy::useless_vec
+    )
+)]
 
-use anyhow::Result;
+extern crate alloc;
+
+#[cfg(feature = "std")]
 use derived_pass::DerivedPass;
-use master_secret::MasterSecret;
-use service_secret::ServiceSecret;
+#[cfg(feature = "std")]
+use session::Session;
+#[cfg(feature = "std")]
 use thiserror::Error;
-pub use user_input::UserInputProvider;
+#[cfg(feature = "std")]
+use user_input::UserInputError;
+#[cfg(feature = "std")]
+pub use user_input::{DeriveParams, UserInputProvider};
 
+#[cfg(feature = "std")]
+pub mod age;
+#[cfg(feature = "std")]
+pub mod ansible;
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bip39;
+#[cfg(feature = "std")]
+pub mod browser;
+#[cfg(feature = "std")]
+pub mod catalog;
+#[cfg(feature = "std")]
+pub mod clipboard;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod core;
+#[cfg(feature = "std")]
+pub mod crypt;
+#[cfg(all(feature = "std", unix))]
+pub mod dbus;
+#[cfg(feature = "std")]
+pub mod derivation_path;
+#[cfg(feature = "std")]
 pub mod derived_pass;
+#[cfg(feature = "std")]
+pub mod doctor;
+#[cfg(feature = "std")]
+pub mod exec;
+#[cfg(feature = "std")]
+pub mod exporter;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod importer;
+#[cfg(feature = "std")]
+pub mod jsonrpc;
+#[cfg(feature = "std")]
+pub mod kdf;
+#[cfg(feature = "std")]
+pub mod key_material;
+#[cfg(feature = "std")]
+pub mod keychain;
+#[cfg(feature = "std")]
+pub mod keyfile;
+#[cfg(feature = "std")]
+pub mod keys;
+#[cfg(feature = "std")]
 pub mod master_secret;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod panic_guard;
+#[cfg(feature = "std")]
+pub mod pepper;
+#[cfg(feature = "std")]
+pub mod pinentry;
+#[cfg(feature = "std")]
+pub mod platform;
+#[cfg(feature = "std")]
+pub mod policy;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "serde")]
+pub mod recipe;
+#[cfg(feature = "std")]
+pub mod recipe_sync;
+#[cfg(feature = "std")]
+pub mod recipe_vault;
+#[cfg(feature = "std")]
+pub mod recovery;
+#[cfg(feature = "std")]
+pub mod rest;
+#[cfg(feature = "std")]
+pub mod scheme;
+#[cfg(all(feature = "std", unix))]
+pub mod secret_service;
+#[cfg(feature = "std")]
+pub mod service_matcher;
+#[cfg(feature = "std")]
 pub mod service_secret;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod site_policy;
+#[cfg(feature = "std")]
+pub mod ssh_agent;
+#[cfg(all(feature = "std", unix))]
+pub mod systemd;
+#[cfg(feature = "std")]
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod totp;
+#[cfg(feature = "cli")]
+pub mod tui;
+#[cfg(feature = "std")]
 pub mod user_input;
+#[cfg(feature = "std")]
 pub mod utils;
+#[cfg(feature = "std")]
+pub mod vault;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod wifi;
+#[cfg(feature = "std")]
+pub mod wireguard;
+#[cfg(feature = "std")]
+pub mod wordlist;
+#[cfg(feature = "std")]
+pub mod yescrypt;
+#[cfg(feature = "std")]
+pub mod yubikey;
 
 pub const SPECIAL_CHARS: &str = r##"!"#$%&'()*+,-./:;<=>?@[\]^_`{|}~"##;
 pub const SMALL_LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
 pub const NUMBERS: &str = "0123456789";
 pub const CAPITAL_LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// The special characters typed with at most Shift on the US, German,
+/// Hungarian and French layouts alike. None of them sits on the same key
+/// on all four, as AZERTY moves every symbol, but none needs AltGr or a
+/// dead key on a foreign machine either.
+pub const LAYOUT_SAFE_SPECIAL_CHARS: &str = r##"!"%'()*+,-./:=?_"##;
+/// Characters easily confused when read aloud or typed from paper.
+pub const AMBIGUOUS_CHARS: &str = r##"O0Il1|`'""##;
 
+#[cfg(feature = "std")]
 pub struct DerivePassRunner {}
 
+#[cfg(feature = "std")]
 impl DerivePassRunner {
-    pub fn run(user_input: &impl UserInputProvider) -> Result<DerivedPass> {
-        Ok(DerivedPass::new(
-            &ServiceSecret::new(
-                &MasterSecret::new(
-                    user_input.get_user_id(),
-                    user_input.get_master_password_plain(),
-                )?,
-                user_input.get_service_id(),
-                user_input.get_generation(),
-                user_input.get_password_length(),
-            )?,
-            user_input.get_char_set(),
-            user_input.get_password_length(),
-        )?)
+    /// Derives the password of `params`, failing when it can not satisfy its
+    /// policy. Takes a `DeriveParams` or a reference to any
+    /// `UserInputProvider`.
+    pub fn run(params: impl Into<DeriveParams>) -> Result<DerivedPass, DerivePassError> {
+        let params = params.into();
+        let session = Session::unlock_with_kdf_params(
+            &params.user_id,
+            &params.master_password_plain,
+            params.scheme,
+            &params.kdf_params,
+        )?;
+        match &params.policy {
+            Some(policy) => session.derive_with_policy(
+                &params.service_id,
+                &params.generation,
+                &params.char_set,
+                &params.password_length,
+                policy,
+            ),
+            None => session.derive(
+                &params.service_id,
+                &params.generation,
+                &params.char_set,
+                &params.password_length,
+            ),
+        }
     }
 }
 
+#[cfg(feature = "std")]
+/// Why a derivation failed. The entry points of the library (`MasterSecret`,
+/// `Session::unlock`, `Session::derive`, `DerivePassRunner::run`) return it
+/// directly, so callers can match on the cause.
 #[derive(Error, Debug)]
 pub enum DerivePassError {
     #[error("Secret error")]
     Secret,
     #[error("Character error")]
     Char,
+    /// The key derivation function rejected its parameters or failed to run.
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+    /// The salt computed from the user identifier is not usable.
+    #[error("Invalid salt: {0}")]
+    InvalidSalt(String),
+    /// No password can be drawn from the character set.
+    #[error("Invalid character set: {0}")]
+    InvalidCharSet(String),
+    #[error("The password length must be between {min} and {max}, not {length}")]
+    LengthOutOfRange { length: u16, min: u16, max: u16 },
+    /// The request can not satisfy the policy, or the password breaks it.
+    #[error("Policy violation: {0}")]
+    PolicyViolation(String),
+    #[error(transparent)]
+    InvalidInput(#[from] UserInputError),
+    /// The derivation could not be recorded in the audit log.
+    #[error("Audit error: {0}")]
+    Audit(String),
+}
+
+#[cfg(feature = "std")]
+impl DerivePassError {
+    /// Whether the request was at fault rather than the library or its
+    /// environment, e.g. for picking an HTTP status.
+    pub fn is_invalid_request(&self) -> bool {
+        matches!(
+            self,
+            DerivePassError::InvalidSalt(_)
+                | DerivePassError::InvalidCharSet(_)
+                | DerivePassError::LengthOutOfRange { .. }
+                | DerivePassError::PolicyViolation(_)
+                | DerivePassError::InvalidInput(_)
+        )
+    }
+    /// Recovers the typed error from the `anyhow` errors of the internal
+    /// steps. What remains are failures of the HMAC and HKDF primitives.
+    pub(crate) fn from_anyhow(error: anyhow::Error) -> Self {
+        match error.downcast::<DerivePassError>() {
+            Ok(error) => error,
+            Err(error) => match error.downcast::<UserInputError>() {
+                Ok(error) => DerivePassError::InvalidInput(error),
+                Err(_) => DerivePassError::Secret,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<crate::core::Error> for DerivePassError {
+    fn from(error: crate::core::Error) -> Self {
+        match error {
+            crate::core::Error::InvalidSalt(e) => DerivePassError::InvalidSalt(e.to_string()),
+            crate::core::Error::Kdf(e) => DerivePassError::Kdf(e.to_string()),
+            crate::core::Error::Secret => DerivePassError::Secret,
+            crate::core::Error::Char => DerivePassError::Char,
+            crate::core::Error::TooLong { .. }
+            | crate::core::Error::TooManyClasses { .. }
+            | crate::core::Error::Exhausted => {
+                DerivePassError::InvalidInput(UserInputError::new(error.to_string()))
+            }
+        }
+    }
 }
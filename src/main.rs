@@ -17,13 +17,2695 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use anyhow::Result;
-use depasswd::{DerivePassRunner, user_input::UserInputCli};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum, builder::TypedValueParser};
+use depasswd::{
+    DerivePassError, SPECIAL_CHARS, UserInputProvider,
+    audit::AuditLog,
+    batch::Batch,
+    catalog::{Catalog, CatalogEntry},
+    clipboard::{Clipboard, DEFAULT_CLEAR_AFTER},
+    config::Config,
+    crypt::{DEFAULT_BCRYPT_COST, ShadowAlgorithm},
+    derivation_path::{DerivationPath, Purpose},
+    derived_pass::{CONSONANTS, DerivedPass, UsernameStyle, VOWELS},
+    doctor::{CheckStatus, Doctor},
+    exec::{Exec, SecretDelivery, SecretMapping},
+    exporter::{
+        kdbx::{KdbxEntry, KdbxWriter},
+        lesspass::LessPassExporter,
+        pass::PassExporter,
+    },
+    history::ServiceHistory,
+    importer::{
+        ImportProposal, bitwarden::BitwardenImporter, bookmarks::BookmarkImporter,
+        csv::CsvImporter, keepass::KeePassImporter,
+    },
+    jsonrpc::JsonRpcServer,
+    kdf::{KdfAlgorithm, KdfParams},
+    key_material::KeyEncoding,
+    keychain,
+    keyfile::Keyfile,
+    keys::{KeyFormat, KeyPair},
+    output::{DerivationReport, Output, OutputFormat, OutputOptions},
+    panic_guard::PanicGuard,
+    pepper::Pepper,
+    profile::{ProfileParams, ProfileStore},
+    recipe_sync::RecipeSync,
+    recipe_vault::{RecipeEntry, RecipeVault},
+    recovery::RecoverySheet,
+    rest::RestServer,
+    scheme::SchemeVersion,
+    service_matcher::ServiceMatcher,
+    session::Session,
+    site_policy::SitePolicies,
+    ssh_agent::{SshIdentity, SshKeySpec},
+    template::Template,
+    tui::Tui,
+    user_input::{
+        CHAR_SET_NAMES, CUSTOM_CHAR_SET_PREFIX, CharSet, EXCLUDE_AMBIGUOUS, EXCLUDE_CHARS_PREFIX,
+        Generation, LAYOUT_SAFE, MasterPasswordPlain, MasterPasswordSource, PasswordLength,
+        ServiceID, UserID, UserInputCli,
+    },
+    utils::Utils,
+    vault::VaultServer,
+    wifi::{Wifi, WifiFormat},
+    wordlist::Wordlist,
+    yubikey::YubiKey,
+};
+use dialoguer::{Input, MultiSelect, Password, theme::ColorfulTheme};
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(version, about = "Stateless password manager")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    context: Context,
+    // Without a subcommand depasswd derives, so interactive use keeps working
+    #[command(flatten)]
+    derive: DeriveArgs,
+    /// Same as the `jsonrpc` subcommand
+    #[arg(long, hide = true)]
+    jsonrpc: bool,
+}
+
+/// Global options, shared by every subcommand that unlocks a session.
+#[derive(Args)]
+struct Context {
+    /// Verify the stored parameters of this profile before deriving (created on first use)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Append an entry to this file for every derivation (no secrets, services are blinded)
+    #[arg(long, global = true, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+    /// Derivation scheme version [default: the one of the profile, from the config, or 1]
+    #[arg(long, global = true, value_name = "VERSION")]
+    scheme: Option<SchemeVersion>,
+    /// KDF of the master secret as printed by `calibrate`, e.g. argon2id-m32768-t4-p4
+    #[arg(long, global = true, value_name = "ID", conflicts_with_all = ["kdf_algorithm", "kdf_memory", "kdf_iterations", "kdf_parallelism"])]
+    kdf: Option<KdfParams>,
+    /// KDF of the master secret: argon2id, scrypt or pbkdf2-sha512 [default: the one of the profile, from the config, or argon2id]
+    #[arg(long, global = true, value_name = "ALGORITHM")]
+    kdf_algorithm: Option<KdfAlgorithm>,
+    /// Memory cost in KiB of the master secret [default: the one of the profile, from the config, or 32768]
+    #[arg(long, global = true, value_name = "KIB")]
+    kdf_memory: Option<u32>,
+    /// Iterations (PBKDF2 rounds) of the master secret [default: the one of the profile, from the config, or 4]
+    #[arg(long, global = true, value_name = "N")]
+    kdf_iterations: Option<u32>,
+    /// Parallelism of the master secret [default: the one of the profile, from the config, or 4]
+    #[arg(long, global = true, value_name = "N")]
+    kdf_parallelism: Option<u32>,
+    /// Read the master password from the first line of stdin instead of prompting
+    #[arg(long, global = true, conflicts_with = "master_fd")]
+    master_stdin: bool,
+    /// Read the master password from the first line of this file descriptor instead of prompting
+    #[arg(long, global = true, value_name = "N")]
+    master_fd: Option<u32>,
+    /// Ask the master password with pinentry (the dialog of gpg-agent) instead of in the terminal [default: from the config]
+    #[arg(long, global = true, conflicts_with_all = ["master_stdin", "master_fd"])]
+    pinentry: bool,
+    /// Neither use nor fill the keychain cache of the master secret (keychain_cache in the config)
+    #[arg(long, global = true)]
+    no_keychain: bool,
+    /// Offer only these characters (ranges like a-f0-9 allowed) as the character set, for sites that accept a specific alphabet
+    #[arg(long, global = true, value_name = "CHARS", value_parser = |s: &str| CharSet::from_custom(s).map(|_| s.to_owned()))]
+    chars: Option<String>,
+    /// Use only the special characters typed without AltGr on US, German, Hungarian and French keyboards
+    #[arg(long, global = true, conflicts_with = "chars")]
+    layout_safe: bool,
+    /// Leave out characters that are easy to confuse on paper or aloud (O/0, l/1/I, quotes)
+    #[arg(long, global = true)]
+    exclude_ambiguous: bool,
+    /// Leave out these characters (ranges like 0-4 allowed, \ escapes), e.g. the ones a site forbids; remembered with the service
+    #[arg(long, global = true, value_name = "CHARS")]
+    exclude_chars: Option<String>,
+    /// Stretch the master password together with the SHA-256 of this file, which is then needed to unlock [default: keyfile from the config]
+    #[arg(long, global = true, value_name = "PATH")]
+    keyfile: Option<PathBuf>,
+    /// Read defaults from this file instead of config.toml in the config directory
+    #[arg(long = "config", global = true, value_name = "FILE")]
+    config_file: Option<PathBuf>,
+    #[arg(skip)]
+    config: Config,
+    /// Peppers by user id, so a YubiKey or smartcard is asked once per run.
+    #[arg(skip)]
+    peppers: Mutex<Vec<(String, Option<Pepper>)>>,
+}
+
+#[derive(Args)]
+struct DeriveArgs {
+    /// Also spell the derived password with the NATO alphabet
+    #[arg(long)]
+    spell: bool,
+    /// Also show the derived password in large, numbered groups of four
+    #[arg(long)]
+    grouped: bool,
+    /// Output format: plain, htpasswd-bcrypt, htpasswd-argon2, crypt-sha512, bcrypt, argon2id,
+    /// shadow, k8s-secret, ansible-vault, tfvars-json, dotenv or json
+    #[arg(long, default_value_t)]
+    format: OutputFormat,
+    /// Copy the password to the clipboard instead of printing it; hashed formats still print the hash
+    #[arg(long, conflicts_with_all = ["batch", "all_generations", "variants", "spell", "grouped"])]
+    copy: bool,
+    /// Print only the password, without banner, colors or trailing newline, for piping
+    #[arg(long, conflicts_with_all = ["batch", "all_generations", "variants", "copy", "qr", "spell", "grouped"])]
+    raw: bool,
+    /// Show the password as a QR code instead of printing it
+    #[arg(long, conflicts_with_all = ["batch", "all_generations", "variants", "copy"])]
+    qr: bool,
+    /// Seconds until the copied password is cleared from the clipboard [default: from the config, or 45]
+    #[arg(long, value_name = "SECONDS", requires = "copy")]
+    clear_after: Option<u64>,
+    /// Print the password (text) or a JSON object with the derivation parameters and an entropy estimate (json)
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputMode,
+    /// Account name for the htpasswd output formats
+    #[arg(long)]
+    username: Option<String>,
+    /// Derive generations 1 to N of the service instead of asking for one
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "batch")]
+    all_generations: Option<u32>,
+    /// Also derive these lengths, each with and without special characters (e.g. 16,20,32)
+    #[arg(long, value_name = "LENGTHS", value_delimiter = ',', conflicts_with_all = ["batch", "all_generations"])]
+    variants: Vec<u16>,
+    /// Save the settings of this derivation to the catalog even if the service is not in it yet
+    #[arg(long)]
+    remember: bool,
+    /// Derive every `[[derivation]]` of a TOML file, prompting only for the master secret
+    #[arg(long, value_name = "FILE")]
+    batch: Option<PathBuf>,
+    /// Name of the derived password in the Kubernetes Secret, dotenv and json formats
+    #[arg(long, default_value = "password")]
+    key: String,
+    /// Name of the Kubernetes Secret
+    #[arg(long, default_value = "depasswd")]
+    k8s_name: String,
+    /// Namespace of the Kubernetes Secret
+    #[arg(long)]
+    k8s_namespace: Option<String>,
+    /// File whose first line is the password of the ansible-vault format
+    #[arg(long, value_name = "FILE")]
+    vault_password_file: Option<PathBuf>,
+    /// Cost of the bcrypt based formats
+    #[arg(long, default_value_t = DEFAULT_BCRYPT_COST, value_parser = clap::value_parser!(u32).range(4..=31))]
+    bcrypt_cost: u32,
+    /// Memory cost in KiB of the argon2id based formats [default: 19456]
+    #[arg(long)]
+    argon2_memory: Option<u32>,
+    /// Iterations of the argon2id based formats [default: 2]
+    #[arg(long)]
+    argon2_iterations: Option<u32>,
+    /// Parallelism of the argon2id based formats [default: 1]
+    #[arg(long)]
+    argon2_parallelism: Option<u32>,
+    /// Hash of the shadow format: sha512 ($6$) or yescrypt ($y$)
+    #[arg(long, default_value_t)]
+    shadow_algorithm: ShadowAlgorithm,
+    /// Service identifier to derive for, instead of asking for it
+    #[arg(long, conflicts_with_all = ["batch", "all_generations", "variants"])]
+    service: Option<ServiceID>,
+    /// Derive the secret of the service meant for this purpose (e.g. pin, recovery); needs scheme v2
+    #[arg(long, default_value_t, conflicts_with_all = ["batch", "all_generations", "variants"])]
+    purpose: Purpose,
+    /// Derive a password from the character sets (chars) or of alternating consonants and vowels (pronounceable); needs scheme v2
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["batch", "all_generations", "variants", "purpose"])]
+    style: PasswordStyle,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prompt for a service and derive its password (the default)
+    Derive(DeriveArgs),
+    /// Full-screen form with every input on one screen
+    Tui,
+    /// Prompt for a service and a password, and tell whether the password is the derived one
+    Verify,
+    /// Measure how long unlocking takes with the current KDF parameters
+    Bench {
+        /// Number of timed runs
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+        runs: u32,
+    },
+    /// Find the strongest KDF parameters that unlock within a time limit on this machine
+    Calibrate {
+        /// Longest acceptable unlock time in milliseconds
+        #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u64).range(1..))]
+        target_ms: u64,
+        /// Store the parameters in the config file, so they are used from now on
+        #[arg(long)]
+        save: bool,
+    },
+    /// Show where depasswd keeps its files
+    Config,
+    /// Serve JSON-RPC 2.0 requests on stdin/stdout instead of prompting
+    Jsonrpc,
+    /// Check this machine for common problems (terminal, clipboard, locale, Argon2 speed)
+    Doctor,
+    /// Derive a password shaped by a Spectre-style template (needs scheme v2)
+    Template {
+        /// Template name (maximum, long, medium, short, basic, pin, name, phrase) or a pattern of
+        /// slots: V/v vowel, C/c consonant, A capital, a letter, n digit, o symbol, x any, space
+        #[arg(long, default_value_t)]
+        template: Template,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the password
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the password, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive a unique username for a service (needs scheme v2)
+    Username {
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the username
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// alphanumeric (a letter, then letters and digits) or pronounceable
+        #[arg(long, default_value_t)]
+        style: UsernameStyle,
+        /// Number of characters
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(3..=32))]
+        length: u16,
+        /// Print only the username, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive the TOTP seed of a service, to enroll it into an authenticator app (needs scheme v2)
+    Totp {
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the seed
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Issuer shown by the authenticator app [default: the service identifier]
+        #[arg(long)]
+        issuer: Option<String>,
+        /// Account name shown by the authenticator app
+        #[arg(long)]
+        account: Option<String>,
+        /// Print the otpauth:// provisioning URI instead of the base32 seed
+        #[arg(long, conflicts_with = "qr")]
+        uri: bool,
+        /// Show the provisioning URI as a QR code to scan with the authenticator app
+        #[arg(long)]
+        qr: bool,
+        /// Print only the seed or URI, without banner or trailing newline, for piping
+        #[arg(long, conflicts_with = "qr")]
+        raw: bool,
+    },
+    /// Derive the age X25519 identity of a service, to decrypt backups encrypted to it (needs scheme v2)
+    Age {
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the identity
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the age1… recipient
+        #[arg(long, conflicts_with_all = ["output", "format"])]
+        recipient: bool,
+        /// Print the X25519 key pair as pem (PKCS#8 and SPKI) or raw (hex) instead of an identity file
+        #[arg(long)]
+        format: Option<KeyFormat>,
+        /// Write the identity file to FILE instead of printing it
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Derive a BIP39 mnemonic for a service, as a recoverable wallet or backup seed (needs scheme v2)
+    Mnemonic {
+        /// Number of words: 12, 15, 18, 21 or 24
+        #[arg(long, default_value_t = 24)]
+        words: usize,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the mnemonic
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the words, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive the 63 character WPA2/WPA3 pre-shared key of a Wi-Fi network (needs scheme v2)
+    Wifi {
+        /// Service identifier to derive for (e.g. the SSID), instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the key
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print the key (psk), a wpa_supplicant.conf network block (wpa-supplicant) or a hostapd.conf snippet (hostapd)
+        #[arg(long, default_value_t)]
+        format: WifiFormat,
+        /// Network name of the snippets [default: the service identifier]
+        #[arg(long)]
+        ssid: Option<String>,
+        /// Print only the key, without banner or trailing newline, for piping
+        #[arg(long, conflicts_with_all = ["format", "ssid"])]
+        raw: bool,
+    },
+    /// Derive the WireGuard key pair of a service, in the base64 form wg expects (needs scheme v2)
+    Wireguard {
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the key pair
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the public key
+        #[arg(long, conflicts_with = "format")]
+        public: bool,
+        /// Print the key pair as pem (PKCS#8 and SPKI) or raw (hex) instead of base64
+        #[arg(long)]
+        format: Option<KeyFormat>,
+    },
+    /// Derive the Ed25519 SSH key of a service, the same one the agent serves
+    SshKey {
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the key
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Key format: openssh, pem (PKCS#8 and SPKI) or raw (hex)
+        #[arg(long, default_value = "openssh")]
+        format: KeyFormat,
+        /// Write the private key to FILE and the public key to FILE.pub instead of printing them
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Derive raw key material for a service: symmetric keys, API tokens, seeds (needs scheme v2)
+    Key {
+        /// Number of bytes
+        #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u16).range(1..=256))]
+        bytes: u16,
+        /// Encoding of the bytes: hex, base64, base32 or base58
+        #[arg(long, default_value_t)]
+        encoding: KeyEncoding,
+        /// Text put before the encoded key, e.g. the required prefix of an API token
+        #[arg(long, default_value = "")]
+        prefix: String,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the key
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the key, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive a hex seed for other deterministic generators, e.g. test fixtures or simulations (needs scheme v2)
+    Seed {
+        /// Number of bytes
+        #[arg(long, default_value_t = 32, value_parser = clap::builder::PossibleValuesParser::new(["32", "64"]).map(|s| s.parse::<u16>().unwrap()))]
+        bytes: u16,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the seed
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the seed, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive a numbered set of recovery codes for a service, to print and store offline (needs scheme v2)
+    RecoveryCodes {
+        /// Number of codes
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=100))]
+        count: u32,
+        /// Characters per code
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(4..=32))]
+        length: u16,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the codes
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the codes, one per line, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive a fake answer to a security question from a wordlist file (needs scheme v2)
+    Answer {
+        /// Wordlist with one word per line, at least 1024 unique words
+        #[arg(long, value_name = "FILE")]
+        wordlist: PathBuf,
+        /// The security question, instead of asking for it; case and punctuation do not matter
+        #[arg(long)]
+        question: Option<String>,
+        /// Number of words
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u16).range(1..=16))]
+        words: u16,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the answer
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the answer, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Derive a passphrase of words from a wordlist file instead of a password (needs scheme v2)
+    Passphrase {
+        /// Wordlist with one word per line, at least 1024 unique words
+        #[arg(long, value_name = "FILE")]
+        wordlist: PathBuf,
+        /// Number of words
+        #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u16).range(1..=64))]
+        words: u16,
+        /// Text between the words
+        #[arg(long, default_value = " ")]
+        separator: String,
+        /// Service identifier to derive for, instead of asking for it
+        #[arg(long)]
+        service: Option<ServiceID>,
+        /// Generation of the passphrase
+        #[arg(long, default_value = "1")]
+        generation: Generation,
+        /// Print only the passphrase, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Look up the password policies of known sites
+    Policy {
+        #[command(subcommand)]
+        action: PolicyAction,
+    },
+    /// Remember the generation, length and notes of services in an encrypted recipe vault
+    Recipe {
+        #[command(subcommand)]
+        action: RecipeAction,
+    },
+    /// Bump the stored generation of a service and show its old and new password
+    Rotate { service: ServiceID },
+    /// Print the known service identifiers resembling a query, best first
+    Find {
+        query: String,
+        /// Search the catalog only, without unlocking the recipe vault
+        #[arg(long)]
+        catalog_only: bool,
+    },
+    /// Propose catalog entries from the exports of other tools
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Run a command with derived secrets in its environment
+    Exec {
+        /// Secret to pass, as VAR=service:generation (repeatable)
+        #[arg(long = "map", value_name = "VAR=SERVICE:GENERATION", required = true)]
+        mappings: Vec<SecretMapping>,
+        #[command(flatten)]
+        defaults: DerivationDefaults,
+        /// Write the secrets to private files on a tmpfs and pass their paths in VAR_FILE
+        #[arg(long)]
+        files: bool,
+        /// The command to run and its arguments
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Serve derived passwords over a minimal Vault KV v2 compatible HTTP API
+    Vault {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8200")]
+        listen: String,
+        /// Mount path of the KV engine
+        #[arg(long, default_value = "secret")]
+        mount: String,
+        #[command(flatten)]
+        defaults: DerivationDefaults,
+    },
+    /// Serve a local JSON API (POST /derive, POST /totp) for GUI frontends and browser extensions
+    Serve {
+        /// Loopback address to listen on, port 0 picks a free one
+        #[arg(long, default_value = "127.0.0.1:0")]
+        listen: String,
+        /// Origin allowed to call the API from a browser, e.g. chrome-extension://<id> (repeatable)
+        #[arg(long = "allow-origin")]
+        allowed_origins: Vec<String>,
+        #[command(flatten)]
+        defaults: DerivationDefaults,
+    },
+    /// Provide the catalog's derived passwords to desktop applications over the Secret Service D-Bus API (libsecret)
+    SecretService {
+        #[command(flatten)]
+        defaults: DerivationDefaults,
+    },
+    /// Print a Markdown sheet with everything but the master password needed to re-derive the catalog
+    RecoverySheet {
+        /// Include the user id instead of leaving a blank for it
+        #[arg(long)]
+        user_id: Option<String>,
+        /// Write the sheet to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Delete the master secrets cached in the keychain
+    Lock,
+    /// Run an agent keeping the unlocked master secret in locked memory, answering JSON-RPC
+    /// derive requests on DEPASSWD_AGENT_SOCK and, with SSH keys, ssh-agent requests
+    Agent {
+        /// Serve the ed25519 key of SERVICE[:GENERATION] over the ssh-agent protocol (repeatable) [default: ssh_keys from the config]
+        #[arg(long = "ssh", value_name = "SERVICE[:GENERATION]")]
+        ssh_keys: Vec<SshKeySpec>,
+        /// Unix socket (named pipe on Windows) of the ssh-agent, use it as SSH_AUTH_SOCK
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Unix socket (named pipe on Windows) of the JSON-RPC derive requests, use it as DEPASSWD_AGENT_SOCK
+        #[arg(long)]
+        rpc_socket: Option<PathBuf>,
+        /// Forget the master secret this many seconds after unlocking, 0 keeps it until the agent stops.
+        /// Clients can unlock it again by sending user_id and master_password.
+        #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+        ttl: u64,
+    },
+    /// Answer the KeePassXC browser extension with derived logins for the current page
+    BrowserServer {
+        /// Unix socket shared with `browser-proxy`
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Accept one new browser association during this many seconds, other clients are refused
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            default_missing_value = "60"
+        )]
+        allow_associate: Option<u64>,
+        #[command(flatten)]
+        defaults: DerivationDefaults,
+    },
+    /// Native messaging host for the KeePassXC browser extension, started by the browser.
+    /// Point the `path` of the host manifest to a script running `depasswd browser-proxy "$@"`.
+    BrowserProxy {
+        /// Unix socket shared with `browser-server`
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Arguments the browser passes to native messaging hosts (ignored)
+        #[arg(hide = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        browser_args: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OutputMode {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum PasswordStyle {
+    #[default]
+    Chars,
+    Pronounceable,
+}
+
+/// Parameters of derivations whose service list comes from elsewhere.
+#[derive(Args)]
+struct DerivationDefaults {
+    /// Password length of every derived secret [default: from the config, or 32]
+    #[arg(long)]
+    length: Option<u16>,
+    /// Character sets of every derived secret, by name or as custom:CHARS [default: from the config, or all]
+    #[arg(long, value_delimiter = ',')]
+    char_sets: Option<Vec<String>>,
+}
+
+impl DerivationDefaults {
+    fn length(&self, config: &Config) -> u16 {
+        self.length.or(config.password_length).unwrap_or(32)
+    }
+    fn char_sets(&self, config: &Config) -> Vec<String> {
+        self.char_sets
+            .clone()
+            .or_else(|| config.char_sets.clone())
+            .unwrap_or_else(|| CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect())
+    }
+}
+
+#[derive(Subcommand)]
+enum PolicyAction {
+    /// Print the policy applied when deriving for a service
+    Show {
+        /// Service identifier, domain or URL
+        service: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum RecipeAction {
+    /// Store or update the recipe of a service; unset options keep their stored value
+    Set {
+        service: ServiceID,
+        /// Generation of the password [default: 1]
+        #[arg(long)]
+        generation: Option<Generation>,
+        /// Password length [default: from the config, or 32]
+        #[arg(long)]
+        length: Option<u16>,
+        /// Character sets, by name or as custom:CHARS [default: from the config, or all]
+        #[arg(long, value_delimiter = ',')]
+        char_sets: Option<Vec<String>>,
+        /// Username of the account
+        #[arg(long)]
+        username: Option<String>,
+        /// Free-form notes, e.g. where the password is used
+        #[arg(long)]
+        notes: Option<String>,
+    },
+    /// Derive the password of a service with its stored recipe
+    Show {
+        service: ServiceID,
+        /// Print only the password, without banner or trailing newline, for piping
+        #[arg(long)]
+        raw: bool,
+    },
+    /// List the stored recipes
+    List,
+    /// Write the recipes as LessPass profiles, for a LessPass client kept as a fallback
+    ExportLesspass {
+        /// File to write the JSON to [default: stdout]
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Write the derived passwords into a pass(1) password store, encrypted to its .gpg-id
+    ExportPass {
+        /// Password store [default: $PASSWORD_STORE_DIR or ~/.password-store]
+        #[arg(long)]
+        store: Option<PathBuf>,
+        /// Folder of the entries in the store
+        #[arg(long, default_value = "depasswd")]
+        prefix: String,
+    },
+    /// Add the accounts of Chrome, Firefox or Bitwarden CSV exports as recipes not yet rotated
+    ImportCsv {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Write the derived passwords into a new KeePass KDBX 4 database with its own passphrase
+    ExportKdbx { output: PathBuf },
+    /// Merge the recipe vault with a shared git repository, keeping the higher generations
+    Sync {
+        /// Path of the bare git repository [default: recipe_sync_remote of the config]
+        #[arg(long)]
+        remote: Option<String>,
+    },
+    /// Forget the recipe of a service
+    Remove { service: ServiceID },
+}
+
+#[derive(Subcommand)]
+enum ImportSource {
+    /// Browser bookmarks (Chrome JSON, Firefox JSON or HTML export) or a history export
+    Bookmarks {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Add every proposed entry without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Unencrypted Bitwarden JSON export (stored passwords are not read)
+    Bitwarden {
+        file: PathBuf,
+        /// Add every proposed entry without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// KeePass 2 XML export (stored passwords are not kept)
+    Keepass {
+        file: PathBuf,
+        /// Add every proposed entry without asking
+        #[arg(long)]
+        yes: bool,
+    },
+}
 
 fn main() -> Result<()> {
+    PanicGuard::install();
+    let mut cli = Cli::parse();
+    cli.context.config = Config::load(&cli.context.config_path()?)?;
+    cli.context.apply_char_set_flags();
+    let context = &cli.context;
+    match cli.command {
+        Some(Command::Derive(args)) => derive(&args, context),
+        Some(Command::Verify) => verify(context),
+        Some(Command::Tui) => tui(context),
+        Some(Command::Bench { runs }) => bench(runs, context),
+        Some(Command::Calibrate { target_ms, save }) => calibrate(target_ms, save, context),
+        Some(Command::Config) => config(context),
+        Some(Command::Jsonrpc) => jsonrpc(context),
+        Some(Command::Doctor) => doctor(),
+        Some(Command::Template {
+            template,
+            service,
+            generation,
+            raw,
+        }) => derive_template(&template, service, &generation, raw, context),
+        Some(Command::Username {
+            service,
+            generation,
+            style,
+            length,
+            raw,
+        }) => username(service, &generation, style, length, raw, context),
+        Some(Command::Totp {
+            service,
+            generation,
+            issuer,
+            account,
+            uri,
+            qr,
+            raw,
+        }) => totp(
+            service,
+            &generation,
+            issuer.as_deref(),
+            account.as_deref(),
+            uri,
+            qr,
+            raw,
+            context,
+        ),
+        Some(Command::Age {
+            service,
+            generation,
+            recipient,
+            format,
+            output,
+        }) => age(
+            service,
+            &generation,
+            recipient,
+            format,
+            output.as_deref(),
+            context,
+        ),
+        Some(Command::Mnemonic {
+            words,
+            service,
+            generation,
+            raw,
+        }) => mnemonic(words, service, &generation, raw, context),
+        Some(Command::Wifi {
+            service,
+            generation,
+            format,
+            ssid,
+            raw,
+        }) => wifi(service, &generation, format, ssid, raw, context),
+        Some(Command::Wireguard {
+            service,
+            generation,
+            public,
+            format,
+        }) => wireguard(service, &generation, public, format, context),
+        Some(Command::SshKey {
+            service,
+            generation,
+            format,
+            output,
+        }) => ssh_key(service, &generation, format, output.as_deref(), context),
+        Some(Command::Key {
+            bytes,
+            encoding,
+            prefix,
+            service,
+            generation,
+            raw,
+        }) => key(bytes, encoding, &prefix, service, &generation, raw, context),
+        Some(Command::Seed {
+            bytes,
+            service,
+            generation,
+            raw,
+        }) => seed(bytes, service, &generation, raw, context),
+        Some(Command::RecoveryCodes {
+            count,
+            length,
+            service,
+            generation,
+            raw,
+        }) => recovery_codes(count, length, service, &generation, raw, context),
+        Some(Command::Answer {
+            wordlist,
+            question,
+            words,
+            service,
+            generation,
+            raw,
+        }) => answer(
+            &wordlist,
+            question,
+            words,
+            service,
+            &generation,
+            raw,
+            context,
+        ),
+        Some(Command::Passphrase {
+            wordlist,
+            words,
+            separator,
+            service,
+            generation,
+            raw,
+        }) => passphrase(
+            &wordlist,
+            words,
+            &separator,
+            service,
+            &generation,
+            raw,
+            context,
+        ),
+        Some(Command::Policy { action }) => policy(action),
+        Some(Command::Recipe { action }) => recipe(action, context),
+        Some(Command::Rotate { service }) => rotate(&service, context),
+        Some(Command::Find {
+            query,
+            catalog_only,
+        }) => find(&query, catalog_only, context),
+        Some(Command::Import { source }) => import(source),
+        Some(Command::Exec {
+            mappings,
+            defaults,
+            files,
+            command,
+        }) => exec(&mappings, &defaults, files, &command, context),
+        Some(Command::Vault {
+            listen,
+            mount,
+            defaults,
+        }) => vault(&listen, &mount, &defaults, context),
+        Some(Command::Serve {
+            listen,
+            allowed_origins,
+            defaults,
+        }) => serve(&listen, &allowed_origins, &defaults, context),
+        Some(Command::SecretService { defaults }) => secret_service(&defaults, context),
+        Some(Command::BrowserServer {
+            socket,
+            allow_associate,
+            defaults,
+        }) => browser_server(socket, allow_associate, &defaults, context),
+        Some(Command::BrowserProxy { socket, .. }) => browser_proxy(socket),
+        Some(Command::RecoverySheet { user_id, output }) => {
+            recovery_sheet(user_id, output, context)
+        }
+        Some(Command::Lock) => lock(context),
+        Some(Command::Agent {
+            ssh_keys,
+            socket,
+            rpc_socket,
+            ttl,
+        }) => agent(&ssh_keys, socket, rpc_socket, ttl, context),
+        None if cli.jsonrpc => jsonrpc(context),
+        None => derive(&cli.derive, context),
+    }
+}
+
+fn doctor() -> Result<()> {
+    let results = Doctor::run();
+    for result in &results {
+        println!("{}", result);
+    }
+    if results.iter().any(|r| r.status == CheckStatus::Error) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn verify(context: &Context) -> Result<()> {
+    let user_input = Arc::new(Mutex::new(UserInputCli::new(
+        &context.config,
+        context.master_password(),
+    )?));
+    PanicGuard::register(&user_input);
+    let matches = {
+        let user_input = user_input.lock().map_err(|_| DerivePassError::Secret)?;
+        let derived_pass = context
+            .unlock(
+                user_input.get_user_id(),
+                user_input.get_master_password_plain(),
+                "cli",
+            )?
+            .derive_path(
+                &DerivationPath::new(
+                    user_input.get_user_id(),
+                    user_input.get_service_id(),
+                    user_input.get_generation(),
+                    &Purpose::password(),
+                ),
+                user_input.get_char_set(),
+                user_input.get_password_length(),
+                user_input.get_policy(),
+            )?;
+        let candidate = Zeroizing::new(
+            Password::with_theme(&ColorfulTheme::default())
+                .with_prompt("Password to verify")
+                .interact()?,
+        );
+        derived_pass.matches(&candidate)
+    };
+    if !matches {
+        eprintln!("The password does not match");
+        std::process::exit(1);
+    }
+    println!("The password matches");
+    Ok(())
+}
+
+fn tui(context: &Context) -> Result<()> {
+    Tui::run(&context.config, |input| {
+        context
+            .unlock(
+                input.get_user_id(),
+                input.get_master_password_plain(),
+                "tui",
+            )?
+            .derive_path(
+                &DerivationPath::new(
+                    input.get_user_id(),
+                    input.get_service_id(),
+                    input.get_generation(),
+                    &Purpose::password(),
+                ),
+                input.get_char_set(),
+                input.get_password_length(),
+                input.get_policy(),
+            )
+    })
+}
+
+fn bench(runs: u32, context: &Context) -> Result<()> {
+    let scheme = context.scheme()?;
+    let kdf_params = context.kdf_params()?;
+    println!("{}", kdf_params);
+    let user_id = UserID::from_str("depasswd-bench")?;
+    let master_password_plain = MasterPasswordPlain::from_str("depasswd-bench")?;
+    let mut timings = vec![];
+    for run in 1..=runs {
+        let start = Instant::now();
+        Session::unlock_with_kdf_params(&user_id, &master_password_plain, scheme, &kdf_params)?;
+        let elapsed = start.elapsed();
+        println!("run {}: {} ms", run, elapsed.as_millis());
+        timings.push(elapsed);
+    }
+    println!(
+        "fastest: {} ms, average: {} ms",
+        timings.iter().min().unwrap_or(&Duration::ZERO).as_millis(),
+        (timings.iter().sum::<Duration>() / runs).as_millis()
+    );
+    Ok(())
+}
+
+fn calibrate(target_ms: u64, save: bool, context: &Context) -> Result<()> {
+    let kdf_params = context.kdf_params()?;
+    let (params, elapsed) = KdfParams::calibrate(
+        kdf_params.algorithm,
+        Duration::from_millis(target_ms),
+        kdf_params.p_cost,
+        KdfParams::measure,
+        |params, elapsed| eprintln!("{}: {} ms", params, elapsed.as_millis()),
+    )?;
+    if elapsed > Duration::from_millis(target_ms) {
+        eprintln!(
+            "Even the smallest setting takes longer than {} ms on this machine",
+            target_ms
+        );
+    }
+    println!("{} ({} ms)", params, elapsed.as_millis());
+    println!("id: {}", params.id());
+    eprintln!(
+        "Keep this id with your user id: other parameters derive other passwords. \
+         Select them with --kdf {} or a profile.",
+        params.id()
+    );
+    if save {
+        let path = context.config_path()?;
+        let mut config = Config::load(&path)?;
+        config.kdf = Some(params);
+        config.save(&path)?;
+        eprintln!("Saved to {}", path.display());
+    }
+    Ok(())
+}
+
+fn config(context: &Context) -> Result<()> {
+    for (name, path) in [
+        ("config directory", Utils::config_dir()?),
+        ("config file", context.config_path()?),
+        ("catalog", Catalog::default_path()?),
+        ("profiles", ProfileStore::default_path()?),
+        ("site policies", SitePolicies::default_path()?),
+        ("history", ServiceHistory::default_path()?),
+        ("recipe vault", RecipeVault::default_path()?),
+    ] {
+        println!(
+            "{}: {}{}",
+            name,
+            path.display(),
+            if path.exists() {
+                ""
+            } else {
+                " (not created yet)"
+            }
+        );
+    }
+    Ok(())
+}
+
+fn recovery_sheet(
+    user_id: Option<String>,
+    output: Option<PathBuf>,
+    context: &Context,
+) -> Result<()> {
+    if let Some(user_id) = &user_id {
+        UserID::from_str(user_id)?;
+    }
+    let sheet = RecoverySheet::render(
+        user_id.as_deref(),
+        &ProfileParams::new(context.scheme()?, &context.kdf_params()?),
+        &Catalog::load(&Catalog::default_path()?)?,
+        &ProfileStore::load(&ProfileStore::default_path()?)?,
+    );
+    match output {
+        Some(path) => {
+            fs::write(&path, sheet)?;
+            eprintln!("Recovery sheet written to {}", path.display());
+        }
+        None => print!("{}", sheet),
+    }
+    Ok(())
+}
+
+fn jsonrpc(context: &Context) -> Result<()> {
+    let mut server = JsonRpcServer::new().with_kdf_params(context.kdf_params()?);
+    if let Some(path) = &context.audit_log {
+        server = server.with_audit(Arc::new(AuditLog::new(path)), "jsonrpc");
+    }
+    PanicGuard::register(server.session());
+    server.serve(std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+fn derive_template(
+    template: &Template,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let password = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_template(&service_id, generation, template)?
+        .expose_secret()
+        .to_owned();
+    if raw {
+        print!("{}", password);
+        std::io::stdout().flush()?;
+    } else {
+        println!("Service password: >>> {} <<<", password);
+    }
+    Ok(())
+}
+
+fn username(
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    style: UsernameStyle,
+    length: u16,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let username = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_username(
+            &service_id,
+            generation,
+            &PasswordLength::from_str(&length.to_string())?,
+            style,
+        )?
+        .expose_secret()
+        .to_owned();
+    if raw {
+        print!("{}", username);
+        std::io::stdout().flush()?;
+    } else {
+        println!("Service username: >>> {} <<<", username);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn totp(
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    issuer: Option<&str>,
+    account: Option<&str>,
+    uri: bool,
+    qr: bool,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let seed = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_totp_seed(&service_id, generation)?;
+    let issuer = issuer.map_or_else(|| service_id.to_string(), str::to_owned);
+    let output = if uri || qr {
+        seed.uri(&issuer, account)
+    } else {
+        seed.base32()
+    };
+    if raw {
+        print!("{}", output.as_str());
+        std::io::stdout().flush()?;
+    } else if qr {
+        println!("{}", Output::qr(&output)?);
+    } else if uri {
+        println!("Provisioning URI: >>> {} <<<", output.as_str());
+    } else {
+        println!("TOTP seed: >>> {} <<<", output.as_str());
+    }
+    Ok(())
+}
+
+fn age(
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    recipient: bool,
+    format: Option<KeyFormat>,
+    output: Option<&Path>,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let identity = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_age_identity(&service_id, generation)?;
+    if recipient {
+        println!("{}", identity.recipient());
+        return Ok(());
+    }
+    if let Some(format) = format {
+        return write_key_pair(&identity.key_pair(&service_id.to_string()), format, output);
+    }
+    let file = Zeroizing::new(format!(
+        "# service: {} (generation {})\n# public key: {}\n{}\n",
+        service_id,
+        generation,
+        identity.recipient(),
+        identity.identity().as_str()
+    ));
+    match output {
+        Some(path) => {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options
+                .open(path)
+                .map_err(|e| anyhow!("Can not create {}: {}", path.display(), e))?
+                .write_all(file.as_bytes())?;
+            eprintln!("Identity written to {}", path.display());
+        }
+        None => print!("{}", file.as_str()),
+    }
+    Ok(())
+}
+
+fn mnemonic(
+    words: usize,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let phrase = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_mnemonic(&service_id, generation, words)?
+        .phrase();
+    if raw {
+        print!("{}", phrase.as_str());
+        std::io::stdout().flush()?;
+    } else {
+        for (i, word) in phrase.split(' ').enumerate() {
+            println!("{:>4}. {}", i + 1, word);
+        }
+    }
+    Ok(())
+}
+
+fn wifi(
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    format: WifiFormat,
+    ssid: Option<String>,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let psk = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_wifi_psk(&service_id, generation)?
+        .expose_secret()
+        .to_owned();
+    let ssid = ssid.unwrap_or_else(|| service_id.to_string());
+    if raw {
+        print!("{}", psk);
+        std::io::stdout().flush()?;
+    } else if format == WifiFormat::Psk {
+        println!("Wi-Fi PSK: >>> {} <<<", psk);
+    } else {
+        println!("{}", Wifi::render(format, &ssid, &psk)?);
+    }
+    Ok(())
+}
+
+fn wireguard(
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    public: bool,
+    format: Option<KeyFormat>,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let keys = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_wireguard_keys(&service_id, generation)?;
+    if let Some(format) = format {
+        write_key_pair(&keys.key_pair(&service_id.to_string()), format, None)?;
+    } else if public {
+        println!("{}", keys.public_key());
+    } else {
+        println!("PrivateKey = {}", keys.private_key().as_str());
+        println!("# PublicKey = {}", keys.public_key());
+    }
+    Ok(())
+}
+
+fn ssh_key(
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    format: KeyFormat,
+    output: Option<&Path>,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let identity = SshIdentity::derive(
+        &*session.lock().map_err(|_| DerivePassError::Secret)?,
+        &service_id.to_string(),
+        generation.as_usize(),
+    )?;
+    write_key_pair(&identity.key_pair(), format, output)
+}
+
+/// Writes the private key to `output` and the public key to `output.pub`,
+/// or prints both.
+fn write_key_pair(key_pair: &KeyPair, format: KeyFormat, output: Option<&Path>) -> Result<()> {
+    let private_key = key_pair.private_key(format)?;
+    let public_key = key_pair.public_key(format)?;
+    match output {
+        Some(path) => {
+            let public_path = PathBuf::from(format!("{}.pub", path.display()));
+            for path in [path, public_path.as_path()] {
+                if path.exists() {
+                    bail!("{} already exists", path.display());
+                }
+            }
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            options.open(path)?.write_all(private_key.as_bytes())?;
+            fs::write(&public_path, public_key)?;
+            eprintln!(
+                "Private key written to {}, public key to {}",
+                path.display(),
+                public_path.display()
+            );
+        }
+        None => print!("{}{}", private_key.as_str(), public_key),
+    }
+    Ok(())
+}
+
+fn key(
+    bytes: u16,
+    encoding: KeyEncoding,
+    prefix: &str,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let key = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_key_material(
+            &service_id,
+            generation,
+            &PasswordLength::from_str(&bytes.to_string())?,
+            &Purpose::key(),
+        )?;
+    let encoded = Zeroizing::new(format!("{}{}", prefix, key.encode(encoding).as_str()));
+    if raw {
+        print!("{}", encoded.as_str());
+        std::io::stdout().flush()?;
+    } else {
+        println!("Service key: >>> {} <<<", encoded.as_str());
+    }
+    Ok(())
+}
+
+fn seed(
+    bytes: u16,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let seed = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_key_material(
+            &service_id,
+            generation,
+            &PasswordLength::from_str(&bytes.to_string())?,
+            &Purpose::rng_seed(),
+        )?
+        .encode(KeyEncoding::Hex);
+    if raw {
+        print!("{}", seed.as_str());
+        std::io::stdout().flush()?;
+    } else {
+        println!("Seed: >>> {} <<<", seed.as_str());
+    }
+    Ok(())
+}
+
+fn recovery_codes(
+    count: u32,
+    length: u16,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let codes = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_recovery_codes(
+            &service_id,
+            generation,
+            count,
+            &PasswordLength::from_str(&length.to_string())?,
+        )?;
+    if raw {
+        for code in &codes {
+            println!("{}", code.expose_secret());
+        }
+    } else {
+        println!(
+            "Recovery codes of {} (generation {}), each usable once:\n",
+            service_id, generation
+        );
+        for (i, code) in codes.iter().enumerate() {
+            println!("{:>4}. {}", i + 1, code.expose_secret());
+        }
+    }
+    Ok(())
+}
+
+fn answer(
+    wordlist: &Path,
+    question: Option<String>,
+    words: u16,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let wordlist = Wordlist::load(wordlist)?;
+    eprintln!(
+        "Wordlist {} ({} words), check that it is the one you used before",
+        wordlist.fingerprint_hex(),
+        wordlist.words().len()
+    );
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let question = match question {
+        Some(question) => question,
+        None => Input::<String>::new()
+            .with_prompt("Security question")
+            .interact_text()?,
+    };
+    eprintln!("Question: {}", DerivedPass::normalize_question(&question));
+    let answer = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_answer(
+            &service_id,
+            generation,
+            &wordlist,
+            &question,
+            &PasswordLength::from_str(&words.to_string())?,
+        )?
+        .expose_secret()
+        .to_owned();
+    if raw {
+        print!("{}", answer);
+        std::io::stdout().flush()?;
+    } else {
+        println!("Answer: >>> {} <<<", answer);
+    }
+    Ok(())
+}
+
+fn passphrase(
+    wordlist: &Path,
+    words: u16,
+    separator: &str,
+    service_id: Option<ServiceID>,
+    generation: &Generation,
+    raw: bool,
+    context: &Context,
+) -> Result<()> {
+    let wordlist = Wordlist::load(wordlist)?;
+    eprintln!(
+        "Wordlist {} ({} words), check that it is the one you used before",
+        wordlist.fingerprint_hex(),
+        wordlist.words().len()
+    );
+    let session = context.unlock_session("cli")?;
+    let service_id = service_id.map_or_else(UserInputCli::prompt_service_id, Ok)?;
+    let passphrase = session
+        .lock()
+        .map_err(|_| DerivePassError::Secret)?
+        .derive_passphrase(
+            &service_id,
+            generation,
+            &wordlist,
+            &PasswordLength::from_str(&words.to_string())?,
+            separator,
+        )?
+        .expose_secret()
+        .to_owned();
+    if raw {
+        print!("{}", passphrase);
+        std::io::stdout().flush()?;
+    } else {
+        println!("Service passphrase: >>> {} <<<", passphrase);
+    }
+    Ok(())
+}
+
+fn policy(action: PolicyAction) -> Result<()> {
+    match action {
+        PolicyAction::Show { service } => {
+            match SitePolicies::load(&SitePolicies::default_path()?)?.find(&service) {
+                Some(site) => println!("{}: {}", site.domain, site.policy),
+                None => println!("No password policy is known for {}", service),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn recipe(action: RecipeAction, context: &Context) -> Result<()> {
+    let path = RecipeVault::default_path()?;
+    let session = context.unlock_session("recipe")?;
+    let session = session.lock().map_err(|_| DerivePassError::Secret)?;
+    let mut vault = RecipeVault::open(&path, &session)?;
+    match action {
+        RecipeAction::Set {
+            service,
+            generation,
+            length,
+            char_sets,
+            username,
+            notes,
+        } => {
+            let service_id = service.to_string();
+            let stored = vault.get(&service_id).cloned();
+            let defaults = DerivationDefaults { length, char_sets };
+            let entry = RecipeEntry {
+                generation: generation
+                    .map(usize::from)
+                    .or(stored.as_ref().map(|e| e.generation))
+                    .unwrap_or(1),
+                password_length: defaults
+                    .length
+                    .or(stored.as_ref().map(|e| e.password_length))
+                    .unwrap_or_else(|| defaults.length(&context.config)),
+                char_sets: defaults
+                    .char_sets
+                    .clone()
+                    .or(stored.as_ref().map(|e| e.char_sets.clone()))
+                    .unwrap_or_else(|| defaults.char_sets(&context.config)),
+                policy: SitePolicies::load(&SitePolicies::default_path()?)?
+                    .find(&service_id)
+                    .map(|site| site.policy.clone())
+                    .or(stored.as_ref().and_then(|e| e.policy.clone())),
+                username: username.or(stored.as_ref().and_then(|e| e.username.clone())),
+                notes: notes.or(stored.as_ref().and_then(|e| e.notes.clone())),
+                pending_rotation: stored.is_some_and(|e| e.pending_rotation),
+                service_id,
+            };
+            entry.derive(&session)?;
+            eprintln!("Saved the recipe of {}", entry.service_id);
+            vault.set(entry);
+            vault.save(&path)?;
+        }
+        RecipeAction::Show { service, raw } => {
+            let Some(entry) = vault.get(&service.to_string()) else {
+                bail!("No recipe is stored for {}", service);
+            };
+            let password = Zeroizing::new(entry.derive(&session)?.expose_secret().to_owned());
+            if raw {
+                print!("{}", password.as_str());
+                std::io::stdout().flush()?;
+            } else {
+                print_recipe(entry);
+                println!("Service password: >>> {} <<<", password.as_str());
+            }
+        }
+        RecipeAction::List => {
+            for entry in vault.entries() {
+                print_recipe(entry);
+            }
+        }
+        RecipeAction::ExportLesspass { output } => {
+            let (profiles, skipped) = LessPassExporter::profiles(
+                vault.entries(),
+                &Catalog::load(&Catalog::default_path()?)?,
+            );
+            let json = serde_json::to_string_pretty(&profiles)?;
+            match output {
+                Some(path) => fs::write(&path, json + "\n")?,
+                None => println!("{}", json),
+            }
+            for service_id in skipped {
+                eprintln!(
+                    "Skipped {}: LessPass has no such length or character sets",
+                    service_id
+                );
+            }
+        }
+        RecipeAction::ExportPass { store, prefix } => {
+            let exporter = PassExporter::new(&match store {
+                Some(store) => store,
+                None => PassExporter::default_store()?,
+            })?;
+            let mut exported = 0;
+            for entry in vault.entries() {
+                if entry.pending_rotation {
+                    eprintln!(
+                        "Skipped {}: the site still has its old password",
+                        entry.service_id
+                    );
+                    continue;
+                }
+                let password = entry.derive(&session)?;
+                exporter.write(
+                    &PassExporter::entry_name(&prefix, &entry.service_id),
+                    &PassExporter::content(password.expose_secret(), entry),
+                )?;
+                exported += 1;
+            }
+            eprintln!("Exported {} passwords", exported);
+        }
+        RecipeAction::ImportCsv { files } => {
+            let policies = SitePolicies::load(&SitePolicies::default_path()?)?;
+            let defaults = DerivationDefaults {
+                length: None,
+                char_sets: None,
+            };
+            let mut added = 0;
+            for file in files {
+                let content = Zeroizing::new(fs::read_to_string(file)?);
+                for account in CsvImporter::entries(&content)? {
+                    if vault.get(&account.service_id).is_some() {
+                        continue;
+                    }
+                    let policy = policies
+                        .find(&account.service_id)
+                        .map(|site| site.policy.clone());
+                    let max_length = policy.as_ref().and_then(|policy| policy.max_length);
+                    vault.set(RecipeEntry {
+                        generation: 1,
+                        password_length: defaults
+                            .length(&context.config)
+                            .min(max_length.unwrap_or(u16::MAX)),
+                        char_sets: defaults.char_sets(&context.config),
+                        policy,
+                        username: account.username,
+                        notes: None,
+                        pending_rotation: true,
+                        service_id: account.service_id,
+                    });
+                    added += 1;
+                }
+            }
+            vault.save(&path)?;
+            eprintln!("Added {} recipes\n", added);
+            let pending: Vec<&RecipeEntry> = vault
+                .entries()
+                .iter()
+                .filter(|e| e.pending_rotation)
+                .collect();
+            println!(
+                "Migration checklist, {} services still have their old password:",
+                pending.len()
+            );
+            for entry in pending {
+                println!(
+                    "- [ ] {}{}",
+                    entry.service_id,
+                    entry
+                        .username
+                        .as_ref()
+                        .map(|username| format!(" ({})", username))
+                        .unwrap_or_default()
+                );
+            }
+            println!(
+                "Change each with `depasswd rotate SERVICE`, it shows the derived password to set."
+            );
+        }
+        RecipeAction::ExportKdbx { output } => {
+            let mut entries = vec![];
+            for entry in vault.entries() {
+                if entry.pending_rotation {
+                    eprintln!(
+                        "Skipped {}: the site still has its old password",
+                        entry.service_id
+                    );
+                    continue;
+                }
+                entries.push(KdbxEntry {
+                    title: entry.service_id.clone(),
+                    username: entry.username.clone(),
+                    password: Zeroizing::new(entry.derive(&session)?.expose_secret().to_owned()),
+                    url: Catalog::canonical_service_id(&format!("https://{}", entry.service_id))
+                        .filter(|domain| *domain == entry.service_id)
+                        .map(|domain| format!("https://{}", domain)),
+                    notes: Some(format!(
+                        "depasswd generation {}{}",
+                        entry.generation,
+                        entry
+                            .notes
+                            .as_ref()
+                            .map(|notes| format!("\n{}", notes))
+                            .unwrap_or_default()
+                    )),
+                });
+            }
+            let passphrase = Zeroizing::new(
+                Password::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Passphrase of the KeePass database")
+                    .with_confirmation("Repeat the passphrase", "The passphrases do not match")
+                    .interact()?,
+            );
+            eprintln!("Encrypting {} entries...", entries.len());
+            let database = KdbxWriter::DEFAULT.write("depasswd", &entries, &passphrase)?;
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&output)
+                .and_then(|mut file| file.write_all(&database))
+                .map_err(|e| anyhow!("Can not create {}: {}", output.display(), e))?;
+            eprintln!("Wrote {}", output.display());
+        }
+        RecipeAction::Sync { remote } => {
+            let Some(remote) = remote.or_else(|| context.config.recipe_sync_remote.clone()) else {
+                bail!("Set recipe_sync_remote in the config or pass --remote");
+            };
+            let sync = RecipeSync::new(&remote)?;
+            let report = sync.sync(&path, &session)?;
+            eprintln!(
+                "{}, {}",
+                if report.pulled {
+                    "Merged recipes from the remote"
+                } else {
+                    "Nothing new on the remote"
+                },
+                if report.pushed {
+                    "pushed the local changes"
+                } else {
+                    "nothing to push"
+                }
+            );
+        }
+        RecipeAction::Remove { service } => {
+            if !vault.remove(&service.to_string()) {
+                bail!("No recipe is stored for {}", service);
+            }
+            vault.save(&path)?;
+            eprintln!("Removed the recipe of {}", service);
+        }
+    }
+    Ok(())
+}
+
+fn rotate(service: &ServiceID, context: &Context) -> Result<()> {
+    let path = RecipeVault::default_path()?;
+    let session = context.unlock_session("rotate")?;
+    let session = session.lock().map_err(|_| DerivePassError::Secret)?;
+    let mut vault = RecipeVault::open(&path, &session)?;
+    let rotation = vault.rotate(service, &session)?;
+    vault.save(&path)?;
+    if let Some(old_password) = &rotation.old_password {
+        println!(
+            "Old password (generation {}): >>> {} <<<",
+            rotation.generation - 1,
+            old_password.expose_secret()
+        );
+    }
+    println!(
+        "New password (generation {}): >>> {} <<<",
+        rotation.generation,
+        rotation.new_password.expose_secret()
+    );
+    match rotation.old_password {
+        Some(_) => eprintln!(
+            "The recipe vault now derives generation {}",
+            rotation.generation
+        ),
+        // The old password is in the password manager it was imported from.
+        None => eprintln!(
+            "The recipe vault no longer marks {} as not rotated",
+            service
+        ),
+    }
+    Ok(())
+}
+
+fn find(query: &str, catalog_only: bool, context: &Context) -> Result<()> {
+    let mut matcher = ServiceMatcher::from_catalog()?;
+    let path = RecipeVault::default_path()?;
+    if !catalog_only && path.exists() {
+        let session = context.unlock_session("find")?;
+        let session = session.lock().map_err(|_| DerivePassError::Secret)?;
+        matcher.add_vault(&RecipeVault::open(&path, &session)?);
+    }
+    let matches = matcher.find(query);
+    if matches.is_empty() {
+        bail!("No known service identifier resembles {}", query);
+    }
+    for service_id in matches {
+        println!("{}", service_id);
+    }
+    Ok(())
+}
+
+fn print_recipe(entry: &RecipeEntry) {
+    println!(
+        "{}{}: generation {}, length {}, {}{}{}",
+        entry.service_id,
+        entry
+            .username
+            .as_ref()
+            .map(|username| format!(" ({})", username))
+            .unwrap_or_default(),
+        entry.generation,
+        entry.password_length,
+        entry.char_sets.join(","),
+        entry
+            .policy
+            .as_ref()
+            .map(|policy| format!(", policy {}", policy))
+            .unwrap_or_default(),
+        if entry.pending_rotation {
+            ", not yet rotated"
+        } else {
+            ""
+        }
+    );
+    if let Some(notes) = &entry.notes {
+        println!("    {}", notes);
+    }
+}
+
+fn import(source: ImportSource) -> Result<()> {
+    let (labels, entries, yes): (Vec<String>, Vec<CatalogEntry>, bool) = match source {
+        ImportSource::Bookmarks { files, yes } => {
+            let mut urls = vec![];
+            for file in files {
+                urls.extend(BookmarkImporter::urls(&fs::read(file)?)?);
+            }
+            let proposals = ImportProposal::from_urls(urls.iter().map(String::as_str));
+            let labels = proposals
+                .iter()
+                .map(|p| format!("{} ({} links)", p.entry.service_id, p.occurrences))
+                .collect();
+            (
+                labels,
+                proposals.into_iter().map(|p| p.entry).collect(),
+                yes,
+            )
+        }
+        ImportSource::Bitwarden { file, yes } => {
+            let entries = BitwardenImporter::entries(&fs::read_to_string(file)?)?;
+            (account_labels(&entries), entries, yes)
+        }
+        ImportSource::Keepass { file, yes } => {
+            let entries = KeePassImporter::entries(&fs::read_to_string(file)?)?;
+            (account_labels(&entries), entries, yes)
+        }
+    };
+    if entries.is_empty() {
+        println!("No services found in the given files");
+        return Ok(());
+    }
+    let selected: Vec<usize> = if yes {
+        (0..entries.len()).collect()
+    } else {
+        MultiSelect::new()
+            .with_prompt("Choose the services to add to the catalog")
+            .items(&labels)
+            .defaults(&vec![true; labels.len()])
+            .interact()?
+    };
+    let path = Catalog::default_path()?;
+    let mut catalog = Catalog::load(&path)?;
+    let added = selected
+        .into_iter()
+        .filter(|i| catalog.add(entries[*i].clone()))
+        .count();
+    catalog.save(&path)?;
+    println!("Added {} entries to {}", added, path.display());
+    Ok(())
+}
+
+fn derive_variants(args: &DeriveArgs, context: &Context, mut options: OutputOptions) -> Result<()> {
+    let user_input = Arc::new(Mutex::new(UserInputCli::new(
+        &context.config,
+        context.master_password(),
+    )?));
+    PanicGuard::register(&user_input);
+    let reports = {
+        let user_input = user_input.lock().map_err(|_| DerivePassError::Secret)?;
+        let mut lengths = vec![user_input.get_password_length().clone()];
+        for length in &args.variants {
+            let length = PasswordLength::from_str(&length.to_string())?;
+            if !lengths.iter().any(|l| l.as_u16() == length.as_u16()) {
+                lengths.push(length);
+            }
+        }
+        let char_set = user_input.get_char_set();
+        let without_special = if char_set.contains_any(SPECIAL_CHARS) {
+            char_set.without(SPECIAL_CHARS).ok()
+        } else {
+            None
+        };
+        let mut labels = vec![];
+        let mut variants = vec![];
+        for length in lengths {
+            labels.push(format!("{} characters", length));
+            variants.push((char_set.clone(), length.clone()));
+            if let Some(without_special) = &without_special {
+                labels.push(format!("{} characters, no special", length));
+                variants.push((without_special.clone(), length));
+            }
+        }
+        // Variants the site policy rules out are left out.
+        let policy = user_input.get_policy();
+        let (labels, variants): (Vec<_>, Vec<_>) = labels
+            .into_iter()
+            .zip(variants)
+            .filter(|(_, (char_set, length))| {
+                policy.is_none_or(|policy| policy.shape(char_set, length).is_ok())
+            })
+            .unzip();
+        let session = context.unlock(
+            user_input.get_user_id(),
+            user_input.get_master_password_plain(),
+            "cli",
+        )?;
+        options.salt_key = salt_key(args.format, &session)?;
+        let derived = session.derive_variants(
+            user_input.get_service_id(),
+            user_input.get_generation(),
+            &variants,
+            policy,
+        )?;
+        labels
+            .iter()
+            .zip(variants)
+            .zip(derived)
+            .map(|((label, (char_set, _)), derived_pass)| {
+                DerivationReport::new(
+                    label,
+                    &user_input.get_service_id().to_string(),
+                    user_input.get_generation().as_usize(),
+                    &char_set,
+                    Zeroizing::new(derived_pass.expose_secret().to_owned()),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    print_reports(args, &reports, &options)
+}
+
+/// The `shadow` format salts with a key of the session, so that deriving
+/// again prints the same hash. Other formats need no key.
+fn salt_key(format: OutputFormat, session: &Session) -> Result<Option<Zeroizing<[u8; 32]>>> {
+    Ok(match format {
+        OutputFormat::Shadow => Some(session.derive_seed(b"shadow-salt")?),
+        _ => None,
+    })
+}
+
+/// Prints the passwords in the `--format` of `args`, or the full reports
+/// with `--output json`.
+fn print_reports(
+    args: &DeriveArgs,
+    reports: &[DerivationReport],
+    options: &OutputOptions,
+) -> Result<()> {
+    if args.output == OutputMode::Json {
+        println!("{}", Output::json(reports)?);
+        return Ok(());
+    }
+    let secrets: Vec<(String, &str)> = reports
+        .iter()
+        .map(|r| (r.name.clone(), r.password.as_str()))
+        .collect();
+    println!("{}", Output::render(args.format, &secrets, options)?);
+    Ok(())
+}
+
+fn exec(
+    mappings: &[SecretMapping],
+    defaults: &DerivationDefaults,
+    files: bool,
+    command: &[String],
+    context: &Context,
+) -> Result<()> {
+    let batch = Batch {
+        derivations: mappings
+            .iter()
+            .map(|m| {
+                m.request(
+                    &defaults.char_sets(&context.config),
+                    defaults.length(&context.config),
+                )
+            })
+            .collect(),
+    };
+    let session = context.unlock_session("exec")?;
+    let secrets: Vec<(String, Zeroizing<String>)> = {
+        let session = session.lock().map_err(|_| DerivePassError::Secret)?;
+        batch
+            .derive_all(&session)?
+            .into_iter()
+            .map(|(var, derived_pass)| {
+                (var, Zeroizing::new(derived_pass.expose_secret().to_owned()))
+            })
+            .collect()
+    };
+    drop(session);
+    if files {
+        let dir = Exec::secret_dir()?;
+        let status = Exec::command(command, &secrets, &SecretDelivery::Files(dir.clone()))
+            .and_then(|mut child| Ok(child.status()?));
+        // `exit` skips destructors, the secrets are wiped here.
+        drop(secrets);
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            eprintln!(
+                "Can not remove the secret directory {}: {}",
+                dir.display(),
+                e
+            );
+        }
+        std::process::exit(status?.code().unwrap_or(1));
+    }
+    let mut child = Exec::command(command, &secrets, &SecretDelivery::Environment)?;
+    drop(secrets);
+    #[cfg(unix)]
+    {
+        Err(std::os::unix::process::CommandExt::exec(&mut child).into())
+    }
+    #[cfg(not(unix))]
+    {
+        std::process::exit(child.status()?.code().unwrap_or(1));
+    }
+}
+
+fn vault(
+    listen: &str,
+    mount: &str,
+    defaults: &DerivationDefaults,
+    context: &Context,
+) -> Result<()> {
+    let (http_server, address) = VaultServer::bind(listen)?;
+    let server = VaultServer::new(
+        context.unlock_session("vault")?,
+        mount,
+        &defaults.char_sets(&context.config),
+        defaults.length(&context.config),
+    );
+    eprintln!(
+        "Listening on {}\nexport VAULT_ADDR=http://{} VAULT_TOKEN={}",
+        address,
+        address,
+        server.token()
+    );
+    server.serve(http_server)
+}
+
+fn serve(
+    listen: &str,
+    allowed_origins: &[String],
+    defaults: &DerivationDefaults,
+    context: &Context,
+) -> Result<()> {
+    let (http_server, address) = RestServer::bind(listen)?;
+    let server = RestServer::new(
+        context.unlock_session("serve")?,
+        allowed_origins,
+        &defaults.char_sets(&context.config),
+        defaults.length(&context.config),
+    );
+    eprintln!(
+        "Listening on http://{}\nAuthorization: Bearer {}",
+        address,
+        server.token()
+    );
+    server.serve(http_server)
+}
+
+#[cfg(unix)]
+fn secret_service(defaults: &DerivationDefaults, context: &Context) -> Result<()> {
+    use depasswd::{dbus::Connection, secret_service::SecretService};
+
+    let catalog = Catalog::load(&Catalog::default_path()?)?;
+    let mut connection = Connection::session()?;
+    SecretService::claim_name(&mut connection)?;
+    let mut service = SecretService::new(
+        context.unlock_session("secret-service")?,
+        catalog.entries(),
+        &defaults.char_sets(&context.config),
+        defaults.length(&context.config),
+    );
+    eprintln!(
+        "Providing org.freedesktop.secrets for {} catalog entries",
+        catalog.entries().len()
+    );
+    service.serve(connection)
+}
+
+#[cfg(not(unix))]
+fn secret_service(_: &DerivationDefaults, _: &Context) -> Result<()> {
+    bail!("The Secret Service provider is only available on Unix")
+}
+
+fn lock(context: &Context) -> Result<()> {
+    let forgotten = keychain::open(&context.config)?.forget(None)?;
+    eprintln!("Deleted {} cached master secrets", forgotten);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn agent(
+    ssh_keys: &[SshKeySpec],
+    socket: Option<PathBuf>,
+    rpc_socket: Option<PathBuf>,
+    ttl: u64,
+    context: &Context,
+) -> Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    use depasswd::{ssh_agent::SshAgent, systemd::Systemd, utils::Utils};
+
+    let ssh_keys = if ssh_keys.is_empty() {
+        context.config.ssh_keys()?
+    } else {
+        ssh_keys.to_vec()
+    };
+    let mut activated = Systemd::listeners()?;
+    let ssh_listener = activated
+        .iter()
+        .position(|(name, _)| name == "ssh")
+        .map(|i| activated.remove(i).1);
+    let rpc_listener = activated
+        .iter()
+        .position(|(name, _)| name == "rpc")
+        .or((!activated.is_empty()).then_some(0))
+        .map(|i| activated.remove(i).1);
+    // The path of an activated socket, else the given or default one.
+    let socket_path = |listener: &Option<UnixListener>,
+                       socket: Option<PathBuf>,
+                       flag: &str,
+                       name: &str|
+     -> Result<PathBuf> {
+        match listener
+            .as_ref()
+            .and_then(|l| l.local_addr().ok())
+            .and_then(|a| a.as_pathname().map(PathBuf::from))
+            .or(socket)
+        {
+            Some(path) => Ok(path),
+            None => Ok(Utils::runtime_dir(flag)?.join(name)),
+        }
+    };
+
+    if !Utils::disable_core_dumps() {
+        eprintln!("Can not disable core dumps, the master secret may be written to disk");
+    }
+    let server = agent_server(ttl, context)?;
+
+    let ssh_agent = if ssh_keys.is_empty() {
+        None
+    } else {
+        let socket = socket_path(&ssh_listener, socket, "--socket", "depasswd-ssh-agent.sock")?;
+        let agent = SshAgent::new(server.session().clone(), &ssh_keys)?;
+        for authorized_key in agent.authorized_keys() {
+            eprintln!("{}", authorized_key);
+        }
+        println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", socket.display());
+        Some((agent, socket))
+    };
+    let rpc_socket = socket_path(
+        &rpc_listener,
+        rpc_socket,
+        "--rpc-socket",
+        "depasswd-agent.sock",
+    )?;
     println!(
-        "{}",
-        r##"
+        "DEPASSWD_AGENT_SOCK={}; export DEPASSWD_AGENT_SOCK;",
+        rpc_socket.display()
+    );
+    std::thread::scope(|scope| {
+        if let Some((agent, socket)) = &ssh_agent {
+            scope.spawn(move || {
+                let served = match ssh_listener {
+                    Some(listener) => agent.serve_listener(listener),
+                    None => agent.serve(socket),
+                };
+                if let Err(e) = served {
+                    eprintln!("The ssh-agent stopped: {}", e);
+                }
+            });
+        }
+        match rpc_listener {
+            Some(listener) => server.serve_listener(listener),
+            None => server.serve_unix(&rpc_socket),
+        }
+    })
+}
+
+#[cfg(windows)]
+fn agent(
+    ssh_keys: &[SshKeySpec],
+    pipe: Option<PathBuf>,
+    rpc_pipe: Option<PathBuf>,
+    ttl: u64,
+    context: &Context,
+) -> Result<()> {
+    use depasswd::{platform::windows::NamedPipe, ssh_agent::SshAgent};
+
+    let ssh_keys = if ssh_keys.is_empty() {
+        context.config.ssh_keys()?
+    } else {
+        ssh_keys.to_vec()
+    };
+    let pipe_path = |pipe: Option<PathBuf>, name: &str| {
+        pipe.map(|p| p.display().to_string())
+            .unwrap_or_else(|| NamedPipe::path(name))
+    };
+    let server = agent_server(ttl, context)?;
+
+    let ssh_agent = if ssh_keys.is_empty() {
+        None
+    } else {
+        let pipe = pipe_path(pipe, "depasswd-ssh-agent");
+        let agent = SshAgent::new(server.session().clone(), &ssh_keys)?;
+        for authorized_key in agent.authorized_keys() {
+            eprintln!("{}", authorized_key);
+        }
+        println!("$env:SSH_AUTH_SOCK = '{}'", pipe);
+        Some((agent, pipe))
+    };
+    let rpc_pipe = pipe_path(rpc_pipe, "depasswd-agent");
+    println!("$env:DEPASSWD_AGENT_SOCK = '{}'", rpc_pipe);
+    std::thread::scope(|scope| {
+        if let Some((agent, pipe)) = &ssh_agent {
+            scope.spawn(move || {
+                if let Err(e) = agent.serve_pipe(pipe) {
+                    eprintln!("The ssh-agent stopped: {}", e);
+                }
+            });
+        }
+        server.serve_pipe(&rpc_pipe)
+    })
+}
+
+/// Unlocks the session of the agent and keeps it out of swap.
+#[cfg(any(unix, windows))]
+fn agent_server(ttl: u64, context: &Context) -> Result<JsonRpcServer> {
+    use std::time::Duration;
+
+    let session = Arc::try_unwrap(context.unlock_session("agent")?)
+        .ok()
+        .and_then(|session| session.into_inner().ok())
+        .ok_or(DerivePassError::Secret)?;
+    if !session.lock_in_memory() {
+        eprintln!("Can not lock the master secret in memory, it may be swapped to disk");
+    }
+    let pepper = context.pepper(session.user_id())?;
+    let mut server = JsonRpcServer::new()
+        .with_session(session)
+        .with_kdf_params(context.kdf_params()?)
+        .with_scheme(context.scheme()?);
+    if let Some(pepper) = pepper {
+        server = server.with_pepper(pepper);
+    }
+    if let Some(keyfile) = context.keyfile()? {
+        server = server.with_keyfile(keyfile);
+    }
+    if ttl > 0 {
+        server = server.with_ttl(Duration::from_secs(ttl));
+    }
+    PanicGuard::register(server.session());
+    Ok(server)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn agent(
+    _: &[SshKeySpec],
+    _: Option<PathBuf>,
+    _: Option<PathBuf>,
+    _: u64,
+    _: &Context,
+) -> Result<()> {
+    bail!("The agent is only available on Unix and Windows")
+}
+
+#[cfg(unix)]
+fn browser_server(
+    socket: Option<PathBuf>,
+    allow_associate: Option<u64>,
+    defaults: &DerivationDefaults,
+    context: &Context,
+) -> Result<()> {
+    use depasswd::browser::BrowserServer;
+    use std::time::Duration;
+
+    let socket = match socket {
+        Some(socket) => socket,
+        None => BrowserServer::default_socket_path()?,
+    };
+    let mut server = BrowserServer::new(
+        context.unlock_session("browser")?,
+        &defaults.char_sets(&context.config),
+        defaults.length(&context.config),
+        Catalog::default_path()?,
+        BrowserServer::default_associations_path()?,
+    );
+    if let Some(seconds) = allow_associate {
+        server = server.allow_associate(Duration::from_secs(seconds));
+        eprintln!(
+            "Accepting one new browser association for {} seconds",
+            seconds
+        );
+    }
+    eprintln!("Listening on {}", socket.display());
+    server.serve(&socket)
+}
+
+#[cfg(unix)]
+fn browser_proxy(socket: Option<PathBuf>) -> Result<()> {
+    use depasswd::browser::{BrowserProxy, BrowserServer};
+
+    let socket = match socket {
+        Some(socket) => socket,
+        None => BrowserServer::default_socket_path()?,
+    };
+    BrowserProxy::relay(&socket)
+}
+
+#[cfg(not(unix))]
+fn browser_server(
+    _: Option<PathBuf>,
+    _: Option<u64>,
+    _: &DerivationDefaults,
+    _: &Context,
+) -> Result<()> {
+    bail!("The browser integration is only available on Unix")
+}
+
+#[cfg(not(unix))]
+fn browser_proxy(_: Option<PathBuf>) -> Result<()> {
+    bail!("The browser integration is only available on Unix")
+}
+
+impl Context {
+    /// Folds the character set flags into the config, where every prompt and
+    /// default of a derivation reads them from.
+    fn apply_char_set_flags(&mut self) {
+        if let Some(chars) = &self.chars {
+            let repeats = CharSet::repeats(chars);
+            if !repeats.is_empty() {
+                eprintln!(
+                    "Warning: {} repeated in --chars, each is used once",
+                    repeats
+                );
+            }
+            self.config.char_sets = Some(vec![format!("{}{}", CUSTOM_CHAR_SET_PREFIX, chars)]);
+        }
+        if self.layout_safe {
+            let names = self
+                .config
+                .char_sets
+                .take()
+                .unwrap_or_else(|| CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect());
+            self.config.char_sets = Some(
+                names
+                    .into_iter()
+                    .map(|name| {
+                        if name == "special" {
+                            LAYOUT_SAFE.to_owned()
+                        } else {
+                            name
+                        }
+                    })
+                    .collect(),
+            );
+        }
+        let mut modifiers = vec![];
+        if self.exclude_ambiguous {
+            modifiers.push(EXCLUDE_AMBIGUOUS.to_owned());
+        }
+        if let Some(chars) = &self.exclude_chars {
+            modifiers.push(format!("{}{}", EXCLUDE_CHARS_PREFIX, chars));
+        }
+        if !modifiers.is_empty() {
+            let mut names = self
+                .config
+                .char_sets
+                .take()
+                .unwrap_or_else(|| CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect());
+            for modifier in modifiers {
+                if !names.contains(&modifier) {
+                    names.push(modifier);
+                }
+            }
+            self.config.char_sets = Some(names);
+        }
+    }
+    /// The scheme to unlock with. A profile pins its scheme, so selecting a
+    /// different one explicitly is an error rather than a silent override.
+    fn scheme(&self) -> Result<SchemeVersion> {
+        let profile_scheme = match &self.profile {
+            Some(name) => ProfileStore::load(&ProfileStore::default_path()?)?.scheme(name)?,
+            None => None,
+        };
+        match (self.scheme, profile_scheme) {
+            (Some(scheme), Some(pinned)) if scheme != pinned => bail!(
+                "Profile {} uses scheme {}, it can not be used with --scheme {}",
+                self.profile.as_deref().unwrap_or_default(),
+                pinned,
+                scheme
+            ),
+            (Some(scheme), _) => Ok(scheme),
+            (None, Some(pinned)) => Ok(pinned),
+            (None, None) => Ok(self.config.scheme.unwrap_or_default()),
+        }
+    }
+    /// The KDF parameters to unlock with, pinned by a profile like the
+    /// scheme. Otherwise flags override the config field by field.
+    fn kdf_params(&self) -> Result<KdfParams> {
+        let profile_params = match &self.profile {
+            Some(name) => ProfileStore::load(&ProfileStore::default_path()?)?.kdf_params(name)?,
+            None => None,
+        };
+        let base = self
+            .kdf
+            .or(profile_params)
+            .or(self.config.kdf)
+            .unwrap_or_default();
+        // Costs of one algorithm mean nothing to another
+        let base = match self.kdf_algorithm {
+            Some(algorithm) if algorithm != base.algorithm => KdfParams::default_for(algorithm),
+            _ => base,
+        };
+        let params = KdfParams::new(
+            base.algorithm,
+            self.kdf_memory.unwrap_or(base.m_cost),
+            self.kdf_iterations.unwrap_or(base.t_cost),
+            self.kdf_parallelism.unwrap_or(base.p_cost),
+        )?;
+        match profile_params {
+            Some(pinned) if pinned != params => bail!(
+                "Profile {} uses {}, it can not be used with {}",
+                self.profile.as_deref().unwrap_or_default(),
+                pinned,
+                params
+            ),
+            _ => Ok(params),
+        }
+    }
+    fn master_password(&self) -> MasterPasswordSource {
+        match (self.master_stdin, self.master_fd) {
+            (true, _) => MasterPasswordSource::Stdin,
+            (false, Some(fd)) => MasterPasswordSource::Fd(fd),
+            (false, None) if self.pinentry || self.config.pinentry == Some(true) => {
+                MasterPasswordSource::Pinentry
+            }
+            (false, None) => MasterPasswordSource::Prompt,
+        }
+    }
+    fn config_path(&self) -> Result<PathBuf> {
+        match &self.config_file {
+            Some(path) => Ok(path.clone()),
+            None => Config::default_path(),
+        }
+    }
+    /// Prompts for the user id and master password only and unlocks a session
+    /// that is wiped if the process panics.
+    fn unlock_session(&self, client: &str) -> Result<Arc<Mutex<Session>>> {
+        if let Some(session) = self.cached_session(client)? {
+            let session = Arc::new(Mutex::new(session));
+            PanicGuard::register(&session);
+            return Ok(session);
+        }
+        let (user_id, master_password_plain) =
+            UserInputCli::prompt_credentials(&self.config, self.master_password())?;
+        let master_password_plain = Arc::new(Mutex::new(master_password_plain));
+        PanicGuard::register(&master_password_plain);
+        let session = {
+            let master_password_plain = master_password_plain
+                .lock()
+                .map_err(|_| DerivePassError::Secret)?;
+            Arc::new(Mutex::new(self.unlock(
+                &user_id,
+                &master_password_plain,
+                client,
+            )?))
+        };
+        PanicGuard::register(&session);
+        Ok(session)
+    }
+    /// Unlocks a session and, when a profile is selected, verifies its stored
+    /// parameters with it before anything is derived. Derivations are reported
+    /// to the audit log as coming from `client`. The keyfile is stretched with
+    /// the master password and the peppers are mixed into the master secret,
+    /// which is then cached in the keychain, when that is enabled.
+    fn unlock(
+        &self,
+        user_id: &UserID,
+        master_password_plain: &MasterPasswordPlain,
+        client: &str,
+    ) -> Result<Session> {
+        let mut session = Session::unlock_with_keyfile(
+            user_id,
+            master_password_plain,
+            self.scheme()?,
+            &self.kdf_params()?,
+            self.keyfile()?.as_ref(),
+        )?;
+        let pepper = self.pepper(user_id)?;
+        if let Some(pepper) = &pepper {
+            session = session.with_pepper(pepper)?;
+        }
+        if let Some(path) = &self.audit_log {
+            session = session.with_audit(Arc::new(AuditLog::new(path)), client);
+        }
+        if let Some(name) = &self.profile {
+            let path = ProfileStore::default_path()?;
+            let mut store = ProfileStore::load(&path)?;
+            if store.verify_or_create(name, &session)?.1 {
+                store.save(&path)?;
+                eprintln!("Created profile {} in {}", name, path.display());
+            }
+        }
+        self.cache_session(&session, pepper.as_ref());
+        Ok(session)
+    }
+    fn keyfile(&self) -> Result<Option<Keyfile>> {
+        self.keyfile
+            .as_ref()
+            .or(self.config.keyfile.as_ref())
+            .map(|path| Keyfile::from_file(path))
+            .transpose()
+    }
+    /// The systemd credential pepper, the static pepper of the config, the
+    /// gpg decrypted pepper file and the YubiKey response for `user_id`,
+    /// combined.
+    fn pepper(&self, user_id: &UserID) -> Result<Option<Pepper>> {
+        let mut asked = self.peppers.lock().unwrap_or_else(|e| e.into_inner());
+        let key = user_id.to_string();
+        if let Some((_, pepper)) = asked.iter().find(|(asked_for, _)| *asked_for == key) {
+            return Ok(pepper.clone());
+        }
+        let mut peppers: Vec<Pepper> = Pepper::from_credentials()?.into_iter().collect();
+        if let Some(source) = &self.config.pepper {
+            peppers.push(source.read()?);
+        }
+        if let Some(path) = &self.config.pepper_gpg_file {
+            peppers.push(Pepper::from_gpg_file(path)?);
+        }
+        if let Some(slot) = self.config.yubikey_slot {
+            peppers.push(YubiKey::new(slot)?.pepper(user_id)?);
+        }
+        let pepper = Pepper::combine(peppers);
+        asked.push((key, pepper.clone()));
+        Ok(pepper)
+    }
+    /// How long master secrets are cached in the keychain, when they are.
+    /// Only typed master passwords are cached, not those of scripts.
+    fn keychain_cache(&self) -> Option<Duration> {
+        match self.master_password() {
+            MasterPasswordSource::Prompt | MasterPasswordSource::Pinentry if !self.no_keychain => {
+                self.config.keychain_cache.map(Duration::from_secs)
+            }
+            _ => None,
+        }
+    }
+    /// The master secret of the configured user id cached in the keychain.
+    /// The keychain being unavailable only warns.
+    fn cached_session(&self, client: &str) -> Result<Option<Session>> {
+        let (Some(_), Some(user_id)) = (self.keychain_cache(), self.config.user_id()?) else {
+            return Ok(None);
+        };
+        let (scheme, kdf_params) = (self.scheme()?, self.kdf_params()?);
+        let pepper = self.pepper(&user_id)?;
+        match keychain::open(&self.config)
+            .and_then(|mut keychain| keychain.load(&user_id, scheme, &kdf_params, pepper.as_ref()))
+        {
+            Ok(Some(mut session)) => {
+                if let Some(path) = &self.audit_log {
+                    session = session.with_audit(Arc::new(AuditLog::new(path)), client);
+                }
+                Ok(Some(session))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                eprintln!("Can not read the keychain cache: {}", e);
+                Ok(None)
+            }
+        }
+    }
+    fn cache_session(&self, session: &Session, pepper: Option<&Pepper>) {
+        if let Some(lifetime) = self.keychain_cache()
+            && let Err(e) = keychain::open(&self.config)
+                .and_then(|mut keychain| keychain.store(session, pepper, lifetime))
+        {
+            eprintln!("Can not cache the master secret in the keychain: {}", e);
+        }
+    }
+}
+
+fn account_labels(entries: &[CatalogEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|e| match &e.username {
+            Some(username) => format!("{} ({})", e.service_id, username),
+            None => e.service_id.clone(),
+        })
+        .collect()
+}
+
+fn derive(args: &DeriveArgs, context: &Context) -> Result<()> {
+    if args.format.is_hashed() && (args.spell || args.grouped) {
+        bail!(
+            "--spell and --grouped would reveal the password, they can not be used with hashed output formats"
+        );
+    }
+    if args.output == OutputMode::Json
+        && (args.format != OutputFormat::Plain || args.spell || args.grouped)
+    {
+        bail!("--output json can not be combined with --format, --spell or --grouped");
+    }
+    if (args.qr || args.raw || (args.copy && !args.format.is_hashed()))
+        && (args.format != OutputFormat::Plain || args.output == OutputMode::Json)
+    {
+        bail!(
+            "--qr and --raw can not be combined with --format or --output, --copy only with hashed formats"
+        );
+    }
+    if args.raw {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    if (args.batch.is_some() || args.all_generations.is_some() || !args.variants.is_empty())
+        && (args.spell || args.grouped)
+    {
+        bail!(
+            "--spell and --grouped can not be used with --batch, --all-generations or --variants"
+        );
+    }
+    if !args.raw {
+        eprintln!(
+            r##"
 /*
  *
  * This program is a stateless password manager. Before you use it consider
@@ -47,11 +2729,211 @@ fn main() -> Result<()> {
  *
  */
 "##
-    );
-    let user_input = UserInputCli::new()?;
-    println!(
-        "Service password: >>> {} <<<",
-        DerivePassRunner::run(&user_input)?
-    );
+        );
+    }
+    let vault_password = match &args.vault_password_file {
+        Some(path) => Some(
+            fs::read_to_string(path)?
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned(),
+        ),
+        None if args.format == OutputFormat::AnsibleVault => {
+            bail!("--format ansible-vault requires --vault-password-file")
+        }
+        None => None,
+    };
+    let mut options = OutputOptions {
+        secret_name: args.k8s_name.clone(),
+        namespace: args.k8s_namespace.clone(),
+        vault_password,
+        bcrypt_cost: args.bcrypt_cost,
+        argon2_params: argon2::Params::new(
+            args.argon2_memory
+                .or(context.config.argon2.memory)
+                .unwrap_or(argon2::Params::DEFAULT_M_COST),
+            args.argon2_iterations
+                .or(context.config.argon2.iterations)
+                .unwrap_or(argon2::Params::DEFAULT_T_COST),
+            args.argon2_parallelism
+                .or(context.config.argon2.parallelism)
+                .unwrap_or(argon2::Params::DEFAULT_P_COST),
+            None,
+        )
+        .map_err(|e| anyhow!("Invalid Argon2 parameters: {}", e))?,
+        shadow_algorithm: args.shadow_algorithm,
+        salt_key: None,
+    };
+    if let Some(path) = &args.batch {
+        let batch = Batch::load(path)?;
+        let session = context.unlock_session("batch")?;
+        let derived = {
+            let session = session.lock().map_err(|_| DerivePassError::Secret)?;
+            options.salt_key = salt_key(args.format, &session)?;
+            batch.derive_all(&session)?
+        };
+        let reports = batch
+            .derivations
+            .iter()
+            .zip(derived)
+            .map(|(request, (key, derived_pass))| {
+                Ok(DerivationReport::new(
+                    &key,
+                    &request.service_id,
+                    request.generation,
+                    &CharSet::from_names(&request.char_sets)?,
+                    Zeroizing::new(derived_pass.expose_secret().to_owned()),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return print_reports(args, &reports, &options);
+    }
+    if let Some(generations) = args.all_generations {
+        let user_input = Arc::new(Mutex::new(UserInputCli::without_generation(
+            &context.config,
+            context.master_password(),
+        )?));
+        PanicGuard::register(&user_input);
+        let reports = {
+            let user_input = user_input.lock().map_err(|_| DerivePassError::Secret)?;
+            let session = context.unlock(
+                user_input.get_user_id(),
+                user_input.get_master_password_plain(),
+                "cli",
+            )?;
+            options.salt_key = salt_key(args.format, &session)?;
+            session
+                .derive_generations(
+                    user_input.get_service_id(),
+                    1..=generations as usize,
+                    user_input.get_char_set(),
+                    user_input.get_password_length(),
+                    user_input.get_policy(),
+                )?
+                .into_iter()
+                .map(|(generation, derived_pass)| {
+                    DerivationReport::new(
+                        &format!("generation {}", generation),
+                        &user_input.get_service_id().to_string(),
+                        generation.as_usize(),
+                        user_input.get_char_set(),
+                        Zeroizing::new(derived_pass.expose_secret().to_owned()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        return print_reports(args, &reports, &options);
+    }
+    if !args.variants.is_empty() {
+        return derive_variants(args, context, options);
+    }
+    let name = if args.format.needs_username() {
+        match &args.username {
+            Some(username) => username.clone(),
+            None => bail!("--format {} requires --username", args.format),
+        }
+    } else {
+        args.key.clone()
+    };
+    let user_input = Arc::new(Mutex::new(match &args.service {
+        _ if args.style == PasswordStyle::Pronounceable => UserInputCli::without_char_sets(
+            &context.config,
+            context.master_password(),
+            args.service.clone(),
+        )?,
+        Some(service_id) => UserInputCli::for_service(
+            &context.config,
+            context.master_password(),
+            service_id.clone(),
+        )?,
+        None => UserInputCli::new(&context.config, context.master_password())?,
+    }));
+    PanicGuard::register(&user_input);
+    let report = {
+        let user_input = user_input.lock().map_err(|_| DerivePassError::Secret)?;
+        let session = context.unlock(
+            user_input.get_user_id(),
+            user_input.get_master_password_plain(),
+            "cli",
+        )?;
+        options.salt_key = salt_key(args.format, &session)?;
+        if args.style == PasswordStyle::Pronounceable {
+            let derived_pass = session.derive_pronounceable(
+                user_input.get_service_id(),
+                user_input.get_generation(),
+                user_input.get_password_length(),
+            )?;
+            let mut report = DerivationReport::new(
+                &name,
+                &user_input.get_service_id().to_string(),
+                user_input.get_generation().as_usize(),
+                &CharSet::from_custom(&format!("{}{}", CONSONANTS, VOWELS))?,
+                Zeroizing::new(derived_pass.expose_secret().to_owned()),
+            );
+            report.entropy_bits = DerivedPass::pronounceable_entropy_bits(report.length);
+            report
+        } else {
+            let derived_pass = session.derive_path(
+                &DerivationPath::new(
+                    user_input.get_user_id(),
+                    user_input.get_service_id(),
+                    user_input.get_generation(),
+                    &args.purpose,
+                ),
+                user_input.get_char_set(),
+                user_input.get_password_length(),
+                user_input.get_policy(),
+            )?;
+            if let Err(e) = user_input.remember(args.remember) {
+                eprintln!("Can not save the settings of this service: {}", e);
+            }
+            if let Err(e) = user_input.record_history(&context.config) {
+                eprintln!("Can not record this service in the history: {}", e);
+            }
+            DerivationReport::new(
+                &name,
+                &user_input.get_service_id().to_string(),
+                user_input.get_generation().as_usize(),
+                user_input.get_char_set(),
+                Zeroizing::new(derived_pass.expose_secret().to_owned()),
+            )
+        }
+    };
+    if args.copy {
+        if args.format.is_hashed() {
+            // The hash line is safe to show, the password only goes to the clipboard.
+            print_reports(args, std::slice::from_ref(&report), &options)?;
+        }
+        let clipboard = Clipboard::detect()?;
+        clipboard.copy(&report.password)?;
+        let clear_after = args
+            .clear_after
+            .or(context.config.clear_clipboard_after)
+            .unwrap_or(DEFAULT_CLEAR_AFTER);
+        eprintln!(
+            "Password copied with {}, the clipboard is cleared in {} seconds",
+            clipboard.tool().name,
+            clear_after
+        );
+        return clipboard.clear_after(&report.password, Duration::from_secs(clear_after));
+    }
+    if args.raw {
+        print!("{}", report.password.as_str());
+        std::io::stdout().flush()?;
+    } else if args.qr {
+        println!("{}", Output::qr(&report.password)?);
+    } else {
+        print_reports(args, std::slice::from_ref(&report), &options)?;
+    }
+    if args.spell {
+        println!("Spelled: {}", Output::spell(&report.password));
+    }
+    if args.grouped {
+        println!(
+            "\n{}\n",
+            Output::grouped(&report.password, console::colors_enabled())
+        );
+    }
     Ok(())
 }
@@ -17,17 +17,19 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::str::FromStr;
-
-use anyhow::Result;
-use argon2::{
-    Argon2, Params, Version,
-    password_hash::{PasswordHasher, SaltString},
+use std::{
+    fmt::{Debug, Display},
+    str::FromStr,
 };
-use base64::prelude::*;
+
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{
     DerivePassError,
+    core::MASTER_SECRET_LENGTH,
+    kdf::KdfParams,
+    keyfile::Keyfile,
+    scheme::{MASTER_LABEL, SchemeVersion},
     user_input::{MasterPasswordPlain, UserID},
     utils::Utils,
 };
@@ -40,31 +42,125 @@ impl MasterSecret {
     pub fn new(
         user_id: &UserID,
         master_password_plain: &MasterPasswordPlain,
-    ) -> Result<MasterSecret> {
-        let salt = BASE64_STANDARD_NO_PAD.encode(user_id.len().to_string() + &user_id.to_string());
-        let salt_string = SaltString::from_b64(&salt)?;
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+    ) -> Result<MasterSecret, DerivePassError> {
+        Self::stretch(
+            user_id,
+            master_password_plain.as_bytes(),
+            scheme,
+            kdf_params,
+        )
+    }
+    /// Stretches the master password together with the digest of
+    /// `keyfile`, see `core::keyfile_password`.
+    pub fn with_keyfile(
+        user_id: &UserID,
+        master_password_plain: &MasterPasswordPlain,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+        keyfile: &Keyfile,
+    ) -> Result<MasterSecret, DerivePassError> {
+        let password = Zeroizing::new(crate::core::keyfile_password(
+            master_password_plain.as_bytes(),
+            keyfile.digest(),
+        ));
+        Self::stretch(user_id, &password, scheme, kdf_params)
+    }
+    fn stretch(
+        user_id: &UserID,
+        password: &[u8],
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+    ) -> Result<MasterSecret, DerivePassError> {
+        let salt = Zeroizing::new(Self::master_salt(user_id, scheme, kdf_params)?);
+        let mut master_secret = vec![0u8; MASTER_SECRET_LENGTH];
+        kdf_params
+            .kdf()
+            .and_then(|kdf| kdf.derive(password, &salt, &mut master_secret))
+            .map_err(|e| DerivePassError::Kdf(e.to_string()))?;
 
+        Ok(MasterSecret { master_secret })
+    }
+    /// `core::master_salt` with the default parameters, so the master
+    /// secrets of existing users stay the same, else
+    /// `core::master_salt_with_kdf`.
+    fn master_salt(
+        user_id: &UserID,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+    ) -> Result<Vec<u8>, DerivePassError> {
+        let user_id = user_id.to_string();
+        Ok(if kdf_params.is_default() {
+            crate::core::master_salt(&user_id, scheme)?
+        } else {
+            crate::core::master_salt_with_kdf(&user_id, scheme, &kdf_params.id())?
+        })
+    }
+    /// A master secret computed earlier, e.g. cached by `Keychain`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<MasterSecret, DerivePassError> {
+        if bytes.len() != MASTER_SECRET_LENGTH {
+            return Err(DerivePassError::Secret);
+        }
         Ok(MasterSecret {
-            master_secret: Argon2::new(
-                argon2::Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(32 * 1024, 4, 4, None)?,
-            )
-            .hash_password(&master_password_plain.as_bytes(), &salt_string)?
-            .hash
-            .ok_or(DerivePassError::Secret)?
-            .as_bytes()
-            .to_owned(),
+            master_secret: bytes.to_vec(),
         })
     }
+    pub fn salt(user_id: &UserID) -> String {
+        crate::core::master_salt_v1(&user_id.to_string())
+    }
+    /// See `core::master_salt`.
+    pub fn canonical_salt(user_id: &UserID) -> Vec<u8> {
+        Utils::length_prefixed(&[MASTER_LABEL, user_id.to_string().as_bytes()])
+    }
     pub fn as_bytes(&self) -> &[u8] {
         &self.master_secret
     }
-    pub fn as_hex(&self) -> String {
-        Utils::bytes_to_hex(&self.master_secret)
+    pub fn as_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(Utils::bytes_to_hex(&self.master_secret))
+    }
+    /// See `core::pepper_master_secret`.
+    pub fn add_pepper(&mut self, pepper: &[u8]) -> Result<(), DerivePassError> {
+        let master_secret: &mut [u8; MASTER_SECRET_LENGTH] = self
+            .master_secret
+            .as_mut_slice()
+            .try_into()
+            .map_err(|_| DerivePassError::Secret)?;
+        Ok(crate::core::pepper_master_secret(master_secret, pepper)?)
+    }
+    /// See `Utils::lock_memory`.
+    pub fn lock_in_memory(&self) -> bool {
+        Utils::lock_memory(&self.master_secret)
+    }
+}
+
+impl Zeroize for MasterSecret {
+    fn zeroize(&mut self) {
+        self.master_secret.zeroize();
+    }
+}
+
+/// Redacted, the secret is only available through `as_bytes` and `as_hex`.
+impl Debug for MasterSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MasterSecret(****)")
+    }
+}
+
+impl Display for MasterSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+
+impl Drop for MasterSecret {
+    fn drop(&mut self) {
+        self.zeroize();
     }
 }
 
+impl ZeroizeOnDrop for MasterSecret {}
+
 impl FromStr for MasterSecret {
     type Err = DerivePassError;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
@@ -83,6 +179,7 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
+    use crate::{kdf::KdfAlgorithm, utils::Utils};
     use argon2::PasswordHash;
 
     #[test]
@@ -114,7 +211,9 @@ mod tests {
             assert_eq!(
                 MasterSecret::new(
                     &UserID::from_str(*test_case.get(0).unwrap()).unwrap(),
-                    &MasterPasswordPlain::from_str(test_case.get(1).unwrap()).unwrap()
+                    &MasterPasswordPlain::from_str(test_case.get(1).unwrap()).unwrap(),
+                    SchemeVersion::V1,
+                    &KdfParams::DEFAULT,
                 )
                 .unwrap()
                 .as_bytes(),
@@ -126,4 +225,67 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn can_generate_v2_master_secret() {
+        for scheme in [SchemeVersion::V2, SchemeVersion::V3] {
+            assert_eq!(
+                Utils::bytes_to_hex(
+                    MasterSecret::new(
+                        &UserID::from_str("Example Eleonora").unwrap(),
+                        &MasterPasswordPlain::from_str(r##"]lE~WExZ468ty{I5mtg["##).unwrap(),
+                        scheme,
+                        &KdfParams::DEFAULT,
+                    )
+                    .unwrap()
+                    .as_bytes()
+                ),
+                "a29cd713a53f5b00572c2e54ea456ce84e1d3a414b686bc94f70f09eaf2bb790"
+            );
+        }
+    }
+
+    #[test]
+    fn can_salt_each_kdf_params_apart() {
+        let user_id = UserID::from_str("Example Eleonora").unwrap();
+        let calibrated = KdfParams {
+            m_cost: 65536,
+            ..KdfParams::DEFAULT
+        };
+        for scheme in [SchemeVersion::V2, SchemeVersion::V3] {
+            let default = MasterSecret::master_salt(&user_id, scheme, &KdfParams::DEFAULT).unwrap();
+            assert_eq!(default, MasterSecret::canonical_salt(&user_id));
+            assert_eq!(
+                Utils::bytes_to_hex(&default),
+                "0000001264657061737377642f76322f6d6173746572000000104578616d706c6520456c656f6e6f7261"
+            );
+            assert_ne!(
+                default,
+                MasterSecret::master_salt(&user_id, scheme, &calibrated).unwrap()
+            );
+        }
+        assert_eq!(
+            MasterSecret::master_salt(&user_id, SchemeVersion::V1, &calibrated).unwrap(),
+            MasterSecret::master_salt(&user_id, SchemeVersion::V1, &KdfParams::DEFAULT).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_salt_each_kdf_algorithm_apart() {
+        let user_id = UserID::from_str("Example Eleonora").unwrap();
+        let salts: Vec<Vec<u8>> = KdfAlgorithm::ALL
+            .iter()
+            .map(|algorithm| {
+                let kdf_params = KdfParams {
+                    algorithm: *algorithm,
+                    m_cost: 65536,
+                    ..KdfParams::DEFAULT
+                };
+                MasterSecret::master_salt(&user_id, SchemeVersion::V3, &kdf_params).unwrap()
+            })
+            .collect();
+        for (i, salt) in salts.iter().enumerate() {
+            assert!(salts[i + 1..].iter().all(|other| other != salt));
+        }
+    }
 }
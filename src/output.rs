@@ -0,0 +1,853 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Result, anyhow, bail};
+use base64::prelude::*;
+#[cfg(feature = "cli")]
+use console::{Color, style};
+use qrcode::{QrCode, render::unicode::Dense1x2};
+use serde::Serialize;
+#[cfg(feature = "cli")]
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    CAPITAL_LETTERS, NUMBERS, SMALL_LETTERS, SPECIAL_CHARS,
+    ansible::AnsibleVault,
+    crypt::{Crypt, DEFAULT_BCRYPT_COST, ShadowAlgorithm},
+    user_input::{CHAR_SET_NAMES, CharSet},
+};
+
+const NATO_ALPHABET: [&str; 26] = [
+    "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett",
+    "Kilo", "Lima", "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango",
+    "Uniform", "Victor", "Whiskey", "X-ray", "Yankee", "Zulu",
+];
+
+const DIGITS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+#[cfg(feature = "cli")]
+const IDENTICON_SIZE: usize = 5;
+#[cfg(feature = "cli")]
+const IDENTICON_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+#[cfg(feature = "cli")]
+const GROUP_SIZE: usize = 4;
+#[cfg(feature = "cli")]
+const GROUPS_PER_LINE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Plain,
+    HtpasswdBcrypt,
+    HtpasswdArgon2,
+    CryptSha512,
+    K8sSecret,
+    AnsibleVault,
+    TfvarsJson,
+    Dotenv,
+    Json,
+    Bcrypt,
+    Argon2id,
+    Shadow,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 12] = [
+        OutputFormat::Plain,
+        OutputFormat::HtpasswdBcrypt,
+        OutputFormat::HtpasswdArgon2,
+        OutputFormat::CryptSha512,
+        OutputFormat::K8sSecret,
+        OutputFormat::AnsibleVault,
+        OutputFormat::TfvarsJson,
+        OutputFormat::Dotenv,
+        OutputFormat::Json,
+        OutputFormat::Bcrypt,
+        OutputFormat::Argon2id,
+        OutputFormat::Shadow,
+    ];
+    /// Whether the format only ever shows a one-way hash of the password.
+    pub fn is_hashed(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::HtpasswdBcrypt
+                | OutputFormat::HtpasswdArgon2
+                | OutputFormat::CryptSha512
+                | OutputFormat::Bcrypt
+                | OutputFormat::Argon2id
+                | OutputFormat::Shadow
+        )
+    }
+    pub fn needs_username(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::HtpasswdBcrypt | OutputFormat::HtpasswdArgon2
+        )
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.to_string() == s)
+            .ok_or(anyhow!(
+                "Unknown output format: {} (expected one of: {})",
+                s,
+                Self::ALL.map(|f| f.to_string()).join(", ")
+            ))
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                OutputFormat::Plain => "plain",
+                OutputFormat::HtpasswdBcrypt => "htpasswd-bcrypt",
+                OutputFormat::HtpasswdArgon2 => "htpasswd-argon2",
+                OutputFormat::CryptSha512 => "crypt-sha512",
+                OutputFormat::K8sSecret => "k8s-secret",
+                OutputFormat::AnsibleVault => "ansible-vault",
+                OutputFormat::TfvarsJson => "tfvars-json",
+                OutputFormat::Dotenv => "dotenv",
+                OutputFormat::Json => "json",
+                OutputFormat::Bcrypt => "bcrypt",
+                OutputFormat::Argon2id => "argon2id",
+                OutputFormat::Shadow => "shadow",
+            }
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct OutputOptions {
+    /// `metadata.name` of the Kubernetes Secret.
+    pub secret_name: String,
+    /// `metadata.namespace` of the Kubernetes Secret.
+    pub namespace: Option<String>,
+    /// Password the `ansible-vault` format encrypts with.
+    pub vault_password: Option<String>,
+    /// Cost of the bcrypt based formats.
+    pub bcrypt_cost: u32,
+    /// Cost of the Argon2id based formats.
+    pub argon2_params: argon2::Params,
+    /// Hash algorithm of the `shadow` format.
+    pub shadow_algorithm: ShadowAlgorithm,
+    /// Key the deterministic salts of the `shadow` format are derived with.
+    pub salt_key: Option<Zeroizing<[u8; 32]>>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            secret_name: "depasswd".to_owned(),
+            namespace: None,
+            vault_password: None,
+            bcrypt_cost: DEFAULT_BCRYPT_COST,
+            argon2_params: argon2::Params::default(),
+            shadow_algorithm: ShadowAlgorithm::default(),
+            salt_key: None,
+        }
+    }
+}
+
+/// One derivation with its non-secret parameters, printed by `--output json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DerivationReport {
+    pub name: String,
+    pub service: String,
+    pub generation: usize,
+    pub length: usize,
+    /// The preset character classes the alphabet draws from.
+    pub char_sets: Vec<String>,
+    pub alphabet_size: usize,
+    #[serde(serialize_with = "DerivationReport::serialize_password")]
+    pub password: Zeroizing<String>,
+    /// `length * log2(alphabet_size)`, assuming the master password is strong.
+    pub entropy_bits: f64,
+}
+
+impl DerivationReport {
+    pub fn new(
+        name: &str,
+        service: &str,
+        generation: usize,
+        char_set: &CharSet,
+        password: Zeroizing<String>,
+    ) -> Self {
+        let alphabet = char_set.to_string();
+        let alphabet_size = alphabet.chars().count();
+        let length = password.chars().count();
+        Self {
+            name: name.to_owned(),
+            service: service.to_owned(),
+            generation,
+            length,
+            char_sets: CHAR_SET_NAMES
+                .iter()
+                .zip([SMALL_LETTERS, CAPITAL_LETTERS, NUMBERS, SPECIAL_CHARS])
+                .filter(|(_, chars)| alphabet.chars().any(|c| chars.contains(c)))
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            alphabet_size,
+            password,
+            entropy_bits: (length as f64 * (alphabet_size as f64).log2() * 10.0).round() / 10.0,
+        }
+    }
+    fn serialize_password<S: serde::Serializer>(
+        password: &Zeroizing<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(password)
+    }
+}
+
+pub struct Output {}
+
+impl Output {
+    /// Renders `(name, password)` pairs in the given format. The name is the
+    /// htpasswd user, the Kubernetes Secret data key or the label of the line.
+    /// A single plain password is shown without its name.
+    pub fn render(
+        format: OutputFormat,
+        secrets: &[(String, impl AsRef<str>)],
+        options: &OutputOptions,
+    ) -> Result<String> {
+        Ok(match format {
+            OutputFormat::Plain => match secrets {
+                [(_, password)] => format!("Service password: >>> {} <<<", password.as_ref()),
+                _ => secrets
+                    .iter()
+                    .map(|(name, password)| format!("{}: >>> {} <<<", name, password.as_ref()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            OutputFormat::HtpasswdBcrypt => secrets
+                .iter()
+                .map(|(name, password)| {
+                    Ok(Crypt::htpasswd_line(
+                        name,
+                        &Crypt::bcrypt(password.as_ref(), options.bcrypt_cost)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+            OutputFormat::HtpasswdArgon2 => secrets
+                .iter()
+                .map(|(name, password)| {
+                    Ok(Crypt::htpasswd_line(
+                        name,
+                        &Crypt::argon2id(password.as_ref(), options.argon2_params.clone())?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+            OutputFormat::CryptSha512 => Self::hashes(secrets, Crypt::sha512_crypt)?,
+            OutputFormat::Bcrypt => {
+                Self::hashes(secrets, |p| Crypt::bcrypt(p, options.bcrypt_cost))?
+            }
+            OutputFormat::Argon2id => Self::hashes(secrets, |p| {
+                Crypt::argon2id(p, options.argon2_params.clone())
+            })?,
+            OutputFormat::Shadow => {
+                let Some(salt_key) = &options.salt_key else {
+                    bail!("The shadow format needs a salt key");
+                };
+                Self::hashes(secrets, |p| {
+                    Crypt::shadow(p, options.shadow_algorithm, salt_key.as_slice())
+                })?
+            }
+            OutputFormat::K8sSecret => Self::k8s_secret(secrets, options)?,
+            OutputFormat::AnsibleVault => {
+                let Some(vault_password) = &options.vault_password else {
+                    bail!("The ansible-vault format needs a vault password");
+                };
+                secrets
+                    .iter()
+                    .map(|(name, password)| {
+                        Self::check_variable_name(name)?;
+                        AnsibleVault::variable(name, password.as_ref(), vault_password)
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .join("\n")
+            }
+            OutputFormat::TfvarsJson => {
+                let mut variables = serde_json::Map::new();
+                for (name, password) in secrets {
+                    Self::check_variable_name(name)?;
+                    variables.insert(name.clone(), password.as_ref().into());
+                }
+                serde_json::to_string_pretty(&variables)?
+            }
+            OutputFormat::Dotenv => secrets
+                .iter()
+                .map(|(name, password)| {
+                    Self::check_variable_name(name)?;
+                    Ok(format!(
+                        "{}={}",
+                        name,
+                        Self::dotenv_value(password.as_ref())
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+            OutputFormat::Json => serde_json::to_string_pretty(
+                &secrets
+                    .iter()
+                    .map(|(name, password)| (name.clone(), password.as_ref().into()))
+                    .collect::<serde_json::Map<_, _>>(),
+            )?,
+        })
+    }
+    /// A single bare hash, or one `name:hash` line per secret.
+    fn hashes(
+        secrets: &[(String, impl AsRef<str>)],
+        hash: impl Fn(&str) -> Result<String>,
+    ) -> Result<String> {
+        Ok(match secrets {
+            [(_, password)] => hash(password.as_ref())?,
+            _ => secrets
+                .iter()
+                .map(|(name, password)| Ok(format!("{}:{}", name, hash(password.as_ref())?)))
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+        })
+    }
+    /// Single quotes keep the value literal in docker compose, python-dotenv
+    /// and shells; values with a single quote fall back to double quotes.
+    fn dotenv_value(password: &str) -> String {
+        if password.contains('\'') {
+            format!(
+                "\"{}\"",
+                password.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        } else {
+            format!("'{}'", password)
+        }
+    }
+    /// Accepts names that are valid both as Ansible and Terraform variables,
+    /// and as environment variables.
+    fn check_variable_name(name: &str) -> Result<()> {
+        if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            Ok(())
+        } else {
+            bail!(
+                "Invalid variable name: {} (use letters, digits and '_', not starting with a digit)",
+                name
+            )
+        }
+    }
+    fn k8s_secret(
+        secrets: &[(String, impl AsRef<str>)],
+        options: &OutputOptions,
+    ) -> Result<String> {
+        Self::check_k8s_name(&options.secret_name)?;
+        let mut manifest = format!(
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: {}\n",
+            options.secret_name
+        );
+        if let Some(namespace) = &options.namespace {
+            Self::check_k8s_name(namespace)?;
+            manifest += &format!("  namespace: {}\n", namespace);
+        }
+        manifest += "type: Opaque\ndata:\n";
+        for (key, password) in secrets {
+            if key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-._".contains(c))
+            {
+                bail!(
+                    "Invalid Kubernetes Secret key: {} (allowed: letters, digits, '-', '.', '_')",
+                    key
+                );
+            }
+            manifest += &format!("  {}: {}\n", key, BASE64_STANDARD.encode(password.as_ref()));
+        }
+        Ok(manifest.trim_end().to_owned())
+    }
+    fn check_k8s_name(name: &str) -> Result<()> {
+        let valid = !name.is_empty()
+            && name.len() <= 253
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+            && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && name.ends_with(|c: char| c.is_ascii_alphanumeric());
+        if valid {
+            Ok(())
+        } else {
+            bail!(
+                "Invalid Kubernetes object name: {} (use lowercase letters, digits, '-' and '.')",
+                name
+            )
+        }
+    }
+    /// A QR code of `text` drawn with half blocks, two modules per character
+    /// cell, light on dark so it scans from the usual dark terminal theme.
+    pub fn qr(text: &str) -> Result<String> {
+        Ok(QrCode::new(text.as_bytes())?
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build())
+    }
+    /// A single report as a JSON object, several as an array.
+    pub fn json(reports: &[DerivationReport]) -> Result<String> {
+        Ok(match reports {
+            [report] => serde_json::to_string_pretty(report)?,
+            _ => serde_json::to_string_pretty(reports)?,
+        })
+    }
+    /// Lays the password out for manual transcription: spaced groups of four
+    /// characters, four groups per line, each line preceded by a ruler with
+    /// the 1-based position of every group. Groups alternate colors when
+    /// `colored` is set.
+    #[cfg(feature = "cli")]
+    pub fn grouped(password: &str, colored: bool) -> String {
+        let chars: Vec<char> = password.chars().collect();
+        let stride = GROUP_SIZE * 2 + 1;
+        chars
+            .chunks(GROUP_SIZE * GROUPS_PER_LINE)
+            .enumerate()
+            .map(|(line, line_chars)| {
+                let groups: Vec<&[char]> = line_chars.chunks(GROUP_SIZE).collect();
+                let ruler = groups
+                    .iter()
+                    .enumerate()
+                    .map(|(i, _)| {
+                        let index = (line * GROUPS_PER_LINE + i) * GROUP_SIZE + 1;
+                        format!("{:<width$}", index, width = stride)
+                    })
+                    .collect::<String>();
+                let text = groups
+                    .iter()
+                    .enumerate()
+                    .map(|(i, group)| {
+                        let group = group
+                            .iter()
+                            .map(char::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        match (colored, i % 2) {
+                            (false, _) => group,
+                            (true, 0) => style(group).cyan().bold().to_string(),
+                            (true, _) => style(group).yellow().bold().to_string(),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                format!("{}\n{}", ruler.trim_end(), text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+    /// A 5x5 mirrored block pattern plus two NATO words, computed from the
+    /// identifiers exactly as they enter the derivation. Shown before the
+    /// Argon2 run so a typo in either identifier is noticed early.
+    #[cfg(feature = "cli")]
+    pub fn identicon(user_id: &str, service_id: &str, colored: bool) -> String {
+        let hash = Sha256::digest(format!("{}\0{}", user_id, service_id).as_bytes());
+        let bits = u16::from_be_bytes([hash[0], hash[1]]);
+        let color = IDENTICON_COLORS[usize::from(hash[2]) % IDENTICON_COLORS.len()];
+        let rows = (0..IDENTICON_SIZE)
+            .map(|row| {
+                (0..IDENTICON_SIZE)
+                    .map(|col| {
+                        let col = col.min(IDENTICON_SIZE - 1 - col);
+                        let filled = bits >> (row * 3 + col) & 1 == 1;
+                        match (filled, colored) {
+                            (false, _) => "  ".to_owned(),
+                            (true, false) => "██".to_owned(),
+                            (true, true) => style("██").fg(color).to_string(),
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "{}\nFingerprint: {} {}",
+            rows,
+            NATO_ALPHABET[usize::from(hash[3]) % NATO_ALPHABET.len()],
+            NATO_ALPHABET[usize::from(hash[4]) % NATO_ALPHABET.len()]
+        )
+    }
+    /// Spells the password with the NATO alphabet for dictation, e.g.
+    /// `Capital Romeo, seven, dollar, lowercase kilo`.
+    pub fn spell(password: &str) -> String {
+        password
+            .chars()
+            .map(Self::spell_char)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+    fn spell_char(c: char) -> String {
+        match c {
+            'a'..='z' => format!(
+                "lowercase {}",
+                NATO_ALPHABET[c as usize - 'a' as usize].to_lowercase()
+            ),
+            'A'..='Z' => format!("Capital {}", NATO_ALPHABET[c as usize - 'A' as usize]),
+            '0'..='9' => DIGITS[c as usize - '0' as usize].to_owned(),
+            _ => Self::symbol_name(c)
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("U+{:04X}", c as u32)),
+        }
+    }
+    fn symbol_name(c: char) -> Option<&'static str> {
+        Some(match c {
+            ' ' => "space",
+            '!' => "exclamation mark",
+            '"' => "double quote",
+            '#' => "hash",
+            '$' => "dollar",
+            '%' => "percent",
+            '&' => "ampersand",
+            '\'' => "single quote",
+            '(' => "left parenthesis",
+            ')' => "right parenthesis",
+            '*' => "asterisk",
+            '+' => "plus",
+            ',' => "comma",
+            '-' => "hyphen",
+            '.' => "period",
+            '/' => "slash",
+            ':' => "colon",
+            ';' => "semicolon",
+            '<' => "less-than",
+            '=' => "equals",
+            '>' => "greater-than",
+            '?' => "question mark",
+            '@' => "at sign",
+            '[' => "left bracket",
+            '\\' => "backslash",
+            ']' => "right bracket",
+            '^' => "caret",
+            '_' => "underscore",
+            '`' => "backtick",
+            '{' => "left brace",
+            '|' => "vertical bar",
+            '}' => "right brace",
+            '~' => "tilde",
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CAPITAL_LETTERS, NUMBERS, SMALL_LETTERS, SPECIAL_CHARS};
+
+    use super::*;
+
+    #[test]
+    fn can_render_qr() {
+        let qr = Output::qr("1@MWtAAqZ0p>;;y@zZ6d").unwrap();
+        let lines: Vec<&str> = qr.lines().collect();
+        // Version 2 (25 modules) with a 4 module quiet zone on each side.
+        assert_eq!(lines.len(), 33usize.div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == 33));
+        assert_eq!(qr, Output::qr("1@MWtAAqZ0p>;;y@zZ6d").unwrap());
+        assert_ne!(qr, Output::qr("1@MWtAAqZ0p>;;y@zZ6e").unwrap());
+    }
+
+    #[test]
+    fn can_report_json() {
+        let report = DerivationReport::new(
+            "password",
+            "Example Service Name",
+            1,
+            &CharSet::try_from([0usize, 2].as_slice()).unwrap(),
+            Zeroizing::new("abc123".to_owned()),
+        );
+        assert_eq!(report.char_sets, ["small", "numbers"]);
+        assert_eq!(report.alphabet_size, 36);
+        assert_eq!(report.entropy_bits, 31.0);
+
+        let value: serde_json::Value =
+            serde_json::from_str(&Output::json(std::slice::from_ref(&report)).unwrap()).unwrap();
+        assert_eq!(value["service"], "Example Service Name");
+        assert_eq!(value["password"], "abc123");
+        assert_eq!(value["length"], 6);
+        let value: serde_json::Value =
+            serde_json::from_str(&Output::json(&[report.clone(), report]).unwrap()).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn can_spell_password() {
+        assert_eq!(
+            Output::spell("R7$k"),
+            "Capital Romeo, seven, dollar, lowercase kilo"
+        );
+        assert_eq!(
+            Output::spell("x-Y"),
+            "lowercase x-ray, hyphen, Capital Yankee"
+        );
+        assert_eq!(Output::spell("é"), "U+00E9");
+    }
+
+    #[test]
+    fn can_parse_output_format() {
+        for format in OutputFormat::ALL {
+            assert_eq!(OutputFormat::from_str(&format.to_string()).unwrap(), format);
+        }
+        assert!(OutputFormat::from_str("yaml").is_err());
+    }
+
+    #[test]
+    fn can_render_output_format() {
+        let options = OutputOptions::default();
+        let single = [("admin".to_owned(), "abc".to_owned())];
+        let batch = [
+            ("DB_PASSWORD".to_owned(), "abc".to_owned()),
+            ("redis".to_owned(), "def".to_owned()),
+        ];
+
+        assert_eq!(
+            Output::render(OutputFormat::Plain, &single, &options).unwrap(),
+            "Service password: >>> abc <<<"
+        );
+        assert_eq!(
+            Output::render(OutputFormat::Plain, &batch, &options).unwrap(),
+            "DB_PASSWORD: >>> abc <<<\nredis: >>> def <<<"
+        );
+        assert!(
+            Output::render(OutputFormat::HtpasswdArgon2, &single, &options)
+                .unwrap()
+                .starts_with("admin:$argon2id$")
+        );
+    }
+
+    #[test]
+    fn can_render_k8s_secret() {
+        let batch = [
+            ("DB_PASSWORD".to_owned(), "abc".to_owned()),
+            ("redis.password".to_owned(), "def".to_owned()),
+        ];
+        let options = OutputOptions {
+            secret_name: "app-secrets".to_owned(),
+            namespace: Some("prod".to_owned()),
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            Output::render(OutputFormat::K8sSecret, &batch, &options).unwrap(),
+            "apiVersion: v1
+kind: Secret
+metadata:
+  name: app-secrets
+  namespace: prod
+type: Opaque
+data:
+  DB_PASSWORD: YWJj
+  redis.password: ZGVm"
+        );
+        assert!(
+            Output::render(
+                OutputFormat::K8sSecret,
+                &[("bad key".to_owned(), "abc".to_owned())],
+                &options
+            )
+            .is_err()
+        );
+        assert!(
+            Output::render(
+                OutputFormat::K8sSecret,
+                &batch,
+                &OutputOptions {
+                    secret_name: "App".to_owned(),
+                    ..OutputOptions::default()
+                }
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn can_render_infrastructure_variables() {
+        let batch = [
+            ("db_password".to_owned(), "abc".to_owned()),
+            ("api_token".to_owned(), "def".to_owned()),
+        ];
+        let options = OutputOptions {
+            vault_password: Some("vault password".to_owned()),
+            ..OutputOptions::default()
+        };
+
+        assert_eq!(
+            Output::render(OutputFormat::TfvarsJson, &batch, &options).unwrap(),
+            "{\n  \"api_token\": \"def\",\n  \"db_password\": \"abc\"\n}"
+        );
+        let vault = Output::render(OutputFormat::AnsibleVault, &batch, &options).unwrap();
+        assert!(vault.starts_with("db_password: !vault |\n          $ANSIBLE_VAULT;1.1;AES256\n"));
+        assert!(vault.contains("\napi_token: !vault |\n"));
+        assert!(
+            Output::render(
+                OutputFormat::AnsibleVault,
+                &batch,
+                &OutputOptions::default()
+            )
+            .is_err()
+        );
+        assert!(
+            Output::render(
+                OutputFormat::TfvarsJson,
+                &[("1st".to_owned(), "abc".to_owned())],
+                &options
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn can_render_secret_files() {
+        let options = OutputOptions::default();
+        let batch = [
+            ("DB_PASSWORD".to_owned(), "a$b\"c".to_owned()),
+            ("API_TOKEN".to_owned(), "it's\\".to_owned()),
+        ];
+
+        assert_eq!(
+            Output::render(OutputFormat::Dotenv, &batch, &options).unwrap(),
+            "DB_PASSWORD='a$b\"c'\nAPI_TOKEN=\"it's\\\\\""
+        );
+        assert!(
+            Output::render(
+                OutputFormat::Dotenv,
+                &[("db.password".to_owned(), "abc".to_owned())],
+                &options
+            )
+            .is_err()
+        );
+        assert_eq!(
+            Output::render(
+                OutputFormat::Json,
+                &[("db.password".to_owned(), "a\"b".to_owned())],
+                &options
+            )
+            .unwrap(),
+            "{\n  \"db.password\": \"a\\\"b\"\n}"
+        );
+    }
+
+    #[test]
+    fn can_render_bare_hashes() {
+        let options = OutputOptions {
+            bcrypt_cost: 4,
+            ..OutputOptions::default()
+        };
+        let single = [("password".to_owned(), "abc".to_owned())];
+
+        assert!(
+            Output::render(OutputFormat::Bcrypt, &single, &options)
+                .unwrap()
+                .starts_with("$2y$04$")
+        );
+        let lines = Output::render(
+            OutputFormat::Argon2id,
+            &[
+                ("alice".to_owned(), "abc".to_owned()),
+                ("bob".to_owned(), "def".to_owned()),
+            ],
+            &options,
+        )
+        .unwrap();
+        assert!(lines.starts_with("alice:$argon2id$"));
+        assert!(lines.contains("\nbob:$argon2id$"));
+
+        assert!(Output::render(OutputFormat::Shadow, &single, &options).is_err());
+        let options = OutputOptions {
+            salt_key: Some(Zeroizing::new([7; 32])),
+            ..options
+        };
+        let shadow = Output::render(OutputFormat::Shadow, &single, &options).unwrap();
+        assert!(shadow.starts_with("$6$"));
+        assert_eq!(
+            Output::render(OutputFormat::Shadow, &single, &options).unwrap(),
+            shadow
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn can_draw_identicon() {
+        let gmail = Output::identicon("Example Eleonora", "gmail.com", false);
+        let rows: Vec<&str> = gmail.lines().collect();
+
+        assert_eq!(
+            gmail,
+            Output::identicon("Example Eleonora", "gmail.com", false)
+        );
+        assert_ne!(
+            gmail,
+            Output::identicon("Example Eleonora", "gmial.com", false)
+        );
+        assert_eq!(rows.len(), 6);
+        for row in &rows[..5] {
+            let cells: Vec<char> = row.chars().collect();
+            assert_eq!(cells.len(), 10);
+            assert!(cells.iter().eq(cells.iter().rev()));
+        }
+        assert!(rows[5].starts_with("Fingerprint: "));
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn can_group_password() {
+        assert_eq!(
+            Output::grouped("abcdefghij", false),
+            "1        5        9\na b c d  e f g h  i j"
+        );
+        assert_eq!(
+            Output::grouped("0123456789abcdefXY", false),
+            "1        5        9        13\n0 1 2 3  4 5 6 7  8 9 a b  c d e f\n\n17\nX Y"
+        );
+    }
+
+    #[test]
+    fn can_spell_every_preset_char() {
+        for c in [SMALL_LETTERS, CAPITAL_LETTERS, NUMBERS, SPECIAL_CHARS]
+            .concat()
+            .chars()
+        {
+            assert!(!Output::spell_char(c).starts_with("U+"), "{}", c);
+        }
+    }
+}
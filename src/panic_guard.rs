@@ -0,0 +1,99 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    io::Write,
+    panic,
+    sync::{Arc, Mutex, Weak},
+};
+
+use zeroize::Zeroize;
+
+type RegisteredSecret = Weak<Mutex<dyn Zeroize + Send>>;
+
+static REGISTRY: Mutex<Vec<RegisteredSecret>> = Mutex::new(Vec::new());
+
+pub struct PanicGuard {}
+
+impl PanicGuard {
+    /// Replaces the default panic hook. The replacement wipes every registered
+    /// secret, prints only the panic location (never the payload or a backtrace)
+    /// and aborts the process.
+    pub fn install() {
+        panic::set_hook(Box::new(|info| {
+            Self::wipe_registered();
+            let location = info
+                .location()
+                .map(|l| format!("{}:{}", l.file(), l.line()))
+                .unwrap_or_else(|| "unknown location".to_owned());
+            let _ = writeln!(
+                std::io::stderr(),
+                "depasswd: internal error at {}, aborting (details suppressed to protect secrets)",
+                location
+            );
+            std::process::abort();
+        }));
+    }
+    /// Registers a secret to be zeroized if the process panics. Only a weak
+    /// reference is kept, so registration never extends the secret's lifetime.
+    /// A secret whose lock is held by the panicking thread can not be wiped.
+    pub fn register<T: Zeroize + Send + 'static>(secret: &Arc<Mutex<T>>) {
+        let secret: Arc<Mutex<dyn Zeroize + Send>> = secret.clone();
+        if let Ok(mut registry) = REGISTRY.lock() {
+            registry.retain(|s| s.strong_count() > 0);
+            registry.push(Arc::downgrade(&secret));
+        }
+    }
+    pub fn wipe_registered() {
+        let registry = match REGISTRY.try_lock() {
+            Ok(registry) => registry,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => return,
+        };
+        for secret in registry.iter().filter_map(Weak::upgrade) {
+            match secret.try_lock() {
+                Ok(mut secret) => secret.zeroize(),
+                Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner().zeroize(),
+                Err(std::sync::TryLockError::WouldBlock) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_wipe_registered_secret() {
+        let secret = Arc::new(Mutex::new(String::from("master password")));
+        PanicGuard::register(&secret);
+        PanicGuard::wipe_registered();
+        assert!(secret.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn does_not_keep_dropped_secret_alive() {
+        let secret = Arc::new(Mutex::new(vec![1u8, 2, 3]));
+        PanicGuard::register(&secret);
+        let weak = Arc::downgrade(&secret);
+        drop(secret);
+        assert!(weak.upgrade().is_none());
+    }
+}
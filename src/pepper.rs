@@ -0,0 +1,277 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A pepper is a secret mixed into the master secret after the KDF, kept
+//! apart from the master password, e.g. in a file only a service can read.
+//! Every derived password depends on it, losing it loses them.
+
+use std::{
+    env,
+    ffi::OsString,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{core::PEPPER_LABEL, user_input::UserInputError, utils::Utils};
+
+/// The name of the pepper among the systemd credentials
+/// (`LoadCredentialEncrypted=depasswd-pepper:...`).
+pub const PEPPER_CREDENTIAL: &str = "depasswd-pepper";
+
+#[derive(Clone)]
+pub struct Pepper {
+    pepper: Zeroizing<Vec<u8>>,
+}
+
+impl Pepper {
+    pub fn new(pepper: Vec<u8>) -> Result<Self> {
+        let pepper = Zeroizing::new(pepper);
+        if pepper.is_empty() {
+            return Err(UserInputError::new("The pepper can not be empty").into());
+        }
+        Ok(Self { pepper })
+    }
+    /// Uses every byte of the file, including a trailing newline.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Self::new(
+            fs::read(path)
+                .with_context(|| format!("Can not read pepper file: {}", path.display()))?,
+        )
+    }
+    /// Decrypts an OpenPGP encrypted file with gpg, which asks gpg-agent
+    /// for the key: with the key on a smartcard the card has to be inserted
+    /// and pinentry asks its PIN.
+    pub fn from_gpg_file(path: &Path) -> Result<Self> {
+        let output = Command::new("gpg")
+            .args(["--decrypt", "--quiet", "--batch", "--"])
+            .arg(path)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .context("Can not start gpg")?;
+        let pepper = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            bail!("gpg can not decrypt the pepper file: {}", path.display());
+        }
+        Self::new(pepper.to_vec())
+    }
+    /// The `PEPPER_CREDENTIAL` from `$CREDENTIALS_DIRECTORY`, when systemd
+    /// passed it to the service.
+    pub fn from_credentials() -> Result<Option<Self>> {
+        match Self::credential_path() {
+            Some(path) if path.exists() => Self::from_file(&path).map(Some),
+            _ => Ok(None),
+        }
+    }
+    fn credential_path() -> Option<PathBuf> {
+        env::var_os("CREDENTIALS_DIRECTORY")
+            .filter(|d| !d.is_empty())
+            .map(|d| PathBuf::from(d).join(PEPPER_CREDENTIAL))
+    }
+    /// One pepper of several, each a length prefixed part so different
+    /// combinations never collide. A single pepper stays as it is.
+    pub fn combine(mut peppers: Vec<Pepper>) -> Option<Self> {
+        if peppers.len() > 1 {
+            let parts: Vec<&[u8]> = peppers.iter().map(Pepper::as_bytes).collect();
+            return Some(Self {
+                pepper: Zeroizing::new(Utils::length_prefixed(&parts)),
+            });
+        }
+        peppers.pop()
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.pepper
+    }
+    /// Tells peppers apart without revealing them, e.g. the master secrets
+    /// `Keychain` cached with different peppers.
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(Utils::length_prefixed(&[PEPPER_LABEL, &self.pepper]));
+        Utils::bytes_to_hex(&digest[..8])
+    }
+}
+
+/// Redacted, the pepper is as secret as the master password.
+impl Debug for Pepper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Pepper(****)")
+    }
+}
+
+/// A static pepper named in the config, e.g. a secret of the organization
+/// or one bound to the machine: `pepper = { value = "..." }`,
+/// `{ env = "VARIABLE" }`, `{ file = "/path" }` or
+/// `{ command = ["pass", "show", "depasswd/pepper"] }`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum PepperSource {
+    /// The text itself.
+    Value(String),
+    /// The value of the environment variable.
+    Env(String),
+    /// Every byte of the file, see `Pepper::from_file`.
+    File(PathBuf),
+    /// What the command prints, without the line ending.
+    Command(Vec<String>),
+}
+
+impl PepperSource {
+    pub fn read(&self) -> Result<Pepper> {
+        match self {
+            PepperSource::Value(value) => Pepper::new(value.as_bytes().to_vec()),
+            PepperSource::Env(name) => Pepper::new(
+                env::var_os(name)
+                    .map(OsString::into_encoded_bytes)
+                    .ok_or_else(|| anyhow!("The pepper variable {} is not set", name))?,
+            ),
+            PepperSource::File(path) => Pepper::from_file(path),
+            PepperSource::Command(command) => {
+                let (program, args) = command
+                    .split_first()
+                    .ok_or(UserInputError::new("The pepper command is empty"))?;
+                let output = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .with_context(|| format!("Can not start the pepper command {}", program))?;
+                let mut pepper = Zeroizing::new(output.stdout);
+                if !output.status.success() {
+                    bail!("The pepper command {} failed", program);
+                }
+                if pepper.ends_with(b"\n") {
+                    pepper.pop();
+                    if pepper.ends_with(b"\r") {
+                        pepper.pop();
+                    }
+                }
+                Pepper::new(pepper.to_vec())
+            }
+        }
+    }
+}
+
+/// Redacts the value, the other sources only name where it is.
+impl Debug for PepperSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PepperSource::Value(_) => write!(f, "Value(****)"),
+            PepperSource::Env(name) => f.debug_tuple("Env").field(name).finish(),
+            PepperSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            PepperSource::Command(command) => f.debug_tuple("Command").field(command).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        session::Session,
+        user_input::{CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID},
+    };
+
+    #[test]
+    fn can_pepper_the_master_secret() {
+        let path = env::temp_dir().join(format!("depasswd-pepper-{}", std::process::id()));
+        fs::write(&path, b"pepper\n").unwrap();
+        let pepper = Pepper::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(pepper.as_bytes(), b"pepper\n");
+        assert!(Pepper::new(vec![]).is_err());
+
+        let derive = |pepper: Option<&Pepper>| {
+            let mut session = Session::unlock(
+                &UserID::from_str("Example Eleonora").unwrap(),
+                &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+                SchemeVersion::V1,
+            )
+            .unwrap();
+            if let Some(pepper) = pepper {
+                session = session.with_pepper(pepper).unwrap();
+            }
+            session
+                .derive(
+                    &ServiceID::from_str("Example Service Name").unwrap(),
+                    &Generation::from_str("1").unwrap(),
+                    &CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap(),
+                    &PasswordLength::from_str("20").unwrap(),
+                )
+                .unwrap()
+                .expose_secret()
+                .to_owned()
+        };
+        assert_eq!(derive(None), "1@MWtAAqZ0p>;;y@zZ6d");
+        let peppered = derive(Some(&pepper));
+        assert_ne!(peppered, derive(None));
+        assert_eq!(peppered, derive(Some(&pepper)));
+        assert_ne!(
+            peppered,
+            derive(Some(&Pepper::new(b"pepper".to_vec()).unwrap()))
+        );
+
+        let combined = |parts: &[&[u8]]| {
+            Pepper::combine(
+                parts
+                    .iter()
+                    .map(|p| Pepper::new(p.to_vec()).unwrap())
+                    .collect(),
+            )
+            .map(|p| p.as_bytes().to_vec())
+        };
+        assert_eq!(combined(&[]), None);
+        assert_eq!(combined(&[b"pepper\n"]).unwrap(), b"pepper\n");
+        assert_ne!(combined(&[b"ab", b"c"]), combined(&[b"a", b"bc"]));
+    }
+
+    #[test]
+    fn can_read_pepper_sources() {
+        let value = PepperSource::Value("pepper".to_owned()).read().unwrap();
+        assert_eq!(value.as_bytes(), b"pepper");
+        assert_eq!(
+            format!("{:?}", PepperSource::Value("pepper".to_owned())),
+            "Value(****)"
+        );
+        assert!(
+            PepperSource::Env("DEPASSWD_TEST_UNSET_PEPPER".to_owned())
+                .read()
+                .is_err()
+        );
+        assert!(PepperSource::Command(vec![]).read().is_err());
+        #[cfg(unix)]
+        {
+            let command = PepperSource::Command(vec!["printf".to_owned(), "pepper\\n".to_owned()]);
+            assert_eq!(command.read().unwrap().as_bytes(), b"pepper");
+            assert!(
+                PepperSource::Command(vec!["false".to_owned()])
+                    .read()
+                    .is_err()
+            );
+        }
+    }
+}
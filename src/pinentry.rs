@@ -0,0 +1,221 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Asks the master password through pinentry, the dialog gpg-agent uses,
+//! which grabs the keyboard and keeps the password out of the terminal's
+//! scrollback. The program is the `pinentry-program` of `gpg-agent.conf`,
+//! or `pinentry` from `PATH`; the two talk the Assuan protocol over its
+//! standard input and output.
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use zeroize::Zeroizing;
+
+use crate::{doctor::Doctor, user_input::UserInputError};
+
+/// The code of `GPG_ERR_CANCELED`, in the low 16 bits of an `ERR` line.
+const ERROR_CANCELED: u32 = 99;
+
+pub struct Pinentry {
+    program: PathBuf,
+}
+
+impl Pinentry {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+    /// The pinentry gpg-agent is configured with, else `pinentry` from `PATH`.
+    pub fn find() -> Result<Self> {
+        let gnupg_home = env::var_os("GNUPGHOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".gnupg")));
+        let configured = gnupg_home
+            .and_then(|dir| std::fs::read_to_string(dir.join("gpg-agent.conf")).ok())
+            .and_then(|conf| Self::pinentry_program(&conf));
+        configured
+            .or_else(|| Doctor::find_in_path("pinentry", &env::var_os("PATH").unwrap_or_default()))
+            .map(Self::new)
+            .ok_or_else(|| {
+                anyhow!("No pinentry found, install one or set pinentry-program in gpg-agent.conf")
+            })
+    }
+    /// The `pinentry-program` option of a `gpg-agent.conf`.
+    pub fn pinentry_program(gpg_agent_conf: &str) -> Option<PathBuf> {
+        gpg_agent_conf
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("pinentry-program"))
+            .filter(|value| value.starts_with(char::is_whitespace))
+            .map(|value| PathBuf::from(value.trim()))
+            .next_back()
+    }
+    /// Shows a dialog with `description` and `prompt` and returns what was
+    /// typed. Cancelling the dialog is a `UserInputError`.
+    pub fn get_pin(&self, description: &str, prompt: &str) -> Result<Zeroizing<String>> {
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Can not start {}", self.program.display()))?;
+        let mut input = child.stdin.take().ok_or(anyhow!("No pinentry stdin"))?;
+        let mut output = BufReader::new(child.stdout.take().ok_or(anyhow!("No pinentry stdout"))?);
+        let result = (|| {
+            Self::response(&mut output)?;
+            let mut options = vec![];
+            if let Some(tty) = env::var_os("GPG_TTY") {
+                options.push(format!("ttyname={}", tty.to_string_lossy()));
+            }
+            if let Ok(term) = env::var("TERM") {
+                options.push(format!("ttytype={}", term));
+            }
+            for option in options {
+                writeln!(input, "OPTION {}", Self::escape(&option))?;
+                // Options a pinentry does not know are not an error.
+                let _ = Self::response(&mut output);
+            }
+            for command in [
+                "SETTITLE depasswd".to_owned(),
+                format!("SETDESC {}", Self::escape(description)),
+                format!("SETPROMPT {}", Self::escape(prompt)),
+            ] {
+                writeln!(input, "{}", command)?;
+                Self::response(&mut output)?;
+            }
+            writeln!(input, "GETPIN")?;
+            let pin = Self::response(&mut output)?;
+            Ok(Zeroizing::new(String::from_utf8(pin.to_vec())?))
+        })();
+        let _ = writeln!(input, "BYE");
+        drop(input);
+        let _ = child.wait();
+        result
+    }
+    /// Reads lines up to `OK` or `ERR` and returns the data of the `D` lines.
+    fn response(output: &mut impl BufRead) -> Result<Zeroizing<Vec<u8>>> {
+        let mut data = Zeroizing::new(vec![]);
+        loop {
+            let mut line = Zeroizing::new(String::new());
+            if output.read_line(&mut line)? == 0 {
+                bail!("pinentry exited unexpectedly");
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line == "OK" || line.starts_with("OK ") {
+                return Ok(data);
+            } else if let Some(chunk) = line.strip_prefix("D ") {
+                data.extend(Self::unescape(chunk).iter());
+            } else if let Some(error) = line.strip_prefix("ERR ") {
+                let (code, message) = error.split_once(' ').unwrap_or((error, ""));
+                if code
+                    .parse::<u32>()
+                    .is_ok_and(|c| c & 0xffff == ERROR_CANCELED)
+                {
+                    bail!(UserInputError::new("Master password entry was cancelled"));
+                }
+                bail!("pinentry failed: {}", message);
+            }
+        }
+    }
+    /// Assuan percent-escapes `%`, CR and LF in arguments.
+    fn escape(value: &str) -> String {
+        value
+            .replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    }
+    fn unescape(value: &str) -> Zeroizing<Vec<u8>> {
+        let bytes = value.as_bytes();
+        let mut unescaped = Zeroizing::new(Vec::with_capacity(bytes.len()));
+        let mut i = 0;
+        while i < bytes.len() {
+            match (bytes[i], value.get(i + 1..i + 3)) {
+                (b'%', Some(hex)) if let Ok(byte) = u8::from_str_radix(hex, 16) => {
+                    unescaped.push(byte);
+                    i += 3;
+                }
+                (byte, _) => {
+                    unescaped.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        unescaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_pinentry_program() {
+        let conf = "# pinentry-program /usr/bin/pinentry-tty\ndefault-cache-ttl 600\n  pinentry-program   /usr/bin/pinentry-gnome3 \npinentry-programs x\n";
+        assert_eq!(
+            Pinentry::pinentry_program(conf),
+            Some(PathBuf::from("/usr/bin/pinentry-gnome3"))
+        );
+        assert_eq!(Pinentry::pinentry_program("default-cache-ttl 600\n"), None);
+
+        assert_eq!(Pinentry::escape("100%\nsure"), "100%25%0Asure");
+        assert_eq!(&*Pinentry::unescape("100%25%0Asure%2"), b"100%\nsure%2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn can_get_pin_from_pinentry() {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let dir = env::temp_dir().join("depasswd-pinentry-test");
+        fs::create_dir_all(&dir).unwrap();
+        let fake = |name: &str, getpin: &str| {
+            let path = dir.join(name);
+            fs::write(
+                &path,
+                format!(
+                    "#!/bin/sh\necho 'OK Pleased to meet you'\nwhile read -r cmd rest; do\n  case \"$cmd\" in\n    GETPIN) {};;\n    OPTION) echo 'ERR 83886254 Unknown option';;\n    BYE) echo OK; exit 0;;\n    *) echo OK;;\n  esac\ndone\n",
+                    getpin
+                ),
+            )
+            .unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o700)).unwrap();
+            Pinentry::new(path)
+        };
+
+        let pinentry = fake("ok", "echo 'D hunter2%25pass'; echo OK");
+        assert_eq!(
+            pinentry
+                .get_pin("Master password of depasswd", "Password")
+                .unwrap()
+                .as_str(),
+            "hunter2%pass"
+        );
+        let pinentry = fake("cancel", "echo 'ERR 83886179 Operation cancelled'");
+        let error = pinentry.get_pin("", "Password").unwrap_err();
+        assert!(error.is::<UserInputError>());
+        assert!(Pinentry::new(dir.join("missing")).get_pin("", "").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sealing to the TPM2 as the `Protection` of the master secrets
+//! `keychain::open` caches, when `keychain_tpm2_pcrs` is set.
+//!
+//! The packed secret is encrypted by `systemd-creds` with a key only the
+//! TPM of this machine can unseal, and only while the given PCRs hold the
+//! values they had when it was sealed (PCR 7: the Secure Boot state). The
+//! keyring keeps the sealed secrets across reboots, so an agent started at
+//! login unlocks without the master password, while a copy of the disk is
+//! useless elsewhere. Unsealing needs access to `/dev/tpmrm0`, usually
+//! through the `tss` group.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use zeroize::Zeroizing;
+
+use crate::{keychain::Protection, user_input::UserID};
+
+/// The name embedded in, and checked when decrypting, every credential.
+const CREDENTIAL_NAME: &str = "depasswd-master-secret";
+
+pub struct Tpm2 {
+    pcrs: String,
+}
+
+impl Tpm2 {
+    /// Seals to `pcrs`, PCR indexes joined by `+`.
+    pub fn new(pcrs: &str) -> Result<Self> {
+        if !pcrs
+            .split('+')
+            .all(|pcr| pcr.parse::<u8>().is_ok_and(|pcr| pcr < 24))
+        {
+            bail!("keychain_tpm2_pcrs must be PCR indexes joined by +, e.g. 0+7");
+        }
+        Ok(Self {
+            pcrs: pcrs.to_owned(),
+        })
+    }
+    fn systemd_creds(args: &[&str], input: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let mut child = Command::new("systemd-creds")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Can not start systemd-creds")?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or(anyhow!("No systemd-creds stdin"))?;
+        let written = stdin.write_all(input);
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            bail!(
+                "systemd-creds {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        written?;
+        Ok(stdout)
+    }
+}
+
+impl Protection for Tpm2 {
+    fn protect(&self, packed: &[u8]) -> Result<Vec<u8>> {
+        let pcrs = format!("--tpm2-pcrs={}", self.pcrs);
+        let name = format!("--name={}", CREDENTIAL_NAME);
+        Ok(Self::systemd_creds(
+            &["encrypt", "--with-key=tpm2", &pcrs, &name, "-", "-"],
+            packed,
+        )?
+        .to_vec())
+    }
+    fn unprotect(&self, protected: &[u8], _: &UserID) -> Result<Zeroizing<Vec<u8>>> {
+        let name = format!("--name={}", CREDENTIAL_NAME);
+        Self::systemd_creds(&["decrypt", &name, "-", "-"], protected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_pcrs() {
+        assert!(Tpm2::new("0+7").is_ok());
+        assert!(Tpm2::new("7,8").is_err());
+        assert!(Tpm2::new("24").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The cache of `keychain::open` with `keychain_biometry` on macOS: an item
+//! of the macOS Keychain only returned after LocalAuthentication matched a
+//! fingerprint of the currently enrolled set, enrolling another finger
+//! invalidates it. `keyring` can not set such an access control, so these
+//! items are written with security-framework directly, under the service
+//! and accounts of `KeyringKeychain`.
+//!
+//! Biometry needs the data protection keychain, which only code signed
+//! builds with a keychain access group entitlement can use. Other builds
+//! get `errSecMissingEntitlement` and should cache without biometry.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use security_framework::{
+    item::{ItemClass, ItemSearchOptions, Limit},
+    passwords::{self, AccessControlOptions, PasswordOptions},
+};
+use zeroize::Zeroizing;
+
+use crate::{
+    kdf::KdfParams,
+    keychain::{self, Keychain, SERVICE},
+    pepper::Pepper,
+    scheme::SchemeVersion,
+    session::Session,
+    user_input::UserID,
+};
+
+/// `errSecItemNotFound`
+const ITEM_NOT_FOUND: i32 = -25300;
+
+pub struct BiometricKeychain;
+
+impl BiometricKeychain {
+    fn options(account: &str) -> PasswordOptions {
+        let mut options = PasswordOptions::new_generic_password(SERVICE, account);
+        options.use_protected_keychain();
+        options
+    }
+    fn delete(account: &str) -> Result<bool> {
+        match passwords::delete_generic_password_options(Self::options(account)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.code() == ITEM_NOT_FOUND => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Keychain for BiometricKeychain {
+    /// Shows the Touch ID dialog.
+    fn load(
+        &mut self,
+        user_id: &UserID,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+        pepper: Option<&Pepper>,
+    ) -> Result<Option<Session>> {
+        let account = keychain::account(user_id, scheme, kdf_params, pepper);
+        let cached = match passwords::generic_password(Self::options(&account)) {
+            Ok(cached) => Zeroizing::new(cached),
+            Err(e) if e.code() == ITEM_NOT_FOUND => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let session = keychain::unpack(&cached, user_id, scheme, kdf_params)?;
+        if session.is_none() {
+            Self::delete(&account)?;
+        }
+        Ok(session)
+    }
+    fn store(
+        &mut self,
+        session: &Session,
+        pepper: Option<&Pepper>,
+        lifetime: Duration,
+    ) -> Result<()> {
+        let account = keychain::account(
+            session.user_id(),
+            session.scheme(),
+            session.kdf_params(),
+            pepper,
+        );
+        // The access control of an existing item can not be updated.
+        Self::delete(&account)?;
+        let mut options = Self::options(&account);
+        options.set_label(&format!("depasswd master secret of {}", session.user_id()));
+        options.set_access_control_options(AccessControlOptions::BIOMETRY_CURRENT_SET);
+        passwords::set_generic_password_options(&keychain::pack(session, lifetime)?, options)?;
+        Ok(())
+    }
+    fn forget(&mut self, user_id: Option<&UserID>) -> Result<usize> {
+        let mut search = ItemSearchOptions::new();
+        search
+            .class(ItemClass::generic_password())
+            .service(SERVICE)
+            .load_attributes(true)
+            .limit(Limit::All);
+        let accounts: Vec<String> = match search.search() {
+            Ok(results) => results
+                .iter()
+                .filter_map(|result| result.simplify_dict()?.remove("acct"))
+                .collect(),
+            Err(e) if e.code() == ITEM_NOT_FOUND => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let user_id = user_id.map(UserID::to_string);
+        let mut forgotten = 0;
+        for account in accounts.iter().filter(|account| {
+            user_id.is_none() || keychain::account_user_id(account) == user_id.as_deref()
+        }) {
+            forgotten += usize::from(Self::delete(account)?);
+        }
+        Ok(forgotten)
+    }
+}
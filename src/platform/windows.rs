@@ -0,0 +1,288 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Windows counterparts of the Unix integrations: the `Protection` of the
+//! master secrets `keychain::open` caches in the Credential Manager,
+//! optionally gated by Windows Hello, and named pipes as the transport of
+//! the agent.
+//!
+//! Cached secrets are encrypted with DPAPI for the current user before they
+//! are written, the Credential Manager alone only protects them with the
+//! login. Windows Hello is a user presence check in front of the decryption,
+//! not a key: processes of the user calling DPAPI themselves bypass it.
+
+use std::{
+    ffi::c_void,
+    fs::File,
+    io,
+    os::windows::io::{FromRawHandle, OwnedHandle},
+    ptr, slice,
+};
+
+use anyhow::{Result, bail};
+use windows::{
+    Security::Credentials::UI::{UserConsentVerificationResult, UserConsentVerifier},
+    core::HSTRING,
+};
+use windows_sys::Win32::{
+    Foundation::{ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE, LocalFree},
+    Security::{
+        Authorization::{ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1},
+        Cryptography::{
+            CRYPT_INTEGER_BLOB, CRYPTPROTECT_UI_FORBIDDEN, CryptProtectData, CryptUnprotectData,
+        },
+        PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES,
+    },
+    Storage::FileSystem::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX},
+    System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    },
+};
+use zeroize::Zeroizing;
+
+use crate::{keychain::Protection, user_input::UserID};
+
+/// Full access for the owner of the pipe and the system, nobody else.
+const OWNER_ONLY: &str = "D:P(A;;GA;;;OW)(A;;GA;;;SY)";
+const PIPE_BUFFER_SIZE: u32 = 64 * 1024;
+
+/// A NUL terminated UTF-16 copy of `s`.
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Copies and frees a blob DPAPI allocated.
+///
+/// # Safety
+/// `blob` must have been filled by `CryptProtectData` or `CryptUnprotectData`.
+unsafe fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Zeroizing<Vec<u8>> {
+    // SAFETY: DPAPI returned `cbData` bytes at `pbData`.
+    let bytes = unsafe { slice::from_raw_parts_mut(blob.pbData, blob.cbData as usize) };
+    let copy = Zeroizing::new(bytes.to_vec());
+    bytes.fill(0);
+    // SAFETY: the buffer was allocated with LocalAlloc and is not used anymore.
+    unsafe { LocalFree(blob.pbData.cast()) };
+    copy
+}
+
+/// Encrypts `data` with the key of the current user.
+fn protect(data: &[u8]) -> Result<Vec<u8>> {
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len().try_into()?,
+        pbData: data.as_ptr().cast_mut(),
+    };
+    let mut output = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: ptr::null_mut(),
+    };
+    let description = wide("depasswd master secret");
+    // SAFETY: every pointer is valid for the call, DPAPI does not write the input.
+    let protected = unsafe {
+        CryptProtectData(
+            &input,
+            description.as_ptr(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if protected == 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    // SAFETY: CryptProtectData succeeded and filled `output`.
+    Ok(unsafe { take_blob(output) }.to_vec())
+}
+
+/// Decrypts what `protect` returned for the current user.
+fn unprotect(data: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len().try_into()?,
+        pbData: data.as_ptr().cast_mut(),
+    };
+    let mut output = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: ptr::null_mut(),
+    };
+    // SAFETY: every pointer is valid for the call, DPAPI does not write the input.
+    let unprotected = unsafe {
+        CryptUnprotectData(
+            &input,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if unprotected == 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    // SAFETY: CryptUnprotectData succeeded and filled `output`.
+    Ok(unsafe { take_blob(output) })
+}
+
+/// Whether the last error of the thread is `code`.
+fn last_error_is(error: &io::Error, code: u32) -> bool {
+    error.raw_os_error() == Some(code as i32)
+}
+
+/// DPAPI for the current user, behind Windows Hello with `biometry`.
+pub struct Dpapi {
+    biometry: bool,
+}
+
+impl Dpapi {
+    pub fn new(biometry: bool) -> Self {
+        Self { biometry }
+    }
+    /// Shows the Windows Hello dialog, fails unless the user was verified.
+    fn verify_user(user_id: &UserID) -> Result<()> {
+        let message = HSTRING::from(format!("Unlock the depasswd master secret of {}", user_id));
+        let result = UserConsentVerifier::RequestVerificationAsync(&message)?.join()?;
+        if result != UserConsentVerificationResult::Verified {
+            bail!("Windows Hello did not verify the user: {:?}", result);
+        }
+        Ok(())
+    }
+}
+
+impl Protection for Dpapi {
+    fn protect(&self, packed: &[u8]) -> Result<Vec<u8>> {
+        protect(packed)
+    }
+    /// Shows the Windows Hello dialog first with biometry.
+    fn unprotect(&self, protected: &[u8], user_id: &UserID) -> Result<Zeroizing<Vec<u8>>> {
+        if self.biometry {
+            Self::verify_user(user_id)?;
+        }
+        unprotect(protected)
+    }
+}
+
+/// A security descriptor allocated by the system.
+struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+impl SecurityDescriptor {
+    fn from_sddl(sddl: &str) -> Result<Self> {
+        let sddl = wide(sddl);
+        let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        // SAFETY: the SDDL is NUL terminated, `descriptor` receives the result.
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut(),
+            )
+        };
+        if converted == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Self(descriptor))
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        // SAFETY: allocated with LocalAlloc by the conversion.
+        unsafe { LocalFree(self.0) };
+    }
+}
+
+/// A local named pipe accessible only by the user running the server.
+pub struct NamedPipe;
+
+impl NamedPipe {
+    /// The full name of the pipe called `name`, e.g. `\\.\pipe\depasswd-agent`.
+    pub fn path(name: &str) -> String {
+        format!(r"\\.\pipe\{}", name)
+    }
+    /// Accepts clients of the pipe at `path` forever, with one thread per
+    /// connection. Fails if another process already serves it.
+    pub fn serve(path: &str, handler: impl Fn(File) -> Result<()> + Sync) -> Result<()> {
+        let path = wide(path);
+        let descriptor = SecurityDescriptor::from_sddl(OWNER_ONLY)?;
+        let handler = &handler;
+        std::thread::scope(|scope| -> Result<()> {
+            let mut first = true;
+            loop {
+                let pipe = Self::create(&path, &descriptor, first)?;
+                first = false;
+                // SAFETY: the handle is an open pipe instance.
+                let connected = unsafe { ConnectNamedPipe(pipe.0, ptr::null_mut()) } != 0
+                    || last_error_is(&io::Error::last_os_error(), ERROR_PIPE_CONNECTED);
+                if connected {
+                    let pipe = pipe.into_file();
+                    scope.spawn(move || handler(pipe));
+                }
+            }
+        })
+    }
+    fn create(path: &[u16], descriptor: &SecurityDescriptor, first: bool) -> Result<PipeInstance> {
+        let attributes = SECURITY_ATTRIBUTES {
+            nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor.0,
+            bInheritHandle: 0,
+        };
+        let mut open_mode = PIPE_ACCESS_DUPLEX;
+        if first {
+            // Nobody else may have created it before us to listen in.
+            open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        // SAFETY: the name is NUL terminated, the attributes outlive the call.
+        let handle = unsafe {
+            CreateNamedPipeW(
+                path.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                PIPE_BUFFER_SIZE,
+                PIPE_BUFFER_SIZE,
+                0,
+                &attributes,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(PipeInstance(handle))
+    }
+}
+
+/// A server end of the pipe, closed when dropped unless it was connected.
+struct PipeInstance(*mut c_void);
+
+impl PipeInstance {
+    fn into_file(self) -> File {
+        let handle = std::mem::ManuallyDrop::new(self).0;
+        // SAFETY: the handle is open and owned by nothing else.
+        File::from(unsafe { OwnedHandle::from_raw_handle(handle) })
+    }
+}
+
+impl Drop for PipeInstance {
+    fn drop(&mut self) {
+        // SAFETY: the handle is open and owned by this instance.
+        drop(unsafe { OwnedHandle::from_raw_handle(self.0) });
+    }
+}
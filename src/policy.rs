@@ -0,0 +1,342 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Password policies describe what a site accepts. A policy is checked
+//! against the request before deriving, so an impossible combination fails
+//! up front, and scheme v2 only keeps candidates that satisfy it.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DerivePassError,
+    user_input::{CHAR_SET_NAMES, CharSet, PasswordLength, UserInputError},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<u16>,
+    /// Names of the preset character sets (see `CHAR_SET_NAMES`) the
+    /// password must contain at least one character of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_classes: Vec<String>,
+    /// Names of the preset character sets the password must not use.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden_classes: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub forbidden_chars: String,
+    /// Whether the first character must be a letter or a digit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_leading_symbol: bool,
+    /// Whether the last character must be a letter or a digit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_trailing_symbol: bool,
+    /// The longest run of one repeated character, e.g. 2 allows `aa`
+    /// but not `aaa`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_repeat_run: Option<u16>,
+}
+
+impl PasswordPolicy {
+    /// The character set without the forbidden characters, checked against
+    /// every rule that does not depend on the derived characters. Errors
+    /// describe why no password of the request could satisfy the policy.
+    pub fn shape(
+        &self,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+    ) -> std::result::Result<CharSet, DerivePassError> {
+        let length = password_length.as_u16();
+        if self.min_length.is_some_and(|min| length < min)
+            || self.max_length.is_some_and(|max| length > max)
+        {
+            return Err(DerivePassError::LengthOutOfRange {
+                length,
+                min: self.min_length.unwrap_or(1),
+                max: self.max_length.unwrap_or(PasswordLength::MAX),
+            });
+        }
+        if self.max_repeat_run == Some(0) {
+            return Err(UserInputError::new(
+                "The longest repeated run of a policy must be at least 1",
+            )
+            .into());
+        }
+        let shaped = char_set.without(&self.forbidden()?).map_err(|_| {
+            DerivePassError::InvalidCharSet(
+                "the policy forbids every character of the character set".to_owned(),
+            )
+        })?;
+        let alphabet = shaped.to_string();
+        for name in &self.required_classes {
+            let class = Self::class_chars(name)?;
+            if !shaped.contains_any(class) {
+                return Err(DerivePassError::InvalidCharSet(format!(
+                    "the policy requires {} characters, but the character set has none left",
+                    name
+                )));
+            }
+        }
+        if self.required_classes.len() > usize::from(length) {
+            return Err(DerivePassError::PolicyViolation(format!(
+                "a password of {} characters can not contain all of the {} required character sets",
+                length,
+                self.required_classes.len()
+            )));
+        }
+        if (self.no_leading_symbol || self.no_trailing_symbol)
+            && !alphabet.chars().any(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(DerivePassError::InvalidCharSet(
+                "the policy forbids leading or trailing symbols, but the character set has only symbols"
+                    .to_owned(),
+            ));
+        }
+        if self
+            .max_repeat_run
+            .is_some_and(|max| max < length && alphabet.chars().count() < 2)
+        {
+            return Err(DerivePassError::InvalidCharSet(
+                "the policy limits repeated characters, but the character set has only one"
+                    .to_owned(),
+            ));
+        }
+        Ok(shaped)
+    }
+    /// The rules `password` breaks, empty if it satisfies the policy.
+    pub fn violations(&self, password: &str) -> Vec<String> {
+        let mut violations = vec![];
+        let length = password.chars().count();
+        if let Some(min) = self.min_length
+            && length < usize::from(min)
+        {
+            violations.push(format!("is shorter than {} characters", min));
+        }
+        if let Some(max) = self.max_length
+            && length > usize::from(max)
+        {
+            violations.push(format!("is longer than {} characters", max));
+        }
+        for name in &self.required_classes {
+            match Self::class_chars(name) {
+                Ok(class) if password.chars().any(|c| class.contains(c)) => {}
+                _ => violations.push(format!("has no {} character", name)),
+            }
+        }
+        let forbidden = self.forbidden().unwrap_or_default();
+        if let Some(c) = password.chars().find(|c| forbidden.contains(*c)) {
+            violations.push(format!("contains the forbidden character {}", c));
+        }
+        let is_symbol = |c: Option<char>| c.is_some_and(|c| !c.is_ascii_alphanumeric());
+        if self.no_leading_symbol && is_symbol(password.chars().next()) {
+            violations.push("starts with a symbol".to_owned());
+        }
+        if self.no_trailing_symbol && is_symbol(password.chars().last()) {
+            violations.push("ends with a symbol".to_owned());
+        }
+        if let Some(max) = self.max_repeat_run
+            && Self::longest_run(password) > usize::from(max)
+        {
+            violations.push(format!(
+                "repeats a character more than {} times in a row",
+                max
+            ));
+        }
+        violations
+    }
+    /// Fails with every rule `password` breaks.
+    pub fn validate(&self, password: &str) -> std::result::Result<(), DerivePassError> {
+        let violations = self.violations(password);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(DerivePassError::PolicyViolation(format!(
+                "the password {}",
+                violations.join(", ")
+            )))
+        }
+    }
+    /// Every forbidden character, of the forbidden classes included.
+    fn forbidden(&self) -> std::result::Result<String, UserInputError> {
+        let mut forbidden = self.forbidden_chars.clone();
+        for name in &self.forbidden_classes {
+            forbidden += Self::class_chars(name)?;
+        }
+        Ok(forbidden)
+    }
+    fn class_chars(name: &str) -> std::result::Result<&'static str, UserInputError> {
+        CHAR_SET_NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|i| {
+                [
+                    crate::SMALL_LETTERS,
+                    crate::CAPITAL_LETTERS,
+                    crate::NUMBERS,
+                    crate::SPECIAL_CHARS,
+                ][i]
+            })
+            .ok_or(UserInputError::new(format!(
+                "Unknown character set: {}",
+                name
+            )))
+    }
+    fn longest_run(password: &str) -> usize {
+        let mut longest = 0;
+        let mut run = 0;
+        let mut previous = None;
+        for c in password.chars() {
+            run = if previous == Some(c) { run + 1 } else { 1 };
+            longest = longest.max(run);
+            previous = Some(c);
+        }
+        longest
+    }
+}
+
+/// Lists the rules, e.g. `8 to 20 characters, no special characters`.
+impl Display for PasswordPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rules = vec![];
+        match (self.min_length, self.max_length) {
+            (Some(min), Some(max)) => rules.push(format!("{} to {} characters", min, max)),
+            (Some(min), None) => rules.push(format!("at least {} characters", min)),
+            (None, Some(max)) => rules.push(format!("at most {} characters", max)),
+            (None, None) => {}
+        }
+        for name in &self.required_classes {
+            rules.push(format!("at least one {} character", name));
+        }
+        for name in &self.forbidden_classes {
+            rules.push(format!("no {} characters", name));
+        }
+        if !self.forbidden_chars.is_empty() {
+            rules.push(format!("none of {}", self.forbidden_chars));
+        }
+        if self.no_leading_symbol {
+            rules.push("no symbol first".to_owned());
+        }
+        if self.no_trailing_symbol {
+            rules.push("no symbol last".to_owned());
+        }
+        if let Some(max) = self.max_repeat_run {
+            rules.push(format!("at most {} repeated characters in a row", max));
+        }
+        if rules.is_empty() {
+            write!(f, "no rules")
+        } else {
+            write!(f, "{}", rules.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn strict_policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: Some(8),
+            max_length: Some(16),
+            required_classes: vec!["capital".to_owned(), "numbers".to_owned()],
+            forbidden_classes: vec![],
+            forbidden_chars: "\";".to_owned(),
+            no_leading_symbol: true,
+            no_trailing_symbol: true,
+            max_repeat_run: Some(2),
+        }
+    }
+
+    #[test]
+    fn can_find_policy_violations() {
+        let policy = strict_policy();
+        assert!(policy.violations("Abcdef12").is_empty());
+        assert!(policy.validate("Abcdef12").is_ok());
+        assert_eq!(
+            policy.violations("!abc;aaa"),
+            [
+                "has no capital character",
+                "has no numbers character",
+                "contains the forbidden character ;",
+                "starts with a symbol",
+                "repeats a character more than 2 times in a row",
+            ]
+        );
+        assert_eq!(policy.violations("Ab1"), ["is shorter than 8 characters"]);
+        assert!(policy.validate("Abcdef12-").is_err());
+        assert_eq!(
+            policy.to_string(),
+            "8 to 16 characters, at least one capital character, at least one numbers character, \
+             none of \";, no symbol first, no symbol last, at most 2 repeated characters in a row"
+        );
+
+        let no_symbols = PasswordPolicy {
+            forbidden_classes: vec!["special".to_owned()],
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(
+            no_symbols.violations("abc!"),
+            ["contains the forbidden character !"]
+        );
+        assert_eq!(no_symbols.to_string(), "no special characters");
+        assert_eq!(PasswordPolicy::default().to_string(), "no rules");
+    }
+
+    #[test]
+    fn can_reject_unsatisfiable_request() {
+        let policy = strict_policy();
+        let all = CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap();
+        let twelve = PasswordLength::from_str("12").unwrap();
+
+        let shaped = policy.shape(&all, &twelve).unwrap();
+        assert!(!shaped.contains_any("\";"));
+        assert!(matches!(
+            policy.shape(&all, &PasswordLength::from_str("20").unwrap()),
+            Err(DerivePassError::LengthOutOfRange {
+                length: 20,
+                min: 8,
+                max: 16
+            })
+        ));
+        assert!(
+            policy
+                .shape(&CharSet::try_from([0usize, 2].as_slice()).unwrap(), &twelve)
+                .is_err()
+        );
+        assert!(matches!(
+            policy.shape(&CharSet::from_custom(";").unwrap(), &twelve),
+            Err(DerivePassError::InvalidCharSet(_))
+        ));
+        let symbols_only = PasswordPolicy {
+            no_leading_symbol: true,
+            ..PasswordPolicy::default()
+        };
+        assert!(
+            symbols_only
+                .shape(&CharSet::try_from([3usize].as_slice()).unwrap(), &twelve)
+                .is_err()
+        );
+    }
+}
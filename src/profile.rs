@@ -0,0 +1,219 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Named profiles pin the derivation parameters a user relies on. Every
+//! stored profile carries an HMAC keyed by the master secret, so a config
+//! that was tampered with (e.g. to weaken the KDF) is rejected before any
+//! password is derived from it.
+
+use std::{fs, io::ErrorKind, path::Path, path::PathBuf, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    kdf::{KdfAlgorithm, KdfParams},
+    scheme::SchemeVersion,
+    session::Session,
+    utils::Utils,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileParams {
+    pub scheme: u32,
+    pub kdf: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub normalization: String,
+}
+
+impl ProfileParams {
+    /// The parameters `MasterSecret` derives with under `scheme` and `kdf_params`.
+    pub fn new(scheme: SchemeVersion, kdf_params: &KdfParams) -> Self {
+        Self {
+            scheme: scheme.as_u32(),
+            kdf: kdf_params.algorithm.name().to_owned(),
+            m_cost: kdf_params.m_cost,
+            t_cost: kdf_params.t_cost,
+            p_cost: kdf_params.p_cost,
+            normalization: "none".to_owned(),
+        }
+    }
+    pub fn kdf_params(&self) -> Result<KdfParams> {
+        KdfParams::new(
+            KdfAlgorithm::from_str(&self.kdf)?,
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(flatten)]
+    pub params: ProfileParams,
+    pub mac: String,
+}
+
+impl Profile {
+    fn new(name: &str, params: ProfileParams, session: &Session) -> Result<Self> {
+        let mac = Utils::bytes_to_hex(&session.authenticate(&Self::message(name, &params)?)?);
+        Ok(Self {
+            name: name.to_owned(),
+            params,
+            mac,
+        })
+    }
+    fn message(name: &str, params: &ProfileParams) -> Result<Vec<u8>> {
+        Ok(format!("depasswd profile\0{}\0{}", name, toml::to_string(params)?).into_bytes())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileStore {
+    #[serde(default, rename = "profile")]
+    profiles: Vec<Profile>,
+}
+
+impl ProfileStore {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Utils::config_dir()?.join("profiles.toml"))
+    }
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid profile file: {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Can not read profile file: {}", path.display()))
+            }
+        }
+    }
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Can not write profile file: {}", path.display()))
+    }
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+    /// The scheme the named profile was created with, read before the
+    /// session exists to unlock it. Verified later by `verify_or_create`.
+    pub fn scheme(&self, name: &str) -> Result<Option<SchemeVersion>> {
+        self.profiles
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| Ok(SchemeVersion::try_from(p.params.scheme)?))
+            .transpose()
+    }
+    /// The KDF parameters the named profile was created with, read and
+    /// verified like `scheme`.
+    pub fn kdf_params(&self, name: &str) -> Result<Option<KdfParams>> {
+        self.profiles
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.params.kdf_params())
+            .transpose()
+    }
+    /// Verifies the named profile with the unlocked session and returns its
+    /// parameters. A missing profile is created with the current parameters
+    /// and `created` is reported so the caller can save the store.
+    pub fn verify_or_create(
+        &mut self,
+        name: &str,
+        session: &Session,
+    ) -> Result<(ProfileParams, bool)> {
+        let Some(profile) = self.profiles.iter().find(|p| p.name == name) else {
+            let params = ProfileParams::new(session.scheme(), session.kdf_params());
+            self.profiles
+                .push(Profile::new(name, params.clone(), session)?);
+            return Ok((params, true));
+        };
+        let mac = Utils::hex_to_bytes(&profile.mac).unwrap_or_default();
+        if !session.verify(&Profile::message(name, &profile.params)?, &mac)? {
+            bail!(
+                "Profile {} failed verification: it was modified, or it belongs to another user id or master password",
+                name
+            );
+        }
+        if profile.params != ProfileParams::new(session.scheme(), session.kdf_params()) {
+            bail!(
+                "Profile {} was created with other parameters than the session was unlocked with",
+                name
+            );
+        }
+        Ok((profile.params.clone(), false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        user_input::{MasterPasswordPlain, UserID},
+    };
+
+    fn session(user_id: &str) -> Session {
+        Session::unlock(
+            &UserID::from_str(user_id).unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn can_detect_tampered_profile() {
+        let session = session("Example Eleonora");
+        let path = std::env::temp_dir().join("depasswd-profile-test.toml");
+        let mut store = ProfileStore::default();
+
+        assert!(store.verify_or_create("work", &session).unwrap().1);
+        store.save(&path).unwrap();
+        let mut store = ProfileStore::load(&path).unwrap();
+        assert_eq!(
+            store.verify_or_create("work", &session).unwrap(),
+            (
+                ProfileParams::new(SchemeVersion::V1, &KdfParams::DEFAULT),
+                false
+            )
+        );
+        assert!(
+            store
+                .verify_or_create("work", &self::session("Someone Else"))
+                .is_err()
+        );
+
+        let tampered = fs::read_to_string(&path)
+            .unwrap()
+            .replace("m_cost = 32768", "m_cost = 8");
+        fs::write(&path, tampered).unwrap();
+        let mut store = ProfileStore::load(&path).unwrap();
+        assert!(store.verify_or_create("work", &session).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
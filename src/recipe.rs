@@ -0,0 +1,168 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A recipe holds every non-secret input of one derivation, so a password
+//! can be re-derived from a config file or an API request given only the
+//! user id and the master password. Deserializing a recipe validates each
+//! field like the corresponding `FromStr` implementation does.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DeriveParams, UserInputProvider,
+    policy::PasswordPolicy,
+    scheme::SchemeVersion,
+    user_input::{
+        CHAR_SET_NAMES, CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID,
+    },
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub service_id: ServiceID,
+    #[serde(default = "Recipe::default_generation")]
+    pub generation: Generation,
+    #[serde(rename = "char_sets", default = "Recipe::default_char_set")]
+    pub char_set: CharSet,
+    pub password_length: PasswordLength,
+    #[serde(default)]
+    pub scheme: SchemeVersion,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PasswordPolicy>,
+}
+
+impl Recipe {
+    fn default_generation() -> Generation {
+        Generation::try_from(1).expect("1 is a valid generation")
+    }
+    fn default_char_set() -> CharSet {
+        CharSet::from_names(&CHAR_SET_NAMES).expect("the presets are valid character sets")
+    }
+    pub fn new(service_id: ServiceID, char_set: CharSet, password_length: PasswordLength) -> Self {
+        Self {
+            service_id,
+            generation: Self::default_generation(),
+            char_set,
+            password_length,
+            scheme: SchemeVersion::default(),
+            policy: None,
+        }
+    }
+    /// The complete inputs of the derivation this recipe describes.
+    pub fn params(
+        &self,
+        user_id: UserID,
+        master_password_plain: MasterPasswordPlain,
+    ) -> DeriveParams {
+        DeriveParams {
+            scheme: self.scheme,
+            policy: self.policy.clone(),
+            ..DeriveParams::new(
+                user_id,
+                master_password_plain,
+                self.service_id.clone(),
+                self.generation.clone(),
+                self.char_set.clone(),
+                self.password_length.clone(),
+            )
+        }
+    }
+}
+
+impl<T: UserInputProvider> From<&T> for Recipe {
+    fn from(user_input: &T) -> Self {
+        Self {
+            service_id: user_input.get_service_id().clone(),
+            generation: user_input.get_generation().clone(),
+            char_set: user_input.get_char_set().clone(),
+            password_length: user_input.get_password_length().clone(),
+            scheme: user_input.get_scheme(),
+            policy: user_input.get_policy().cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::DerivePassRunner;
+
+    #[test]
+    fn can_round_trip_recipe() {
+        let recipe = Recipe {
+            generation: Generation::try_from(3).unwrap(),
+            scheme: SchemeVersion::V2,
+            policy: Some(PasswordPolicy {
+                max_length: Some(24),
+                ..PasswordPolicy::default()
+            }),
+            ..Recipe::new(
+                ServiceID::from_str("example.com").unwrap(),
+                CharSet::from_names(&["small", "custom:a-f_-", "no-ambiguous"]).unwrap(),
+                PasswordLength::try_from(20).unwrap(),
+            )
+        };
+
+        let json = serde_json::to_string(&recipe).unwrap();
+        assert_eq!(
+            json,
+            r#"{"service_id":"example.com","generation":3,"char_sets":["custom:abcdefghijkmnopqrstuvwxyz","custom:abcdef_\\-"],"password_length":20,"scheme":2,"policy":{"max_length":24}}"#
+        );
+        let from_json: Recipe = serde_json::from_str(&json).unwrap();
+        let from_toml: Recipe = toml::from_str(&toml::to_string(&recipe).unwrap()).unwrap();
+        for decoded in [from_json, from_toml] {
+            assert_eq!(decoded.char_set.to_string(), recipe.char_set.to_string());
+            assert_eq!(decoded.char_set.classes(), recipe.char_set.classes());
+            assert_eq!(decoded.generation.as_usize(), 3);
+            assert_eq!(decoded.scheme, SchemeVersion::V2);
+            assert_eq!(decoded.policy, recipe.policy);
+        }
+
+        let minimal: Recipe =
+            toml::from_str("service_id = \"Example Service Name\"\npassword_length = 20").unwrap();
+        assert_eq!(
+            "1@MWtAAqZ0p>;;y@zZ6d",
+            DerivePassRunner::run(minimal.params(
+                UserID::from_str("Example Eleonora").unwrap(),
+                MasterPasswordPlain::from_str(r##"]lE~WExZ468ty{I5mtg["##).unwrap(),
+            ))
+            .unwrap()
+            .expose_secret()
+        );
+    }
+
+    #[test]
+    fn can_reject_invalid_recipe() {
+        let test_cases = [
+            r#"{"service_id":"a","generation":0,"password_length":20}"#,
+            r#"{"service_id":"a","password_length":0}"#,
+            r#"{"service_id":"a","password_length":257}"#,
+            r#"{"service_id":"a","char_sets":["digits"],"password_length":20}"#,
+            r#"{"service_id":"a","char_sets":[],"password_length":20}"#,
+            r#"{"service_id":"a","password_length":20,"scheme":9}"#,
+        ];
+        for json in test_cases {
+            assert!(serde_json::from_str::<Recipe>(json).is_err(), "{}", json);
+        }
+        assert!(serde_json::from_str::<UserID>(r#""short""#).is_err());
+        assert!(serde_json::to_string(&CharSet::try_from([3, 4].as_slice()).unwrap()).is_err());
+    }
+}
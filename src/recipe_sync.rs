@@ -0,0 +1,239 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Keeps the recipe vault in a git repository so it follows the user across
+//! machines. Each sync reads the vault of the repository, merges it with the
+//! local one (the higher generation wins, see `RecipeVault::merge`) and
+//! commits the result. The repository only ever sees the encrypted file.
+//!
+//! Removing a recipe does not propagate, another machine brings it back.
+//!
+//! The repository is accessed with gix, the embedded git implementation, so
+//! no `git` command is needed and no hooks or configuration of the user are
+//! run. gix can not push yet: the remote is a bare repository on a path all
+//! machines reach, like a mounted share or a synced folder, and it is
+//! created on the first sync.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use gix::{
+    actor::Signature,
+    date::{Time, parse::TimeBuf},
+    objs::{
+        Tree,
+        tree::{Entry, EntryKind},
+    },
+};
+
+use crate::{recipe_vault::RecipeVault, session::Session, user_input::UserInputError};
+
+/// Branch of the repository holding the vault.
+const REFERENCE: &str = "refs/heads/main";
+/// Name of the vault in the repository.
+const FILE_NAME: &str = "recipes.vault";
+
+pub struct RecipeSync {
+    remote: PathBuf,
+}
+
+/// What a sync changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The local vault took entries or generations from the remote.
+    pub pulled: bool,
+    /// The remote took entries or generations from the local vault.
+    pub pushed: bool,
+}
+
+impl RecipeSync {
+    /// Takes the path of the repository, or a `file://` URL of it. Fails
+    /// for network remotes, gix can not push to them.
+    pub fn new(remote: &str) -> Result<Self> {
+        let path = remote.strip_prefix("file://").unwrap_or(remote);
+        if path.contains("://")
+            || path
+                .split_once(':')
+                .is_some_and(|(host, _)| host.len() > 1 && !host.contains(['/', '\\']))
+        {
+            bail!(UserInputError::new(
+                "The recipe vault syncs through the path of a bare git repository, like a mounted share or a synced folder, not a network remote"
+            ));
+        }
+        Ok(Self {
+            remote: PathBuf::from(path),
+        })
+    }
+    /// Merges the vault at `vault_path` with the one of the remote and
+    /// saves the result to both.
+    pub fn sync(&self, vault_path: &Path, session: &Session) -> Result<SyncReport> {
+        let repository = self.repository()?;
+        let head = repository
+            .try_find_reference(REFERENCE)?
+            .map(|mut reference| reference.peel_to_commit())
+            .transpose()?;
+        let origin = format!("{}:{}", self.remote.display(), FILE_NAME);
+        let mut shared = match &head {
+            Some(commit) => {
+                let tree = commit.tree()?;
+                let entry = tree
+                    .find_entry(FILE_NAME)
+                    .with_context(|| format!("Not found: {}", origin))?;
+                RecipeVault::unseal(&entry.object()?.data, session, &origin)?
+            }
+            None => RecipeVault::new(session)?,
+        };
+        let mut local = RecipeVault::open(vault_path, session)?;
+        let pulled = local.merge(shared.entries());
+        let pushed = shared.merge(local.entries()) || head.is_none();
+        if pulled {
+            local.save(vault_path)?;
+        }
+        if pushed {
+            let blob = repository.write_blob(shared.seal()?)?.detach();
+            let tree = repository.write_object(Tree {
+                entries: vec![Entry {
+                    mode: EntryKind::Blob.into(),
+                    filename: FILE_NAME.into(),
+                    oid: blob,
+                }],
+            })?;
+            let signature = Signature {
+                name: "depasswd".into(),
+                email: "depasswd@localhost".into(),
+                time: Time::now_local_or_utc(),
+            };
+            let (mut committer_time, mut author_time) = (TimeBuf::default(), TimeBuf::default());
+            // Fails when another machine committed since `head` was read.
+            repository
+                .commit_as(
+                    signature.to_ref(&mut committer_time),
+                    signature.to_ref(&mut author_time),
+                    REFERENCE,
+                    "Update recipes",
+                    tree,
+                    head.map(|commit| commit.id),
+                )
+                .context("Can not commit the recipe vault, run the sync again to merge the new remote state")?;
+        }
+        Ok(SyncReport { pulled, pushed })
+    }
+    /// Opens the repository without the configuration of the user and the
+    /// environment, creating it first.
+    fn repository(&self) -> Result<gix::Repository> {
+        if self.remote.exists() {
+            gix::open_opts(&self.remote, gix::open::Options::isolated()).map_err(|e| anyhow!(e))
+        } else {
+            gix::init_bare(&self.remote).map_err(|e| anyhow!(e))
+        }
+        .with_context(|| format!("Can not open the git repository {}", self.remote.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, str::FromStr};
+
+    use super::*;
+    use crate::{
+        recipe_vault::RecipeEntry,
+        scheme::SchemeVersion,
+        user_input::{MasterPasswordPlain, UserID},
+    };
+
+    #[test]
+    fn can_sync_through_remote() {
+        let dir = env::temp_dir().join(format!("depasswd-recipe-sync-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let sync = RecipeSync::new(dir.join("remote.git").to_str().unwrap()).unwrap();
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let laptop_vault = dir.join("laptop.vault");
+        let desktop_vault = dir.join("desktop.vault");
+
+        let entry = RecipeEntry {
+            service_id: "example.com".to_owned(),
+            generation: 3,
+            password_length: 20,
+            char_sets: vec!["small".to_owned(), "numbers".to_owned()],
+            policy: None,
+            username: None,
+            notes: None,
+            pending_rotation: false,
+        };
+        let mut vault = RecipeVault::open(&laptop_vault, &session).unwrap();
+        vault.set(entry.clone());
+        vault.save(&laptop_vault).unwrap();
+        assert_eq!(
+            sync.sync(&laptop_vault, &session).unwrap(),
+            SyncReport {
+                pulled: false,
+                pushed: true
+            }
+        );
+
+        let mut vault = RecipeVault::open(&desktop_vault, &session).unwrap();
+        vault.set(RecipeEntry {
+            service_id: "example.org".to_owned(),
+            ..entry.clone()
+        });
+        vault.save(&desktop_vault).unwrap();
+        assert_eq!(
+            sync.sync(&desktop_vault, &session).unwrap(),
+            SyncReport {
+                pulled: true,
+                pushed: true
+            }
+        );
+        assert_eq!(
+            RecipeVault::open(&desktop_vault, &session)
+                .unwrap()
+                .get("example.com"),
+            Some(&entry)
+        );
+
+        sync.sync(&laptop_vault, &session).unwrap();
+        assert!(
+            RecipeVault::open(&laptop_vault, &session)
+                .unwrap()
+                .get("example.org")
+                .is_some()
+        );
+        assert_eq!(
+            sync.sync(&laptop_vault, &session).unwrap(),
+            SyncReport {
+                pulled: false,
+                pushed: false
+            }
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_network_remotes() {
+        assert!(RecipeSync::new("https://example.com/recipes.git").is_err());
+        assert!(RecipeSync::new("git@example.com:recipes.git").is_err());
+        assert!(RecipeSync::new("file:///srv/recipes.git").is_ok());
+    }
+}
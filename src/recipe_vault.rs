@@ -0,0 +1,494 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The recipe vault is an optional file remembering the non-secret inputs
+//! of each service (generation, length, character sets, policy) and notes,
+//! so nobody has to remember which generation or length a site got. Unlike
+//! the plain `Catalog` it is encrypted with a key derived from the master
+//! secret: without the master password it does not even tell which
+//! services exist. The passwords stay derived, losing the vault only loses
+//! the bookkeeping.
+//!
+//! The file is `MAGIC`, a random nonce and the XSalsa20-Poly1305 sealed
+//! TOML of the entries.
+
+use std::{
+    fmt::Display,
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use crypto_secretbox::{
+    AeadCore, KeyInit, Nonce, XSalsa20Poly1305,
+    aead::{Aead, OsRng},
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::{
+    core::RECIPE_VAULT_LABEL,
+    derived_pass::DerivedPass,
+    policy::PasswordPolicy,
+    session::Session,
+    user_input::{CharSet, Generation, PasswordLength, ServiceID},
+    utils::Utils,
+};
+
+const MAGIC: &[u8] = b"depasswd-recipes-1\n";
+const NONCE_LENGTH: usize = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeEntry {
+    pub service_id: String,
+    pub generation: usize,
+    pub password_length: u16,
+    pub char_sets: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PasswordPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Imported from another password manager, the site still has the old
+    /// password until `depasswd rotate` changes it to the derived one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pending_rotation: bool,
+}
+
+impl RecipeEntry {
+    /// Derives the password of the service with the stored parameters.
+    pub fn derive(&self, session: &Session) -> Result<DerivedPass> {
+        let service_id = ServiceID::from_str(&self.service_id)?;
+        let generation = Generation::try_from(self.generation)?;
+        let char_set = CharSet::from_names(&self.char_sets)?;
+        let password_length = PasswordLength::from_str(&self.password_length.to_string())?;
+        Ok(match &self.policy {
+            Some(policy) => session.derive_with_policy(
+                &service_id,
+                &generation,
+                &char_set,
+                &password_length,
+                policy,
+            )?,
+            None => session.derive(&service_id, &generation, &char_set, &password_length)?,
+        })
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Recipes {
+    #[serde(default, rename = "recipe")]
+    entries: Vec<RecipeEntry>,
+}
+
+/// The passwords of a service around `RecipeVault::rotate`.
+pub struct Rotation {
+    /// The generation the vault derives from now on.
+    pub generation: usize,
+    /// The password of the previous generation, none when the rotation only
+    /// confirmed an imported entry, whose old password was not derived.
+    pub old_password: Option<DerivedPass>,
+    pub new_password: DerivedPass,
+}
+
+pub struct RecipeVault {
+    cipher: XSalsa20Poly1305,
+    entries: Vec<RecipeEntry>,
+}
+
+impl RecipeVault {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Utils::config_dir()?.join("recipes.vault"))
+    }
+    /// An empty vault with the key of `session`.
+    pub fn new(session: &Session) -> Result<Self> {
+        Ok(Self {
+            cipher: Self::cipher(session)?,
+            entries: vec![],
+        })
+    }
+    /// Decrypts the vault at `path` with the key of `session`, a missing
+    /// file is an empty vault.
+    pub fn open(path: &Path, session: &Session) -> Result<Self> {
+        match fs::read(path) {
+            Ok(sealed) => Self::unseal(&sealed, session, path.display()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Self::new(session),
+            Err(e) => {
+                Err(e).with_context(|| format!("Can not read recipe vault: {}", path.display()))
+            }
+        }
+    }
+    /// Decrypts the content of a vault file, `origin` names it in the errors.
+    pub fn unseal(sealed: &[u8], session: &Session, origin: impl Display) -> Result<Self> {
+        let cipher = Self::cipher(session)?;
+        let Some((nonce, ciphertext)) = sealed
+            .strip_prefix(MAGIC)
+            .and_then(|rest| rest.split_at_checked(NONCE_LENGTH))
+        else {
+            bail!("Not a recipe vault: {}", origin);
+        };
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| {
+                    anyhow!(
+                        "Can not decrypt the recipe vault {}, it belongs to another master password or was modified",
+                        origin
+                    )
+                })?,
+        );
+        let recipes: Recipes = toml::from_str(std::str::from_utf8(&plaintext)?)
+            .with_context(|| format!("Invalid recipe vault: {}", origin))?;
+        Ok(Self {
+            cipher,
+            entries: recipes.entries,
+        })
+    }
+    /// The content of the vault file, encrypted with a fresh nonce.
+    pub fn seal(&self) -> Result<Vec<u8>> {
+        let plaintext = Zeroizing::new(toml::to_string_pretty(&Recipes {
+            entries: self.entries.clone(),
+        })?);
+        let nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow!("Can not encrypt the recipe vault"))?;
+        Ok([MAGIC, &nonce, &ciphertext].concat())
+    }
+    /// Seals the vault and replaces the file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let sealed = self.seal()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written next to it and renamed, a failed write keeps the old vault.
+        let temporary = path.with_extension("vault.tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&temporary)
+            .and_then(|mut file| {
+                file.write_all(&sealed)?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&temporary, path))
+            .with_context(|| format!("Can not write recipe vault: {}", path.display()))
+    }
+    pub fn entries(&self) -> &[RecipeEntry] {
+        &self.entries
+    }
+    pub fn get(&self, service_id: &str) -> Option<&RecipeEntry> {
+        self.entries.iter().find(|e| e.service_id == service_id)
+    }
+    pub fn get_mut(&mut self, service_id: &str) -> Option<&mut RecipeEntry> {
+        self.entries.iter_mut().find(|e| e.service_id == service_id)
+    }
+    /// Adds the entry or replaces the one with the same service identifier.
+    pub fn set(&mut self, entry: RecipeEntry) {
+        match self.get_mut(&entry.service_id) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+    /// Merges the entries of another copy of the vault: unknown services are
+    /// added, known ones take the whole recipe of the higher generation (the
+    /// local one on a tie), so the merge never mixes settings into a password
+    /// neither side uses, and fill its unset username and notes from the
+    /// other. A rotation done on either side is kept. Returns whether
+    /// anything changed.
+    pub fn merge(&mut self, entries: &[RecipeEntry]) -> bool {
+        let mut changed = false;
+        for entry in entries {
+            let Some(existing) = self.get_mut(&entry.service_id) else {
+                self.entries.push(entry.clone());
+                changed = true;
+                continue;
+            };
+            let (newer, older) = if entry.generation > existing.generation {
+                (entry, &*existing)
+            } else {
+                (&*existing, entry)
+            };
+            let merged = RecipeEntry {
+                username: newer.username.clone().or(older.username.clone()),
+                notes: newer.notes.clone().or(older.notes.clone()),
+                pending_rotation: existing.pending_rotation && entry.pending_rotation,
+                ..newer.clone()
+            };
+            if *existing != merged {
+                *existing = merged;
+                changed = true;
+            }
+        }
+        changed
+    }
+    /// Moves the service to its next generation. An entry imported with
+    /// `pending_rotation` keeps its generation, as the site still has the
+    /// password of the other manager, and is only marked as rotated.
+    pub fn rotate(&mut self, service_id: &ServiceID, session: &Session) -> Result<Rotation> {
+        let Some(entry) = self.get_mut(&service_id.to_string()) else {
+            bail!(
+                "No recipe is stored for {}, add it with `depasswd recipe set` first",
+                service_id
+            );
+        };
+        if entry.pending_rotation {
+            entry.pending_rotation = false;
+            return Ok(Rotation {
+                generation: entry.generation,
+                old_password: None,
+                new_password: entry.derive(session)?,
+            });
+        }
+        let old_password = entry.derive(session)?;
+        let rotated = RecipeEntry {
+            generation: entry.generation + 1,
+            ..entry.clone()
+        };
+        let new_password = rotated.derive(session)?;
+        *entry = rotated;
+        Ok(Rotation {
+            generation: entry.generation,
+            old_password: Some(old_password),
+            new_password,
+        })
+    }
+    /// Returns whether there was an entry to remove.
+    pub fn remove(&mut self, service_id: &str) -> bool {
+        let count = self.entries.len();
+        self.entries.retain(|e| e.service_id != service_id);
+        self.entries.len() != count
+    }
+    /// HMAC-SHA256 of `RECIPE_VAULT_LABEL` keyed with the master secret.
+    fn cipher(session: &Session) -> Result<XSalsa20Poly1305> {
+        let mut hmac = <Hmac<Sha256> as Mac>::new_from_slice(session.master_secret().as_bytes())
+            .map_err(|_| anyhow!("Invalid master secret"))?;
+        hmac.update(RECIPE_VAULT_LABEL);
+        let key = Zeroizing::new(hmac.finalize().into_bytes());
+        Ok(XSalsa20Poly1305::new(&key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        user_input::{MasterPasswordPlain, UserID},
+    };
+
+    #[test]
+    fn can_round_trip_recipe_vault() {
+        let path = env::temp_dir().join(format!("depasswd-recipes-{}.vault", std::process::id()));
+        let unlock = |master_password: &str| {
+            Session::unlock(
+                &UserID::from_str("Example Eleonora").unwrap(),
+                &MasterPasswordPlain::from_str(master_password).unwrap(),
+                SchemeVersion::V1,
+            )
+            .unwrap()
+        };
+        let session = unlock("]lE~WExZ468ty{I5mtg[");
+
+        let mut vault = RecipeVault::open(&path, &session).unwrap();
+        assert!(vault.entries().is_empty());
+        let entry = RecipeEntry {
+            service_id: "example.com".to_owned(),
+            generation: 3,
+            password_length: 20,
+            char_sets: vec!["small".to_owned(), "numbers".to_owned()],
+            policy: None,
+            username: Some("eleonora".to_owned()),
+            notes: Some("Security questions: derived with purpose=answer".to_owned()),
+            pending_rotation: true,
+        };
+        vault.set(entry.clone());
+        vault.save(&path).unwrap();
+        let sealed = fs::read(&path).unwrap();
+        assert!(!sealed.windows(11).any(|w| w == b"example.com"));
+
+        let mut vault = RecipeVault::open(&path, &session).unwrap();
+        assert_eq!(vault.get("example.com"), Some(&entry));
+        assert!(RecipeVault::open(&path, &unlock("another master password")).is_err());
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        fs::write(&path, tampered).unwrap();
+        assert!(RecipeVault::open(&path, &session).is_err());
+
+        assert!(vault.remove("example.com"));
+        assert!(!vault.remove("example.com"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn can_merge_recipe_vaults() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let path = env::temp_dir().join(format!(
+            "depasswd-recipes-merge-{}.vault",
+            std::process::id()
+        ));
+        let local = RecipeEntry {
+            service_id: "example.com".to_owned(),
+            generation: 3,
+            password_length: 20,
+            char_sets: vec!["small".to_owned(), "numbers".to_owned()],
+            policy: None,
+            username: Some("eleonora".to_owned()),
+            notes: Some("Security questions: derived with purpose=answer".to_owned()),
+            pending_rotation: true,
+        };
+        let remote = RecipeEntry {
+            generation: 5,
+            password_length: 16,
+            char_sets: vec!["capital".to_owned(), "special".to_owned()],
+            username: None,
+            notes: None,
+            pending_rotation: false,
+            ..local.clone()
+        };
+        let other = RecipeEntry {
+            service_id: "example.org".to_owned(),
+            ..local.clone()
+        };
+        let mut vault = RecipeVault::open(&path, &session).unwrap();
+        vault.set(local.clone());
+
+        assert!(vault.merge(&[remote.clone(), other.clone()]));
+        let merged = vault.get("example.com").unwrap();
+        assert_eq!(merged.generation, 5);
+        assert_eq!(merged.password_length, remote.password_length);
+        assert_eq!(merged.char_sets, remote.char_sets);
+        assert_eq!(merged.username, local.username);
+        assert_eq!(merged.notes, local.notes);
+        assert!(!merged.pending_rotation);
+        assert_eq!(vault.get("example.org"), Some(&other));
+
+        // The older recipe changes nothing, also not its settings.
+        assert!(!vault.merge(std::slice::from_ref(&local)));
+        assert_eq!(vault.get("example.com").unwrap().password_length, 16);
+
+        // On a tie the local recipe is kept.
+        let mut tie = vault.get("example.com").unwrap().clone();
+        tie.password_length = 24;
+        assert!(!vault.merge(std::slice::from_ref(&tie)));
+        assert_eq!(vault.get("example.com").unwrap().password_length, 16);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn can_rotate_recipe() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let path = env::temp_dir().join(format!(
+            "depasswd-recipes-rotate-{}.vault",
+            std::process::id()
+        ));
+        let entry = RecipeEntry {
+            service_id: "Example Service Name".to_owned(),
+            generation: 1,
+            password_length: 20,
+            char_sets: vec![
+                "small".to_owned(),
+                "capital".to_owned(),
+                "numbers".to_owned(),
+                "special".to_owned(),
+            ],
+            policy: None,
+            username: None,
+            notes: None,
+            pending_rotation: false,
+        };
+        let imported = RecipeEntry {
+            service_id: "example.com".to_owned(),
+            generation: 2,
+            pending_rotation: true,
+            ..entry.clone()
+        };
+        let mut vault = RecipeVault::open(&path, &session).unwrap();
+        vault.set(entry.clone());
+        vault.set(imported.clone());
+
+        let rotation = vault
+            .rotate(
+                &ServiceID::from_str("Example Service Name").unwrap(),
+                &session,
+            )
+            .unwrap();
+        assert_eq!(rotation.generation, 2);
+        assert_eq!(
+            rotation.old_password.unwrap().expose_secret(),
+            "1@MWtAAqZ0p>;;y@zZ6d"
+        );
+        let rotated = vault.get("Example Service Name").unwrap();
+        assert_eq!(rotated.generation, 2);
+        assert_eq!(
+            rotation.new_password.expose_secret(),
+            rotated.derive(&session).unwrap().expose_secret()
+        );
+        assert_ne!(
+            rotation.new_password.expose_secret(),
+            "1@MWtAAqZ0p>;;y@zZ6d"
+        );
+
+        let confirmed = vault
+            .rotate(&ServiceID::from_str("example.com").unwrap(), &session)
+            .unwrap();
+        assert_eq!(confirmed.generation, 2);
+        assert!(confirmed.old_password.is_none());
+        assert_eq!(
+            confirmed.new_password.expose_secret(),
+            imported.derive(&session).unwrap().expose_secret()
+        );
+        assert_eq!(
+            vault.get("example.com"),
+            Some(&RecipeEntry {
+                pending_rotation: false,
+                ..imported
+            })
+        );
+
+        assert!(
+            vault
+                .rotate(&ServiceID::from_str("example.org").unwrap(), &session)
+                .is_err()
+        );
+        assert_eq!(vault.entries().len(), 2);
+        assert!(!path.exists());
+    }
+}
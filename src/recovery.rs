@@ -0,0 +1,255 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A printable document with every non-secret input needed to re-derive the
+//! catalog, and the algorithm itself, for whoever has to do it without us.
+
+use std::fmt::Write;
+
+use crate::{
+    CAPITAL_LETTERS, LAYOUT_SAFE_SPECIAL_CHARS, NUMBERS, SMALL_LETTERS, SPECIAL_CHARS,
+    catalog::Catalog,
+    profile::{ProfileParams, ProfileStore},
+    scheme::SchemeVersion,
+};
+
+pub struct RecoverySheet {}
+
+impl RecoverySheet {
+    /// Renders the sheet as Markdown for the master secret derived with
+    /// `params`. Without `user_id` a blank is left to be filled in by hand.
+    pub fn render(
+        user_id: Option<&str>,
+        params: &ProfileParams,
+        catalog: &Catalog,
+        profiles: &ProfileStore,
+    ) -> String {
+        let mut sheet = String::new();
+        let _ = writeln!(sheet, "# depasswd recovery sheet\n");
+        let _ = writeln!(
+            sheet,
+            "Every password listed here can be re-created from the master password \
+             and the values below. Nothing on this sheet is secret on its own, but \
+             keep it private: it tells an attacker which accounts exist.\n"
+        );
+        let _ = writeln!(sheet, "## Master secret\n");
+        let _ = writeln!(
+            sheet,
+            "- User id: {}",
+            user_id
+                .map(|u| format!("`{}`", u))
+                .unwrap_or_else(|| "________________________________".to_owned())
+        );
+        let _ = writeln!(sheet, "- Master password: not written down, ever");
+        let _ = writeln!(
+            sheet,
+            "- Scheme {}: {} with m={} KiB, t={}, p={}, unicode normalization: {}\n",
+            params.scheme,
+            params.kdf,
+            params.m_cost,
+            params.t_cost,
+            params.p_cost,
+            params.normalization
+        );
+        if !profiles.profiles().is_empty() {
+            let _ = writeln!(sheet, "## Profiles\n");
+            for profile in profiles.profiles() {
+                let _ = writeln!(
+                    sheet,
+                    "- {}: scheme {}, {} m={} KiB t={} p={}",
+                    profile.name,
+                    profile.params.scheme,
+                    profile.params.kdf,
+                    profile.params.m_cost,
+                    profile.params.t_cost,
+                    profile.params.p_cost
+                );
+            }
+            let _ = writeln!(sheet);
+        }
+        let _ = writeln!(sheet, "## Services\n");
+        if catalog.entries().is_empty() {
+            let _ = writeln!(sheet, "The catalog is empty.\n");
+        } else {
+            let _ = writeln!(
+                sheet,
+                "| Service id | User name | URL | Generation | Length | Character sets (in order) |"
+            );
+            let _ = writeln!(sheet, "|---|---|---|---|---|---|");
+            for entry in catalog.entries() {
+                let _ = writeln!(
+                    sheet,
+                    "| {} | {} | {} | {} | {} | {} |",
+                    Self::cell(&entry.service_id),
+                    Self::cell(entry.username.as_deref().unwrap_or("")),
+                    Self::cell(entry.url.as_deref().unwrap_or("")),
+                    entry
+                        .generation
+                        .map(|g| g.to_string())
+                        .unwrap_or_else(|| "?".to_owned()),
+                    entry
+                        .password_length
+                        .map(|l| l.to_string())
+                        .unwrap_or_else(|| "?".to_owned()),
+                    entry
+                        .char_sets
+                        .as_ref()
+                        .map(|c| c.join(", "))
+                        .unwrap_or_else(|| "?".to_owned()),
+                );
+            }
+            let _ = writeln!(
+                sheet,
+                "\n`?` marks settings that were never recorded; try generation 1 and the \
+                 lengths the site accepts.\n"
+            );
+        }
+        let _ = writeln!(sheet, "## Re-deriving a password\n");
+        let _ = writeln!(
+            sheet,
+            "With depasswd: run `depasswd`, enter the user id, then the values of the \
+             service's row, then the master password.\n"
+        );
+        let _ = writeln!(sheet, "Without depasswd, by hand in any language:\n");
+        let scheme = SchemeVersion::try_from(params.scheme).unwrap_or_default();
+        let (master_salt, service_salt) = match scheme {
+            SchemeVersion::V1 => (
+                "the byte length of the user id in decimal followed by the user id itself",
+                "the standard base64 text (no padding) of: byte length of the service id in \
+                 decimal, the service id, the password length, the generation",
+            ),
+            SchemeVersion::V2 | SchemeVersion::V3 => (
+                "`depasswd/v2/master`, the user id and, unless the KDF parameters are the \
+                 defaults, their id (e.g. `argon2id-m65536-t4-p4`), each preceded by its byte \
+                 length as a big-endian 32-bit number",
+                "`depasswd/v2/service`, the service id, the password length as a big-endian \
+                 16-bit number and the generation as a big-endian 64-bit number, each preceded \
+                 by its byte length as a big-endian 32-bit number",
+            ),
+        };
+        let _ = writeln!(
+            sheet,
+            "1. Master secret: {} of the UTF-8 master password, salted with {}.",
+            match params.kdf_params() {
+                Ok(kdf_params) => kdf_params.recipe(),
+                Err(_) => format!(
+                    "{} (m={}, t={}, p={})",
+                    params.kdf, params.m_cost, params.t_cost, params.p_cost
+                ),
+            },
+            master_salt
+        );
+        let _ = writeln!(
+            sheet,
+            "2. Service secret: {}, over {}.",
+            match scheme {
+                SchemeVersion::V1 | SchemeVersion::V2 => {
+                    "HMAC-SHA512 keyed with the lowercase hex text of the master secret"
+                }
+                SchemeVersion::V3 => "the first 64 bytes of BLAKE3 keyed with the master secret",
+            },
+            service_salt
+        );
+        let _ = writeln!(
+            sheet,
+            "3. Character pool: the chosen sets concatenated in the listed order, where \
+             small = `{}`, capital = `{}`, numbers = `{}`, special = `` {} ``, \
+             layout-safe = `{}`.",
+            SMALL_LETTERS, CAPITAL_LETTERS, NUMBERS, SPECIAL_CHARS, LAYOUT_SAFE_SPECIAL_CHARS
+        );
+        let _ = match scheme {
+            SchemeVersion::V1 => writeln!(
+                sheet,
+                "4. Character i of the password (from 0): pool[secret byte i mod pool length]."
+            ),
+            SchemeVersion::V2 | SchemeVersion::V3 => writeln!(
+                sheet,
+                "4. Characters: sort the pool by ASCII code and drop repeated characters, then \
+                 expand the secret with {}. Take its bytes in order, skip every \
+                 byte at or above the largest multiple of the pool length not above 256, and \
+                 append pool[byte mod pool length] for the others. Every time the password \
+                 is complete, keep it if it has a character of each chosen set, otherwise \
+                 start over with the next byte.",
+                match scheme {
+                    SchemeVersion::V3 => {
+                        "the BLAKE3 output of `depasswd/v2/password`, keyed with the first 32 \
+                         secret bytes"
+                    }
+                    _ => {
+                        "HMAC-SHA512 blocks keyed with the secret, over `depasswd/v2/password` \
+                         and a big-endian 32-bit counter starting at 1"
+                    }
+                }
+            ),
+        };
+        sheet
+    }
+    fn cell(value: &str) -> String {
+        value.replace('|', "\\|")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{catalog::CatalogEntry, kdf::KdfParams};
+
+    #[test]
+    fn can_render_recovery_sheet() {
+        let mut catalog = Catalog::default();
+        catalog.add(CatalogEntry {
+            username: Some("octocat".to_owned()),
+            generation: Some(2),
+            password_length: Some(20),
+            char_sets: Some(vec!["small".to_owned(), "numbers".to_owned()]),
+            ..CatalogEntry::new("github.com")
+        });
+        catalog.add(CatalogEntry::new("a|b"));
+
+        let sheet = RecoverySheet::render(
+            Some("Example Eleonora"),
+            &ProfileParams::new(SchemeVersion::V1, &KdfParams::DEFAULT),
+            &catalog,
+            &ProfileStore::default(),
+        );
+        assert!(sheet.contains("- User id: `Example Eleonora`"));
+        assert!(sheet.contains("| github.com | octocat |  | 2 | 20 | small, numbers |"));
+        assert!(sheet.contains("| a\\|b |  |  | ? | ? | ? |"));
+        assert!(sheet.contains("argon2id (version 0x13, m=32768 KiB, t=4, p=4"));
+        assert!(sheet.contains("pool[secret byte i mod pool length]"));
+        assert!(
+            RecoverySheet::render(
+                None,
+                &ProfileParams::new(SchemeVersion::V3, &KdfParams::DEFAULT),
+                &catalog,
+                &ProfileStore::default()
+            )
+            .contains("BLAKE3 keyed with the master secret")
+        );
+        assert!(
+            RecoverySheet::render(
+                None,
+                &ProfileParams::new(SchemeVersion::V1, &KdfParams::DEFAULT),
+                &Catalog::default(),
+                &ProfileStore::default()
+            )
+            .contains("The catalog is empty.")
+        );
+    }
+}
@@ -0,0 +1,395 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A local JSON API for GUI frontends and browser extensions that can not
+//! use native messaging.
+//!
+//! Endpoints:
+//! - `POST /derive` with `{"service_id", "generation"?, "char_sets"?,
+//!   "password_length"?}`: answers `{"password"}`.
+//! - `POST /totp` with `{"service_id", "generation"?, "issuer"?, "account"?}`:
+//!   answers `{"seed", "uri"}`.
+//!
+//! Every request must carry the random token of the server run in
+//! `Authorization: Bearer <token>`. A `Host` other than a loopback address
+//! is refused, so a DNS rebinding page can not reach the API, and so is any
+//! `Origin` not explicitly allowed. Allowed origins get CORS headers.
+
+use std::{
+    io::Read,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, anyhow};
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use subtle::ConstantTimeEq;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    DerivePassError,
+    batch::DerivationRequest,
+    session::Session,
+    user_input::{Generation, ServiceID, UserInputError},
+    utils::Utils,
+};
+
+/// Requests are a handful of short strings.
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
+
+pub struct RestRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub host: Option<&'a str>,
+    pub origin: Option<&'a str>,
+    pub authorization: Option<&'a str>,
+    pub body: &'a str,
+}
+
+pub struct RestResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl RestResponse {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({"error": message.to_string()}),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeriveBody {
+    service_id: String,
+    generation: Option<usize>,
+    char_sets: Option<Vec<String>>,
+    password_length: Option<u16>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TotpBody {
+    service_id: String,
+    generation: Option<usize>,
+    issuer: Option<String>,
+    account: Option<String>,
+}
+
+pub struct RestServer {
+    session: Arc<Mutex<Session>>,
+    token: String,
+    allowed_origins: Vec<String>,
+    char_sets: Vec<String>,
+    password_length: u16,
+}
+
+impl RestServer {
+    pub fn new(
+        session: Arc<Mutex<Session>>,
+        allowed_origins: &[String],
+        char_sets: &[String],
+        password_length: u16,
+    ) -> Self {
+        let mut token = [0u8; 32];
+        OsRng.fill_bytes(&mut token);
+        Self {
+            session,
+            token: Utils::bytes_to_hex(&token),
+            allowed_origins: allowed_origins
+                .iter()
+                .map(|o| o.trim_end_matches('/').to_owned())
+                .collect(),
+            char_sets: char_sets.to_vec(),
+            password_length,
+        }
+    }
+    /// The random token clients must send as `Authorization: Bearer <token>`.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+    /// Binds `address`, which must be a loopback address, and returns the
+    /// server with the address actually bound (port 0 picks a free one).
+    pub fn bind(address: &str) -> Result<(Server, SocketAddr)> {
+        Utils::bind_loopback(address, "the API only serves local frontends")
+    }
+    pub fn serve(&self, server: Server) -> Result<()> {
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            let response = match request
+                .as_reader()
+                .take(MAX_BODY_LENGTH + 1)
+                .read_to_string(&mut body)
+            {
+                Ok(length) if length as u64 > MAX_BODY_LENGTH => {
+                    RestResponse::error(413, "request body is too large")
+                }
+                Ok(_) => self.handle(&RestRequest {
+                    method: request.method().as_str(),
+                    path: request.url(),
+                    host: Self::header_value(&request, "Host"),
+                    origin: Self::header_value(&request, "Origin"),
+                    authorization: Self::header_value(&request, "Authorization"),
+                    body: &body,
+                }),
+                Err(_) => RestResponse::error(400, "request body is not UTF-8"),
+            };
+            let origin = Self::header_value(&request, "Origin")
+                .filter(|origin| self.is_allowed_origin(origin))
+                .map(str::to_owned);
+            let mut http_response = Response::from_string(response.body.to_string())
+                .with_status_code(response.status)
+                .with_header(Self::header("Content-Type", "application/json")?)
+                .with_header(Self::header("Cache-Control", "no-store")?);
+            if let Some(origin) = origin {
+                http_response = http_response
+                    .with_header(Self::header("Access-Control-Allow-Origin", &origin)?)
+                    .with_header(Self::header("Vary", "Origin")?);
+                if *request.method() == Method::Options {
+                    http_response = http_response
+                        .with_header(Self::header("Access-Control-Allow-Methods", "POST")?)
+                        .with_header(Self::header(
+                            "Access-Control-Allow-Headers",
+                            "Authorization, Content-Type",
+                        )?);
+                }
+            }
+            request.respond(http_response)?;
+        }
+        Ok(())
+    }
+    /// Handles one request, checking its host, origin and token first.
+    pub fn handle(&self, request: &RestRequest) -> RestResponse {
+        if !request.host.is_some_and(Self::is_loopback_host) {
+            return RestResponse::error(421, "the API only answers on a loopback host");
+        }
+        if request
+            .origin
+            .is_some_and(|origin| !self.is_allowed_origin(origin))
+        {
+            return RestResponse::error(403, "origin is not allowed");
+        }
+        if request.method == "OPTIONS" {
+            return RestResponse::ok(json!({}));
+        }
+        let token = request
+            .authorization
+            .and_then(|a| a.strip_prefix("Bearer "));
+        if !token.is_some_and(|t| t.as_bytes().ct_eq(self.token.as_bytes()).into()) {
+            return RestResponse::error(401, "missing or invalid token");
+        }
+        let path = request.path.split_once('?').map_or(request.path, |p| p.0);
+        match (request.method, path) {
+            ("POST", "/derive") => Self::parse(request.body).map_or_else(|e| e, |b| self.derive(b)),
+            ("POST", "/totp") => Self::parse(request.body).map_or_else(|e| e, |b| self.totp(b)),
+            (_, "/derive" | "/totp") => RestResponse::error(405, "only POST is supported"),
+            _ => RestResponse::error(404, format!("no handler for route \"{}\"", path)),
+        }
+    }
+    fn parse<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, RestResponse> {
+        serde_json::from_str(body)
+            .map_err(|e| RestResponse::error(400, format!("invalid request body: {}", e)))
+    }
+    fn derive(&self, body: DeriveBody) -> RestResponse {
+        let request = DerivationRequest {
+            key: None,
+            service_id: body.service_id,
+            generation: body.generation.unwrap_or(1),
+            char_sets: body.char_sets.unwrap_or_else(|| self.char_sets.clone()),
+            password_length: body.password_length.unwrap_or(self.password_length),
+        };
+        let derived_pass = match self.session.lock() {
+            Ok(session) => request.derive(&session),
+            Err(_) => return RestResponse::error(500, "session state is unavailable"),
+        };
+        match derived_pass {
+            Ok(derived_pass) => RestResponse::ok(json!({"password": derived_pass.expose_secret()})),
+            Err(e) => Self::derivation_error(e),
+        }
+    }
+    fn totp(&self, body: TotpBody) -> RestResponse {
+        let seed = (|| {
+            let service_id: ServiceID = body.service_id.parse()?;
+            let generation: Generation = body.generation.unwrap_or(1).to_string().parse()?;
+            let session = self.session.lock().map_err(|_| DerivePassError::Secret)?;
+            session.derive_totp_seed(&service_id, &generation)
+        })();
+        match seed {
+            Ok(seed) => RestResponse::ok(json!({
+                "seed": seed.base32().as_str(),
+                "uri": seed
+                    .uri(
+                        body.issuer.as_deref().unwrap_or(&body.service_id),
+                        body.account.as_deref(),
+                    )
+                    .as_str(),
+            })),
+            Err(e) => Self::derivation_error(e),
+        }
+    }
+    fn derivation_error(e: anyhow::Error) -> RestResponse {
+        if e.is::<UserInputError>()
+            || e.downcast_ref::<DerivePassError>()
+                .is_some_and(DerivePassError::is_invalid_request)
+        {
+            RestResponse::error(400, e)
+        } else {
+            RestResponse::error(500, e)
+        }
+    }
+    fn is_allowed_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == origin.trim_end_matches('/'))
+    }
+    fn is_loopback_host(host: &str) -> bool {
+        let name = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+            None => host.split(':').next().unwrap_or_default(),
+        };
+        name.eq_ignore_ascii_case("localhost")
+            || name
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+    }
+    fn header_value<'a>(request: &'a tiny_http::Request, name: &'static str) -> Option<&'a str> {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str())
+    }
+    fn header(name: &str, value: &str) -> Result<Header> {
+        Header::from_bytes(name, value).map_err(|_| anyhow!("Invalid header: {}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        user_input::{CHAR_SET_NAMES, MasterPasswordPlain, UserID},
+    };
+
+    fn server(scheme: SchemeVersion) -> RestServer {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            scheme,
+        )
+        .unwrap();
+        let char_sets: Vec<String> = CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect();
+        RestServer::new(
+            Arc::new(Mutex::new(session)),
+            &["chrome-extension://abcdef".to_owned()],
+            &char_sets,
+            20,
+        )
+    }
+
+    #[test]
+    fn can_serve_derive_and_totp() {
+        let server = server(SchemeVersion::V2);
+        let authorization = format!("Bearer {}", server.token());
+        let request = |path, body| RestRequest {
+            method: "POST",
+            path,
+            host: Some("127.0.0.1:8300"),
+            origin: Some("chrome-extension://abcdef"),
+            authorization: Some(&authorization),
+            body,
+        };
+
+        let response = server.handle(&request("/derive", r#"{"service_id": "github.com"}"#));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["password"].as_str().unwrap().len(), 20);
+
+        let response = server.handle(&request(
+            "/totp",
+            r#"{"service_id": "github.com", "account": "octocat"}"#,
+        ));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["seed"].as_str().unwrap().len(), 32);
+        assert!(
+            response.body["uri"]
+                .as_str()
+                .unwrap()
+                .starts_with("otpauth://totp/github.com:octocat?secret=")
+        );
+
+        let response = server.handle(&request("/derive", r#"{"service_id": "a", "x": 1}"#));
+        assert_eq!(response.status, 400);
+        assert_eq!(server.handle(&request("/other", "{}")).status, 404);
+    }
+
+    #[test]
+    fn can_reject_untrusted_requests() {
+        let server = server(SchemeVersion::V1);
+        let authorization = format!("Bearer {}", server.token());
+        let body = r#"{"service_id": "Example Service Name"}"#;
+        let request = RestRequest {
+            method: "POST",
+            path: "/derive",
+            host: Some("localhost:8300"),
+            origin: None,
+            authorization: Some(&authorization),
+            body,
+        };
+        let response = server.handle(&request);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["password"], "1@MWtAAqZ0p>;;y@zZ6d");
+
+        let test_cases = [
+            (Some("[::1]:8300"), None, Some(authorization.as_str()), 200),
+            (Some("evil.example:8300"), None, Some(&authorization), 421),
+            (None, None, Some(&authorization), 421),
+            (
+                Some("127.0.0.1"),
+                Some("https://evil.example"),
+                Some(&authorization),
+                403,
+            ),
+            (Some("127.0.0.1"), None, Some("Bearer wrong"), 401),
+            (Some("127.0.0.1"), None, None, 401),
+        ];
+        for (host, origin, authorization, status) in test_cases {
+            let request = RestRequest {
+                host,
+                origin,
+                authorization,
+                ..request
+            };
+            assert_eq!(server.handle(&request).status, status, "{:?}", host);
+        }
+
+        assert!(RestServer::bind("0.0.0.0:0").is_err());
+        let (_, address) = RestServer::bind("127.0.0.1:0").unwrap();
+        assert!(address.ip().is_loopback() && address.port() != 0);
+    }
+}
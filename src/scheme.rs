@@ -0,0 +1,97 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Every change to the derivation algorithm ships as a new scheme version.
+//! Released versions are never modified, so a password derived with a given
+//! version can be re-derived with any later release of depasswd. Versions
+//! newer than the last release may still change until they ship.
+
+use std::{fmt::Display, str::FromStr};
+
+pub use crate::core::{MASTER_LABEL, PASSWORD_LABEL, SERVICE_LABEL, SchemeVersion};
+use crate::user_input::UserInputError;
+
+/// Domain separation labels mixed into every stage from scheme v2 on, so
+/// their output can never collide with another tool's, or with other kinds
+/// of secrets derived from the same service secret.
+pub const PASSPHRASE_LABEL: &[u8] = b"depasswd/v2/passphrase";
+pub const ANSWER_LABEL: &[u8] = b"depasswd/v2/answer";
+pub const RECOVERY_CODE_LABEL: &[u8] = b"depasswd/v2/recovery-code";
+pub const PRONOUNCEABLE_LABEL: &[u8] = b"depasswd/v2/pronounceable";
+pub const TEMPLATE_LABEL: &[u8] = b"depasswd/v2/template";
+pub const TOTP_LABEL: &[u8] = b"depasswd/v2/totp";
+
+impl TryFrom<u32> for SchemeVersion {
+    type Error = UserInputError;
+    fn try_from(value: u32) -> std::result::Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|v| v.as_u32() == value)
+            .ok_or(UserInputError::new(format!(
+                "Unknown scheme version: {} (this version of depasswd supports 1 to {})",
+                value,
+                Self::LATEST.as_u32()
+            )))
+    }
+}
+
+impl From<SchemeVersion> for u32 {
+    fn from(value: SchemeVersion) -> Self {
+        value.as_u32()
+    }
+}
+
+impl FromStr for SchemeVersion {
+    type Err = UserInputError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.strip_prefix('v')
+            .unwrap_or(s)
+            .parse::<u32>()
+            .map_err(|_| UserInputError::new(format!("Invalid scheme version: {}", s)))
+            .and_then(Self::try_from)
+    }
+}
+
+impl Display for SchemeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}", self.as_u32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_scheme_version() {
+        assert_eq!(SchemeVersion::from_str("1").unwrap(), SchemeVersion::V1);
+        assert_eq!(SchemeVersion::from_str("v1").unwrap(), SchemeVersion::V1);
+        assert_eq!(SchemeVersion::from_str("v2").unwrap(), SchemeVersion::V2);
+        assert_eq!(SchemeVersion::V1.to_string(), "v1");
+        assert!(SchemeVersion::from_str("0").is_err());
+        assert!(SchemeVersion::from_str("999").is_err());
+        assert!(SchemeVersion::from_str("latest").is_err());
+        for version in SchemeVersion::ALL {
+            assert_eq!(
+                SchemeVersion::from_str(&version.to_string()).unwrap(),
+                version
+            );
+        }
+    }
+}
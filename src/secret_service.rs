@@ -0,0 +1,643 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A provider of the freedesktop.org Secret Service API
+//! (`org.freedesktop.secrets`), so desktop applications looking passwords
+//! up through libsecret receive derived ones for the services of the
+//! catalog. Nothing can be stored: creating, changing or deleting items is
+//! refused.
+//!
+//! The items are the catalog entries, in one always unlocked `login`
+//! collection that is also the `default` alias. A search matches an entry
+//! when one of its service attributes (`service`, `server`, `domain`,
+//! `host`, `url`, `origin`) names the entry's service id or URL, and its
+//! user attributes (`username`, `user`, `account`) name the entry's
+//! username if it has one. Other attributes are ignored, and an empty
+//! search lists every item.
+//!
+//! Like an unlocked keyring, the secrets are readable by every process of
+//! the desktop session. Only the `plain` transfer algorithm is offered;
+//! libsecret falls back to it.
+
+use std::{
+    collections::BTreeSet,
+    io::ErrorKind,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, bail};
+use zeroize::Zeroize;
+
+use crate::{
+    batch::DerivationRequest,
+    catalog::{Catalog, CatalogEntry},
+    dbus::{Connection, Message, MessageType, Value},
+    session::Session,
+};
+
+const SERVICE_NAME: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/login";
+const DEFAULT_ALIAS_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+const SESSION_PATH: &str = "/org/freedesktop/secrets/session";
+
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const SESSION_INTERFACE: &str = "org.freedesktop.Secret.Session";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+
+const SERVICE_ATTRIBUTES: [&str; 6] = ["service", "server", "domain", "host", "url", "origin"];
+const USER_ATTRIBUTES: [&str; 3] = ["username", "user", "account"];
+
+const CONTENT_TYPE: &str = "text/plain; charset=utf8";
+
+enum Object {
+    Service,
+    Collection,
+    Item(usize),
+    Session(String),
+}
+
+struct MethodError {
+    name: &'static str,
+    message: String,
+}
+
+impl MethodError {
+    fn new(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            message: message.into(),
+        }
+    }
+    fn not_supported() -> Self {
+        Self::new(
+            "org.freedesktop.DBus.Error.NotSupported",
+            "depasswd derives secrets, it does not store them",
+        )
+    }
+    fn invalid_args(signature: &str) -> Self {
+        Self::new(
+            "org.freedesktop.DBus.Error.InvalidArgs",
+            format!("Expected arguments of type ({})", signature),
+        )
+    }
+}
+
+pub struct SecretService {
+    session: Arc<Mutex<Session>>,
+    entries: Vec<CatalogEntry>,
+    char_sets: Vec<String>,
+    password_length: u16,
+    sessions: BTreeSet<String>,
+    next_session: u32,
+}
+
+impl SecretService {
+    pub fn new(
+        session: Arc<Mutex<Session>>,
+        entries: &[CatalogEntry],
+        char_sets: &[String],
+        password_length: u16,
+    ) -> Self {
+        Self {
+            session,
+            entries: entries.to_vec(),
+            char_sets: char_sets.to_vec(),
+            password_length,
+            sessions: BTreeSet::new(),
+            next_session: 0,
+        }
+    }
+    /// Takes `org.freedesktop.secrets` on the bus of `connection`, failing
+    /// when another keyring already provides it.
+    pub fn claim_name(connection: &mut Connection) -> Result<()> {
+        if !connection.request_name(SERVICE_NAME)? {
+            bail!(
+                "{} is already provided by another program (gnome-keyring, KeePassXC...), stop it first",
+                SERVICE_NAME
+            );
+        }
+        Ok(())
+    }
+    /// Answers method calls until the connection is closed.
+    pub fn serve(&mut self, mut connection: Connection) -> Result<()> {
+        loop {
+            let call = match connection.receive() {
+                Err(e)
+                    if e.downcast_ref::<std::io::Error>()
+                        .is_some_and(|e| e.kind() == ErrorKind::UnexpectedEof) =>
+                {
+                    return Ok(());
+                }
+                call => call?,
+            };
+            if call.message_type != MessageType::MethodCall {
+                continue;
+            }
+            let mut reply = self.handle(&call);
+            if call.expects_reply() {
+                connection.send(&mut reply)?;
+            }
+            reply.body.zeroize();
+        }
+    }
+    /// Answers one method call.
+    pub fn handle(&mut self, call: &Message) -> Message {
+        match self.dispatch(call) {
+            Ok(body) => Message::method_return(call, body),
+            Err(e) => Message::error(call, e.name, &e.message),
+        }
+    }
+    fn dispatch(&mut self, call: &Message) -> Result<Vec<Value>, MethodError> {
+        let path = call.path.as_deref().unwrap_or_default();
+        let object = self.object(path).ok_or_else(|| {
+            MethodError::new(
+                "org.freedesktop.DBus.Error.UnknownObject",
+                format!("No such object: {}", path),
+            )
+        })?;
+        let interface = call.interface.as_deref();
+        let member = call.member.as_deref().unwrap_or_default();
+        let is = |expected: &str| interface.is_none_or(|i| i == expected);
+        match member {
+            "Introspect" if is(INTROSPECTABLE_INTERFACE) => {
+                Ok(vec![Value::str(self.introspect(&object))])
+            }
+            "Get" if is(PROPERTIES_INTERFACE) => {
+                let [interface, name] = Self::args(call, "ss")?;
+                let properties = self.properties(&object, interface.as_str().unwrap_or(""))?;
+                properties
+                    .into_iter()
+                    .find(|(n, _)| Some(*n) == name.as_str())
+                    .map(|(_, value)| vec![Value::variant(value)])
+                    .ok_or_else(|| {
+                        MethodError::new(
+                            "org.freedesktop.DBus.Error.UnknownProperty",
+                            format!("No such property: {}", name.as_str().unwrap_or_default()),
+                        )
+                    })
+            }
+            "GetAll" if is(PROPERTIES_INTERFACE) => {
+                let [interface] = Self::args(call, "s")?;
+                let properties = self.properties(&object, interface.as_str().unwrap_or(""))?;
+                Ok(vec![Value::dict(
+                    "s",
+                    "v",
+                    properties
+                        .into_iter()
+                        .map(|(n, v)| (Value::str(n), Value::variant(v))),
+                )])
+            }
+            "Set" if is(PROPERTIES_INTERFACE) => Err(MethodError::new(
+                "org.freedesktop.DBus.Error.PropertyReadOnly",
+                "Every property is read-only",
+            )),
+            _ => match object {
+                Object::Service if is(SERVICE_INTERFACE) => self.service_method(member, call),
+                Object::Collection if is(COLLECTION_INTERFACE) => {
+                    self.collection_method(member, call)
+                }
+                Object::Item(index) if is(ITEM_INTERFACE) => self.item_method(index, member, call),
+                Object::Session(path) if is(SESSION_INTERFACE) && member == "Close" => {
+                    Self::args::<0>(call, "")?;
+                    self.sessions.remove(&path);
+                    Ok(vec![])
+                }
+                _ => Err(MethodError::new(
+                    "org.freedesktop.DBus.Error.UnknownMethod",
+                    format!("No such method: {}", member),
+                )),
+            },
+        }
+    }
+    fn service_method(&mut self, member: &str, call: &Message) -> Result<Vec<Value>, MethodError> {
+        match member {
+            "OpenSession" => {
+                let [algorithm, _] = Self::args(call, "sv")?;
+                if algorithm.as_str() != Some("plain") {
+                    return Err(MethodError::new(
+                        "org.freedesktop.DBus.Error.NotSupported",
+                        "Only the plain algorithm is supported",
+                    ));
+                }
+                self.next_session += 1;
+                let path = format!("{}/{}", SESSION_PATH, self.next_session);
+                self.sessions.insert(path.clone());
+                Ok(vec![Value::variant(Value::str("")), Value::path(path)])
+            }
+            "SearchItems" => {
+                let [attributes] = Self::args(call, "a{ss}")?;
+                Ok(vec![
+                    Value::paths(self.search(attributes)),
+                    Value::paths(Vec::<String>::new()),
+                ])
+            }
+            "Unlock" => {
+                let [objects] = Self::args(call, "ao")?;
+                let unlocked = objects
+                    .as_array()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter(|path| self.object(path).is_some())
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>();
+                Ok(vec![Value::paths(unlocked), Value::path("/")])
+            }
+            "Lock" => {
+                Self::args::<1>(call, "ao")?;
+                Ok(vec![Value::paths(Vec::<String>::new()), Value::path("/")])
+            }
+            "GetSecrets" => {
+                let [items, session] = Self::args(call, "aoo")?;
+                let session = self.session_path(session)?;
+                let mut secrets = vec![];
+                for path in items.as_array().unwrap_or_default() {
+                    if let Some(Object::Item(index)) = path.as_str().and_then(|p| self.object(p)) {
+                        secrets.push((path.clone(), self.secret(index, &session)?));
+                    }
+                }
+                Ok(vec![Value::dict("o", "(oayays)", secrets)])
+            }
+            "ReadAlias" => {
+                let [name] = Self::args(call, "s")?;
+                Ok(vec![Value::path(match name.as_str() {
+                    Some("default" | "login") => COLLECTION_PATH,
+                    _ => "/",
+                })])
+            }
+            "CreateCollection" | "SetAlias" => Err(MethodError::not_supported()),
+            _ => Err(MethodError::new(
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                format!("No such method: {}", member),
+            )),
+        }
+    }
+    fn collection_method(&self, member: &str, call: &Message) -> Result<Vec<Value>, MethodError> {
+        match member {
+            "SearchItems" => {
+                let [attributes] = Self::args(call, "a{ss}")?;
+                Ok(vec![Value::paths(self.search(attributes))])
+            }
+            "CreateItem" | "Delete" => Err(MethodError::not_supported()),
+            _ => Err(MethodError::new(
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                format!("No such method: {}", member),
+            )),
+        }
+    }
+    fn item_method(
+        &self,
+        index: usize,
+        member: &str,
+        call: &Message,
+    ) -> Result<Vec<Value>, MethodError> {
+        match member {
+            "GetSecret" => {
+                let [session] = Self::args(call, "o")?;
+                let session = self.session_path(session)?;
+                Ok(vec![self.secret(index, &session)?])
+            }
+            "SetSecret" | "Delete" => Err(MethodError::not_supported()),
+            _ => Err(MethodError::new(
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                format!("No such method: {}", member),
+            )),
+        }
+    }
+    /// The body of `call`, checked against `signature`.
+    fn args<'a, const N: usize>(
+        call: &'a Message,
+        signature: &str,
+    ) -> Result<&'a [Value; N], MethodError> {
+        if call.signature() != signature {
+            return Err(MethodError::invalid_args(signature));
+        }
+        call.body
+            .as_slice()
+            .try_into()
+            .map_err(|_| MethodError::invalid_args(signature))
+    }
+    fn session_path(&self, session: &Value) -> Result<String, MethodError> {
+        session
+            .as_str()
+            .filter(|path| self.sessions.contains(*path))
+            .map(str::to_owned)
+            .ok_or_else(|| {
+                MethodError::new(
+                    "org.freedesktop.Secret.Error.NoSession",
+                    "No such session, call OpenSession first",
+                )
+            })
+    }
+    fn object(&self, path: &str) -> Option<Object> {
+        match path {
+            SERVICE_PATH => Some(Object::Service),
+            COLLECTION_PATH | DEFAULT_ALIAS_PATH => Some(Object::Collection),
+            _ if self.sessions.contains(path) => Some(Object::Session(path.to_owned())),
+            _ => (0..self.entries.len())
+                .find(|i| Self::item_path(*i) == path)
+                .map(Object::Item),
+        }
+    }
+    fn item_path(index: usize) -> String {
+        format!("{}/{}", COLLECTION_PATH, index)
+    }
+    fn search(&self, attributes: &Value) -> Vec<String> {
+        let attributes = attributes.as_string_dict().unwrap_or_default();
+        (0..self.entries.len())
+            .filter(|i| Self::matches(&self.entries[*i], &attributes))
+            .map(Self::item_path)
+            .collect()
+    }
+    /// Whether `entry` is what a search for `attributes` looks for, see the
+    /// module documentation.
+    pub fn matches(entry: &CatalogEntry, attributes: &[(&str, &str)]) -> bool {
+        if attributes.is_empty() {
+            return true;
+        }
+        let mut named = false;
+        for (key, value) in attributes {
+            if SERVICE_ATTRIBUTES.contains(key) {
+                if *value != entry.service_id
+                    && entry.url.as_deref() != Some(*value)
+                    && Catalog::canonical_service_id(value).as_ref() != Some(&entry.service_id)
+                {
+                    return false;
+                }
+                named = true;
+            } else if USER_ATTRIBUTES.contains(key)
+                && entry.username.as_deref().is_some_and(|u| u != *value)
+            {
+                return false;
+            }
+        }
+        named
+    }
+    fn attributes(entry: &CatalogEntry) -> Value {
+        let mut attributes = vec![("service", entry.service_id.as_str())];
+        attributes.extend(entry.username.as_deref().map(|u| ("username", u)));
+        attributes.extend(entry.url.as_deref().map(|u| ("url", u)));
+        Value::dict(
+            "s",
+            "s",
+            attributes
+                .into_iter()
+                .map(|(k, v)| (Value::str(k), Value::str(v))),
+        )
+    }
+    /// The `(oayays)` secret of an item: session, parameters, value and
+    /// content type.
+    fn secret(&self, index: usize, session: &str) -> Result<Value, MethodError> {
+        let entry = &self.entries[index];
+        let request = DerivationRequest {
+            key: None,
+            service_id: entry.service_id.clone(),
+            generation: entry.generation.unwrap_or(1),
+            char_sets: entry
+                .char_sets
+                .clone()
+                .unwrap_or_else(|| self.char_sets.clone()),
+            password_length: entry.password_length.unwrap_or(self.password_length),
+        };
+        let failed = |e: &dyn std::fmt::Display| {
+            MethodError::new("org.freedesktop.DBus.Error.Failed", e.to_string())
+        };
+        let session_state = self
+            .session
+            .lock()
+            .map_err(|_| failed(&"session state is unavailable"))?;
+        let derived_pass = request.derive(&session_state).map_err(|e| failed(&e))?;
+        Ok(Value::Struct(vec![
+            Value::path(session),
+            Value::bytes(&[]),
+            Value::bytes(derived_pass.expose_secret().as_bytes()),
+            Value::str(CONTENT_TYPE),
+        ]))
+    }
+    fn properties(
+        &self,
+        object: &Object,
+        interface: &str,
+    ) -> Result<Vec<(&'static str, Value)>, MethodError> {
+        let (name, properties) = match object {
+            Object::Service => (
+                SERVICE_INTERFACE,
+                vec![("Collections", Value::paths([COLLECTION_PATH]))],
+            ),
+            Object::Collection => (
+                COLLECTION_INTERFACE,
+                vec![
+                    (
+                        "Items",
+                        Value::paths((0..self.entries.len()).map(Self::item_path)),
+                    ),
+                    ("Label", Value::str("depasswd")),
+                    ("Locked", Value::Bool(false)),
+                    ("Created", Value::U64(0)),
+                    ("Modified", Value::U64(0)),
+                ],
+            ),
+            Object::Item(index) => (
+                ITEM_INTERFACE,
+                vec![
+                    ("Locked", Value::Bool(false)),
+                    ("Attributes", Self::attributes(&self.entries[*index])),
+                    ("Label", Value::str(&self.entries[*index].service_id)),
+                    ("Created", Value::U64(0)),
+                    ("Modified", Value::U64(0)),
+                ],
+            ),
+            Object::Session(_) => (SESSION_INTERFACE, vec![]),
+        };
+        if !interface.is_empty() && interface != name {
+            return Err(MethodError::new(
+                "org.freedesktop.DBus.Error.UnknownInterface",
+                format!("No such interface: {}", interface),
+            ));
+        }
+        Ok(properties)
+    }
+    fn introspect(&self, object: &Object) -> String {
+        let (interface, children) = match object {
+            Object::Service => (SERVICE_INTERFACE, vec!["collection".to_owned()]),
+            Object::Collection => (
+                COLLECTION_INTERFACE,
+                (0..self.entries.len()).map(|i| i.to_string()).collect(),
+            ),
+            Object::Item(_) => (ITEM_INTERFACE, vec![]),
+            Object::Session(_) => (SESSION_INTERFACE, vec![]),
+        };
+        let mut xml = format!(
+            "<!DOCTYPE node PUBLIC \"-//freedesktop//DTD D-BUS Object Introspection 1.0//EN\" \"http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd\">\n<node>\n  <interface name=\"{}\"/>\n  <interface name=\"{}\"/>\n  <interface name=\"{}\"/>\n",
+            interface, PROPERTIES_INTERFACE, INTROSPECTABLE_INTERFACE
+        );
+        for child in children {
+            xml.push_str(&format!("  <node name=\"{}\"/>\n", child));
+        }
+        xml.push_str("</node>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        user_input::{CHAR_SET_NAMES, MasterPasswordPlain, UserID},
+    };
+
+    fn call(path: &str, interface: &str, member: &str, body: Vec<Value>) -> Message {
+        let mut call = Message::method_call(SERVICE_NAME, path, interface, member, body);
+        call.serial = 1;
+        call.sender = Some(":1.42".to_owned());
+        call
+    }
+
+    #[test]
+    fn can_serve_derived_secrets() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let char_sets: Vec<String> = CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect();
+        let entries = [
+            CatalogEntry {
+                username: Some("eleonora".to_owned()),
+                ..CatalogEntry::new("Example Service Name")
+            },
+            CatalogEntry::new("github.com"),
+        ];
+        let mut service =
+            SecretService::new(Arc::new(Mutex::new(session)), &entries, &char_sets, 20);
+
+        let reply = service.handle(&call(
+            SERVICE_PATH,
+            SERVICE_INTERFACE,
+            "OpenSession",
+            vec![
+                Value::str("dh-ietf1024-sha256-aes128-cbc-pkcs7"),
+                Value::variant(Value::bytes(&[1])),
+            ],
+        ));
+        assert_eq!(
+            reply.error_name.as_deref(),
+            Some("org.freedesktop.DBus.Error.NotSupported")
+        );
+        let reply = service.handle(&call(
+            SERVICE_PATH,
+            SERVICE_INTERFACE,
+            "OpenSession",
+            vec![Value::str("plain"), Value::variant(Value::str(""))],
+        ));
+        assert_eq!(reply.message_type, MessageType::MethodReturn);
+        let session_path = reply.body[1].clone();
+
+        let search = |service: &mut SecretService, attributes: &[(&str, &str)]| {
+            service.handle(&call(
+                SERVICE_PATH,
+                SERVICE_INTERFACE,
+                "SearchItems",
+                vec![Value::dict(
+                    "s",
+                    "s",
+                    attributes
+                        .iter()
+                        .map(|(k, v)| (Value::str(*k), Value::str(*v))),
+                )],
+            ))
+        };
+        let reply = search(
+            &mut service,
+            &[
+                ("xdg:schema", "org.example.Password"),
+                ("service", "Example Service Name"),
+                ("user", "eleonora"),
+            ],
+        );
+        assert_eq!(reply.body[0], Value::paths([SecretService::item_path(0)]));
+        let reply = search(&mut service, &[("url", "https://www.github.com/login")]);
+        assert_eq!(reply.body[0], Value::paths([SecretService::item_path(1)]));
+        let reply = search(&mut service, &[("protocol", "imap")]);
+        assert_eq!(reply.body[0], Value::paths(Vec::<String>::new()));
+        let reply = search(&mut service, &[]);
+        assert_eq!(reply.body[0].as_array().unwrap().len(), 2);
+
+        let reply = service.handle(&call(
+            &SecretService::item_path(0),
+            ITEM_INTERFACE,
+            "GetSecret",
+            vec![session_path.clone()],
+        ));
+        assert_eq!(
+            reply.body[0],
+            Value::Struct(vec![
+                session_path.clone(),
+                Value::bytes(&[]),
+                Value::bytes(b"1@MWtAAqZ0p>;;y@zZ6d"),
+                Value::str(CONTENT_TYPE),
+            ])
+        );
+        let reply = service.handle(&call(
+            &SecretService::item_path(0),
+            PROPERTIES_INTERFACE,
+            "Get",
+            vec![Value::str(ITEM_INTERFACE), Value::str("Label")],
+        ));
+        assert_eq!(
+            reply.body,
+            vec![Value::variant(Value::str("Example Service Name"))]
+        );
+
+        let reply = service.handle(&call(
+            COLLECTION_PATH,
+            COLLECTION_INTERFACE,
+            "CreateItem",
+            vec![],
+        ));
+        assert_eq!(
+            reply.error_name.as_deref(),
+            Some("org.freedesktop.DBus.Error.NotSupported")
+        );
+        service.handle(&call(
+            session_path.as_str().unwrap(),
+            SESSION_INTERFACE,
+            "Close",
+            vec![],
+        ));
+        let reply = service.handle(&call(
+            &SecretService::item_path(0),
+            ITEM_INTERFACE,
+            "GetSecret",
+            vec![session_path],
+        ));
+        assert_eq!(
+            reply.error_name.as_deref(),
+            Some("org.freedesktop.Secret.Error.NoSession")
+        );
+    }
+}
@@ -0,0 +1,112 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Service identifiers are derivation inputs, "github" and "GitHub" derive
+//! different passwords. The matcher resolves a sloppy query to the exact
+//! identifier used before, from the catalog and the recipe vault.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use crate::{catalog::Catalog, recipe_vault::RecipeVault};
+
+#[derive(Debug, Clone, Default)]
+pub struct ServiceMatcher {
+    known: BTreeSet<String>,
+}
+
+impl ServiceMatcher {
+    pub fn new(known: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            known: known.into_iter().collect(),
+        }
+    }
+    /// The service identifiers of the catalog, readable without unlocking.
+    pub fn from_catalog() -> Result<Self> {
+        let catalog = Catalog::load(&Catalog::default_path()?)?;
+        Ok(Self::new(
+            catalog.entries().iter().map(|e| e.service_id.clone()),
+        ))
+    }
+    pub fn add_vault(&mut self, vault: &RecipeVault) {
+        self.known
+            .extend(vault.entries().iter().map(|e| e.service_id.clone()));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+    pub fn is_known(&self, service_id: &str) -> bool {
+        self.known.contains(service_id)
+    }
+    /// The known identifiers resembling `query`, best first: equal but for
+    /// case, then containing the characters of `query` in order, then
+    /// within a few typos of it.
+    pub fn find(&self, query: &str) -> Vec<&str> {
+        let mut matches: Vec<((u8, usize), &str)> = self
+            .known
+            .iter()
+            .filter_map(|known| Some((Self::score(query, known)?, known.as_str())))
+            .collect();
+        matches.sort();
+        matches.into_iter().map(|(_, known)| known).collect()
+    }
+    fn score(query: &str, known: &str) -> Option<(u8, usize)> {
+        let query = query.trim().to_lowercase();
+        let known = known.to_lowercase();
+        if query.is_empty() {
+            return None;
+        }
+        if query == known {
+            return Some((0, 0));
+        }
+        let mut rest = known.chars();
+        if query.chars().all(|c| rest.any(|k| k == c)) {
+            return Some((1, known.chars().count() - query.chars().count()));
+        }
+        let distance = strsim::damerau_levenshtein(&query, &known);
+        (distance <= (query.chars().count() / 3).max(1)).then_some((2, distance))
+    }
+}
+
+/// Tab completes to the best match.
+#[cfg(feature = "cli")]
+impl dialoguer::Completion for ServiceMatcher {
+    fn get(&self, input: &str) -> Option<String> {
+        self.find(input).first().map(|known| known.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_fuzzy_match_service_ids() {
+        let matcher = ServiceMatcher::new(
+            ["GitHub", "github.com", "gitlab.com", "example.com"].map(String::from),
+        );
+        assert_eq!(matcher.find("githb"), vec!["GitHub", "github.com"]);
+        assert_eq!(matcher.find("GITHUB"), vec!["GitHub", "github.com"]);
+        assert_eq!(matcher.find("exmaple.com"), vec!["example.com"]);
+        assert!(matcher.find("bank").is_empty());
+        assert!(matcher.is_known("GitHub"));
+        assert!(!matcher.is_known("github"));
+    }
+}
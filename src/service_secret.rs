@@ -20,21 +20,19 @@
 use std::str::FromStr;
 
 use anyhow::Result;
-use base64::prelude::*;
-use hmac::{Hmac, Mac};
-use sha2::Sha512;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{
     DerivePassError,
     master_secret::MasterSecret,
+    scheme::SchemeVersion,
     user_input::{Generation, PasswordLength, ServiceID},
     utils::Utils,
 };
 
-type HmacSha512 = Hmac<Sha512>;
-
 pub struct ServiceSecret {
     service_secret: Vec<u8>,
+    scheme: SchemeVersion,
 }
 
 impl ServiceSecret {
@@ -43,35 +41,89 @@ impl ServiceSecret {
         service_id: &ServiceID,
         generation: &Generation,
         password_length: &PasswordLength,
+        scheme: SchemeVersion,
     ) -> Result<ServiceSecret> {
-        let salt = BASE64_STANDARD_NO_PAD.encode(
-            service_id.len().to_string()
-                + &service_id.to_string()
-                + &password_length.to_string()
-                + &generation.to_string(),
-        );
-
-        let mut hmac_sha512 = HmacSha512::new_from_slice(master_secret.as_hex().as_bytes())?;
-        hmac_sha512.update(&salt.as_bytes());
+        let service_secret = crate::core::service_secret(
+            master_secret.as_bytes(),
+            &service_id.to_string(),
+            generation.as_usize() as u64,
+            password_length.as_u16(),
+            scheme,
+        )
+        .map_err(DerivePassError::from)?;
 
         Ok(ServiceSecret {
-            service_secret: hmac_sha512.finalize().into_bytes().to_vec(),
+            service_secret,
+            scheme,
         })
     }
+    pub fn salt(
+        service_id: &ServiceID,
+        generation: &Generation,
+        password_length: &PasswordLength,
+    ) -> String {
+        crate::core::service_salt_v1(
+            &service_id.to_string(),
+            generation.as_usize() as u64,
+            password_length.as_u16(),
+        )
+    }
+    /// See `core::canonical_service_salt`.
+    pub fn canonical_salt(
+        service_id: &ServiceID,
+        generation: &Generation,
+        password_length: &PasswordLength,
+    ) -> Vec<u8> {
+        crate::core::canonical_service_salt(
+            &service_id.to_string(),
+            generation.as_usize() as u64,
+            password_length.as_u16(),
+        )
+    }
+    /// Expansion keyed by the service secret, for output longer than the 64
+    /// bytes of the secret itself, see `core::expand`.
+    pub fn expand(&self, info: &[u8], length: usize) -> Result<Vec<u8>> {
+        Ok(
+            crate::core::expand(&self.service_secret, self.scheme, info, length)
+                .map_err(DerivePassError::from)?,
+        )
+    }
     pub fn len(&self) -> usize {
         self.service_secret.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.service_secret.is_empty()
+    }
+    pub fn scheme(&self) -> SchemeVersion {
+        self.scheme
+    }
     pub fn as_bytes(&self) -> &[u8] {
         &self.service_secret
     }
 }
 
+impl Zeroize for ServiceSecret {
+    fn zeroize(&mut self) {
+        self.service_secret.zeroize();
+    }
+}
+
+impl Drop for ServiceSecret {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for ServiceSecret {}
+
+/// Parsed secrets expand like scheme v1 and v2 ones.
 impl FromStr for ServiceSecret {
     type Err = DerivePassError;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         if s.len() == 128 {
             Ok(Self {
                 service_secret: Utils::hex_to_bytes(s).ok_or(DerivePassError::Secret)?,
+                scheme: SchemeVersion::V1,
             })
         } else {
             Err(DerivePassError::Secret)
@@ -83,7 +135,7 @@ impl FromStr for ServiceSecret {
 mod tests {
     use std::str::FromStr;
 
-    use crate::utils::Utils;
+    use crate::{scheme::SERVICE_LABEL, utils::Utils};
 
     use super::*;
 
@@ -126,7 +178,8 @@ mod tests {
                     &MasterSecret::from_str(test_case.get(1).unwrap()).unwrap(),
                     &ServiceID::from_str(test_case.get(0).unwrap()).unwrap(),
                     &Generation::from_str(test_case.get(2).unwrap()).unwrap(),
-                    &PasswordLength::from_str(test_case.get(3).unwrap()).unwrap()
+                    &PasswordLength::from_str(test_case.get(3).unwrap()).unwrap(),
+                    SchemeVersion::V1,
                 )
                 .unwrap()
                 .as_bytes(),
@@ -134,4 +187,102 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn can_generate_v2_service_secret() {
+        let service_secret = ServiceSecret::new(
+            &MasterSecret::from_str(
+                "7ad5d8df9f80f749fd4316c9681719eb7ba29c24c38311d0e9bb56047024ab91",
+            )
+            .unwrap(),
+            &ServiceID::from_str("Example Service Name").unwrap(),
+            &Generation::from_str("1").unwrap(),
+            &PasswordLength::from_str("20").unwrap(),
+            SchemeVersion::V2,
+        )
+        .unwrap();
+        assert_eq!(
+            Utils::bytes_to_hex(service_secret.as_bytes()),
+            "4b5a8e6ee58d714d5fb5b4876fcecaaed4f0b0db19cb92526f11bd07409d3f2a7cae3a22c06f6519fcf9bd7d3c29e46845a3220b80c5d0f3d29ceac2c8f36142"
+        );
+        assert_eq!(
+            Utils::bytes_to_hex(&service_secret.expand(b"test", 32).unwrap()),
+            "4bbb641f65b59bc9368e743e2d197e7ff2ff0fd04ce012e3ac3d97c47f33b2c5"
+        );
+    }
+
+    #[test]
+    fn can_expand_service_secret() {
+        let service_secret = ServiceSecret::from_str(&"ab".repeat(64)).unwrap();
+        let long = service_secret.expand(b"test", 300).unwrap();
+        assert_eq!(long.len(), 300);
+        assert_eq!(service_secret.expand(b"test", 70).unwrap(), long[..70]);
+        assert_ne!(service_secret.expand(b"other", 70).unwrap(), long[..70]);
+        assert_ne!(long[..64], long[64..128]);
+    }
+
+    #[test]
+    fn can_generate_blake3_service_secret() {
+        let master_secret = MasterSecret::from_str(
+            "7ad5d8df9f80f749fd4316c9681719eb7ba29c24c38311d0e9bb56047024ab91",
+        )
+        .unwrap();
+        let service_secret = |scheme| {
+            ServiceSecret::new(
+                &master_secret,
+                &ServiceID::from_str("Example Service Name").unwrap(),
+                &Generation::from_str("1").unwrap(),
+                &PasswordLength::from_str("20").unwrap(),
+                scheme,
+            )
+            .unwrap()
+        };
+        let v3 = service_secret(SchemeVersion::V3);
+        assert_eq!(
+            Utils::bytes_to_hex(v3.as_bytes()),
+            "8baf768e8f96510dbf5c89e476f137659d5dd3d78e5ae233b3e45421c8b833c1b3187d4c30969c561984c03a9c3ef95b8049d3402dbbc2b76891d8b77f38ae53"
+        );
+        assert_ne!(v3.as_bytes(), service_secret(SchemeVersion::V2).as_bytes());
+        assert_eq!(v3.as_bytes(), service_secret(SchemeVersion::V3).as_bytes());
+
+        assert_eq!(
+            Utils::bytes_to_hex(&v3.expand(b"test", 32).unwrap()),
+            "a82843d879a4c5313ebc87f4f95e519f6231dbf94cb539df261ebe7852bf298e"
+        );
+        let long = v3.expand(b"test", 300).unwrap();
+        assert_eq!(v3.expand(b"test", 70).unwrap(), long[..70]);
+        assert_ne!(v3.expand(b"other", 70).unwrap(), long[..70]);
+    }
+
+    #[test]
+    fn can_tell_apart_ambiguous_salts() {
+        let service_id = ServiceID::from_str("Example Service Name").unwrap();
+        let salts = |salt: fn(&ServiceID, &Generation, &PasswordLength) -> Vec<u8>| {
+            (
+                salt(
+                    &service_id,
+                    &Generation::from_str("11").unwrap(),
+                    &PasswordLength::from_str("1").unwrap(),
+                ),
+                salt(
+                    &service_id,
+                    &Generation::from_str("1").unwrap(),
+                    &PasswordLength::from_str("11").unwrap(),
+                ),
+            )
+        };
+        let (a, b) = salts(|s, g, l| ServiceSecret::salt(s, g, l).into_bytes());
+        assert_eq!(a, b);
+        let (a, b) = salts(ServiceSecret::canonical_salt);
+        assert_ne!(a, b);
+        assert!(a.starts_with(&Utils::length_prefixed(&[SERVICE_LABEL])));
+        assert_eq!(
+            Utils::bytes_to_hex(&ServiceSecret::canonical_salt(
+                &service_id,
+                &Generation::from_str("1").unwrap(),
+                &PasswordLength::from_str("20").unwrap(),
+            )),
+            "0000001364657061737377642f76322f73657276696365000000144578616d706c652053657276696365204e616d65000000020014000000080000000000000001"
+        );
+    }
 }
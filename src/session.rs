@@ -0,0 +1,799 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{ops::RangeInclusive, str::FromStr, sync::Arc};
+
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    DerivePassError,
+    age::AgeIdentity,
+    audit::{AuditEvent, AuditSink},
+    bip39::Mnemonic,
+    derivation_path::{DerivationPath, Purpose},
+    derived_pass::{DerivedPass, UsernameStyle},
+    kdf::KdfParams,
+    key_material::KeyMaterial,
+    keyfile::Keyfile,
+    master_secret::MasterSecret,
+    pepper::Pepper,
+    policy::PasswordPolicy,
+    scheme::SchemeVersion,
+    service_secret::ServiceSecret,
+    template::Template,
+    totp::TotpSeed,
+    user_input::{
+        CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID, UserInputError,
+    },
+    utils::Utils,
+    wifi::{WIFI_PSK_LENGTH, Wifi},
+    wireguard::WireguardKeys,
+    wordlist::Wordlist,
+};
+
+/// An unlocked master secret. Unlocking runs the expensive Argon2 step once,
+/// after which any number of service passwords can be derived cheaply.
+pub struct Session {
+    user_id: UserID,
+    master_secret: MasterSecret,
+    scheme: SchemeVersion,
+    kdf_params: KdfParams,
+    audit: Option<(Arc<dyn AuditSink>, String)>,
+}
+
+impl Session {
+    /// Every derivation of the session uses `scheme`.
+    pub fn unlock(
+        user_id: &UserID,
+        master_password_plain: &MasterPasswordPlain,
+        scheme: SchemeVersion,
+    ) -> Result<Self, DerivePassError> {
+        Self::unlock_with_kdf_params(user_id, master_password_plain, scheme, &KdfParams::DEFAULT)
+    }
+    pub fn unlock_with_kdf_params(
+        user_id: &UserID,
+        master_password_plain: &MasterPasswordPlain,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+    ) -> Result<Self, DerivePassError> {
+        Ok(Self {
+            user_id: user_id.clone(),
+            master_secret: MasterSecret::new(user_id, master_password_plain, scheme, kdf_params)?,
+            scheme,
+            kdf_params: *kdf_params,
+            audit: None,
+        })
+    }
+    /// Like `unlock_with_kdf_params`, stretching the master password
+    /// together with `keyfile` when there is one.
+    pub fn unlock_with_keyfile(
+        user_id: &UserID,
+        master_password_plain: &MasterPasswordPlain,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+        keyfile: Option<&Keyfile>,
+    ) -> Result<Self, DerivePassError> {
+        let Some(keyfile) = keyfile else {
+            return Self::unlock_with_kdf_params(
+                user_id,
+                master_password_plain,
+                scheme,
+                kdf_params,
+            );
+        };
+        Ok(Self {
+            user_id: user_id.clone(),
+            master_secret: MasterSecret::with_keyfile(
+                user_id,
+                master_password_plain,
+                scheme,
+                kdf_params,
+                keyfile,
+            )?,
+            scheme,
+            kdf_params: *kdf_params,
+            audit: None,
+        })
+    }
+    /// Mixes `pepper` into the master secret, every derivation depends on it
+    /// from then on.
+    pub fn with_pepper(mut self, pepper: &Pepper) -> Result<Self, DerivePassError> {
+        self.master_secret.add_pepper(pepper.as_bytes())?;
+        Ok(self)
+    }
+    /// A session of a master secret unlocked earlier, see `Keychain`.
+    pub(crate) fn from_master_secret(
+        user_id: &UserID,
+        master_secret: MasterSecret,
+        scheme: SchemeVersion,
+        kdf_params: &KdfParams,
+    ) -> Self {
+        Self {
+            user_id: user_id.clone(),
+            master_secret,
+            scheme,
+            kdf_params: *kdf_params,
+            audit: None,
+        }
+    }
+    pub(crate) fn master_secret(&self) -> &MasterSecret {
+        &self.master_secret
+    }
+    /// Reports every derivation of this session to `sink`, attributed to `client`.
+    pub fn with_audit(mut self, sink: Arc<dyn AuditSink>, client: &str) -> Self {
+        self.audit = Some((sink, client.to_owned()));
+        self
+    }
+    pub fn user_id(&self) -> &UserID {
+        &self.user_id
+    }
+    pub fn scheme(&self) -> SchemeVersion {
+        self.scheme
+    }
+    pub fn kdf_params(&self) -> &KdfParams {
+        &self.kdf_params
+    }
+    /// Keeps the master secret out of swap, see `Utils::lock_memory`.
+    pub fn lock_in_memory(&self) -> bool {
+        self.master_secret.lock_in_memory()
+    }
+    pub fn derive(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+    ) -> Result<DerivedPass, DerivePassError> {
+        let derived_pass = ServiceSecret::new(
+            &self.master_secret,
+            service_id,
+            generation,
+            password_length,
+            self.scheme,
+        )
+        .and_then(|service_secret| {
+            DerivedPass::new(&service_secret, char_set, password_length, self.scheme)
+        })
+        .map_err(DerivePassError::from_anyhow)?;
+        self.audit("derive", &service_id.to_string())
+            .map_err(|e| DerivePassError::Audit(e.to_string()))?;
+        Ok(derived_pass)
+    }
+    /// Like `derive`, but fails unless the password satisfies `policy`.
+    pub fn derive_with_policy(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        policy: &PasswordPolicy,
+    ) -> Result<DerivedPass, DerivePassError> {
+        let derived_pass = ServiceSecret::new(
+            &self.master_secret,
+            service_id,
+            generation,
+            password_length,
+            self.scheme,
+        )
+        .and_then(|service_secret| {
+            DerivedPass::with_policy(
+                &service_secret,
+                char_set,
+                password_length,
+                self.scheme,
+                &Purpose::password(),
+                Some(policy),
+            )
+        })
+        .map_err(DerivePassError::from_anyhow)?;
+        self.audit("derive", &service_id.to_string())
+            .map_err(|e| DerivePassError::Audit(e.to_string()))?;
+        Ok(derived_pass)
+    }
+    /// Derives a pronounceable password, see `DerivedPass::pronounceable`.
+    pub fn derive_pronounceable(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        password_length: &PasswordLength,
+    ) -> Result<DerivedPass> {
+        let derived_pass = DerivedPass::pronounceable(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                password_length,
+                self.scheme,
+            )?,
+            password_length,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(derived_pass)
+    }
+    /// Derives the age identity of a service, see `AgeIdentity::new`.
+    pub fn derive_age_identity(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+    ) -> Result<AgeIdentity> {
+        let identity = AgeIdentity::new(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                &PasswordLength::from_str("32")?,
+                self.scheme,
+            )?,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(identity)
+    }
+    /// Derives a BIP39 mnemonic of `words` words, see `Mnemonic::new`.
+    pub fn derive_mnemonic(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        words: usize,
+    ) -> Result<Mnemonic> {
+        let mnemonic = Mnemonic::new(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                &PasswordLength::from_str(&words.to_string())?,
+                self.scheme,
+            )?,
+            words,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(mnemonic)
+    }
+    /// Derives the WireGuard keys of a service, see `WireguardKeys::new`.
+    pub fn derive_wireguard_keys(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+    ) -> Result<WireguardKeys> {
+        let keys = WireguardKeys::new(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                &PasswordLength::from_str("32")?,
+                self.scheme,
+            )?,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(keys)
+    }
+    /// Derives the Wi-Fi PSK of a network, see `Wifi::psk`.
+    pub fn derive_wifi_psk(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+    ) -> Result<DerivedPass> {
+        let psk = Wifi::psk(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                &PasswordLength::from_str(&WIFI_PSK_LENGTH.to_string())?,
+                self.scheme,
+            )?,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(psk)
+    }
+    /// Derives `length` bytes of key material for `purpose`, see
+    /// `KeyMaterial::with_purpose`.
+    pub fn derive_key_material(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        length: &PasswordLength,
+        purpose: &Purpose,
+    ) -> Result<KeyMaterial> {
+        let key = KeyMaterial::with_purpose(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                length,
+                self.scheme,
+            )?,
+            length,
+            purpose,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(key)
+    }
+    /// Derives `count` recovery codes, see `DerivedPass::recovery_codes`.
+    pub fn derive_recovery_codes(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        count: u32,
+        length: &PasswordLength,
+    ) -> Result<Vec<DerivedPass>> {
+        let codes = DerivedPass::recovery_codes(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                length,
+                self.scheme,
+            )?,
+            count,
+            length,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(codes)
+    }
+    /// Derives the answer to a security question, see `DerivedPass::answer`.
+    pub fn derive_answer(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        wordlist: &Wordlist,
+        question: &str,
+        words: &PasswordLength,
+    ) -> Result<DerivedPass> {
+        let derived_pass = DerivedPass::answer(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                words,
+                self.scheme,
+            )?,
+            wordlist,
+            question,
+            words,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(derived_pass)
+    }
+    /// Derives a password shaped by `template`, see `DerivedPass::from_template`.
+    pub fn derive_template(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        template: &Template,
+    ) -> Result<DerivedPass> {
+        let derived_pass = DerivedPass::from_template(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                &PasswordLength::from_str(&template.max_length().to_string())?,
+                self.scheme,
+            )?,
+            template,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(derived_pass)
+    }
+    /// Derives a username of `length` characters, see `DerivedPass::username`.
+    pub fn derive_username(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        length: &PasswordLength,
+        style: UsernameStyle,
+    ) -> Result<DerivedPass> {
+        let derived_pass = DerivedPass::username(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                length,
+                self.scheme,
+            )?,
+            length,
+            style,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(derived_pass)
+    }
+    /// Derives the TOTP seed of a service, see `TotpSeed::new`.
+    pub fn derive_totp_seed(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+    ) -> Result<TotpSeed> {
+        let seed = TotpSeed::new(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                &PasswordLength::from_str(&TotpSeed::LENGTH.to_string())?,
+                self.scheme,
+            )?,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(seed)
+    }
+    /// Derives a passphrase of `words` words from `wordlist`.
+    pub fn derive_passphrase(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        wordlist: &Wordlist,
+        words: &PasswordLength,
+        separator: &str,
+    ) -> Result<DerivedPass> {
+        let derived_pass = DerivedPass::passphrase(
+            &ServiceSecret::new(
+                &self.master_secret,
+                service_id,
+                generation,
+                words,
+                self.scheme,
+            )?,
+            wordlist,
+            words,
+            separator,
+            self.scheme,
+        )?;
+        self.audit("derive", &service_id.to_string())?;
+        Ok(derived_pass)
+    }
+    /// Derives the secret at `path`, which must belong to the unlocked user,
+    /// following `policy` if given.
+    pub fn derive_path(
+        &self,
+        path: &DerivationPath,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        policy: Option<&PasswordPolicy>,
+    ) -> Result<DerivedPass> {
+        if path.user_id.to_string() != self.user_id.to_string() {
+            return Err(UserInputError::new(format!(
+                "The derivation path {} belongs to another user",
+                path
+            ))
+            .into());
+        }
+        let derived_pass = DerivedPass::with_policy(
+            &ServiceSecret::new(
+                &self.master_secret,
+                &path.service_id,
+                &path.generation,
+                password_length,
+                self.scheme,
+            )?,
+            char_set,
+            password_length,
+            self.scheme,
+            &path.purpose,
+            policy,
+        )?;
+        self.audit("derive", &path.service_id.to_string())?;
+        Ok(derived_pass)
+    }
+    /// Identifies a service in audit events without revealing it.
+    pub fn blind(&self, service_id: &str) -> Result<String> {
+        let tag = self.authenticate(format!("depasswd audit\0{}", service_id).as_bytes())?;
+        Ok(Utils::bytes_to_hex(&tag[..16]))
+    }
+    fn audit(&self, action: &str, service_id: &str) -> Result<()> {
+        match &self.audit {
+            Some((sink, client)) => sink.record(&AuditEvent::new(
+                client,
+                action,
+                self.blind(service_id)?,
+                self.scheme.as_u32(),
+            )),
+            None => Ok(()),
+        }
+    }
+    /// HMAC-SHA256 of `message` keyed by the master secret, for data that
+    /// must not be modified without knowing the master password.
+    pub fn authenticate(&self, message: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.mac(message)?.finalize().into_bytes().to_vec())
+    }
+    /// Checks a tag made by `authenticate` in constant time.
+    pub fn verify(&self, message: &[u8], tag: &[u8]) -> Result<bool> {
+        Ok(self.mac(message)?.verify_slice(tag).is_ok())
+    }
+    /// 32 bytes of key material for `context`, e.g. the seed of a derived
+    /// signing key. Contexts must be unique per kind of key.
+    pub fn derive_seed(&self, context: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+        let mut mac = self.mac(b"depasswd seed\0")?;
+        mac.update(context);
+        let seed = Zeroizing::new(mac.finalize().into_bytes().into());
+        self.audit("derive-seed", &String::from_utf8_lossy(context))?;
+        Ok(seed)
+    }
+    fn mac(&self, message: &[u8]) -> Result<Hmac<Sha256>> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.master_secret.as_bytes())?;
+        mac.update(message);
+        Ok(mac)
+    }
+    /// Derives the password of every generation in `generations`, for when
+    /// it is not known which one a service is on.
+    pub fn derive_generations(
+        &self,
+        service_id: &ServiceID,
+        generations: RangeInclusive<usize>,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        policy: Option<&PasswordPolicy>,
+    ) -> Result<Vec<(Generation, DerivedPass)>> {
+        generations
+            .map(|g| {
+                let generation = Generation::from_str(&g.to_string())?;
+                let derived_pass = self.derive_password(
+                    service_id,
+                    &generation,
+                    char_set,
+                    password_length,
+                    policy,
+                )?;
+                Ok((generation, derived_pass))
+            })
+            .collect()
+    }
+    /// Derives one password per `(char_set, password_length)` variant of the
+    /// same service and generation, as fallbacks for sites with undocumented
+    /// password rules.
+    pub fn derive_variants(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        variants: &[(CharSet, PasswordLength)],
+        policy: Option<&PasswordPolicy>,
+    ) -> Result<Vec<DerivedPass>> {
+        variants
+            .iter()
+            .map(|(char_set, password_length)| {
+                self.derive_password(service_id, generation, char_set, password_length, policy)
+            })
+            .collect()
+    }
+    /// The password purpose of `derive_path`, for the unlocked user.
+    fn derive_password(
+        &self,
+        service_id: &ServiceID,
+        generation: &Generation,
+        char_set: &CharSet,
+        password_length: &PasswordLength,
+        policy: Option<&PasswordPolicy>,
+    ) -> Result<DerivedPass> {
+        self.derive_path(
+            &DerivationPath::new(&self.user_id, service_id, generation, &Purpose::password()),
+            char_set,
+            password_length,
+            policy,
+        )
+    }
+}
+
+impl Zeroize for Session {
+    fn zeroize(&mut self) {
+        self.master_secret.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_derive_path() {
+        let user_id = UserID::from_str("Example Eleonora").unwrap();
+        let service_id = ServiceID::from_str("Example Service Name").unwrap();
+        let generation = Generation::from_str("1").unwrap();
+        let char_set = CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap();
+        let password_length = PasswordLength::from_str("20").unwrap();
+        let path = |purpose: &str| {
+            DerivationPath::new(
+                &user_id,
+                &service_id,
+                &generation,
+                &Purpose::from_str(purpose).unwrap(),
+            )
+        };
+
+        let session = Session::unlock(
+            &user_id,
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V2,
+        )
+        .unwrap();
+        let password = session
+            .derive_path(&path("password"), &char_set, &password_length, None)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_eq!(
+            password,
+            session
+                .derive(&service_id, &generation, &char_set, &password_length)
+                .unwrap()
+                .expose_secret()
+        );
+        let pin = session
+            .derive_path(&path("pin"), &char_set, &password_length, None)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_ne!(pin, password);
+
+        let other_user = DerivationPath::from_str("m/Example Bob/Example Service Name/1/pin");
+        assert!(
+            session
+                .derive_path(&other_user.unwrap(), &char_set, &password_length, None)
+                .is_err()
+        );
+
+        let session = Session::unlock(
+            &user_id,
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        assert!(
+            session
+                .derive_path(&path("pin"), &char_set, &password_length, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_derive_generations() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let service_id = ServiceID::from_str("Example Service Name").unwrap();
+        let char_set = CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap();
+        let password_length = PasswordLength::from_str("20").unwrap();
+
+        let derived = session
+            .derive_generations(&service_id, 1..=3, &char_set, &password_length, None)
+            .unwrap();
+        assert_eq!(derived.len(), 3);
+        assert_eq!(derived[0].0.as_usize(), 1);
+        assert_eq!(derived[0].1.expose_secret(), "1@MWtAAqZ0p>;;y@zZ6d");
+        assert_eq!(
+            derived[2].1.expose_secret(),
+            session
+                .derive(
+                    &service_id,
+                    &Generation::from_str("3").unwrap(),
+                    &char_set,
+                    &password_length
+                )
+                .unwrap()
+                .expose_secret()
+        );
+        assert_ne!(derived[1].1.expose_secret(), derived[0].1.expose_secret());
+        assert!(
+            session
+                .derive_generations(&service_id, 0..=1, &char_set, &password_length, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_derive_variants() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let service_id = ServiceID::from_str("Example Service Name").unwrap();
+        let char_set = CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap();
+        let without_special = char_set.without(crate::SPECIAL_CHARS).unwrap();
+
+        let derived = session
+            .derive_variants(
+                &service_id,
+                &Generation::from_str("1").unwrap(),
+                &[
+                    (char_set, PasswordLength::from_str("20").unwrap()),
+                    (without_special, PasswordLength::from_str("32").unwrap()),
+                ],
+                None,
+            )
+            .unwrap();
+        assert_eq!(derived[0].expose_secret(), "1@MWtAAqZ0p>;;y@zZ6d");
+        assert_eq!(derived[1].expose_secret().len(), 32);
+        assert!(
+            derived[1]
+                .expose_secret()
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric())
+        );
+    }
+
+    #[test]
+    fn can_derive_with_site_policy() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V2,
+        )
+        .unwrap();
+        let service_id = ServiceID::from_str("bankofamerica.com").unwrap();
+        let generation = Generation::from_str("1").unwrap();
+        let char_set = CharSet::try_from([0usize, 1, 2, 3].as_slice()).unwrap();
+        let password_length = PasswordLength::from_str("20").unwrap();
+        let policies = crate::site_policy::SitePolicies::builtin();
+        let policy = &policies.find("bankofamerica.com").unwrap().policy;
+
+        // What `derive` prints is what `verify`, `tui`, `--all-generations`
+        // and `--variants` derive.
+        let derived = session
+            .derive_path(
+                &DerivationPath::new(
+                    session.user_id(),
+                    &service_id,
+                    &generation,
+                    &Purpose::password(),
+                ),
+                &char_set,
+                &password_length,
+                Some(policy),
+            )
+            .unwrap();
+        assert!(policy.violations(derived.expose_secret()).is_empty());
+        let generations = session
+            .derive_generations(
+                &service_id,
+                1..=1,
+                &char_set,
+                &password_length,
+                Some(policy),
+            )
+            .unwrap();
+        assert!(derived.matches(generations[0].1.expose_secret()));
+        let variants = session
+            .derive_variants(
+                &service_id,
+                &generation,
+                &[(char_set.clone(), password_length.clone())],
+                Some(policy),
+            )
+            .unwrap();
+        assert!(derived.matches(variants[0].expose_secret()));
+        let unshaped = session
+            .derive(&service_id, &generation, &char_set, &password_length)
+            .unwrap();
+        assert!(!derived.matches(unshaped.expose_secret()));
+    }
+}
@@ -0,0 +1,148 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The password policies of known sites: a database compiled into the
+//! binary, extended by an optional `policies.toml` in the config directory.
+
+use std::{fs, io::ErrorKind, path::Path, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{catalog::Catalog, policy::PasswordPolicy, utils::Utils};
+
+const BUILTIN: &str = include_str!("site_policies.toml");
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SitePolicy {
+    /// The domain, which also covers its subdomains.
+    pub domain: String,
+    #[serde(flatten)]
+    pub policy: PasswordPolicy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SitePolicies {
+    #[serde(default, rename = "site")]
+    sites: Vec<SitePolicy>,
+}
+
+impl SitePolicies {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Utils::config_dir()?.join("policies.toml"))
+    }
+    /// The compiled-in database.
+    pub fn builtin() -> Self {
+        toml::from_str(BUILTIN).expect("the bundled site policies are valid")
+    }
+    /// The user file on top of the compiled-in database, whose entries for
+    /// the same domain it replaces.
+    pub fn load(path: &Path) -> Result<Self> {
+        let user: Self = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid site policy file: {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Can not read site policy file: {}", path.display()));
+            }
+        };
+        let mut policies = Self::builtin();
+        policies
+            .sites
+            .retain(|site| !user.sites.iter().any(|u| u.domain == site.domain));
+        policies.sites.splice(0..0, user.sites);
+        Ok(policies)
+    }
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Can not write site policy file: {}", path.display()))
+    }
+    pub fn sites(&self) -> &[SitePolicy] {
+        &self.sites
+    }
+    /// The policy of the most specific domain covering `service_id`, which
+    /// may be a domain or a URL.
+    pub fn find(&self, service_id: &str) -> Option<&SitePolicy> {
+        let host = Catalog::canonical_service_id(service_id)
+            .unwrap_or_else(|| service_id.trim().trim_end_matches('.').to_lowercase());
+        self.sites
+            .iter()
+            .filter(|site| {
+                host == site.domain
+                    || host
+                        .strip_suffix(&site.domain)
+                        .is_some_and(|sub| sub.ends_with('.'))
+            })
+            .max_by_key(|site| site.domain.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_find_site_policy() {
+        let policies = SitePolicies::builtin();
+        let paypal = policies.find("paypal.com").unwrap();
+        assert_eq!(paypal.policy.max_length, Some(20));
+        assert_eq!(
+            policies.find("https://www.paypal.com/signin").unwrap(),
+            paypal
+        );
+        assert_eq!(policies.find("Business.PayPal.com").unwrap(), paypal);
+        assert!(policies.find("notpaypal.com").is_none());
+        assert!(policies.find("Example Service Name").is_none());
+    }
+
+    #[test]
+    fn can_override_builtin_policies() {
+        let path = std::env::temp_dir().join("depasswd-site-policy-test.toml");
+        let mut user = SitePolicies::default();
+        user.sites.push(SitePolicy {
+            domain: "paypal.com".to_owned(),
+            policy: PasswordPolicy {
+                max_length: Some(16),
+                ..PasswordPolicy::default()
+            },
+        });
+        user.sites.push(SitePolicy {
+            domain: "example.com".to_owned(),
+            policy: PasswordPolicy {
+                forbidden_classes: vec!["special".to_owned()],
+                ..PasswordPolicy::default()
+            },
+        });
+        user.save(&path).unwrap();
+
+        let policies = SitePolicies::load(&path).unwrap();
+        assert_eq!(
+            policies.find("paypal.com").unwrap().policy.max_length,
+            Some(16)
+        );
+        assert!(policies.find("login.example.com").is_some());
+        assert!(policies.find("chase.com").is_some());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(SitePolicies::load(&path).unwrap(), SitePolicies::builtin());
+    }
+}
@@ -0,0 +1,97 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Socket activation, so `depasswd agent` can run as a per-user service
+//! whose sockets systemd creates:
+//!
+//! ```ini
+//! # ~/.config/systemd/user/depasswd-agent.socket
+//! [Socket]
+//! ListenStream=%t/depasswd-agent.sock
+//! SocketMode=0600
+//! FileDescriptorName=rpc
+//!
+//! # ~/.config/systemd/user/depasswd-agent.service
+//! [Service]
+//! ExecStart=depasswd --pinentry agent
+//! LoadCredentialEncrypted=depasswd-pepper:%h/.config/depasswd/pepper.cred
+//! ```
+//!
+//! A second socket unit with `FileDescriptorName=ssh` and `Service=` set
+//! to the agent passes the ssh-agent socket.
+
+use std::{
+    env,
+    os::{
+        fd::{FromRawFd, RawFd},
+        unix::net::UnixListener,
+    },
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Result, anyhow, bail};
+
+/// The first file descriptor passed by systemd, `SD_LISTEN_FDS_START`.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The descriptors can only be owned once.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+pub struct Systemd;
+
+impl Systemd {
+    /// The listening sockets passed to this process in `LISTEN_FDS`, with
+    /// their `FileDescriptorName`s (the socket unit's name by default).
+    /// Empty when the process was not socket-activated, or on any call but
+    /// the first.
+    pub fn listeners() -> Result<Vec<(String, UnixListener)>> {
+        if env::var("LISTEN_PID").ok() != Some(std::process::id().to_string())
+            || TAKEN.swap(true, Ordering::SeqCst)
+        {
+            return Ok(vec![]);
+        }
+        let count: RawFd = env::var("LISTEN_FDS")
+            .map_err(|_| anyhow!("LISTEN_PID is set without LISTEN_FDS"))?
+            .parse()
+            .map_err(|_| anyhow!("LISTEN_FDS is not a number"))?;
+        let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+        let mut names = names.split(':');
+        (LISTEN_FDS_START..LISTEN_FDS_START + count)
+            .map(|fd| {
+                if !Self::is_socket(fd) {
+                    bail!("File descriptor {} from LISTEN_FDS is not a socket", fd);
+                }
+                // SAFETY: LISTEN_PID names this process, so systemd passed it
+                // these descriptors, and TAKEN makes sure nothing else owns them.
+                let listener = unsafe { UnixListener::from_raw_fd(fd) };
+                // SAFETY: the descriptor is open, FD_CLOEXEC keeps it from the
+                // child processes.
+                unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+                let name = names.next().filter(|n| !n.is_empty()).unwrap_or("unknown");
+                Ok((name.to_owned(), listener))
+            })
+            .collect()
+    }
+    fn is_socket(fd: RawFd) -> bool {
+        // SAFETY: fstat only writes the zeroed struct it is given.
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        let stated = unsafe { libc::fstat(fd, &mut stat) } == 0;
+        stated && stat.st_mode & libc::S_IFMT == libc::S_IFSOCK
+    }
+}
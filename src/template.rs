@@ -0,0 +1,197 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Templates in the style of Spectre (formerly Master Password): every
+//! character of a pattern names the class its slot is filled from, so the
+//! password has a predictable shape, e.g. `CvcvnoCvcvCvcv` gives something
+//! like `Jafu4*NuyiWexo`. A named template is a list of patterns of the
+//! same shape, one of which is picked per service.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::user_input::{PasswordLength, UserInputError};
+
+/// The slot classes of Spectre: `V`/`v` vowels, `C`/`c` consonants, `A`
+/// capital letters, `a` letters, `n` digits, `o` symbols, `x` letters, digits
+/// and symbols, and a literal space.
+pub const TEMPLATE_CLASSES: [(char, &str); 10] = [
+    ('V', "AEIOU"),
+    ('C', "BCDFGHJKLMNPQRSTVWXYZ"),
+    ('v', "aeiou"),
+    ('c', "bcdfghjklmnpqrstvwxyz"),
+    ('A', "AEIOUBCDFGHJKLMNPQRSTVWXYZ"),
+    ('a', "AEIOUaeiouBCDFGHJKLMNPQRSTVWXYZbcdfghjklmnpqrstvwxyz"),
+    ('n', "0123456789"),
+    ('o', "@&%?,=[]_:-+*$#!'^~;()/."),
+    (
+        'x',
+        "AEIOUaeiouBCDFGHJKLMNPQRSTVWXYZbcdfghjklmnpqrstvwxyz0123456789!@#$%^&*()",
+    ),
+    (' ', " "),
+];
+
+/// The named templates of Spectre, with the same patterns.
+pub const NAMED_TEMPLATES: [(&str, &[&str]); 8] = [
+    ("maximum", &["anoxxxxxxxxxxxxxxxxx", "axxxxxxxxxxxxxxxxxno"]),
+    (
+        "long",
+        &[
+            "CvcvnoCvcvCvcv",
+            "CvcvCvcvnoCvcv",
+            "CvcvCvcvCvcvno",
+            "CvccnoCvcvCvcv",
+            "CvccCvcvnoCvcv",
+            "CvccCvcvCvcvno",
+            "CvcvnoCvccCvcv",
+            "CvcvCvccnoCvcv",
+            "CvcvCvccCvcvno",
+            "CvcvnoCvcvCvcc",
+            "CvcvCvcvnoCvcc",
+            "CvcvCvcvCvccno",
+            "CvccnoCvccCvcv",
+            "CvccCvccnoCvcv",
+            "CvccCvccCvcvno",
+            "CvcvnoCvccCvcc",
+            "CvcvCvccnoCvcc",
+            "CvcvCvccCvccno",
+            "CvccnoCvcvCvcc",
+            "CvccCvcvnoCvcc",
+            "CvccCvcvCvccno",
+        ],
+    ),
+    ("medium", &["CvcnoCvc", "CvcCvcno"]),
+    ("short", &["Cvcn"]),
+    ("basic", &["aaanaaan", "aannaaan", "aaannaaa"]),
+    ("pin", &["nnnn"]),
+    ("name", &["cvccvcvcv"]),
+    (
+        "phrase",
+        &[
+            "cvcc cvc cvccvcv cvc",
+            "cvc cvccvcvcv cvcv",
+            "cv cvccv cvc cvcvccv",
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    name: Option<&'static str>,
+    patterns: Vec<String>,
+}
+
+impl Template {
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+    /// The characters a slot of `class` is filled from.
+    pub fn class(class: char) -> Option<&'static str> {
+        TEMPLATE_CLASSES
+            .iter()
+            .find(|(c, _)| *c == class)
+            .map(|(_, chars)| *chars)
+    }
+    /// The length of the longest pattern.
+    pub fn max_length(&self) -> usize {
+        self.patterns
+            .iter()
+            .map(|p| p.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self::from_str("long").expect("the long template is built in")
+    }
+}
+
+impl FromStr for Template {
+    type Err = UserInputError;
+    /// Takes a template name (case-insensitive) or a pattern of slot classes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((name, patterns)) = NAMED_TEMPLATES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(Self {
+                name: Some(name),
+                patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            });
+        }
+        if s.is_empty() || s.chars().count() > usize::from(PasswordLength::MAX) {
+            return Err(UserInputError::new(format!(
+                "A template has 1 to {} slots",
+                PasswordLength::MAX
+            )));
+        }
+        if let Some(c) = s.chars().find(|c| Self::class(*c).is_none()) {
+            return Err(UserInputError::new(format!(
+                "Unknown template slot {:?} (use one of VCvcAanox and space, or a template name: {})",
+                c,
+                NAMED_TEMPLATES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(Self {
+            name: None,
+            patterns: vec![s.to_owned()],
+        })
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}", self.patterns[0]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_template() {
+        let long = Template::from_str("Long").unwrap();
+        assert_eq!(long.to_string(), "long");
+        assert_eq!(long.patterns().len(), 21);
+        assert_eq!(long.max_length(), 14);
+        assert_eq!(Template::default(), long);
+
+        let custom = Template::from_str("CvcvnoCvcv").unwrap();
+        assert_eq!(custom.to_string(), "CvcvnoCvcv");
+        assert_eq!(custom.patterns(), ["CvcvnoCvcv"]);
+
+        assert!(Template::from_str("").is_err());
+        assert!(Template::from_str("Cvcq").is_err());
+        assert!(Template::from_str(&"n".repeat(257)).is_err());
+        for (_, patterns) in NAMED_TEMPLATES {
+            for pattern in patterns {
+                assert!(pattern.chars().all(|c| Template::class(c).is_some()));
+            }
+        }
+    }
+}
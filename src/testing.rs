@@ -0,0 +1,160 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Input generators for property-based tests and fuzzing, enabled by the
+//! `testing` feature. Every generator only yields values that pass the
+//! validation of the corresponding `FromStr`/`TryFrom` implementation.
+
+use std::str::FromStr;
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+
+use crate::{
+    UserInputProvider,
+    user_input::{CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID},
+};
+
+pub fn user_id() -> impl Strategy<Value = UserID> {
+    ".{8,32}".prop_map(|s| UserID::from_str(&s).unwrap())
+}
+
+pub fn master_password_plain() -> impl Strategy<Value = MasterPasswordPlain> {
+    ".{8,64}".prop_map(|s| MasterPasswordPlain::from_str(&s).unwrap())
+}
+
+pub fn service_id() -> impl Strategy<Value = ServiceID> {
+    ".{0,64}".prop_map(|s| ServiceID::from_str(&s).unwrap())
+}
+
+pub fn generation() -> impl Strategy<Value = Generation> {
+    (1usize..=usize::MAX).prop_map(|g| Generation::from_str(&g.to_string()).unwrap())
+}
+
+pub fn char_set() -> impl Strategy<Value = CharSet> {
+    proptest::sample::subsequence(vec![0usize, 1, 2, 3], 1..=4)
+        .prop_shuffle()
+        .prop_map(|pools| CharSet::try_from(pools.as_slice()).unwrap())
+}
+
+pub fn password_length() -> impl Strategy<Value = PasswordLength> {
+    (1u8..=64).prop_map(|l| PasswordLength::from_str(&l.to_string()).unwrap())
+}
+
+pub fn user_input() -> impl Strategy<Value = TestUserInput> {
+    (
+        user_id(),
+        master_password_plain(),
+        service_id(),
+        generation(),
+        char_set(),
+        password_length(),
+    )
+        .prop_map(
+            |(
+                user_id,
+                master_password_plain,
+                service_id,
+                generation,
+                char_set,
+                password_length,
+            )| {
+                TestUserInput {
+                    user_id,
+                    master_password_plain,
+                    service_id,
+                    generation,
+                    char_set,
+                    password_length,
+                }
+            },
+        )
+}
+
+#[derive(Debug, Clone)]
+pub struct TestUserInput {
+    pub user_id: UserID,
+    pub master_password_plain: MasterPasswordPlain,
+    pub service_id: ServiceID,
+    pub generation: Generation,
+    pub char_set: CharSet,
+    pub password_length: PasswordLength,
+}
+
+impl UserInputProvider for TestUserInput {
+    fn get_user_id(&self) -> &UserID {
+        &self.user_id
+    }
+    fn get_master_password_plain(&self) -> &MasterPasswordPlain {
+        &self.master_password_plain
+    }
+    fn get_service_id(&self) -> &ServiceID {
+        &self.service_id
+    }
+    fn get_generation(&self) -> &Generation {
+        &self.generation
+    }
+    fn get_char_set(&self) -> &CharSet {
+        &self.char_set
+    }
+    fn get_password_length(&self) -> &PasswordLength {
+        &self.password_length
+    }
+}
+
+impl<'a> Arbitrary<'a> for UserID {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        UserID::from_str(u.arbitrary()?).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for MasterPasswordPlain {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        MasterPasswordPlain::from_str(u.arbitrary()?).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ServiceID {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        ServiceID::from_str(u.arbitrary()?).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Generation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Generation::from_str(&u.int_in_range(1..=usize::MAX)?.to_string())
+            .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for CharSet {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let pools = (0..u.int_in_range(1..=4)?)
+            .map(|_| u.int_in_range(0..=3))
+            .collect::<arbitrary::Result<Vec<usize>>>()?;
+        CharSet::try_from(pools.as_slice()).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for PasswordLength {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        PasswordLength::from_str(&u.int_in_range(1u8..=64)?.to_string())
+            .map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
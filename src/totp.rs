@@ -0,0 +1,127 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! TOTP seeds derived per service, so a second factor can be enrolled again
+//! into any authenticator app without a backup of the original secret.
+
+use anyhow::Result;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use zeroize::Zeroizing;
+
+use crate::{
+    scheme::{SchemeVersion, TOTP_LABEL},
+    service_secret::ServiceSecret,
+    user_input::UserInputError,
+    utils::Utils,
+};
+
+/// Everything but the unreserved characters of RFC 3986 is escaped.
+const ESCAPED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+pub struct TotpSeed {
+    seed: Zeroizing<Vec<u8>>,
+}
+
+impl TotpSeed {
+    /// 160 bits, the length RFC 4226 recommends for HMAC-SHA1.
+    pub const LENGTH: usize = 20;
+
+    /// Takes the first `LENGTH` bytes of the expansion of the service secret
+    /// under `TOTP_LABEL`.
+    pub fn new(service_secret: &ServiceSecret, scheme: SchemeVersion) -> Result<Self> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for TOTP seeds",
+            )
+            .into());
+        }
+        Ok(Self {
+            seed: Zeroizing::new(service_secret.expand(TOTP_LABEL, Self::LENGTH)?),
+        })
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.seed
+    }
+    /// The seed as authenticator apps expect it when typed in.
+    pub fn base32(&self) -> Zeroizing<String> {
+        Zeroizing::new(Utils::bytes_to_base32(&self.seed))
+    }
+    /// The `otpauth://` provisioning URI of the seed with the default
+    /// parameters (SHA1, 6 digits, 30 seconds), labelled `issuer:account`.
+    pub fn uri(&self, issuer: &str, account: Option<&str>) -> Zeroizing<String> {
+        let issuer = utf8_percent_encode(issuer, ESCAPED).to_string();
+        let label = match account {
+            Some(account) => format!("{}:{}", issuer, utf8_percent_encode(account, ESCAPED)),
+            None => issuer.clone(),
+        };
+        Zeroizing::new(format!(
+            "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits=6&period=30",
+            label,
+            self.base32().as_str(),
+            issuer
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::derivation_path::Purpose;
+
+    #[test]
+    fn can_derive_totp_seed() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let seed = TotpSeed::new(&service_secret, SchemeVersion::V2).unwrap();
+        assert_eq!(seed.as_bytes().len(), TotpSeed::LENGTH);
+        assert_eq!(seed.base32().len(), 32);
+        assert!(TotpSeed::new(&service_secret, SchemeVersion::V1).is_err());
+
+        assert_eq!(
+            *seed.uri("Example Corp", Some("alice@example.com")),
+            format!(
+                "otpauth://totp/Example%20Corp:alice%40example.com?secret={}&issuer=Example%20Corp&algorithm=SHA1&digits=6&period=30",
+                seed.base32().as_str()
+            )
+        );
+        assert!(
+            seed.uri("github.com", None)
+                .starts_with("otpauth://totp/github.com?secret=")
+        );
+    }
+
+    #[test]
+    fn user_purpose_does_not_read_totp_seed() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let seed = TotpSeed::new(&service_secret, SchemeVersion::V2).unwrap();
+        let purpose = Purpose::from_str("totp").unwrap();
+        assert_ne!(purpose.label(), TOTP_LABEL);
+        let stream = service_secret
+            .expand(&purpose.label(), TotpSeed::LENGTH)
+            .unwrap();
+        assert_ne!(stream.as_slice(), seed.as_bytes());
+    }
+}
@@ -0,0 +1,481 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Full-screen alternative to the sequential prompts of `UserInputCli`: every
+//! input on one form, the master password masked, and the derived password
+//! only shown for a short time.
+
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Gauge, Paragraph},
+};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    CAPITAL_LETTERS, NUMBERS, SMALL_LETTERS, UserInputProvider,
+    config::Config,
+    derived_pass::DerivedPass,
+    policy::PasswordPolicy,
+    site_policy::SitePolicies,
+    user_input::{
+        CHAR_SET_NAMES, CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID,
+    },
+};
+
+/// Seconds the derived password stays visible after deriving or `Ctrl-R`.
+pub const REVEAL_SECONDS: u64 = 15;
+
+const CHAR_SET_LABELS: [&str; 4] = [
+    "small letters [a-z]",
+    "capital letters [A-Z]",
+    "numbers [0-9]",
+    "special characters",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    UserId,
+    ServiceId,
+    Generation,
+    CharSet(usize),
+    PasswordLength,
+    MasterPassword,
+}
+
+const FIELDS: [Field; 9] = [
+    Field::UserId,
+    Field::ServiceId,
+    Field::Generation,
+    Field::CharSet(0),
+    Field::CharSet(1),
+    Field::CharSet(2),
+    Field::CharSet(3),
+    Field::PasswordLength,
+    Field::MasterPassword,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormAction {
+    None,
+    /// An input changed, so a shown result is stale.
+    Edited,
+    Submit,
+    Reveal,
+    Quit,
+}
+
+pub struct TuiForm {
+    user_id: String,
+    service_id: String,
+    generation: String,
+    char_sets: [bool; 4],
+    password_length: String,
+    master_password: Zeroizing<String>,
+    focus: usize,
+}
+
+impl TuiForm {
+    /// An empty form with the defaults of `config`.
+    pub fn new(config: &Config) -> Self {
+        Self {
+            user_id: config.user_id.clone().unwrap_or_default(),
+            service_id: String::new(),
+            generation: "1".to_owned(),
+            char_sets: match &config.char_sets {
+                Some(names) => CHAR_SET_NAMES.map(|n| names.iter().any(|name| name == n)),
+                None => [true; 4],
+            },
+            password_length: config
+                .password_length
+                .map(|l| l.to_string())
+                .unwrap_or_default(),
+            master_password: Zeroizing::new(String::new()),
+            // Start at the service when the user id is already known.
+            focus: usize::from(config.user_id.is_some()),
+        }
+    }
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormAction {
+        if key.kind != KeyEventKind::Press {
+            return FormAction::None;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => FormAction::Quit,
+            KeyCode::Char('r') if ctrl => FormAction::Reveal,
+            KeyCode::Esc => FormAction::Quit,
+            KeyCode::Enter => FormAction::Submit,
+            KeyCode::Tab | KeyCode::Down => {
+                self.focus = (self.focus + 1) % FIELDS.len();
+                FormAction::None
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.focus = (self.focus + FIELDS.len() - 1) % FIELDS.len();
+                FormAction::None
+            }
+            KeyCode::Char(' ') if matches!(FIELDS[self.focus], Field::CharSet(_)) => {
+                if let Field::CharSet(i) = FIELDS[self.focus] {
+                    self.char_sets[i] = !self.char_sets[i];
+                }
+                FormAction::Edited
+            }
+            KeyCode::Char(c) if !ctrl => match self.text_mut() {
+                Some((text, numeric)) if !numeric || c.is_ascii_digit() => {
+                    text.push(c);
+                    FormAction::Edited
+                }
+                _ => FormAction::None,
+            },
+            KeyCode::Backspace => match self.text_mut() {
+                Some((text, _)) => {
+                    text.pop();
+                    FormAction::Edited
+                }
+                None => FormAction::None,
+            },
+            _ => FormAction::None,
+        }
+    }
+    /// The focused text input and whether it only takes digits.
+    fn text_mut(&mut self) -> Option<(&mut String, bool)> {
+        match FIELDS[self.focus] {
+            Field::UserId => Some((&mut self.user_id, false)),
+            Field::ServiceId => Some((&mut self.service_id, false)),
+            Field::Generation => Some((&mut self.generation, true)),
+            Field::PasswordLength => Some((&mut self.password_length, true)),
+            Field::MasterPassword => Some((&mut self.master_password, false)),
+            Field::CharSet(_) => None,
+        }
+    }
+    /// Validates every input the way the prompts do.
+    pub fn input(&self) -> Result<FormInput> {
+        let pools: Vec<usize> = (0..4).filter(|i| self.char_sets[*i]).collect();
+        let service_id = ServiceID::from_str(&self.service_id)?;
+        let policy = SitePolicies::load(&SitePolicies::default_path()?)?
+            .find(&service_id.to_string())
+            .map(|site| site.policy.clone());
+        Ok(FormInput {
+            user_id: UserID::from_str(&self.user_id)?,
+            master_password_plain: MasterPasswordPlain::from_str(&self.master_password)?,
+            service_id,
+            generation: Generation::from_str(&self.generation)?,
+            char_set: CharSet::try_from(pools.as_slice())?,
+            password_length: PasswordLength::from_str(&self.password_length)?,
+            policy,
+        })
+    }
+    /// Rough entropy of the typed master password in bits, from its length
+    /// and the character classes it uses. It ignores words and patterns, so
+    /// it is an upper bound.
+    pub fn master_password_strength(&self) -> f64 {
+        let password = self.master_password.as_str();
+        let pool: usize = [(SMALL_LETTERS, 26), (CAPITAL_LETTERS, 26), (NUMBERS, 10)]
+            .iter()
+            .filter(|(chars, _)| password.chars().any(|c| chars.contains(c)))
+            .map(|(_, size)| size)
+            .sum::<usize>()
+            + if password
+                .chars()
+                .any(|c| c.is_ascii_punctuation() || c == ' ')
+            {
+                33
+            } else {
+                0
+            }
+            + if !password.is_ascii() { 100 } else { 0 };
+        if pool == 0 {
+            0.0
+        } else {
+            password.chars().count() as f64 * (pool as f64).log2()
+        }
+    }
+}
+
+/// The validated inputs of a submitted form.
+pub struct FormInput {
+    user_id: UserID,
+    master_password_plain: MasterPasswordPlain,
+    service_id: ServiceID,
+    generation: Generation,
+    char_set: CharSet,
+    password_length: PasswordLength,
+    /// From the site policy database, like the prompts of `UserInputCli`.
+    policy: Option<PasswordPolicy>,
+}
+
+impl Zeroize for FormInput {
+    fn zeroize(&mut self) {
+        self.master_password_plain.zeroize();
+    }
+}
+
+impl UserInputProvider for FormInput {
+    fn get_user_id(&self) -> &UserID {
+        &self.user_id
+    }
+    fn get_master_password_plain(&self) -> &MasterPasswordPlain {
+        &self.master_password_plain
+    }
+    fn get_service_id(&self) -> &ServiceID {
+        &self.service_id
+    }
+    fn get_generation(&self) -> &Generation {
+        &self.generation
+    }
+    fn get_char_set(&self) -> &CharSet {
+        &self.char_set
+    }
+    fn get_password_length(&self) -> &PasswordLength {
+        &self.password_length
+    }
+    fn get_policy(&self) -> Option<&PasswordPolicy> {
+        self.policy.as_ref()
+    }
+}
+
+struct TuiState {
+    form: TuiForm,
+    derived_pass: Option<Zeroizing<String>>,
+    revealed_until: Option<Instant>,
+    status: String,
+}
+
+pub struct Tui {}
+
+impl Tui {
+    /// Runs the form until the user quits. `derive` is called with the
+    /// inputs on every submit.
+    pub fn run(
+        config: &Config,
+        derive: impl FnMut(&FormInput) -> Result<DerivedPass>,
+    ) -> Result<()> {
+        let mut terminal = ratatui::init();
+        let result = Self::event_loop(&mut terminal, config, derive);
+        ratatui::restore();
+        result
+    }
+    fn event_loop(
+        terminal: &mut DefaultTerminal,
+        config: &Config,
+        mut derive: impl FnMut(&FormInput) -> Result<DerivedPass>,
+    ) -> Result<()> {
+        let mut state = TuiState {
+            form: TuiForm::new(config),
+            derived_pass: None,
+            revealed_until: None,
+            status: "Tab: next field, Space: toggle, Enter: derive, Ctrl-R: reveal, Esc: quit"
+                .to_owned(),
+        };
+        loop {
+            if state
+                .revealed_until
+                .is_some_and(|until| until <= Instant::now())
+            {
+                state.revealed_until = None;
+            }
+            terminal.draw(|frame| Self::draw(frame, &state))?;
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            match state.form.handle_key(key) {
+                FormAction::None => {}
+                FormAction::Quit => return Ok(()),
+                FormAction::Edited => {
+                    state.derived_pass = None;
+                    state.revealed_until = None;
+                }
+                FormAction::Reveal => {
+                    if state.derived_pass.is_some() {
+                        state.revealed_until =
+                            Some(Instant::now() + Duration::from_secs(REVEAL_SECONDS));
+                    }
+                }
+                FormAction::Submit => {
+                    state.status = "Deriving...".to_owned();
+                    terminal.draw(|frame| Self::draw(frame, &state))?;
+                    let derived = state.form.input().and_then(|mut input| {
+                        let derived = derive(&input);
+                        input.zeroize();
+                        derived
+                    });
+                    match derived {
+                        Ok(derived_pass) => {
+                            state.derived_pass =
+                                Some(Zeroizing::new(derived_pass.expose_secret().to_owned()));
+                            state.revealed_until =
+                                Some(Instant::now() + Duration::from_secs(REVEAL_SECONDS));
+                            state.status = "Derived, Ctrl-R shows it again".to_owned();
+                        }
+                        Err(e) => state.status = format!("Error: {}", e),
+                    }
+                }
+            }
+        }
+    }
+    fn draw(frame: &mut Frame, state: &TuiState) {
+        let [form_area, strength_area, result_area, status_area] = Layout::vertical([
+            Constraint::Length(FIELDS.len() as u16 + 2),
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let form = &state.form;
+        let lines: Vec<Line> = FIELDS
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let (label, value) = match field {
+                    Field::UserId => ("User identifier", form.user_id.clone()),
+                    Field::ServiceId => ("Service identifier", form.service_id.clone()),
+                    Field::Generation => ("Generation", form.generation.clone()),
+                    Field::CharSet(c) => (
+                        CHAR_SET_LABELS[*c],
+                        if form.char_sets[*c] { "[x]" } else { "[ ]" }.to_owned(),
+                    ),
+                    Field::PasswordLength => ("Password length", form.password_length.clone()),
+                    Field::MasterPassword => (
+                        "Master password",
+                        "*".repeat(form.master_password.chars().count()),
+                    ),
+                };
+                let style = if i == form.focus {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    Span::raw(format!("{:>22}: ", label)),
+                    Span::styled(value, style),
+                ])
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" depasswd ")),
+            form_area,
+        );
+
+        let bits = form.master_password_strength();
+        let (verdict, color) = match bits {
+            b if b < 50.0 => ("weak", Color::Red),
+            b if b < 80.0 => ("fair", Color::Yellow),
+            _ => ("strong", Color::Green),
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(" Master password strength "))
+                .gauge_style(Style::default().fg(color))
+                .ratio((bits / 128.0).clamp(0.0, 1.0))
+                .label(format!("{} (~{:.0} bits)", verdict, bits)),
+            strength_area,
+        );
+
+        let result = match (&state.derived_pass, state.revealed_until) {
+            (Some(derived_pass), Some(until)) => vec![
+                Line::from(derived_pass.as_str().to_owned()),
+                Line::from(format!(
+                    "hidden in {} s",
+                    until.saturating_duration_since(Instant::now()).as_secs() + 1
+                )),
+            ],
+            (Some(derived_pass), None) => {
+                vec![Line::from("*".repeat(derived_pass.chars().count()))]
+            }
+            (None, _) => vec![],
+        };
+        frame.render_widget(
+            Paragraph::new(result).block(Block::bordered().title(" Password ")),
+            result_area,
+        );
+        frame.render_widget(Paragraph::new(state.status.as_str()), status_area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(form: &mut TuiForm, code: KeyCode) -> FormAction {
+        form.handle_key(KeyEvent::from(code))
+    }
+
+    fn type_text(form: &mut TuiForm, text: &str) {
+        for c in text.chars() {
+            press(form, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn can_fill_form() {
+        let config = Config {
+            user_id: Some("Example Eleonora".to_owned()),
+            ..Config::default()
+        };
+        let mut form = TuiForm::new(&config);
+        type_text(&mut form, "Example Service Name");
+        press(&mut form, KeyCode::Tab);
+        press(&mut form, KeyCode::Backspace);
+        type_text(&mut form, "2x");
+        press(&mut form, KeyCode::Tab);
+        assert_eq!(press(&mut form, KeyCode::Char(' ')), FormAction::Edited);
+        for _ in 0..4 {
+            press(&mut form, KeyCode::Tab);
+        }
+        type_text(&mut form, "20");
+        press(&mut form, KeyCode::Tab);
+        type_text(&mut form, "]lE~WExZ468ty{I5mtg[");
+        assert_eq!(press(&mut form, KeyCode::Enter), FormAction::Submit);
+
+        let input = form.input().unwrap();
+        assert_eq!(input.get_user_id().to_string(), "Example Eleonora");
+        assert_eq!(input.get_service_id().to_string(), "Example Service Name");
+        assert_eq!(input.get_generation().as_usize(), 2);
+        assert_eq!(
+            input.get_char_set().to_string(),
+            CharSet::try_from([1usize, 2, 3].as_slice())
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(input.get_password_length().as_u16(), 20);
+        assert_eq!(
+            input.get_master_password_plain().as_bytes(),
+            b"]lE~WExZ468ty{I5mtg["
+        );
+        assert!(form.master_password_strength() > 100.0);
+
+        press(&mut form, KeyCode::Up);
+        press(&mut form, KeyCode::Backspace);
+        press(&mut form, KeyCode::Backspace);
+        assert!(form.input().is_err());
+        assert_eq!(press(&mut form, KeyCode::Esc), FormAction::Quit);
+    }
+}
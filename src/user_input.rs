@@ -17,13 +17,36 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, fmt::Display, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    fs::File,
+    io::{BufRead, BufReader},
+    str::FromStr,
+};
 
-use anyhow::Result;
-use dialoguer::{Input, MultiSelect, Password, theme::ColorfulTheme};
+use anyhow::{Context, Result, bail};
+#[cfg(feature = "cli")]
+use dialoguer::{Confirm, Input, MultiSelect, Password, Select, theme::ColorfulTheme};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
 use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-use crate::{CAPITAL_LETTERS, NUMBERS, SMALL_LETTERS, SPECIAL_CHARS};
+use crate::{
+    AMBIGUOUS_CHARS, CAPITAL_LETTERS, LAYOUT_SAFE_SPECIAL_CHARS, NUMBERS, SMALL_LETTERS,
+    SPECIAL_CHARS, kdf::KdfParams, pinentry::Pinentry, policy::PasswordPolicy,
+    scheme::SchemeVersion,
+};
+#[cfg(feature = "cli")]
+use crate::{
+    catalog::{Catalog, CatalogEntry},
+    config::Config,
+    history::ServiceHistory,
+    output::Output,
+    service_matcher::ServiceMatcher,
+    site_policy::SitePolicies,
+};
 
 pub trait UserInputProvider {
     fn get_user_id(&self) -> &UserID;
@@ -32,11 +55,117 @@ pub trait UserInputProvider {
     fn get_generation(&self) -> &Generation;
     fn get_char_set(&self) -> &CharSet;
     fn get_password_length(&self) -> &PasswordLength;
+    fn get_scheme(&self) -> SchemeVersion {
+        SchemeVersion::V1
+    }
+    fn get_kdf_params(&self) -> KdfParams {
+        KdfParams::DEFAULT
+    }
+    fn get_policy(&self) -> Option<&PasswordPolicy> {
+        None
+    }
+}
+
+/// The inputs of one derivation as a plain owned value, for requests built
+/// from deserialized data, FFI calls or tests rather than from prompts.
+/// `DeriveParams::new` uses the defaults of `UserInputProvider`.
+#[derive(Clone)]
+pub struct DeriveParams {
+    pub user_id: UserID,
+    pub master_password_plain: MasterPasswordPlain,
+    pub service_id: ServiceID,
+    pub generation: Generation,
+    pub char_set: CharSet,
+    pub password_length: PasswordLength,
+    pub scheme: SchemeVersion,
+    pub kdf_params: KdfParams,
+    pub policy: Option<PasswordPolicy>,
+}
+
+impl DeriveParams {
+    pub fn new(
+        user_id: UserID,
+        master_password_plain: MasterPasswordPlain,
+        service_id: ServiceID,
+        generation: Generation,
+        char_set: CharSet,
+        password_length: PasswordLength,
+    ) -> Self {
+        Self {
+            user_id,
+            master_password_plain,
+            service_id,
+            generation,
+            char_set,
+            password_length,
+            scheme: SchemeVersion::V1,
+            kdf_params: KdfParams::DEFAULT,
+            policy: None,
+        }
+    }
+}
+
+impl<T: UserInputProvider> From<&T> for DeriveParams {
+    fn from(user_input: &T) -> Self {
+        Self {
+            user_id: user_input.get_user_id().clone(),
+            master_password_plain: user_input.get_master_password_plain().clone(),
+            service_id: user_input.get_service_id().clone(),
+            generation: user_input.get_generation().clone(),
+            char_set: user_input.get_char_set().clone(),
+            password_length: user_input.get_password_length().clone(),
+            scheme: user_input.get_scheme(),
+            kdf_params: user_input.get_kdf_params(),
+            policy: user_input.get_policy().cloned(),
+        }
+    }
+}
+
+impl Zeroize for DeriveParams {
+    fn zeroize(&mut self) {
+        self.master_password_plain.zeroize();
+    }
+}
+
+impl UserInputProvider for DeriveParams {
+    fn get_user_id(&self) -> &UserID {
+        &self.user_id
+    }
+    fn get_master_password_plain(&self) -> &MasterPasswordPlain {
+        &self.master_password_plain
+    }
+    fn get_service_id(&self) -> &ServiceID {
+        &self.service_id
+    }
+    fn get_generation(&self) -> &Generation {
+        &self.generation
+    }
+    fn get_char_set(&self) -> &CharSet {
+        &self.char_set
+    }
+    fn get_password_length(&self) -> &PasswordLength {
+        &self.password_length
+    }
+    fn get_scheme(&self) -> SchemeVersion {
+        self.scheme
+    }
+    fn get_kdf_params(&self) -> KdfParams {
+        self.kdf_params
+    }
+    fn get_policy(&self) -> Option<&PasswordPolicy> {
+        self.policy.as_ref()
+    }
 }
 
 #[derive(Error, Debug)]
 pub struct UserInputError(String);
 
+impl UserInputError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
 impl Display for UserInputError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "UserInputError: {}", self.0)
@@ -44,6 +173,11 @@ impl Display for UserInputError {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct UserID {
     user_id: String,
 }
@@ -52,6 +186,9 @@ impl UserID {
     pub fn len(&self) -> usize {
         self.user_id.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.user_id.is_empty()
+    }
 }
 
 impl FromStr for UserID {
@@ -75,7 +212,20 @@ impl Display for UserID {
     }
 }
 
-#[derive(Debug, Clone)]
+impl TryFrom<String> for UserID {
+    type Error = UserInputError;
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        Self::from_str(&value)
+    }
+}
+
+impl From<UserID> for String {
+    fn from(value: UserID) -> Self {
+        value.user_id
+    }
+}
+
+#[derive(Clone)]
 pub struct MasterPasswordPlain {
     master_password_plain: String,
 }
@@ -84,8 +234,82 @@ impl MasterPasswordPlain {
     pub fn as_bytes(&self) -> &[u8] {
         self.master_password_plain.as_bytes()
     }
+    pub fn expose_secret(&self) -> &str {
+        &self.master_password_plain
+    }
+    /// Reads the first line of `reader`. Only its line ending (`\n` or
+    /// `\r\n`) is removed, any other whitespace is part of the password.
+    pub fn read_line(mut reader: impl BufRead) -> Result<Self> {
+        let mut line = Zeroizing::new(String::new());
+        reader.read_line(&mut line)?;
+        let password = line
+            .strip_suffix('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .unwrap_or(&line);
+        Ok(Self::from_str(password)?)
+    }
+}
+
+/// Where the master password comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MasterPasswordSource {
+    #[default]
+    Prompt,
+    Stdin,
+    /// An inherited file descriptor, e.g. `--master-fd 3 3< <(pass show depasswd)`.
+    Fd(u32),
+    /// The pinentry dialog of gpg-agent, see `Pinentry`.
+    Pinentry,
+}
+
+impl MasterPasswordSource {
+    pub fn read(&self) -> Result<MasterPasswordPlain> {
+        match self {
+            #[cfg(not(feature = "cli"))]
+            MasterPasswordSource::Prompt => {
+                bail!("Prompting for the master password needs the cli feature")
+            }
+            #[cfg(feature = "cli")]
+            MasterPasswordSource::Prompt => {
+                let master_password_plain = Zeroizing::new(
+                    Password::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Master password")
+                        .interact()?,
+                );
+                Ok(MasterPasswordPlain::from_str(&master_password_plain)?)
+            }
+            MasterPasswordSource::Stdin => MasterPasswordPlain::read_line(std::io::stdin().lock()),
+            MasterPasswordSource::Fd(fd) => {
+                if !cfg!(unix) {
+                    bail!("Reading the master password from a file descriptor needs Unix");
+                }
+                MasterPasswordPlain::read_line(BufReader::new(
+                    File::open(format!("/dev/fd/{}", fd))
+                        .with_context(|| format!("Can not read file descriptor {}", fd))?,
+                ))
+            }
+            MasterPasswordSource::Pinentry => Ok(MasterPasswordPlain::from_str(
+                &Pinentry::find()?
+                    .get_pin("Enter the master password of depasswd", "Master password")?,
+            )?),
+        }
+    }
 }
 
+impl Zeroize for MasterPasswordPlain {
+    fn zeroize(&mut self) {
+        self.master_password_plain.zeroize();
+    }
+}
+
+impl Drop for MasterPasswordPlain {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for MasterPasswordPlain {}
+
 impl FromStr for MasterPasswordPlain {
     type Err = UserInputError;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
@@ -101,13 +325,25 @@ impl FromStr for MasterPasswordPlain {
     }
 }
 
+/// Redacted, the secret is only available through `expose_secret`.
+impl Debug for MasterPasswordPlain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MasterPasswordPlain(****)")
+    }
+}
+
 impl Display for MasterPasswordPlain {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.master_password_plain)
+        Debug::fmt(self, f)
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct ServiceID {
     service_id: String,
 }
@@ -116,6 +352,9 @@ impl ServiceID {
     pub fn len(&self) -> usize {
         self.service_id.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.service_id.is_empty()
+    }
 }
 
 impl FromStr for ServiceID {
@@ -132,7 +371,25 @@ impl Display for ServiceID {
         write!(f, "{}", self.service_id)
     }
 }
+
+impl TryFrom<String> for ServiceID {
+    type Error = UserInputError;
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        Self::from_str(&value)
+    }
+}
+
+impl From<ServiceID> for String {
+    fn from(value: ServiceID) -> Self {
+        value.service_id
+    }
+}
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "usize", into = "usize")
+)]
 pub struct Generation {
     generation: usize,
 }
@@ -168,25 +425,278 @@ impl Display for Generation {
         write!(f, "{}", self.generation)
     }
 }
+
+impl TryFrom<usize> for Generation {
+    type Error = UserInputError;
+    fn try_from(value: usize) -> std::result::Result<Self, Self::Error> {
+        Self::from_str(&value.to_string())
+    }
+}
+
+impl From<Generation> for usize {
+    fn from(value: Generation) -> Self {
+        value.generation
+    }
+}
+pub const CHAR_SET_NAMES: [&str; 4] = ["small", "capital", "numbers", "special"];
+/// Name of the `LAYOUT_SAFE_SPECIAL_CHARS` preset, a narrower alternative to
+/// `special` outside `CHAR_SET_NAMES` so that "every set" stays the same.
+pub const LAYOUT_SAFE: &str = "layout-safe";
+/// Index of the `LAYOUT_SAFE` preset for `CharSet::try_from`.
+pub const LAYOUT_SAFE_POOL: usize = 4;
+/// Prefix of the names of custom character sets, e.g. `custom:abc123`.
+pub const CUSTOM_CHAR_SET_PREFIX: &str = "custom:";
+/// Not a set but a modifier: removes `AMBIGUOUS_CHARS` from the other sets.
+pub const EXCLUDE_AMBIGUOUS: &str = "no-ambiguous";
+/// Prefix of the modifier removing specific characters, written like a
+/// custom set, e.g. `exclude:"\\;` or `exclude:0-4`.
+pub const EXCLUDE_CHARS_PREFIX: &str = "exclude:";
+
+/// Serialized as the names of `CharSet::names`.
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(try_from = "Vec<String>")
+)]
 pub struct CharSet {
     char_set: String,
+    /// The selected pools, of which scheme v2 puts at least one character
+    /// into every password.
+    classes: Vec<String>,
+}
+
+impl CharSet {
+    /// Builds the set from preset names (see `CHAR_SET_NAMES`) and custom
+    /// sets written as `custom:<characters>`, in the given order, minus the
+    /// ambiguous characters if `EXCLUDE_AMBIGUOUS` is among the names and
+    /// the characters of every `exclude:<characters>` modifier, which takes
+    /// ranges and escapes like a custom set.
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> std::result::Result<Self, UserInputError> {
+        let mut char_set = "".to_owned();
+        let mut classes = vec![];
+        let mut excluded = "".to_owned();
+        for name in names {
+            let name = name.as_ref();
+            if name == EXCLUDE_AMBIGUOUS {
+                excluded += AMBIGUOUS_CHARS;
+                continue;
+            }
+            if let Some(chars) = name.strip_prefix(EXCLUDE_CHARS_PREFIX) {
+                excluded += &Self::expand_ranges(chars)?;
+                continue;
+            }
+            let set = match name.strip_prefix(CUSTOM_CHAR_SET_PREFIX) {
+                Some(chars) => Self::from_custom(chars)?,
+                None if name == LAYOUT_SAFE => Self::try_from([LAYOUT_SAFE_POOL].as_slice())?,
+                None => {
+                    let pool = CHAR_SET_NAMES
+                        .iter()
+                        .position(|n| *n == name)
+                        .ok_or(UserInputError(format!("Unknown character set: {}", name)))?;
+                    Self::try_from([pool].as_slice())?
+                }
+            };
+            char_set += &set.char_set;
+            classes.extend(set.classes);
+        }
+        if char_set.is_empty() {
+            return Err(UserInputError(
+                "You must select at least one character set!".to_owned(),
+            ));
+        }
+        let char_set = Self {
+            char_set: Self::dedup(&char_set),
+            classes,
+        };
+        if excluded.is_empty() {
+            Ok(char_set)
+        } else {
+            char_set.without(&excluded)
+        }
+    }
+    /// Builds the set from the characters a site accepts, written as a spec
+    /// for `expand_ranges`. Duplicates are dropped so that no character is
+    /// more likely than the others. Only printable ASCII is allowed, as
+    /// scheme v1 indexes the set by bytes.
+    pub fn from_custom(spec: &str) -> std::result::Result<Self, UserInputError> {
+        let chars = Self::expand_ranges(spec)?;
+        if !chars.chars().all(|c| (' '..='~').contains(&c)) {
+            return Err(UserInputError(
+                "A custom character set may only contain printable ASCII characters".to_owned(),
+            ));
+        }
+        if chars.is_empty() {
+            return Err(UserInputError(
+                "A custom character set needs at least one character".to_owned(),
+            ));
+        }
+        Ok(Self {
+            char_set: Self::dedup(&chars),
+            classes: vec![Self::dedup(&chars)],
+        })
+    }
+    /// Expands a spec of single characters and ranges such as `a-f0-9`.
+    /// `\` escapes the next character, and a `-` that does not sit between
+    /// two characters stands for itself, so `_-` and `a\-z` need no ranges.
+    pub fn expand_ranges(spec: &str) -> std::result::Result<String, UserInputError> {
+        // Each character with whether it was escaped.
+        let mut tokens: Vec<(char, bool)> = vec![];
+        let mut chars = spec.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                let escaped = chars.next().ok_or(UserInputError(format!(
+                    "Character set {} ends with an unfinished \\ escape",
+                    spec
+                )))?;
+                tokens.push((escaped, true));
+            } else {
+                tokens.push((c, false));
+            }
+        }
+        let mut expanded = "".to_owned();
+        let mut i = 0;
+        while i < tokens.len() {
+            let start = tokens[i].0;
+            match (tokens.get(i + 1), tokens.get(i + 2)) {
+                (Some(('-', false)), Some((end, _))) => {
+                    if *end < start {
+                        return Err(UserInputError(format!(
+                            "Invalid character range {}-{}: it starts after its end",
+                            start, end
+                        )));
+                    }
+                    expanded.extend(start..=*end);
+                    i += 3;
+                }
+                _ => {
+                    expanded.push(start);
+                    i += 1;
+                }
+            }
+        }
+        Ok(expanded)
+    }
+    /// The characters of a valid spec that occur more than once, each
+    /// listed once.
+    pub fn repeats(spec: &str) -> String {
+        let chars = Self::expand_ranges(spec).unwrap_or_default();
+        Self::dedup(
+            &chars
+                .chars()
+                .filter(|c| chars.matches(*c).count() > 1)
+                .collect::<String>(),
+        )
+    }
+    /// The alphabet sorted by code point without duplicates, so that neither
+    /// the order of the selected sets nor overlaps between them matter.
+    pub fn canonical(&self) -> Vec<char> {
+        let mut alphabet: Vec<char> = self.char_set.chars().collect();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        alphabet
+    }
+    fn dedup(chars: &str) -> String {
+        let mut seen = String::with_capacity(chars.len());
+        for c in chars.chars() {
+            if !seen.contains(c) {
+                seen.push(c);
+            }
+        }
+        seen
+    }
+    /// The same set without the characters of `excluded`, order preserved.
+    /// Pools left without characters are dropped.
+    pub fn without(&self, excluded: &str) -> std::result::Result<Self, UserInputError> {
+        let keep = |chars: &String| -> String {
+            chars.chars().filter(|c| !excluded.contains(*c)).collect()
+        };
+        let char_set = keep(&self.char_set);
+        if char_set.is_empty() {
+            Err(UserInputError(
+                "You must select at least one character set!".to_owned(),
+            ))
+        } else {
+            Ok(Self {
+                char_set,
+                classes: self
+                    .classes
+                    .iter()
+                    .map(keep)
+                    .filter(|class| !class.is_empty())
+                    .collect(),
+            })
+        }
+    }
+    pub fn contains_any(&self, chars: &str) -> bool {
+        self.char_set.chars().any(|c| chars.contains(c))
+    }
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+    /// The names `from_names` rebuilds the same set from: the preset names
+    /// of the classes that are whole presets, `custom:` sets for the others.
+    /// `None` if the set repeats characters, which `from_names` would drop.
+    pub fn names(&self) -> Option<Vec<String>> {
+        if Self::dedup(&self.classes.concat()) != self.char_set {
+            return None;
+        }
+        let presets = [SMALL_LETTERS, CAPITAL_LETTERS, NUMBERS, SPECIAL_CHARS];
+        Some(
+            self.classes
+                .iter()
+                .map(
+                    |class| match presets.iter().position(|preset| preset == class) {
+                        Some(pool) => CHAR_SET_NAMES[pool].to_owned(),
+                        None if class == LAYOUT_SAFE_SPECIAL_CHARS => LAYOUT_SAFE.to_owned(),
+                        None => format!(
+                            "{}{}",
+                            CUSTOM_CHAR_SET_PREFIX,
+                            class.replace('\\', r"\\").replace('-', r"\-")
+                        ),
+                    },
+                )
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<Vec<String>> for CharSet {
+    type Error = UserInputError;
+    fn try_from(value: Vec<String>) -> std::result::Result<Self, Self::Error> {
+        Self::from_names(&value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for CharSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.names()
+            .ok_or_else(|| {
+                serde::ser::Error::custom("a character set with repeated characters has no names")
+            })?
+            .serialize(serializer)
+    }
 }
 
 impl TryFrom<&[usize]> for CharSet {
     type Error = UserInputError;
     fn try_from(value: &[usize]) -> std::result::Result<Self, Self::Error> {
         let mut char_set = "".to_owned();
+        let mut classes = vec![];
         let mut presets = HashMap::new();
         presets.insert(0, SMALL_LETTERS.to_owned());
         presets.insert(1, CAPITAL_LETTERS.to_owned());
         presets.insert(2, NUMBERS.to_owned());
         presets.insert(3, SPECIAL_CHARS.to_owned());
+        presets.insert(LAYOUT_SAFE_POOL, LAYOUT_SAFE_SPECIAL_CHARS.to_owned());
 
         for v in value {
-            char_set += presets
+            let preset = presets
                 .get(v)
                 .ok_or(UserInputError("Invalid character set!".to_owned()))?;
+            char_set += preset;
+            classes.push(preset.clone());
         }
 
         if char_set.is_empty() {
@@ -195,7 +705,7 @@ impl TryFrom<&[usize]> for CharSet {
             ));
         }
 
-        Ok(Self { char_set })
+        Ok(Self { char_set, classes })
     }
 }
 
@@ -205,12 +715,21 @@ impl Display for CharSet {
     }
 }
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u16", into = "u16")
+)]
 pub struct PasswordLength {
-    password_length: u8,
+    password_length: u16,
 }
 
 impl PasswordLength {
-    pub fn as_u8(&self) -> u8 {
+    /// Longest password any scheme can derive. Scheme v1 reads one byte of
+    /// the service secret per character, so it stops at 64.
+    pub const MAX: u16 = 256;
+
+    pub fn as_u16(&self) -> u16 {
         self.password_length
     }
     pub fn as_usize(&self) -> usize {
@@ -221,21 +740,14 @@ impl PasswordLength {
 impl FromStr for PasswordLength {
     type Err = UserInputError;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s.parse::<u8>() {
-            Ok(value) => {
-                if value > 0 && value <= 64 {
-                    Ok(Self {
-                        password_length: value,
-                    })
-                } else {
-                    Err(UserInputError(
-                        "PasswordLength must be a number between 1 and 64".to_owned(),
-                    ))
-                }
-            }
-            Err(_) => Err(UserInputError(
-                "PasswordLength must be a number between 1 and 64".to_owned(),
-            )),
+        match s.parse::<u16>() {
+            Ok(value) if value > 0 && value <= Self::MAX => Ok(Self {
+                password_length: value,
+            }),
+            _ => Err(UserInputError(format!(
+                "PasswordLength must be a number between 1 and {}",
+                Self::MAX
+            ))),
         }
     }
 }
@@ -246,71 +758,388 @@ impl Display for PasswordLength {
     }
 }
 
+impl TryFrom<u16> for PasswordLength {
+    type Error = UserInputError;
+    fn try_from(value: u16) -> std::result::Result<Self, Self::Error> {
+        Self::from_str(&value.to_string())
+    }
+}
+
+impl From<PasswordLength> for u16 {
+    fn from(value: PasswordLength) -> Self {
+        value.password_length
+    }
+}
+
+/// Interactive prompts for every input of a derivation, only available with
+/// the `cli` feature.
+#[cfg(feature = "cli")]
 pub struct UserInputCli {
     user_id: UserID,
     master_password_plain: MasterPasswordPlain,
     service_id: ServiceID,
     generation: Generation,
     char_pools: CharSet,
+    char_set_names: Vec<String>,
     password_length: PasswordLength,
+    policy: Option<PasswordPolicy>,
 }
 
+#[cfg(feature = "cli")]
 impl UserInputCli {
-    pub fn new() -> Result<Self> {
-        let user_id = Input::<UserID>::new()
-            .with_prompt("User identifier (ex.: fullname, username...)")
-            .interact_text()?;
-        let service_id = Input::<ServiceID>::new()
-            .with_prompt("Service identifier (ex.: name, url...)")
-            .interact_text()?;
-        let generation = Input::<Generation>::new()
-            .with_prompt("Generation (increase this variable to regenerate password for a service) (default: 1)")
-            .default(Generation::from_str("1")?)
-            .interact_text()?;
+    /// Prompts for every input, offering the values of `config` as defaults.
+    pub fn new(config: &Config, master_password: MasterPasswordSource) -> Result<Self> {
+        Self::prompt(true, true, config, master_password, None)
+    }
+    /// Prompts for every input but the service identifier.
+    pub fn for_service(
+        config: &Config,
+        master_password: MasterPasswordSource,
+        service_id: ServiceID,
+    ) -> Result<Self> {
+        Self::prompt(true, true, config, master_password, Some(service_id))
+    }
+    /// Prompts for everything but the character sets, which come from
+    /// `config`, for outputs that pick their own characters.
+    pub fn without_char_sets(
+        config: &Config,
+        master_password: MasterPasswordSource,
+        service_id: Option<ServiceID>,
+    ) -> Result<Self> {
+        Self::prompt(true, false, config, master_password, service_id)
+    }
+    /// Prompts for everything but the generation, which is left at 1.
+    pub fn without_generation(
+        config: &Config,
+        master_password: MasterPasswordSource,
+    ) -> Result<Self> {
+        Self::prompt(false, true, config, master_password, None)
+    }
+    fn prompt(
+        ask_generation: bool,
+        ask_char_sets: bool,
+        config: &Config,
+        master_password: MasterPasswordSource,
+        service_id: Option<ServiceID>,
+    ) -> Result<Self> {
+        let user_id = Self::prompt_user_id(config, master_password)?;
+        let service_id = match service_id {
+            Some(service_id) => service_id,
+            None => Self::prompt_service_id()?,
+        };
+        eprintln!(
+            "\n{}\nCheck that this matches what you usually see for this account.\n",
+            Output::identicon(
+                &user_id.to_string(),
+                &service_id.to_string(),
+                console::colors_enabled_stderr()
+            )
+        );
+        if config.history == Some(true) {
+            Self::check_history(&service_id)?;
+        }
+        let policy = Self::site_policy(&service_id)?;
+
+        if let Some((generation, char_set_names, password_length)) =
+            Self::remembered_settings(&service_id)
+            && Confirm::new()
+                .with_prompt(format!(
+                    "Use the last settings of this service (generation {}, length {}, {})?",
+                    generation,
+                    password_length,
+                    char_set_names.join(", ")
+                ))
+                .default(true)
+                .interact()?
+        {
+            return Ok(Self {
+                user_id,
+                master_password_plain: master_password.read()?,
+                service_id,
+                generation: if ask_generation {
+                    generation
+                } else {
+                    Generation::from_str("1")?
+                },
+                char_pools: CharSet::from_names(&char_set_names)?,
+                char_set_names,
+                password_length,
+                policy,
+            });
+        }
+
+        let generation = if ask_generation {
+            Input::<Generation>::new()
+                .with_prompt("Generation (increase this variable to regenerate password for a service) (default: 1)")
+                .default(Generation::from_str("1")?)
+                .interact_text()?
+        } else {
+            Generation::from_str("1")?
+        };
+
+        let (char_pools, char_set_names) = if ask_char_sets {
+            Self::prompt_char_sets(config)?
+        } else {
+            let char_set_names = config
+                .char_sets
+                .clone()
+                .unwrap_or_else(|| CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect());
+            (CharSet::from_names(&char_set_names)?, char_set_names)
+        };
+
+        let password_length = Input::<PasswordLength>::new()
+            .with_prompt(format!("Password length (max {})", PasswordLength::MAX));
+        let max_length = policy.as_ref().and_then(|policy| policy.max_length);
+        let default_length = match (config.password_length()?, max_length) {
+            (Some(default), Some(max)) if default.as_u16() <= max => Some(default),
+            (_, Some(max)) => Some(PasswordLength::from_str(&max.to_string())?),
+            (default, None) => default,
+        };
+        let password_length = match default_length {
+            Some(default) => password_length.default(default),
+            None => password_length,
+        }
+        .interact_text()?;
 
+        let master_password_plain = master_password.read()?;
+
+        Ok(Self {
+            user_id,
+            master_password_plain,
+            service_id,
+            generation,
+            char_pools,
+            char_set_names,
+            password_length,
+            policy,
+        })
+    }
+    /// Asks for the character sets, offering those of `config` as defaults.
+    fn prompt_char_sets(config: &Config) -> Result<(CharSet, Vec<String>)> {
+        // The presets of `CHAR_SET_NAMES` come first, at their own index.
+        let layout_safe_item = LAYOUT_SAFE_POOL;
+        let custom_item = layout_safe_item + 1;
+        let exclude_ambiguous_item = custom_item + 1;
         let char_pool_item = vec![
             "small letters [a-z]",
             "capital letters [A-Z]",
             "numbers [0-9]",
             r##"special characters [ !"#$%&'()*+,-./:;<=>?@[\]^_`{|}~ ]"##,
+            r##"keyboard layout safe special characters [ !"%'()*+,-./:=?_ ]"##,
+            "custom characters (only the ones a site accepts)",
+            "exclude ambiguous characters [ O0Il1|`'\" ]",
         ];
-        let char_pool_item_defaults = vec![true, true, true, true];
+        let custom_default = config
+            .char_sets
+            .iter()
+            .flatten()
+            .find_map(|name| name.strip_prefix(CUSTOM_CHAR_SET_PREFIX).map(str::to_owned));
+        let char_pool_item_defaults: Vec<bool> = match &config.char_sets {
+            Some(names) => CHAR_SET_NAMES
+                .iter()
+                .map(|n| names.iter().any(|name| name == n))
+                .chain([
+                    names.iter().any(|name| name == LAYOUT_SAFE),
+                    custom_default.is_some(),
+                    names.iter().any(|name| name == EXCLUDE_AMBIGUOUS),
+                ])
+                .collect(),
+            None => vec![true, true, true, true, false, false, false],
+        };
         let mut char_pools = MultiSelect::new()
             .with_prompt("Choose character sets")
             .items(&char_pool_item)
             .defaults(&char_pool_item_defaults)
             .interact()?;
 
-        while char_pools.is_empty() {
+        while char_pools.iter().all(|i| *i == exclude_ambiguous_item) {
             char_pools = MultiSelect::new()
                 .with_prompt("Choose at least one character set")
                 .items(&char_pool_item)
                 .interact()?;
         }
 
-        let char_pools = CharSet::try_from(char_pools.as_slice())?;
-
-        let password_length = Input::<PasswordLength>::new()
-            .with_prompt("Password length (max 64)")
+        let mut char_set_names: Vec<String> = char_pools
+            .iter()
+            .filter_map(|i| CHAR_SET_NAMES.get(*i).map(|n| n.to_string()))
+            .collect();
+        if char_pools.contains(&layout_safe_item) {
+            char_set_names.push(LAYOUT_SAFE.to_owned());
+        }
+        if char_pools.contains(&custom_item) {
+            let custom = Input::<String>::new()
+                .with_prompt("Custom characters (ranges like a-z0-9 allowed)")
+                .validate_with(|chars: &String| CharSet::from_custom(chars).map(|_| ()));
+            let custom = match custom_default {
+                Some(default) => custom.default(default),
+                None => custom,
+            }
             .interact_text()?;
-
-        let master_password_plain = Password::with_theme(&ColorfulTheme::default())
-            .with_prompt("Master password")
+            let repeats = CharSet::repeats(&custom);
+            if !repeats.is_empty() {
+                eprintln!(
+                    "Warning: {} repeated in the custom characters, each is used once",
+                    repeats
+                );
+            }
+            char_set_names.push(format!("{}{}", CUSTOM_CHAR_SET_PREFIX, custom));
+        }
+        if char_pools.contains(&exclude_ambiguous_item) {
+            char_set_names.push(EXCLUDE_AMBIGUOUS.to_owned());
+        }
+        char_set_names.extend(
+            config
+                .char_sets
+                .iter()
+                .flatten()
+                .filter(|name| name.starts_with(EXCLUDE_CHARS_PREFIX))
+                .cloned(),
+        );
+        Ok((CharSet::from_names(&char_set_names)?, char_set_names))
+    }
+    /// Completes with Tab from the catalog and offers the known identifiers
+    /// resembling an unknown one, as a typo derives another password.
+    pub fn prompt_service_id() -> Result<ServiceID> {
+        let matcher = ServiceMatcher::from_catalog().unwrap_or_default();
+        let service_id = Input::<ServiceID>::new()
+            .with_prompt("Service identifier (ex.: name, url...)")
+            .completion_with(&matcher)
+            .interact_text()?;
+        let typed = service_id.to_string();
+        if matcher.is_known(&typed) {
+            return Ok(service_id);
+        }
+        let mut candidates = matcher.find(&typed);
+        if candidates.is_empty() {
+            return Ok(service_id);
+        }
+        candidates.truncate(5);
+        let new = format!("{} (not used before)", typed);
+        let choice = Select::new()
+            .with_prompt("Did you mean a service you used before?")
+            .items(&candidates)
+            .item(&new)
+            .default(0)
             .interact()?;
-
-        let master_password_plain = MasterPasswordPlain::from_str(&master_password_plain)?;
-
-        Ok(Self {
-            user_id,
-            master_password_plain,
-            service_id,
-            generation,
-            char_pools,
-            password_length,
+        match candidates.get(choice) {
+            Some(known) => Ok(ServiceID::from_str(known)?),
+            None => Ok(service_id),
+        }
+    }
+    /// Warns when the exact identifier was never derived for, before the
+    /// user changes an existing password to a misspelt one.
+    fn check_history(service_id: &ServiceID) -> Result<()> {
+        let history = ServiceHistory::load(&ServiceHistory::default_path()?)?;
+        if history.last_used(&service_id.to_string())?.is_none() {
+            eprintln!(
+                "Warning: \"{}\" was never used before, check its spelling and case.\n",
+                service_id
+            );
+        }
+        Ok(())
+    }
+    /// The policy of the service from the site policy database, announced
+    /// so that the user knows why the choices are constrained.
+    fn site_policy(service_id: &ServiceID) -> Result<Option<PasswordPolicy>> {
+        let policies = SitePolicies::load(&SitePolicies::default_path()?)?;
+        Ok(policies.find(&service_id.to_string()).map(|site| {
+            eprintln!(
+                "Applying the password policy of {}: {}\n",
+                site.domain, site.policy
+            );
+            site.policy.clone()
+        }))
+    }
+    /// The settings stored in the catalog by `remember`, if complete and valid.
+    fn remembered_settings(
+        service_id: &ServiceID,
+    ) -> Option<(Generation, Vec<String>, PasswordLength)> {
+        let catalog = Catalog::load(&Catalog::default_path().ok()?).ok()?;
+        Self::settings_in(&catalog, service_id)
+    }
+    /// The settings of the service in `catalog`, if complete and valid.
+    fn settings_in(
+        catalog: &Catalog,
+        service_id: &ServiceID,
+    ) -> Option<(Generation, Vec<String>, PasswordLength)> {
+        let entry = catalog.get(&service_id.to_string())?;
+        let char_set_names = entry.char_sets.clone()?;
+        CharSet::from_names(&char_set_names).ok()?;
+        Some((
+            Generation::from_str(&entry.generation?.to_string()).ok()?,
+            char_set_names,
+            PasswordLength::from_str(&entry.password_length?.to_string()).ok()?,
+        ))
+    }
+    /// Records the use of the service in the history, if enabled in `config`.
+    pub fn record_history(&self, config: &Config) -> Result<()> {
+        if config.history != Some(true) {
+            return Ok(());
+        }
+        let path = ServiceHistory::default_path()?;
+        let mut history = ServiceHistory::load(&path)?;
+        history.touch(&self.service_id.to_string())?;
+        history.save(&path)
+    }
+    /// Stores the non-secret settings of this derivation in the catalog entry
+    /// of the service, creating the entry only if `add_missing` is set.
+    pub fn remember(&self, add_missing: bool) -> Result<()> {
+        let path = Catalog::default_path()?;
+        let mut catalog = Catalog::load(&path)?;
+        if self.remember_in(&mut catalog, add_missing) {
+            catalog.save(&path)?;
+        }
+        Ok(())
+    }
+    /// Stores the settings in `catalog`, returns whether it has an entry
+    /// for the service to store them in.
+    fn remember_in(&self, catalog: &mut Catalog, add_missing: bool) -> bool {
+        let service_id = self.service_id.to_string();
+        if catalog.get(&service_id).is_none() {
+            if !add_missing {
+                return false;
+            }
+            catalog.add(CatalogEntry::new(service_id.clone()));
+        }
+        if let Some(entry) = catalog.get_mut(&service_id) {
+            entry.generation = Some(self.generation.as_usize());
+            entry.password_length = Some(self.password_length.as_u16());
+            entry.char_sets = Some(self.char_set_names.clone());
+        }
+        true
+    }
+    /// Asks only for the inputs of the master secret, for callers that read
+    /// the service parameters from elsewhere (e.g. a batch file).
+    pub fn prompt_credentials(
+        config: &Config,
+        master_password: MasterPasswordSource,
+    ) -> Result<(UserID, MasterPasswordPlain)> {
+        Ok((
+            Self::prompt_user_id(config, master_password)?,
+            master_password.read()?,
+        ))
+    }
+    /// Uses the configured user id without asking when the master password is
+    /// not typed either, so scripts can run without any prompt.
+    fn prompt_user_id(config: &Config, master_password: MasterPasswordSource) -> Result<UserID> {
+        let user_id =
+            Input::<UserID>::new().with_prompt("User identifier (ex.: fullname, username...)");
+        Ok(match config.user_id()? {
+            Some(default) if master_password != MasterPasswordSource::Prompt => default,
+            Some(default) => user_id.default(default).interact_text()?,
+            None => user_id.interact_text()?,
         })
     }
 }
 
+#[cfg(feature = "cli")]
+impl Zeroize for UserInputCli {
+    fn zeroize(&mut self) {
+        self.master_password_plain.zeroize();
+    }
+}
+
+#[cfg(feature = "cli")]
 impl UserInputProvider for UserInputCli {
     fn get_user_id(&self) -> &UserID {
         &self.user_id
@@ -330,4 +1159,239 @@ impl UserInputProvider for UserInputCli {
     fn get_password_length(&self) -> &PasswordLength {
         &self.password_length
     }
+    fn get_policy(&self) -> Option<&PasswordPolicy> {
+        self.policy.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_canonicalize_char_set() {
+        let numbers_first = CharSet::try_from([2usize, 0].as_slice()).unwrap();
+        let letters_first = CharSet::try_from([0usize, 2].as_slice()).unwrap();
+        assert_ne!(numbers_first.to_string(), letters_first.to_string());
+        assert_eq!(numbers_first.canonical(), letters_first.canonical());
+        assert_eq!(
+            CharSet::try_from([2usize, 2].as_slice())
+                .unwrap()
+                .canonical(),
+            NUMBERS.chars().collect::<Vec<char>>()
+        );
+        assert_eq!(CharSet::repeats("abcab!"), "ab");
+        assert_eq!(CharSet::repeats("abc"), "");
+    }
+
+    #[test]
+    fn can_expand_ranges() {
+        let test_cases = [
+            ("a-f0-9", Some("abcdef0123456789")),
+            ("A-Z0-9", Some("ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789")),
+            ("x-x", Some("x")),
+            ("-ab-", Some("-ab-")),
+            (r"a\-c", Some("a-c")),
+            (r"\\-_", Some("\\]^_")),
+            ("abc", Some("abc")),
+            ("z-a", None),
+            (r"ab\", None),
+        ];
+        for (spec, expected) in test_cases {
+            assert_eq!(
+                CharSet::expand_ranges(spec).ok().as_deref(),
+                expected,
+                "{}",
+                spec
+            );
+        }
+        assert_eq!(
+            CharSet::from_custom("0-9a-f0").unwrap().to_string().len(),
+            16
+        );
+        assert_eq!(CharSet::repeats("a-fa"), "a");
+    }
+
+    #[test]
+    fn can_use_layout_safe_preset() {
+        let char_set = CharSet::from_names(&["small", LAYOUT_SAFE]).unwrap();
+        assert!(!char_set.contains_any("#$&;<>@[\\]^`{|}~"));
+        assert!(char_set.contains_any(LAYOUT_SAFE_SPECIAL_CHARS));
+        assert!(
+            LAYOUT_SAFE_SPECIAL_CHARS
+                .chars()
+                .all(|c| SPECIAL_CHARS.contains(c))
+        );
+        assert_eq!(char_set.classes().len(), 2);
+    }
+
+    #[test]
+    fn can_build_custom_char_set() {
+        assert_eq!(
+            CharSet::from_custom("abc123!?a1").unwrap().to_string(),
+            "abc123!?"
+        );
+        assert!(CharSet::from_custom("").is_err());
+        assert!(CharSet::from_custom("abc\u{e9}").is_err());
+        assert!(CharSet::from_custom("abc\n").is_err());
+
+        assert_eq!(
+            CharSet::from_names(&["numbers", "custom:xyz123"])
+                .unwrap()
+                .to_string(),
+            "0123456789xyz"
+        );
+        assert_eq!(
+            CharSet::from_names(&CHAR_SET_NAMES).unwrap().to_string(),
+            CharSet::try_from([0usize, 1, 2, 3].as_slice())
+                .unwrap()
+                .to_string()
+        );
+        assert!(CharSet::from_names(&["custom:"]).is_err());
+        assert_eq!(
+            CharSet::from_names(&["numbers", "custom:`'\"_-", EXCLUDE_AMBIGUOUS])
+                .unwrap()
+                .to_string(),
+            "23456789_-"
+        );
+        assert!(CharSet::from_names(&["custom:O0l1", EXCLUDE_AMBIGUOUS]).is_err());
+        assert_eq!(
+            CharSet::from_names(&["numbers", "exclude:13", "special", "exclude:\"\\\\;"])
+                .unwrap()
+                .to_string(),
+            r##"02456789!#$%&'()*+,-./:<=>?@[]^_`{|}~"##
+        );
+        assert!(CharSet::from_names::<&str>(&[]).is_err());
+    }
+
+    #[test]
+    fn can_exclude_ambiguous_chars() {
+        let char_set = CharSet::try_from([0usize, 1, 2, 3].as_slice())
+            .unwrap()
+            .without(AMBIGUOUS_CHARS)
+            .unwrap();
+        assert!(!char_set.contains_any(AMBIGUOUS_CHARS));
+        assert_eq!(char_set.classes().len(), 4);
+        assert!(
+            char_set
+                .classes()
+                .iter()
+                .all(|class| !class.chars().any(|c| AMBIGUOUS_CHARS.contains(c)))
+        );
+        assert_eq!(char_set.classes()[2], "23456789");
+
+        let char_set = CharSet::from_names(&["numbers", "custom:O0Il1|"])
+            .unwrap()
+            .without(AMBIGUOUS_CHARS)
+            .unwrap();
+        assert_eq!(char_set.classes(), ["23456789"]);
+        assert_eq!(char_set.to_string(), "23456789");
+
+        assert!(
+            CharSet::from_custom("O0Il1|`'\"")
+                .unwrap()
+                .without(AMBIGUOUS_CHARS)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_exclude_chars() {
+        let test_cases = [
+            (&["small", "exclude:a-w"][..], Some("xyz")),
+            (&["numbers", "exclude:0-46-8"], Some("59")),
+            (&["numbers", "custom:_-", "exclude:-"], Some("0123456789_")),
+            (&[r"custom:a-c\\", r"exclude:\\"], Some("abc")),
+            (&["numbers", "exclude:a-f", "exclude:5"], Some("012346789")),
+            (&["numbers", "exclude:0-9"], None),
+            (&["numbers", "exclude:0-4", "exclude:5-9"], None),
+            (&["numbers", "exclude:9-0"], None),
+            (&["numbers", r"exclude:1\"], None),
+        ];
+        for (names, expected) in test_cases {
+            assert_eq!(
+                CharSet::from_names(names).ok().map(|c| c.to_string()),
+                expected.map(str::to_owned),
+                "{:?}",
+                names
+            );
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn can_apply_remembered_exclusion() {
+        use crate::DerivePassRunner;
+
+        let char_set_names = vec![
+            "small".to_owned(),
+            "numbers".to_owned(),
+            "exclude:a-m0-4".to_owned(),
+        ];
+        let user_input = UserInputCli {
+            user_id: UserID::from_str("Example Eleonora").unwrap(),
+            master_password_plain: MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            service_id: ServiceID::from_str("example.com").unwrap(),
+            generation: Generation::from_str("2").unwrap(),
+            char_pools: CharSet::from_names(&char_set_names).unwrap(),
+            char_set_names,
+            password_length: PasswordLength::from_str("32").unwrap(),
+            policy: None,
+        };
+        let mut catalog = Catalog::default();
+        assert!(!user_input.remember_in(&mut catalog, false));
+        assert!(user_input.remember_in(&mut catalog, true));
+        let catalog: Catalog = toml::from_str(&toml::to_string(&catalog).unwrap()).unwrap();
+
+        assert!(
+            UserInputCli::settings_in(&catalog, &ServiceID::from_str("example.org").unwrap())
+                .is_none()
+        );
+        let (generation, char_set_names, password_length) =
+            UserInputCli::settings_in(&catalog, &user_input.service_id).unwrap();
+        assert_eq!(char_set_names, user_input.char_set_names);
+        let again = UserInputCli {
+            user_id: UserID::from_str("Example Eleonora").unwrap(),
+            master_password_plain: MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            service_id: ServiceID::from_str("example.com").unwrap(),
+            generation,
+            char_pools: CharSet::from_names(&char_set_names).unwrap(),
+            char_set_names,
+            password_length,
+            policy: None,
+        };
+        assert_eq!(again.char_pools.to_string(), "nopqrstuvwxyz56789");
+        let password = DerivePassRunner::run(&again).unwrap();
+        assert!(
+            !password
+                .expose_secret()
+                .contains(|c| "abcdefghijklm01234".contains(c))
+        );
+        assert_eq!(
+            password.expose_secret(),
+            DerivePassRunner::run(&user_input).unwrap().expose_secret()
+        );
+    }
+
+    #[test]
+    fn can_read_master_password_line() {
+        let test_cases: [(&[u8], &[u8]); 5] = [
+            (b"]lE~WExZ468ty{I5mtg[", b"]lE~WExZ468ty{I5mtg["),
+            (b"]lE~WExZ468ty{I5mtg[\n", b"]lE~WExZ468ty{I5mtg["),
+            (b"]lE~WExZ468ty{I5mtg[\r\n", b"]lE~WExZ468ty{I5mtg["),
+            (b" ]lE~WExZ468ty{I5mtg[ \n\n", b" ]lE~WExZ468ty{I5mtg[ "),
+            (
+                b"]lE~WExZ468ty{I5mtg[\nurl: example.com\n",
+                b"]lE~WExZ468ty{I5mtg[",
+            ),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(
+                MasterPasswordPlain::read_line(input).unwrap().as_bytes(),
+                expected
+            );
+        }
+        assert!(MasterPasswordPlain::read_line(&b"short\n"[..]).is_err());
+        assert!(MasterPasswordPlain::read_line(&b""[..]).is_err());
+    }
 }
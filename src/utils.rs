@@ -17,6 +17,17 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::{
+    env,
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+};
+
+use anyhow::{Result, anyhow, bail};
+use tiny_http::Server;
+
+use crate::user_input::UserInputError;
+
 pub struct Utils {}
 
 impl Utils {
@@ -27,7 +38,7 @@ impl Utils {
             .join("")
     }
     pub fn hex_to_bytes(s: &str) -> Option<Vec<u8>> {
-        if s.len() % 2 == 0 {
+        if s.len().is_multiple_of(2) {
             (0..s.len())
                 .step_by(2)
                 .map(|i| {
@@ -39,6 +50,224 @@ impl Utils {
             None
         }
     }
+    /// RFC 4648 base32 without padding, the encoding of TOTP secrets.
+    pub fn bytes_to_base32(b: &[u8]) -> String {
+        const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+        let mut encoded = String::with_capacity(b.len().div_ceil(5) * 8);
+        for chunk in b.chunks(5) {
+            let mut block = [0u8; 5];
+            block[..chunk.len()].copy_from_slice(chunk);
+            let bits = block.iter().fold(0u64, |bits, b| bits << 8 | u64::from(*b));
+            for i in 0..(chunk.len() * 8).div_ceil(5) {
+                encoded.push(char::from(ALPHABET[(bits >> (35 - 5 * i) & 31) as usize]));
+            }
+        }
+        encoded
+    }
+    /// Base58 with the Bitcoin alphabet: every leading zero byte becomes a
+    /// `1`, the rest is the big-endian number in base 58.
+    pub fn bytes_to_base58(b: &[u8]) -> String {
+        const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+        let zeros = b.iter().take_while(|b| **b == 0).count();
+        let mut digits: Vec<u8> = Vec::with_capacity(b.len() * 138 / 100 + 1);
+        for byte in &b[zeros..] {
+            let mut carry = u32::from(*byte);
+            for digit in digits.iter_mut() {
+                carry += u32::from(*digit) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits.push((carry % 58) as u8);
+                carry /= 58;
+            }
+        }
+        "1".repeat(zeros)
+            + &digits
+                .iter()
+                .rev()
+                .map(|d| char::from(ALPHABET[usize::from(*d)]))
+                .collect::<String>()
+    }
+    /// Bech32 (BIP 173) of `data` under the human readable part `hrp`,
+    /// without the 90 character limit, as age encodes its keys.
+    pub fn bech32(hrp: &str, data: &[u8]) -> String {
+        const ALPHABET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+        const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+        let polymod = |values: &[u8]| {
+            values.iter().fold(1u32, |checksum, value| {
+                let top = checksum >> 25;
+                let mut checksum = (checksum & 0x1ffffff) << 5 ^ u32::from(*value);
+                for (i, generator) in GENERATOR.iter().enumerate() {
+                    if top >> i & 1 == 1 {
+                        checksum ^= generator;
+                    }
+                }
+                checksum
+            })
+        };
+        let mut words: Vec<u8> = Vec::with_capacity((data.len() * 8).div_ceil(5));
+        let (mut bits, mut count) = (0u32, 0);
+        for byte in data {
+            bits = bits << 8 | u32::from(*byte);
+            count += 8;
+            while count >= 5 {
+                count -= 5;
+                words.push((bits >> count & 31) as u8);
+            }
+        }
+        if count > 0 {
+            words.push((bits << (5 - count) & 31) as u8);
+        }
+        let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+        values.push(0);
+        values.extend(hrp.bytes().map(|b| b & 31));
+        values.extend(&words);
+        values.extend([0; 6]);
+        let checksum = polymod(&values) ^ 1;
+        words.extend((0..6).map(|i| (checksum >> (5 * (5 - i)) & 31) as u8));
+        format!(
+            "{}1{}",
+            hrp,
+            words
+                .iter()
+                .map(|w| char::from(ALPHABET[usize::from(*w)]))
+                .collect::<String>()
+        )
+    }
+    /// See `core::length_prefixed`.
+    pub fn length_prefixed(fields: &[&[u8]]) -> Vec<u8> {
+        crate::core::length_prefixed(fields)
+    }
+    /// Keeps the pages holding `bytes` out of swap, best effort. Returns
+    /// whether they were locked, they stay locked until the process exits.
+    pub fn lock_memory(bytes: &[u8]) -> bool {
+        #[cfg(unix)]
+        {
+            // SAFETY: the range is a live allocation, mlock does not access it.
+            !bytes.is_empty() && unsafe { libc::mlock(bytes.as_ptr().cast(), bytes.len()) } == 0
+        }
+        #[cfg(windows)]
+        {
+            // SAFETY: the range is a live allocation, VirtualLock does not access it.
+            !bytes.is_empty()
+                && unsafe {
+                    windows_sys::Win32::System::Memory::VirtualLock(
+                        bytes.as_ptr().cast(),
+                        bytes.len(),
+                    )
+                } != 0
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            let _ = bytes;
+            false
+        }
+    }
+    /// Stops the process from writing core dumps (and, on Linux, from being
+    /// attached to by other processes of the user), which would expose the
+    /// unlocked secrets of long-running processes.
+    pub fn disable_core_dumps() -> bool {
+        #[cfg(unix)]
+        {
+            let limit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            // SAFETY: setrlimit only reads the struct passed by reference.
+            let disabled = unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } == 0;
+            #[cfg(target_os = "linux")]
+            // SAFETY: PR_SET_DUMPABLE takes a plain integer argument.
+            let disabled = disabled && unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } == 0;
+            disabled
+        }
+        #[cfg(not(unix))]
+        {
+            false
+        }
+    }
+    pub fn config_dir() -> Result<PathBuf> {
+        if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            return Ok(PathBuf::from(dir).join("depasswd"));
+        }
+        if cfg!(windows)
+            && let Some(dir) = env::var_os("APPDATA").filter(|d| !d.is_empty())
+        {
+            return Ok(PathBuf::from(dir).join("depasswd"));
+        }
+        env::var_os("HOME")
+            .filter(|d| !d.is_empty())
+            .map(|home| PathBuf::from(home).join(".config").join("depasswd"))
+            .ok_or(anyhow!(
+                "Can not find the configuration directory, set XDG_CONFIG_HOME or HOME"
+            ))
+    }
+    /// Binds an HTTP server to `address`, which must resolve to loopback
+    /// addresses only, and returns it with the address actually bound (port
+    /// 0 picks a free one). `refusal` ends the error of any other address.
+    pub fn bind_loopback(address: &str, refusal: &str) -> Result<(Server, SocketAddr)> {
+        let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+        if addresses.is_empty() || !addresses.iter().all(|a| a.ip().is_loopback()) {
+            bail!(UserInputError::new(format!(
+                "{} is not a loopback address, {}",
+                address, refusal
+            )));
+        }
+        let server = Server::http(addresses.as_slice()).map_err(|e| anyhow!(e))?;
+        let bound = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| anyhow!("Not listening on an IP address"))?;
+        Ok((server, bound))
+    }
+    /// `XDG_RUNTIME_DIR`, where the sockets go: only the user can enter it,
+    /// while in a shared directory like `/tmp` anyone could take their
+    /// names first. `flag` is the option naming the socket instead.
+    pub fn runtime_dir(flag: &str) -> Result<PathBuf> {
+        env::var_os("XDG_RUNTIME_DIR")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .ok_or(anyhow!(
+                "XDG_RUNTIME_DIR is not set, pass the socket path with {}",
+                flag
+            ))
+    }
+    /// Listens on a Unix socket at `path` that only the current user can
+    /// connect to. It is created under umask 0177 rather than chmod'ed
+    /// after it is already reachable.
+    #[cfg(unix)]
+    pub fn bind_private(path: &std::path::Path) -> Result<std::os::unix::net::UnixListener> {
+        let _ = std::fs::remove_file(path);
+        // SAFETY: umask only swaps the file mode mask of the process.
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = std::os::unix::net::UnixListener::bind(path);
+        // SAFETY: see above, this restores the previous mask.
+        unsafe { libc::umask(umask) };
+        listener.map_err(|e| anyhow!("Can not listen on {}: {}", path.display(), e))
+    }
+    /// Where data that should survive reboots but is not configuration
+    /// lives, e.g. sealed caches.
+    pub fn state_dir() -> Result<PathBuf> {
+        if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+            return Ok(PathBuf::from(dir).join("depasswd"));
+        }
+        if cfg!(windows)
+            && let Some(dir) = env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty())
+        {
+            return Ok(PathBuf::from(dir).join("depasswd"));
+        }
+        env::var_os("HOME")
+            .filter(|d| !d.is_empty())
+            .map(|home| {
+                PathBuf::from(home)
+                    .join(".local")
+                    .join("state")
+                    .join("depasswd")
+            })
+            .ok_or(anyhow!(
+                "Can not find the state directory, set XDG_STATE_HOME or HOME"
+            ))
+    }
 }
 
 #[cfg(test)]
@@ -76,4 +305,74 @@ mod tests {
 
         assert_eq!(Utils::hex_to_bytes(&test_hex).unwrap(), test_bytes);
     }
+
+    #[test]
+    pub fn can_bytes_to_base32() {
+        let test_cases = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(Utils::bytes_to_base32(input.as_bytes()), expected);
+        }
+    }
+    #[test]
+    pub fn can_bytes_to_base58() {
+        assert_eq!(Utils::bytes_to_base58(b""), "");
+        assert_eq!(Utils::bytes_to_base58(b"Hello World!"), "2NEpo7TZRRrLZSi2U");
+        assert_eq!(Utils::bytes_to_base58(&[0, 0, 0, 1]), "1112");
+        assert_eq!(
+            Utils::bytes_to_base58(
+                &Utils::hex_to_bytes("00eb15231dfceb60925886b67d065299925915aeb172c06647").unwrap()
+            ),
+            "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"
+        );
+    }
+    #[test]
+    pub fn can_bech32() {
+        assert_eq!(Utils::bech32("a", b""), "a12uel5l");
+        assert_eq!(
+            Utils::bech32(
+                "bc",
+                &Utils::hex_to_bytes("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+            ),
+            "bc1qq2828nkaqver9k52j2pc3w3kw3j8u2r80tqukal5w"
+        );
+    }
+    #[test]
+    pub fn can_bind_loopback() {
+        for address in ["0.0.0.0:0", "192.0.2.1:0", "not an address"] {
+            assert!(Utils::bind_loopback(address, "test").is_err());
+        }
+        let (_, address) = Utils::bind_loopback("127.0.0.1:0", "test").unwrap();
+        assert!(address.ip().is_loopback());
+        assert_ne!(address.port(), 0);
+    }
+    #[cfg(unix)]
+    #[test]
+    pub fn can_bind_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = env::temp_dir().join(format!("depasswd-private-{}.sock", std::process::id()));
+        let _listener = Utils::bind_private(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    pub fn can_length_prefix() {
+        assert_eq!(
+            Utils::length_prefixed(&[b"ab", b"", b"c"]),
+            b"\0\0\0\x02ab\0\0\0\0\0\0\0\x01c"
+        );
+        assert_ne!(
+            Utils::length_prefixed(&[b"1", b"11"]),
+            Utils::length_prefixed(&[b"11", b"1"])
+        );
+    }
 }
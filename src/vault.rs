@@ -0,0 +1,268 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A minimal HashiCorp Vault compatible HTTP API, so tooling that already
+//! reads KV secrets from Vault can fetch derived passwords instead.
+//!
+//! Endpoints:
+//! - `GET /v1/<mount>/data/<service_id>?version=<generation>`: KV v2 read, the
+//!   password is returned under the `password` key.
+//! - `GET /v1/sys/internal/ui/mounts/<mount>`: mount discovery of the vault CLI.
+//! - `GET /v1/auth/token/lookup-self`: token check.
+//! - `GET /v1/sys/health`: unauthenticated health check.
+//!
+//! Every other endpoint answers 404, writes are not supported.
+
+use std::{
+    io::Read,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, anyhow};
+use percent_encoding::percent_decode_str;
+use rand_core::{OsRng, RngCore};
+use serde_json::{Value, json};
+use subtle::ConstantTimeEq;
+use tiny_http::{Header, Response, Server};
+
+use crate::{
+    DerivePassError, batch::DerivationRequest, session::Session, user_input::UserInputError,
+    utils::Utils,
+};
+
+/// Only reads are served, their bodies are empty.
+const MAX_BODY_LENGTH: u64 = 4 * 1024;
+
+pub struct VaultResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl VaultResponse {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({"errors": [message.to_string()]}),
+        }
+    }
+}
+
+pub struct VaultServer {
+    session: Arc<Mutex<Session>>,
+    token: String,
+    mount: String,
+    char_sets: Vec<String>,
+    password_length: u16,
+}
+
+impl VaultServer {
+    pub fn new(
+        session: Arc<Mutex<Session>>,
+        mount: &str,
+        char_sets: &[String],
+        password_length: u16,
+    ) -> Self {
+        let mut token = [0u8; 16];
+        OsRng.fill_bytes(&mut token);
+        Self {
+            session,
+            token: format!("hvs.{}", Utils::bytes_to_hex(&token)),
+            mount: mount.trim_matches('/').to_owned(),
+            char_sets: char_sets.to_vec(),
+            password_length,
+        }
+    }
+    /// The random token clients must send in `X-Vault-Token`.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+    /// Binds `address`, which must be a loopback address, and returns the
+    /// server with the address actually bound (port 0 picks a free one).
+    pub fn bind(address: &str) -> Result<(Server, SocketAddr)> {
+        Utils::bind_loopback(address, "the Vault API only serves local clients")
+    }
+    pub fn serve(&self, server: Server) -> Result<()> {
+        for mut request in server.incoming_requests() {
+            // Bodies are never read, drain them so the connection stays usable.
+            let response = match std::io::copy(
+                &mut request.as_reader().take(MAX_BODY_LENGTH + 1),
+                &mut std::io::sink(),
+            ) {
+                Ok(length) if length > MAX_BODY_LENGTH => {
+                    VaultResponse::error(413, "request body is too large")
+                }
+                Ok(_) => {
+                    let token = request
+                        .headers()
+                        .iter()
+                        .find(|h| h.field.equiv("X-Vault-Token"))
+                        .map(|h| h.value.to_string());
+                    self.handle(request.method().as_str(), request.url(), token.as_deref())
+                }
+                Err(_) => VaultResponse::error(400, "can not read the request body"),
+            };
+            request.respond(
+                Response::from_string(response.body.to_string())
+                    .with_status_code(response.status)
+                    .with_header(
+                        Header::from_bytes("Content-Type", "application/json")
+                            .map_err(|_| anyhow!("Invalid header"))?,
+                    ),
+            )?;
+        }
+        Ok(())
+    }
+    /// Handles one request given its method, URL (path and query) and token.
+    pub fn handle(&self, method: &str, url: &str, token: Option<&str>) -> VaultResponse {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let path = path.trim_end_matches('/');
+        if path == "/v1/sys/health" {
+            return VaultResponse::ok(json!({
+                "initialized": true,
+                "sealed": false,
+                "standby": false,
+                "version": env!("CARGO_PKG_VERSION"),
+            }));
+        }
+        if !token.is_some_and(|t| t.as_bytes().ct_eq(self.token.as_bytes()).into()) {
+            return VaultResponse::error(403, "permission denied");
+        }
+        if method != "GET" {
+            return VaultResponse::error(405, "only reads are supported");
+        }
+        let mount_prefix = format!("/v1/{}/", self.mount);
+        if path == "/v1/auth/token/lookup-self" {
+            VaultResponse::ok(
+                json!({"data": {"policies": ["default"], "ttl": 0, "renewable": false}}),
+            )
+        } else if path == format!("/v1/sys/internal/ui/mounts/{}", self.mount) {
+            VaultResponse::ok(json!({"data": {
+                "path": format!("{}/", self.mount),
+                "type": "kv",
+                "options": {"version": "2"},
+            }}))
+        } else if let Some(service_id) = path
+            .strip_prefix(&mount_prefix)
+            .and_then(|p| p.strip_prefix("data/"))
+            .filter(|s| !s.is_empty())
+        {
+            self.read(&percent_decode_str(service_id).decode_utf8_lossy(), query)
+        } else {
+            VaultResponse::error(404, format!("no handler for route \"{}\"", path))
+        }
+    }
+    fn read(&self, service_id: &str, query: &str) -> VaultResponse {
+        let generation = match url::form_urlencoded::parse(query.as_bytes())
+            .find(|(k, _)| k == "version")
+            .map(|(_, v)| v.parse::<usize>())
+        {
+            None | Some(Ok(0)) => 1,
+            Some(Ok(generation)) => generation,
+            Some(Err(_)) => return VaultResponse::error(400, "invalid version"),
+        };
+        let request = DerivationRequest {
+            key: None,
+            service_id: service_id.to_owned(),
+            generation,
+            char_sets: self.char_sets.clone(),
+            password_length: self.password_length,
+        };
+        let derived_pass = match self.session.lock() {
+            Ok(session) => request.derive(&session),
+            Err(_) => return VaultResponse::error(500, "session state is unavailable"),
+        };
+        match derived_pass {
+            Ok(derived_pass) => VaultResponse::ok(json!({"data": {
+                "data": {"password": derived_pass.expose_secret()},
+                "metadata": {
+                    "version": generation,
+                    "created_time": "1970-01-01T00:00:00Z",
+                    "deletion_time": "",
+                    "destroyed": false,
+                },
+            }})),
+            Err(e)
+                if e.is::<UserInputError>()
+                    || e.downcast_ref::<DerivePassError>()
+                        .is_some_and(DerivePassError::is_invalid_request) =>
+            {
+                VaultResponse::error(400, e)
+            }
+            Err(e) => VaultResponse::error(500, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        scheme::SchemeVersion,
+        user_input::{CHAR_SET_NAMES, MasterPasswordPlain, UserID},
+    };
+
+    #[test]
+    fn can_serve_kv_v2_reads() {
+        let session = Session::unlock(
+            &UserID::from_str("Example Eleonora").unwrap(),
+            &MasterPasswordPlain::from_str("]lE~WExZ468ty{I5mtg[").unwrap(),
+            SchemeVersion::V1,
+        )
+        .unwrap();
+        let char_sets: Vec<String> = CHAR_SET_NAMES.iter().map(|n| n.to_string()).collect();
+        let server = VaultServer::new(Arc::new(Mutex::new(session)), "secret", &char_sets, 20);
+        let token = Some(server.token().to_owned());
+
+        let response = server.handle(
+            "GET",
+            "/v1/secret/data/Example%20Service%20Name?version=1",
+            token.as_deref(),
+        );
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.body["data"]["data"]["password"],
+            "1@MWtAAqZ0p>;;y@zZ6d"
+        );
+        assert_eq!(response.body["data"]["metadata"]["version"], 1);
+
+        assert_eq!(
+            server
+                .handle("GET", "/v1/secret/data/x", Some("hvs.wrong"))
+                .status,
+            403
+        );
+        assert_eq!(
+            server
+                .handle("GET", "/v1/other/data/x", token.as_deref())
+                .status,
+            404
+        );
+        assert_eq!(server.handle("GET", "/v1/sys/health", None).status, 200);
+
+        assert!(VaultServer::bind("0.0.0.0:0").is_err());
+        let (_, address) = VaultServer::bind("127.0.0.1:0").unwrap();
+        assert!(address.ip().is_loopback());
+    }
+}
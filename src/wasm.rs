@@ -0,0 +1,312 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The entry point of browser and extension frontends: one JSON object in,
+//! one JSON object out, so the JavaScript side needs no knowledge of the
+//! Rust types and derives with the exact same implementation.
+//!
+//! It only uses the `no_std` `core` pipeline, so `--no-default-features
+//! --features wasm` builds for `wasm32-unknown-unknown` without the native
+//! dependencies (sockets, OS randomness, terminals). `derive_password` is
+//! exported through wasm-bindgen; build the module with `cargo rustc
+//! --target wasm32-unknown-unknown --crate-type cdylib` and generate the
+//! JavaScript glue with `wasm-bindgen`.
+//!
+//! The input has `user_id`, `master_password`, `service_id`, `generation`
+//! (1 by default), `password_length`, `char_sets` (preset names, all by
+//! default), `scheme` (1 by default) and an optional `kdf` with the Argon2id
+//! `m_cost`, `t_cost` and `p_cost`. The output is `{"password": ...}` or
+//! `{"error": ..., "invalid_request": ...}`.
+//!
+//! A page gets far less memory than a native process, so the master
+//! password hash is capped at `MAX_MEMORY_KIB`. Profiles calibrated for a
+//! native machine may need a lower `m_cost` in the browser, which derives
+//! other passwords.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use serde::Deserialize;
+use serde_json::json;
+use wasm_bindgen::prelude::wasm_bindgen;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    CAPITAL_LETTERS, NUMBERS, SMALL_LETTERS, SPECIAL_CHARS,
+    core::{self as pipeline, Argon2Costs, Error, MASTER_SECRET_LENGTH, SchemeVersion},
+};
+
+/// Memory limit of the master password hash, 256 MiB: mobile browsers
+/// refuse to grow a WebAssembly memory much further, and the page needs
+/// room of its own.
+pub const MAX_MEMORY_KIB: u32 = 256 * 1024;
+
+/// The preset character sets by name, in the order of `CHAR_SET_NAMES`.
+const PRESETS: [(&str, &str); 4] = [
+    ("small", SMALL_LETTERS),
+    ("capital", CAPITAL_LETTERS),
+    ("numbers", NUMBERS),
+    ("special", SPECIAL_CHARS),
+];
+
+#[derive(Deserialize)]
+struct WasmParams {
+    user_id: String,
+    master_password: String,
+    service_id: String,
+    #[serde(default = "WasmParams::default_generation")]
+    generation: u64,
+    password_length: u16,
+    #[serde(default)]
+    char_sets: Option<Vec<String>>,
+    #[serde(default = "WasmParams::default_scheme")]
+    scheme: u32,
+    #[serde(default)]
+    kdf: Option<WasmKdf>,
+}
+
+impl WasmParams {
+    fn default_generation() -> u64 {
+        1
+    }
+    fn default_scheme() -> u32 {
+        1
+    }
+}
+
+impl Drop for WasmParams {
+    fn drop(&mut self) {
+        self.master_password.zeroize();
+    }
+}
+
+#[derive(Deserialize)]
+struct WasmKdf {
+    #[serde(default)]
+    algorithm: Option<String>,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// An error with whether the request caused it.
+struct WasmError {
+    message: String,
+    invalid_request: bool,
+}
+
+impl WasmError {
+    fn invalid(message: impl ToString) -> Self {
+        Self {
+            message: message.to_string(),
+            invalid_request: true,
+        }
+    }
+}
+
+impl From<Error> for WasmError {
+    fn from(e: Error) -> Self {
+        Self {
+            message: e.to_string(),
+            invalid_request: !matches!(e, Error::Secret | Error::Char),
+        }
+    }
+}
+
+/// `Wasm::derive_password`, exported to JavaScript.
+#[wasm_bindgen]
+pub fn derive_password(json_params: &str) -> String {
+    Wasm::derive_password(json_params)
+}
+
+pub struct Wasm {}
+
+impl Wasm {
+    /// Derives the password described by `json_params`. Never fails: errors
+    /// are reported in the returned JSON.
+    pub fn derive_password(json_params: &str) -> String {
+        match Self::derive(json_params) {
+            Ok(password) => json!({ "password": *password }),
+            Err(e) => json!({
+                "error": e.message,
+                "invalid_request": e.invalid_request,
+            }),
+        }
+        .to_string()
+    }
+    fn derive(json_params: &str) -> Result<Zeroizing<String>, WasmError> {
+        let params: WasmParams = serde_json::from_str(json_params)
+            .map_err(|e| WasmError::invalid(format!("Invalid parameters: {}", e)))?;
+        // The same rules as `UserID`, `MasterPasswordPlain`, `Generation`
+        // and `PasswordLength`.
+        if params.user_id.len() < 8 {
+            return Err(WasmError::invalid(
+                "The user identifier must be at least 8 characters long",
+            ));
+        }
+        if params.master_password.len() < 8 {
+            return Err(WasmError::invalid(
+                "The master password must be at least 8 characters long",
+            ));
+        }
+        if params.generation == 0 {
+            return Err(WasmError::invalid("The generation must be at least 1"));
+        }
+        if !(1..=256).contains(&params.password_length) {
+            return Err(WasmError::invalid(
+                "The password length must be between 1 and 256",
+            ));
+        }
+        let scheme = match params.scheme {
+            1 => SchemeVersion::V1,
+            2 => SchemeVersion::V2,
+            3 => SchemeVersion::V3,
+            other => {
+                return Err(WasmError::invalid(format!(
+                    "Unknown scheme version: {}",
+                    other
+                )));
+            }
+        };
+        let classes = match &params.char_sets {
+            None => PRESETS.iter().map(|(_, chars)| *chars).collect(),
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    PRESETS
+                        .iter()
+                        .find(|(preset, _)| preset == name)
+                        .map(|(_, chars)| *chars)
+                        .ok_or_else(|| {
+                            WasmError::invalid(format!("Unknown character set: {}", name))
+                        })
+                })
+                .collect::<Result<Vec<&str>, _>>()?,
+        };
+        if classes.is_empty() {
+            return Err(WasmError::invalid(
+                "You must select at least one character set!",
+            ));
+        }
+        let costs = match &params.kdf {
+            None => Argon2Costs::DEFAULT,
+            Some(kdf) => {
+                if kdf.algorithm.as_ref().is_some_and(|a| a != "argon2id") {
+                    return Err(WasmError::invalid(
+                        "Only the argon2id KDF is available in the browser",
+                    ));
+                }
+                Argon2Costs {
+                    m_cost: kdf.m_cost,
+                    t_cost: kdf.t_cost,
+                    p_cost: kdf.p_cost,
+                }
+            }
+        };
+        if costs.m_cost > MAX_MEMORY_KIB {
+            return Err(WasmError::invalid(format!(
+                "The KDF needs {} KiB of memory, browsers only grant {} KiB",
+                costs.m_cost, MAX_MEMORY_KIB
+            )));
+        }
+        let mut master_secret = Zeroizing::new([0u8; MASTER_SECRET_LENGTH]);
+        pipeline::master_secret_with_costs(
+            &params.user_id,
+            params.master_password.as_bytes(),
+            scheme,
+            costs,
+            &mut master_secret,
+        )?;
+        Ok(Zeroizing::new(pipeline::password(
+            &master_secret,
+            &params.service_id,
+            params.generation,
+            &classes,
+            params.password_length,
+            scheme,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn derive(json_params: &str) -> Value {
+        serde_json::from_str(&derive_password(json_params)).unwrap()
+    }
+
+    #[test]
+    fn can_derive_password() {
+        assert_eq!(
+            derive(
+                r#"{
+                    "user_id": "Example Eleonora",
+                    "master_password": "]lE~WExZ468ty{I5mtg[",
+                    "service_id": "Example Service Name",
+                    "password_length": 20
+                }"#
+            ),
+            json!({ "password": "1@MWtAAqZ0p>;;y@zZ6d" })
+        );
+
+        // Pinned against `Session::unlock_with_kdf_params` + `DerivationRequest::derive`
+        // with the same costs, so both builds agree on custom KDF parameters.
+        for (scheme, password) in [
+            (1, "7fd2ubkvv6hovrzz"),
+            (2, "29um64iki539rzqt"),
+            (3, "rtbblx8tsn87wf54"),
+        ] {
+            let json_params = format!(
+                r#"{{
+                    "user_id": "Example Eleonora",
+                    "master_password": "]lE~WExZ468ty{{I5mtg[",
+                    "service_id": "Example Service Name",
+                    "generation": 2,
+                    "password_length": 16,
+                    "char_sets": ["small", "numbers"],
+                    "scheme": {},
+                    "kdf": {{"algorithm": "argon2id", "m_cost": 8192, "t_cost": 2, "p_cost": 1}}
+                }}"#,
+                scheme
+            );
+            assert_eq!(derive(&json_params), json!({ "password": password }));
+        }
+
+        let test_cases = [
+            r#"{"user_id": "short", "master_password": "12345678", "service_id": "a", "password_length": 20}"#,
+            r#"{"user_id": "Example Eleonora", "master_password": "12345678", "password_length": 20}"#,
+            r#"{"user_id": "Example Eleonora", "master_password": "12345678", "service_id": "a", "password_length": 20, "kdf": {"m_cost": 2097152, "t_cost": 1, "p_cost": 1}}"#,
+            r#"{"user_id": "Example Eleonora", "master_password": "12345678", "service_id": "a", "password_length": 20, "kdf": {"algorithm": "scrypt", "m_cost": 16384, "t_cost": 1, "p_cost": 1}}"#,
+            r#"{"user_id": "Example Eleonora", "master_password": "12345678", "service_id": "a", "password_length": 20, "char_sets": ["emoji"]}"#,
+            r#"{"user_id": "Example Eleonora", "master_password": "12345678", "service_id": "a", "password_length": 3, "scheme": 3}"#,
+            "not json",
+        ];
+        for json_params in test_cases {
+            let output = derive(json_params);
+            assert!(output["error"].is_string(), "{}", json_params);
+            assert_eq!(output["invalid_request"], true, "{}", json_params);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! WPA2/WPA3 personal pre-shared keys. A passphrase may be 8 to 63 printable
+//! ASCII characters, but routers, IoT firmwares and config files disagree on
+//! spaces, quotes and backslashes, so the PSK is the longest one made of
+//! letters and digits only.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+    derivation_path::Purpose,
+    derived_pass::DerivedPass,
+    scheme::SchemeVersion,
+    service_secret::ServiceSecret,
+    user_input::{CHAR_SET_NAMES, CharSet, PasswordLength, UserInputError},
+    utils::Utils,
+};
+
+/// Length of the derived PSK, the maximum a WPA passphrase allows.
+pub const WIFI_PSK_LENGTH: u16 = 63;
+
+/// How the `wifi` command prints the PSK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WifiFormat {
+    #[default]
+    Psk,
+    WpaSupplicant,
+    Hostapd,
+}
+
+impl WifiFormat {
+    pub const ALL: [WifiFormat; 3] = [
+        WifiFormat::Psk,
+        WifiFormat::WpaSupplicant,
+        WifiFormat::Hostapd,
+    ];
+}
+
+impl FromStr for WifiFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.to_string() == s)
+            .ok_or(anyhow!(
+                "Unknown Wi-Fi format: {} (expected one of: {})",
+                s,
+                Self::ALL.map(|f| f.to_string()).join(", ")
+            ))
+    }
+}
+
+impl Display for WifiFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                WifiFormat::Psk => "psk",
+                WifiFormat::WpaSupplicant => "wpa-supplicant",
+                WifiFormat::Hostapd => "hostapd",
+            }
+        )
+    }
+}
+
+pub struct Wifi {}
+
+impl Wifi {
+    /// Derives the PSK of a network under the label of the wifi purpose.
+    pub fn psk(service_secret: &ServiceSecret, scheme: SchemeVersion) -> Result<DerivedPass> {
+        DerivedPass::with_purpose(
+            service_secret,
+            &CharSet::from_names(&CHAR_SET_NAMES[..3])?,
+            &PasswordLength::from_str(&WIFI_PSK_LENGTH.to_string())?,
+            scheme,
+            &Purpose::wifi(),
+        )
+    }
+    /// Renders the PSK in `format`. Both snippets enable WPA3 (SAE) in
+    /// transition mode, so WPA2-only clients can still join.
+    pub fn render(format: WifiFormat, ssid: &str, psk: &str) -> Result<String> {
+        if ssid.is_empty() || ssid.len() > 32 {
+            return Err(UserInputError::new("An SSID must be 1 to 32 bytes long").into());
+        }
+        // Anything that can not be written as a plain quoted string goes in hex.
+        let quotable = ssid
+            .bytes()
+            .all(|b| (b' '..=b'~').contains(&b) && b != b'"');
+        Ok(match format {
+            WifiFormat::Psk => psk.to_owned(),
+            WifiFormat::WpaSupplicant => format!(
+                "network={{\n\tssid={}\n\tpsk=\"{}\"\n\tkey_mgmt=WPA-PSK SAE\n\tieee80211w=1\n}}",
+                if quotable {
+                    format!("\"{}\"", ssid)
+                } else {
+                    Utils::bytes_to_hex(ssid.as_bytes())
+                },
+                psk
+            ),
+            WifiFormat::Hostapd => format!(
+                "{}\nwpa=2\nwpa_key_mgmt=WPA-PSK SAE\nrsn_pairwise=CCMP\nieee80211w=1\nwpa_passphrase={}",
+                if quotable {
+                    format!("ssid={}", ssid)
+                } else {
+                    format!("ssid2={}", Utils::bytes_to_hex(ssid.as_bytes()))
+                },
+                psk
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_derive_wifi_psk() {
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        let psk = Wifi::psk(&service_secret, SchemeVersion::V2)
+            .unwrap()
+            .expose_secret()
+            .to_owned();
+        assert_eq!(psk.len(), 63);
+        assert!(psk.bytes().all(|b| b.is_ascii_alphanumeric()));
+        assert_eq!(
+            Wifi::psk(&service_secret, SchemeVersion::V2)
+                .unwrap()
+                .expose_secret(),
+            psk
+        );
+        assert!(Wifi::psk(&service_secret, SchemeVersion::V1).is_err());
+    }
+
+    #[test]
+    fn can_render_wifi_formats() {
+        let psk = "a".repeat(63);
+        assert_eq!(Wifi::render(WifiFormat::Psk, "Home", &psk).unwrap(), psk);
+        assert_eq!(
+            Wifi::render(WifiFormat::WpaSupplicant, "Home", "abc").unwrap(),
+            "network={\n\tssid=\"Home\"\n\tpsk=\"abc\"\n\tkey_mgmt=WPA-PSK SAE\n\tieee80211w=1\n}"
+        );
+        assert_eq!(
+            Wifi::render(WifiFormat::Hostapd, "Home", "abc").unwrap(),
+            "ssid=Home\nwpa=2\nwpa_key_mgmt=WPA-PSK SAE\nrsn_pairwise=CCMP\nieee80211w=1\nwpa_passphrase=abc"
+        );
+        assert!(
+            Wifi::render(WifiFormat::WpaSupplicant, "Caf\u{e9}", "abc")
+                .unwrap()
+                .contains("\tssid=436166c3a9\n")
+        );
+        assert!(
+            Wifi::render(WifiFormat::Hostapd, "\"5G\"", "abc")
+                .unwrap()
+                .starts_with("ssid2=22354722\n")
+        );
+        assert!(Wifi::render(WifiFormat::Psk, "", "abc").is_err());
+        assert!(Wifi::render(WifiFormat::Psk, &"x".repeat(33), "abc").is_err());
+    }
+
+    #[test]
+    fn can_parse_wifi_format() {
+        for format in WifiFormat::ALL {
+            assert_eq!(WifiFormat::from_str(&format.to_string()).unwrap(), format);
+        }
+        assert!(WifiFormat::from_str("nmcli").is_err());
+    }
+}
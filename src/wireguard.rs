@@ -0,0 +1,108 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! WireGuard Curve25519 keys derived per service, so a VPN config can be
+//! rebuilt from the master password alone.
+
+use anyhow::Result;
+use base64::prelude::*;
+use crypto_box::SecretKey;
+use zeroize::Zeroizing;
+
+use crate::{
+    DerivePassError, derivation_path::Purpose, keys::KeyPair, scheme::SchemeVersion,
+    service_secret::ServiceSecret, user_input::UserInputError,
+};
+
+pub struct WireguardKeys {
+    secret_key: SecretKey,
+}
+
+impl WireguardKeys {
+    /// The private key is the first 32 bytes of the expansion of the service
+    /// secret under the label of the wireguard purpose, clamped like the
+    /// output of `wg genkey`.
+    pub fn new(service_secret: &ServiceSecret, scheme: SchemeVersion) -> Result<Self> {
+        if scheme == SchemeVersion::V1 {
+            return Err(UserInputError::new(
+                "Scheme v1 only derives passwords, use scheme v2 for WireGuard keys",
+            )
+            .into());
+        }
+        let expanded = Zeroizing::new(service_secret.expand(&Purpose::wireguard().label(), 32)?);
+        let mut bytes: Zeroizing<[u8; 32]> = Zeroizing::new(
+            expanded[..]
+                .try_into()
+                .map_err(|_| DerivePassError::Secret)?,
+        );
+        Ok(Self::from_bytes(&mut bytes))
+    }
+    fn from_bytes(bytes: &mut [u8; 32]) -> Self {
+        bytes[0] &= 248;
+        bytes[31] &= 127;
+        bytes[31] |= 64;
+        Self {
+            secret_key: SecretKey::from_bytes(*bytes),
+        }
+    }
+    /// The key pair, to serialize it with `KeyPair::private_key`.
+    pub fn key_pair(&self, comment: &str) -> KeyPair {
+        KeyPair::x25519(&Zeroizing::new(self.secret_key.to_bytes()), comment)
+    }
+    /// The `PrivateKey` of the `[Interface]` section, in base64.
+    pub fn private_key(&self) -> Zeroizing<String> {
+        Zeroizing::new(BASE64_STANDARD.encode(Zeroizing::new(self.secret_key.to_bytes())))
+    }
+    /// The `PublicKey` of the `[Peer]` section on the other end, in base64.
+    pub fn public_key(&self) -> String {
+        BASE64_STANDARD.encode(self.secret_key.public_key().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::utils::Utils;
+
+    #[test]
+    fn can_derive_wireguard_keys() {
+        let keys = WireguardKeys::from_bytes(&mut std::array::from_fn(|i| i as u8));
+        assert_eq!(
+            *keys.private_key(),
+            "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHl8="
+        );
+        assert_eq!(
+            keys.public_key(),
+            "j0DFrbaPJWJK5bIU6nZ6bslNgp09e14a0bpvPiE4KF8="
+        );
+
+        let service_secret =
+            ServiceSecret::from_str(&Utils::bytes_to_hex(&Vec::from_iter(0..64))).unwrap();
+        assert_eq!(
+            WireguardKeys::new(&service_secret, SchemeVersion::V2)
+                .unwrap()
+                .private_key()
+                .len(),
+            44
+        );
+        assert!(WireguardKeys::new(&service_secret, SchemeVersion::V1).is_err());
+    }
+}
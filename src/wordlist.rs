@@ -0,0 +1,122 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Wordlists for passphrases, read from a file with one word per line. The
+//! fingerprint of a list is part of the derivation, so a passphrase is only
+//! ever re-derived from the exact list it came from.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{user_input::UserInputError, utils::Utils};
+
+pub struct Wordlist {
+    words: Vec<String>,
+    fingerprint: [u8; 32],
+}
+
+impl Wordlist {
+    /// Fewer words would give less than 10 bits of entropy per word.
+    pub const MIN_WORDS: usize = 1024;
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Can not read wordlist file: {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid wordlist file: {}", path.display()))
+    }
+    /// Takes every non-blank line as a word, so line endings and surrounding
+    /// whitespace do not change the list or its fingerprint.
+    pub fn parse(content: &str) -> std::result::Result<Self, UserInputError> {
+        let mut words: Vec<String> = vec![];
+        for (number, line) in content.lines().enumerate() {
+            let word = line.trim();
+            if word.is_empty() {
+                continue;
+            }
+            if word.contains(char::is_whitespace) {
+                return Err(UserInputError::new(format!(
+                    "Line {} holds more than one word: {}",
+                    number + 1,
+                    word
+                )));
+            }
+            words.push(word.to_owned());
+        }
+        let mut sorted: Vec<&String> = words.iter().collect();
+        sorted.sort_unstable();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(UserInputError::new(format!(
+                "The wordlist repeats the word {}",
+                pair[0]
+            )));
+        }
+        if words.len() < Self::MIN_WORDS {
+            return Err(UserInputError::new(format!(
+                "The wordlist has {} words, at least {} are needed",
+                words.len(),
+                Self::MIN_WORDS
+            )));
+        }
+        let fingerprint = Sha256::digest(words.join("\n").as_bytes()).into();
+        Ok(Self { words, fingerprint })
+    }
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+    pub fn fingerprint(&self) -> &[u8; 32] {
+        &self.fingerprint
+    }
+    /// The start of the fingerprint, short enough to compare by eye.
+    pub fn fingerprint_hex(&self) -> String {
+        Utils::bytes_to_hex(&self.fingerprint[..8])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(count: usize) -> String {
+        (0..count).map(|i| format!("word{}\n", i)).collect()
+    }
+
+    #[test]
+    fn can_parse_wordlist() {
+        let wordlist = Wordlist::parse(&numbered(Wordlist::MIN_WORDS)).unwrap();
+        assert_eq!(wordlist.words().len(), Wordlist::MIN_WORDS);
+        assert_eq!(wordlist.words()[1], "word1");
+
+        let crlf = numbered(Wordlist::MIN_WORDS).replace('\n', " \r\n") + "\n";
+        assert_eq!(
+            Wordlist::parse(&crlf).unwrap().fingerprint(),
+            wordlist.fingerprint()
+        );
+        let swapped = numbered(Wordlist::MIN_WORDS).replacen("word0\nword1", "word1\nword0", 1);
+        assert_ne!(
+            Wordlist::parse(&swapped).unwrap().fingerprint(),
+            wordlist.fingerprint()
+        );
+
+        assert!(Wordlist::parse(&numbered(Wordlist::MIN_WORDS - 1)).is_err());
+        assert!(Wordlist::parse(&(numbered(Wordlist::MIN_WORDS) + "word7\n")).is_err());
+        assert!(Wordlist::parse(&(numbered(Wordlist::MIN_WORDS) + "two words\n")).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! yescrypt as used by libxcrypt for `$y$` hashes, the `/etc/shadow` default
+//! of current Debian, Fedora and Arch. Only the parameters `mkpasswd` and
+//! `passwd` pick by default are implemented: `$y$j9T$`, i.e. N = 4096,
+//! r = 32, p = 1, t = 0 and pwxform with 12 KiB S-boxes.
+
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use sha2::{Digest, Sha256};
+
+/// Alphabet of the crypt(3) base64 flavor, also used for salts.
+pub const ITOA64: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Setting prefix of the supported parameters: flavor `j` (the default
+/// pwxform flags), N = 2^(1 + 11) and r = 1 + 31.
+const PREFIX: &str = "$y$j9T$";
+const N: usize = 4096;
+const R: usize = 32;
+
+const PWX_SIMPLE: usize = 2;
+const PWX_GATHER: usize = 4;
+const PWX_ROUNDS: usize = 6;
+const PWX_WORDS: usize = PWX_SIMPLE * PWX_GATHER * 2;
+const S_WORDS: usize = 3 * 256 * PWX_SIMPLE * 2;
+const S_MASK: u32 = 255 * PWX_SIMPLE as u32 * 8;
+
+pub struct Yescrypt {}
+
+impl Yescrypt {
+    /// `$y$j9T$<salt>$<hash>` crypt string of `password`.
+    pub fn hash(password: &[u8], salt: &[u8]) -> String {
+        let mut hash = PREFIX.to_owned();
+        Self::encode64(salt, &mut hash);
+        hash.push('$');
+        Self::encode64(&Self::kdf(password, salt), &mut hash);
+        hash
+    }
+    fn kdf(password: &[u8], salt: &[u8]) -> [u8; 32] {
+        // Large enough costs first hash the password with N / 64, so that
+        // the main pass can not be started without spending some work.
+        let prehashed = Self::kdf_body(password, salt, N >> 6, true);
+        Self::kdf_body(&prehashed, salt, N, false)
+    }
+    fn kdf_body(password: &[u8], salt: &[u8], n: usize, prehash: bool) -> [u8; 32] {
+        let key: &[u8] = if prehash {
+            b"yescrypt-prehash"
+        } else {
+            b"yescrypt"
+        };
+        let passwd = Self::hmac(key, password);
+        let mut b = vec![0u8; 128 * R];
+        pbkdf2_hmac::<Sha256>(&passwd, salt, 1, &mut b);
+        let mut passwd: [u8; 32] = b[..32].try_into().expect("32 bytes");
+        Self::smix(&mut b, n, &mut passwd);
+        let mut dk = [0u8; 32];
+        pbkdf2_hmac::<Sha256>(&passwd, &b, 1, &mut dk);
+        if prehash {
+            dk
+        } else {
+            // ClientKey and StoredKey of SCRAM (RFC 5802).
+            Sha256::digest(Self::hmac(&dk, b"Client Key")).into()
+        }
+    }
+    fn smix(b: &mut [u8], n: usize, passwd: &mut [u8; 32]) {
+        let s = 32 * R;
+        let mut sbox = vec![0u32; S_WORDS];
+        Self::smix1(&mut b[..128], 1, S_WORDS / 32, &mut sbox, None);
+        *passwd = Self::hmac(&b[(s - 16) * 4..s * 4], passwd);
+        let mut pwxform = Pwxform {
+            sbox,
+            s0: 2 * S_WORDS / 3,
+            s1: S_WORDS / 3,
+            s2: 0,
+            w: 0,
+        };
+        // With t = 0 two thirds of the work is the sequential write pass.
+        let nloop = (n.div_ceil(3) + 1) & !1;
+        let mut v = vec![0u32; s * n];
+        Self::smix1(b, R, n, &mut v, Some(&mut pwxform));
+        Self::smix2(b, R, n, nloop, &mut v, &mut pwxform);
+    }
+    fn smix1(b: &mut [u8], r: usize, n: usize, v: &mut [u32], mut pwxform: Option<&mut Pwxform>) {
+        let s = 32 * r;
+        let mut x = Self::load(b, r);
+        for i in 0..n {
+            v[i * s..(i + 1) * s].copy_from_slice(&x);
+            if pwxform.is_some() && i > 1 {
+                let j = Self::wrap(Self::integerify(&x, r), i);
+                Self::xor(&mut x, &v[j * s..(j + 1) * s]);
+            }
+            match pwxform.as_deref_mut() {
+                Some(pwxform) => pwxform.blockmix(&mut x, r),
+                None => Self::blockmix_salsa8(&mut x, r),
+            }
+        }
+        Self::store(&x, b, r);
+    }
+    fn smix2(b: &mut [u8], r: usize, n: usize, nloop: usize, v: &mut [u32], pwxform: &mut Pwxform) {
+        let s = 32 * r;
+        let mut x = Self::load(b, r);
+        for _ in 0..nloop {
+            let j = Self::integerify(&x, r) & (n - 1);
+            Self::xor(&mut x, &v[j * s..(j + 1) * s]);
+            v[j * s..(j + 1) * s].copy_from_slice(&x);
+            pwxform.blockmix(&mut x, r);
+        }
+        Self::store(&x, b, r);
+    }
+    /// Reads the little-endian block into words, in the SIMD friendly order
+    /// of the reference implementation that pwxform depends on.
+    fn load(b: &[u8], r: usize) -> Vec<u32> {
+        let mut x = vec![0u32; 32 * r];
+        for k in 0..2 * r {
+            for i in 0..16 {
+                let offset = (k * 16 + i * 5 % 16) * 4;
+                x[k * 16 + i] = u32::from_le_bytes(b[offset..offset + 4].try_into().unwrap());
+            }
+        }
+        x
+    }
+    fn store(x: &[u32], b: &mut [u8], r: usize) {
+        for k in 0..2 * r {
+            for i in 0..16 {
+                let offset = (k * 16 + i * 5 % 16) * 4;
+                b[offset..offset + 4].copy_from_slice(&x[k * 16 + i].to_le_bytes());
+            }
+        }
+    }
+    fn integerify(x: &[u32], r: usize) -> usize {
+        let last = &x[(2 * r - 1) * 16..];
+        (((last[13] as u64) << 32) + last[0] as u64) as usize
+    }
+    /// Maps `x` into the blocks written so far, favoring the recent ones.
+    fn wrap(x: usize, i: usize) -> usize {
+        let n = 1 << i.ilog2();
+        (x & (n - 1)) + (i - n)
+    }
+    fn xor(x: &mut [u32], y: &[u32]) {
+        x.iter_mut().zip(y).for_each(|(x, y)| *x ^= y);
+    }
+    fn blockmix_salsa8(b: &mut [u32], r: usize) {
+        let mut x = [0u32; 16];
+        x.copy_from_slice(&b[(2 * r - 1) * 16..]);
+        let mut y = vec![0u32; 32 * r];
+        for i in 0..2 * r {
+            Self::xor(&mut x, &b[i * 16..(i + 1) * 16]);
+            Self::salsa20(&mut x, 8);
+            y[i * 16..(i + 1) * 16].copy_from_slice(&x);
+        }
+        for i in 0..r {
+            b[i * 16..(i + 1) * 16].copy_from_slice(&y[2 * i * 16..(2 * i + 1) * 16]);
+            b[(i + r) * 16..(i + r + 1) * 16]
+                .copy_from_slice(&y[(2 * i + 1) * 16..(2 * i + 2) * 16]);
+        }
+    }
+    fn salsa20(b: &mut [u32], rounds: usize) {
+        let mut x = [0u32; 16];
+        for i in 0..16 {
+            x[i * 5 % 16] = b[i];
+        }
+        let quarter = |x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+            x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+            x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+            x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+            x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+        };
+        for _ in (0..rounds).step_by(2) {
+            quarter(&mut x, 0, 4, 8, 12);
+            quarter(&mut x, 5, 9, 13, 1);
+            quarter(&mut x, 10, 14, 2, 6);
+            quarter(&mut x, 15, 3, 7, 11);
+            quarter(&mut x, 0, 1, 2, 3);
+            quarter(&mut x, 5, 6, 7, 4);
+            quarter(&mut x, 10, 11, 8, 9);
+            quarter(&mut x, 15, 12, 13, 14);
+        }
+        for i in 0..16 {
+            b[i] = b[i].wrapping_add(x[i * 5 % 16]);
+        }
+    }
+    fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+        mac.update(message);
+        mac.finalize().into_bytes().into()
+    }
+    /// The crypt flavor of base64: little-endian groups of up to 24 bits.
+    fn encode64(bytes: &[u8], out: &mut String) {
+        for chunk in bytes.chunks(3) {
+            let value = chunk
+                .iter()
+                .rev()
+                .fold(0u32, |value, byte| value << 8 | *byte as u32);
+            for i in 0..(chunk.len() * 8).div_ceil(6) {
+                out.push(ITOA64[(value >> (6 * i)) as usize & 0x3f] as char);
+            }
+        }
+    }
+}
+
+/// The S-boxes and write position of pwxform, rotated after every call.
+struct Pwxform {
+    sbox: Vec<u32>,
+    s0: usize,
+    s1: usize,
+    s2: usize,
+    w: usize,
+}
+
+impl Pwxform {
+    fn blockmix(&mut self, b: &mut [u32], r: usize) {
+        let blocks = 2 * r;
+        let mut x = [0u32; PWX_WORDS];
+        x.copy_from_slice(&b[(blocks - 1) * PWX_WORDS..]);
+        for i in 0..blocks {
+            Yescrypt::xor(&mut x, &b[i * PWX_WORDS..(i + 1) * PWX_WORDS]);
+            self.pwxform(&mut x);
+            b[i * PWX_WORDS..(i + 1) * PWX_WORDS].copy_from_slice(&x);
+        }
+        Yescrypt::salsa20(&mut b[(blocks - 1) * 16..], 2);
+    }
+    fn pwxform(&mut self, x: &mut [u32; PWX_WORDS]) {
+        for round in 0..PWX_ROUNDS {
+            for j in 0..PWX_GATHER {
+                let lane = j * PWX_SIMPLE * 2;
+                let p0 = self.s0 + (x[lane] & S_MASK) as usize / 4;
+                let p1 = self.s1 + (x[lane + 1] & S_MASK) as usize / 4;
+                for k in 0..PWX_SIMPLE {
+                    let word = |sbox: &[u32], p: usize| {
+                        (sbox[p + 2 * k + 1] as u64) << 32 | sbox[p + 2 * k] as u64
+                    };
+                    let (lo, hi) = (x[lane + 2 * k], x[lane + 2 * k + 1]);
+                    let value = ((hi as u64) * (lo as u64)).wrapping_add(word(&self.sbox, p0))
+                        ^ word(&self.sbox, p1);
+                    x[lane + 2 * k] = value as u32;
+                    x[lane + 2 * k + 1] = (value >> 32) as u32;
+                    if round != 0 && round != PWX_ROUNDS - 1 {
+                        self.sbox[self.s2 + 2 * self.w] = value as u32;
+                        self.sbox[self.s2 + 2 * self.w + 1] = (value >> 32) as u32;
+                        self.w += 1;
+                    }
+                }
+            }
+        }
+        (self.s0, self.s1, self.s2) = (self.s2, self.s0, self.s1);
+        self.w &= 256 * PWX_SIMPLE - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_hash_with_yescrypt() {
+        // Reference hashes from libxcrypt's crypt(3).
+        let salt: Vec<u8> = (0..16).collect();
+        assert_eq!(
+            Yescrypt::hash(b"1@MWtAAqZ0p>;;y@zZ6d", &salt),
+            "$y$j9T$.2U.1EE/4Q.07ck0AoU1D.$bLk8j8sXEyrZ/AnWjSkJys8zsqKa4jZPfz7B6UY67pA"
+        );
+        assert_eq!(
+            Yescrypt::hash(b"", &salt),
+            "$y$j9T$.2U.1EE/4Q.07ck0AoU1D.$SmJZIbjHRp4Z4W4wxy1fs7FwUQaU7p33Xm6oOyuYI22"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The HMAC-SHA1 challenge-response of a YubiKey OTP slot as a pepper,
+//! the hardware factor KeePassXC users already configured (usually slot
+//! 2). The challenge is derived from the user id, the 20-byte response is
+//! mixed into the master secret, so every derived password needs the key.
+//!
+//! The key is asked through `ykman` or, without it, `ykchalresp` of
+//! yubikey-personalization. A slot requiring touch makes them wait for it.
+
+use std::{
+    env,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{
+    core::{YUBIKEY_CHALLENGE_LABEL, length_prefixed},
+    doctor::Doctor,
+    pepper::Pepper,
+    user_input::{UserID, UserInputError},
+    utils::Utils,
+};
+
+/// Length of an HMAC-SHA1 response.
+const RESPONSE_LENGTH: usize = 20;
+
+pub struct YubiKey {
+    slot: u8,
+}
+
+impl YubiKey {
+    pub fn new(slot: u8) -> Result<Self> {
+        if !matches!(slot, 1 | 2) {
+            return Err(UserInputError::new("The YubiKey slot must be 1 or 2").into());
+        }
+        Ok(Self { slot })
+    }
+    /// SHA-256 of the labelled user id, the same on every machine.
+    pub fn challenge(user_id: &UserID) -> [u8; 32] {
+        Sha256::digest(length_prefixed(&[
+            YUBIKEY_CHALLENGE_LABEL,
+            user_id.to_string().as_bytes(),
+        ]))
+        .into()
+    }
+    /// The response of the key to the challenge of `user_id`.
+    pub fn pepper(&self, user_id: &UserID) -> Result<Pepper> {
+        Pepper::new(self.respond(&Self::challenge(user_id))?.to_vec())
+    }
+    pub fn respond(&self, challenge: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let challenge = Utils::bytes_to_hex(challenge);
+        let path = env::var_os("PATH").unwrap_or_default();
+        let mut command = if let Some(ykman) = Doctor::find_in_path("ykman", &path) {
+            let mut command = Command::new(ykman);
+            command
+                .args(["otp", "calculate"])
+                .arg(self.slot.to_string())
+                .arg(&challenge);
+            command
+        } else if let Some(ykchalresp) = Doctor::find_in_path("ykchalresp", &path) {
+            let mut command = Command::new(ykchalresp);
+            command
+                .arg(format!("-{}", self.slot))
+                .arg("-x")
+                .arg(&challenge);
+            command
+        } else {
+            bail!("Neither ykman nor ykchalresp found, install one to use the YubiKey");
+        };
+        // The touch prompt goes to stderr.
+        let output = command
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .context("Can not ask the YubiKey")?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            bail!(
+                "The YubiKey did not answer the challenge of slot {}",
+                self.slot
+            );
+        }
+        Self::parse_response(&stdout)
+    }
+    /// The hex encoded response on the first line of `stdout`.
+    fn parse_response(stdout: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let hex = std::str::from_utf8(stdout)?
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim();
+        let response = Zeroizing::new(
+            Utils::hex_to_bytes(hex).ok_or(anyhow!("The YubiKey response is not hex"))?,
+        );
+        if response.len() != RESPONSE_LENGTH {
+            bail!("The YubiKey response is not HMAC-SHA1, is the slot configured for it?");
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn can_parse_yubikey_response() {
+        let challenge = YubiKey::challenge(&UserID::from_str("Example Eleonora").unwrap());
+        assert_eq!(
+            challenge,
+            YubiKey::challenge(&UserID::from_str("Example Eleonora").unwrap())
+        );
+        assert_ne!(
+            challenge,
+            YubiKey::challenge(&UserID::from_str("Example Eleonora2").unwrap())
+        );
+
+        let response = YubiKey::parse_response(b"0123456789abcdef0123456789abcdef01234567\n");
+        assert_eq!(response.unwrap().len(), RESPONSE_LENGTH);
+        assert!(YubiKey::parse_response(b"0123456789abcdef\n").is_err());
+        assert!(YubiKey::parse_response(b"Touch your YubiKey...\n").is_err());
+        assert!(YubiKey::new(3).is_err());
+    }
+}
@@ -17,11 +17,11 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-
 use std::str::FromStr;
 
 use depasswd::{
-    DerivePassRunner, UserInputProvider,
+    DeriveParams, DerivePassError, DerivePassRunner, UserInputProvider,
+    policy::PasswordPolicy,
     user_input::{CharSet, Generation, MasterPasswordPlain, PasswordLength, ServiceID, UserID},
 };
 
@@ -98,24 +98,77 @@ fn integration_test1() {
         "1@MWtAAqZ0p>;;y@zZ6d",
         DerivePassRunner::run(&test_user_input_1)
             .unwrap()
-            .to_string()
+            .expose_secret()
     );
     assert_eq!(
         "1@MWtAAqZ0p>;;y@zZ6d",
         DerivePassRunner::run(&test_user_input_1)
             .unwrap()
-            .to_string()
+            .expose_secret()
     );
     assert_eq!(
         r##"7o^qjF"dFpX;sp,8bwE#+c&FRIDUfM`o,1e}Q2K{+mc%I:~vVd2u$V&=_<\n{M--"##,
         DerivePassRunner::run(&test_user_input_2)
             .unwrap()
-            .to_string()
+            .expose_secret()
     );
     assert_eq!(
         r##"7o^qjF"dFpX;sp,8bwE#+c&FRIDUfM`o,1e}Q2K{+mc%I:~vVd2u$V&=_<\n{M--"##,
         DerivePassRunner::run(&test_user_input_2)
             .unwrap()
-            .to_string()
+            .expose_secret()
+    );
+}
+
+#[test]
+fn integration_test_derive_params() {
+    let params = DeriveParams::new(
+        UserID::from_str("Example Eleonora").unwrap(),
+        MasterPasswordPlain::from_str(r##"]lE~WExZ468ty{I5mtg["##).unwrap(),
+        ServiceID::from_str("Example Service Name").unwrap(),
+        Generation::from_str("1").unwrap(),
+        CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap(),
+        PasswordLength::from_str("20").unwrap(),
+    );
+    let from_provider = DeriveParams::from(&params);
+
+    assert_eq!(
+        "1@MWtAAqZ0p>;;y@zZ6d",
+        DerivePassRunner::run(params).unwrap().expose_secret()
+    );
+    assert_eq!(
+        "1@MWtAAqZ0p>;;y@zZ6d",
+        DerivePassRunner::run(from_provider)
+            .unwrap()
+            .expose_secret()
     );
 }
+
+#[test]
+fn integration_test_typed_errors() {
+    let params = DeriveParams {
+        policy: Some(PasswordPolicy {
+            max_length: Some(16),
+            ..PasswordPolicy::default()
+        }),
+        ..DeriveParams::new(
+            UserID::from_str("Example Eleonora").unwrap(),
+            MasterPasswordPlain::from_str(r##"]lE~WExZ468ty{I5mtg["##).unwrap(),
+            ServiceID::from_str("Example Service Name").unwrap(),
+            Generation::from_str("1").unwrap(),
+            CharSet::try_from([0, 1, 2, 3].as_slice()).unwrap(),
+            PasswordLength::from_str("20").unwrap(),
+        )
+    };
+
+    let error = DerivePassRunner::run(params).err().unwrap();
+    assert!(matches!(
+        error,
+        DerivePassError::LengthOutOfRange {
+            length: 20,
+            max: 16,
+            ..
+        }
+    ));
+    assert!(error.is_invalid_request());
+}
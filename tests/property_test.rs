@@ -0,0 +1,83 @@
+/*
+ * This file is part of depasswd stateless password manager.
+ *
+ * Copyright (C) 2025 Kovács Dávid <kapcsolat@kovacsdavid.dev>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published
+ * by the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#![cfg(feature = "testing")]
+
+use std::str::FromStr;
+
+use base64::prelude::*;
+use depasswd::{
+    derived_pass::DerivedPass, scheme::SchemeVersion, service_secret::ServiceSecret, testing,
+    utils::Utils,
+};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn hex_round_trips(bytes in proptest::collection::vec(any::<u8>(), 0..128)) {
+        prop_assert_eq!(Utils::hex_to_bytes(&Utils::bytes_to_hex(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn derived_pass_uses_only_char_set(
+        secret in proptest::collection::vec(any::<u8>(), 64),
+        char_set in testing::char_set(),
+        password_length in testing::password_length(),
+    ) {
+        let derived_pass = DerivedPass::new(
+            &ServiceSecret::from_str(&Utils::bytes_to_hex(&secret)).unwrap(),
+            &char_set,
+            &password_length,
+            SchemeVersion::V1,
+        )
+        .unwrap()
+        .expose_secret()
+        .to_owned();
+        prop_assert_eq!(derived_pass.chars().count(), password_length.as_usize());
+        prop_assert!(derived_pass.chars().all(|c| char_set.to_string().contains(c)));
+    }
+
+    #[test]
+    fn service_salt_encodes_all_fields(
+        service_id in testing::service_id(),
+        generation in testing::generation(),
+        password_length in testing::password_length(),
+    ) {
+        let salt = ServiceSecret::salt(&service_id, &generation, &password_length);
+        prop_assert_eq!(
+            BASE64_STANDARD_NO_PAD.decode(salt).unwrap(),
+            format!("{}{}{}{}", service_id.len(), service_id, password_length, generation).into_bytes()
+        );
+    }
+
+    #[test]
+    fn canonical_service_salt_is_injective(
+        a in (testing::service_id(), testing::generation(), testing::password_length()),
+        b in (testing::service_id(), testing::generation(), testing::password_length()),
+    ) {
+        let same_input = a.0.to_string() == b.0.to_string()
+            && a.1.as_usize() == b.1.as_usize()
+            && a.2.as_u16() == b.2.as_u16();
+        prop_assert_eq!(
+            ServiceSecret::canonical_salt(&a.0, &a.1, &a.2)
+                == ServiceSecret::canonical_salt(&b.0, &b.1, &b.2),
+            same_input
+        );
+    }
+}